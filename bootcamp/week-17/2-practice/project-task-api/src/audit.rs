@@ -0,0 +1,80 @@
+//! Task Change History
+//!
+//! Every task mutation is recorded in `task_events` with the before/after
+//! value of each changed field.
+
+use serde_json::{json, Map, Value};
+use sqlx::{types::Json, SqliteConnection};
+
+use crate::models::{Task, TaskEventKind};
+
+/// Task fields tracked in the history
+const TRACKED_FIELDS: &[&str] = &[
+    "title",
+    "description",
+    "completed",
+    "due_at",
+    "priority",
+    "parent_id",
+    "tags",
+];
+
+/// Field-level differences between two versions of a task
+///
+/// `None` stands for "no task", so creations record every field as new.
+pub fn diff(before: Option<&Task>, after: Option<&Task>) -> Map<String, Value> {
+    let before = before.map(snapshot).unwrap_or_default();
+    let after = after.map(snapshot).unwrap_or_default();
+    let mut changes = Map::new();
+
+    for field in TRACKED_FIELDS {
+        let old = before.get(*field).cloned().unwrap_or(Value::Null);
+        let new = after.get(*field).cloned().unwrap_or(Value::Null);
+
+        if old != new {
+            changes.insert(field.to_string(), json!({ "before": old, "after": new }));
+        }
+    }
+
+    changes
+}
+
+/// Change of the soft-delete state
+pub fn deleted_change(deleted: bool) -> Map<String, Value> {
+    let mut changes = Map::new();
+    changes.insert(
+        "deleted".into(),
+        json!({ "before": !deleted, "after": deleted }),
+    );
+    changes
+}
+
+fn snapshot(task: &Task) -> Map<String, Value> {
+    match serde_json::to_value(task) {
+        Ok(Value::Object(fields)) => fields,
+        _ => Map::new(),
+    }
+}
+
+/// Store an event; updates that changed nothing are skipped
+pub async fn record(
+    conn: &mut SqliteConnection,
+    task_id: i64,
+    actor_id: i64,
+    kind: TaskEventKind,
+    changes: Map<String, Value>,
+) -> Result<(), sqlx::Error> {
+    if kind == TaskEventKind::Updated && changes.is_empty() {
+        return Ok(());
+    }
+
+    sqlx::query("INSERT INTO task_events (task_id, actor_id, kind, changes) VALUES (?, ?, ?, ?)")
+        .bind(task_id)
+        .bind(actor_id)
+        .bind(kind)
+        .bind(Json(changes))
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,170 @@
+//! Authentication: password hashing, JWT tokens and the current-user extractor
+
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use axum::{
+    extract::{FromRef, FromRequestParts},
+    http::{header, request::Parts, HeaderMap},
+};
+use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+use crate::error::ApiError;
+
+/// Default access token lifetime (24 hours)
+const DEFAULT_TOKEN_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// JWT signing configuration
+#[derive(Clone)]
+pub struct AuthConfig {
+    secret: Arc<[u8]>,
+    /// Access token lifetime
+    pub token_ttl: Duration,
+}
+
+impl AuthConfig {
+    /// Create a configuration with the given HMAC secret
+    pub fn new(secret: impl AsRef<[u8]>) -> Self {
+        Self {
+            secret: Arc::from(secret.as_ref()),
+            token_ttl: DEFAULT_TOKEN_TTL,
+        }
+    }
+
+    /// Read the secret from `JWT_SECRET`
+    ///
+    /// Falls back to a random secret, so tokens do not survive a restart.
+    pub fn from_env() -> Self {
+        match std::env::var("JWT_SECRET") {
+            Ok(secret) if !secret.is_empty() => Self::new(secret),
+            _ => {
+                tracing::warn!("JWT_SECRET not set, using a random secret");
+                let mut secret = [0u8; 32];
+                OsRng.fill_bytes(&mut secret);
+                Self::new(secret)
+            }
+        }
+    }
+
+    /// Issue a signed access token for a user
+    pub fn issue_token(&self, user_id: i64, username: &str) -> Result<String, ApiError> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let claims = Claims {
+            sub: user_id,
+            username: username.to_string(),
+            iat: now,
+            exp: now + self.token_ttl.as_secs(),
+        };
+
+        jsonwebtoken::encode(&Header::default(), &claims, &EncodingKey::from_secret(&self.secret))
+            .map_err(|e| ApiError::Internal(format!("Token encoding failed: {}", e)))
+    }
+
+    /// Validate a token's signature and expiration
+    pub fn verify_token(&self, token: &str) -> Result<Claims, ApiError> {
+        jsonwebtoken::decode::<Claims>(
+            token,
+            &DecodingKey::from_secret(&self.secret),
+            &Validation::default(),
+        )
+        .map(|data| data.claims)
+        .map_err(|_| ApiError::Unauthorized("Invalid or expired token".into()))
+    }
+}
+
+/// JWT claims
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Claims {
+    /// User ID
+    pub sub: i64,
+    /// Username
+    pub username: String,
+    /// Issued at (seconds since epoch)
+    pub iat: u64,
+    /// Expiration (seconds since epoch)
+    pub exp: u64,
+}
+
+/// Hash a password with Argon2 and a random salt
+pub fn hash_password(password: &str) -> Result<String, ApiError> {
+    let salt = SaltString::generate(&mut OsRng);
+
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| ApiError::Internal(format!("Password hashing failed: {}", e)))
+}
+
+/// Hash of a random password, checked when a login names an unknown user
+///
+/// Verifying against it costs the same as against a real hash, so response
+/// times do not reveal which usernames exist.
+pub fn dummy_hash() -> &'static str {
+    static DUMMY_HASH: LazyLock<String> = LazyLock::new(|| {
+        let mut password = [0u8; 32];
+        OsRng.fill_bytes(&mut password);
+        hash_password(&hex::encode(password)).expect("Argon2 hashes any password")
+    });
+
+    &DUMMY_HASH
+}
+
+/// Check a password against a stored Argon2 hash
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash)
+        .map(|parsed| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &parsed)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+/// Token of an `Authorization: Bearer` header, not verified yet
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+}
+
+/// Authenticated caller, resolved from the `Authorization: Bearer` header
+///
+/// Only the token's signature and expiration are checked, without a database
+/// lookup, so a token outlives its account until it expires. Accounts cannot
+/// be deleted through the API.
+#[derive(Debug, Clone)]
+pub struct CurrentUser {
+    pub id: i64,
+    pub username: String,
+}
+
+impl<S> FromRequestParts<S> for CurrentUser
+where
+    AuthConfig: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let token = bearer_token(&parts.headers)
+            .ok_or_else(|| ApiError::Unauthorized("Missing bearer token".into()))?;
+
+        let claims = AuthConfig::from_ref(state).verify_token(token)?;
+
+        Ok(CurrentUser {
+            id: claims.sub,
+            username: claims.username,
+        })
+    }
+}
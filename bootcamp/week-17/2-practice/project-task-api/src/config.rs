@@ -0,0 +1,579 @@
+//! Application Configuration
+//!
+//! Settings are read from a TOML file, then environment variables, then
+//! command-line flags; each source overrides the previous one. Every value
+//! has a default, so all three are optional.
+//!
+//! ```toml
+//! [server]
+//! bind = "0.0.0.0:3000"
+//! body_limit = 2097152        # bytes
+//! request_timeout_secs = 30
+//! shutdown_timeout_secs = 30
+//!
+//! [database]
+//! url = "sqlite:tasks.db?mode=rwc"
+//! max_connections = 5
+//! acquire_timeout_secs = 30
+//!
+//! [cors]
+//! allowed_origins = ["*"]     # or a list of origins
+//!
+//! [log]
+//! format = "compact"          # compact | full | pretty
+//! level = "info"
+//!
+//! [metrics]
+//! enabled = true              # request metrics and GET /metrics
+//!
+//! [rate_limit]
+//! enabled = true
+//! trust_forwarded_for = false # use X-Forwarded-For behind a proxy
+//! auth = { burst = 10, per_minute = 10 }
+//! bulk = { burst = 5, per_minute = 10 }
+//! default = { burst = 100, per_minute = 600 }
+//!
+//! [idempotency]
+//! enabled = true              # honour Idempotency-Key on POST
+//! ttl_secs = 86400            # how long a key is remembered
+//! ```
+
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use axum::http::HeaderValue;
+use clap::ValueEnum;
+use serde::Deserialize;
+use tracing_subscriber::filter::LevelFilter;
+
+/// File read when neither `--config` nor `TASK_API_CONFIG` is given, if it exists
+pub const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+/// Complete application configuration
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AppConfig {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub cors: CorsConfig,
+    pub log: LogConfig,
+    pub metrics: MetricsConfig,
+    pub rate_limit: RateLimitConfig,
+    pub idempotency: IdempotencyConfig,
+}
+
+/// HTTP server settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Address and port to listen on
+    pub bind: SocketAddr,
+    /// Largest accepted request body, in bytes
+    pub body_limit: usize,
+    /// Time to produce a response before answering 408
+    pub request_timeout_secs: u64,
+    /// Time given to in-flight requests to finish on shutdown
+    pub shutdown_timeout_secs: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: SocketAddr::from(([0, 0, 0, 0], 3000)),
+            body_limit: 2 * 1024 * 1024,
+            request_timeout_secs: 30,
+            shutdown_timeout_secs: 30,
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.request_timeout_secs)
+    }
+
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.shutdown_timeout_secs)
+    }
+}
+
+/// Database connection settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    /// SQLite connection URL
+    pub url: String,
+    /// Size of the connection pool
+    pub max_connections: u32,
+    /// Time to wait for a free connection
+    pub acquire_timeout_secs: u64,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            url: "sqlite:tasks.db?mode=rwc".to_string(),
+            max_connections: 5,
+            acquire_timeout_secs: 30,
+        }
+    }
+}
+
+impl DatabaseConfig {
+    /// Default settings for another database URL
+    pub fn with_url(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            ..Self::default()
+        }
+    }
+
+    pub fn acquire_timeout(&self) -> Duration {
+        Duration::from_secs(self.acquire_timeout_secs)
+    }
+}
+
+/// Cross-origin request settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    /// Origins allowed to call the API; `["*"]` allows any
+    pub allowed_origins: Vec<String>,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: vec!["*".to_string()],
+        }
+    }
+}
+
+impl CorsConfig {
+    /// Whether any origin is allowed
+    pub fn allows_any(&self) -> bool {
+        self.allowed_origins.iter().any(|origin| origin == "*")
+    }
+}
+
+/// Log output settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// Line format
+    pub format: LogFormat,
+    /// Most verbose level logged: error, warn, info, debug or trace
+    pub level: String,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            format: LogFormat::Compact,
+            level: "info".to_string(),
+        }
+    }
+}
+
+impl LogConfig {
+    /// Parsed `level`; valid once the configuration is validated
+    pub fn level_filter(&self) -> LevelFilter {
+        LevelFilter::from_str(&self.level).unwrap_or(LevelFilter::INFO)
+    }
+}
+
+/// Prometheus metrics settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig {
+    /// Record request metrics and serve them at `GET /metrics`
+    pub enabled: bool,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+/// Request rate limits, per client and route group
+///
+/// Clients are the authenticated user, or the IP address for anonymous
+/// requests and `/auth/*`. Health checks and metrics are never limited.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// Answer 429 to clients over their limit
+    pub enabled: bool,
+    /// Take the client IP from `X-Forwarded-For`; only safe behind a proxy
+    pub trust_forwarded_for: bool,
+    /// `/auth/register` and `/auth/login`
+    pub auth: RateLimit,
+    /// `/tasks/bulk`, `/tasks/import` and `/tasks/export`
+    pub bulk: RateLimit,
+    /// Every other route
+    pub default: RateLimit,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            trust_forwarded_for: false,
+            auth: RateLimit::new(10, 10),
+            bulk: RateLimit::new(5, 10),
+            default: RateLimit::new(100, 600),
+        }
+    }
+}
+
+/// Token bucket: up to `burst` requests at once, refilled at `per_minute`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimit {
+    pub burst: u32,
+    pub per_minute: u32,
+}
+
+impl RateLimit {
+    pub fn new(burst: u32, per_minute: u32) -> Self {
+        Self { burst, per_minute }
+    }
+}
+
+/// `Idempotency-Key` settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IdempotencyConfig {
+    /// Replay the stored response of a `POST` repeated with the same key
+    pub enabled: bool,
+    /// Time a key is remembered after its first use
+    pub ttl_secs: u64,
+}
+
+impl Default for IdempotencyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            ttl_secs: 24 * 60 * 60,
+        }
+    }
+}
+
+impl IdempotencyConfig {
+    pub fn ttl(&self) -> Duration {
+        Duration::from_secs(self.ttl_secs)
+    }
+}
+
+/// Log line format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// One short line per event
+    Compact,
+    /// One line per event with its span context
+    Full,
+    /// Multi-line, for development
+    Pretty,
+}
+
+/// Command-line flags that override the configuration
+#[derive(Debug, Clone, Default, clap::Args)]
+pub struct ConfigArgs {
+    /// TOML configuration file [env: TASK_API_CONFIG] [default: config.toml, if present]
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+    /// Address to listen on, e.g. 127.0.0.1:8080
+    #[arg(long, value_name = "ADDR")]
+    pub bind: Option<SocketAddr>,
+    /// SQLite connection URL
+    #[arg(long, value_name = "URL")]
+    pub database_url: Option<String>,
+    /// Size of the connection pool
+    #[arg(long, value_name = "N")]
+    pub max_connections: Option<u32>,
+    /// Seconds to wait for a free database connection
+    #[arg(long, value_name = "SECS")]
+    pub acquire_timeout: Option<u64>,
+    /// Allowed CORS origin (repeat for several, `*` for any)
+    #[arg(long = "cors-origin", value_name = "ORIGIN")]
+    pub cors_origins: Vec<String>,
+    /// Log line format
+    #[arg(long, value_enum)]
+    pub log_format: Option<LogFormat>,
+    /// Most verbose log level
+    #[arg(long, value_name = "LEVEL")]
+    pub log_level: Option<String>,
+    /// Largest accepted request body, in bytes
+    #[arg(long, value_name = "BYTES")]
+    pub body_limit: Option<usize>,
+    /// Seconds to produce a response before answering 408
+    #[arg(long, value_name = "SECS")]
+    pub request_timeout: Option<u64>,
+    /// Seconds given to in-flight requests to finish on shutdown
+    #[arg(long, value_name = "SECS")]
+    pub shutdown_timeout: Option<u64>,
+    /// Record request metrics and serve `GET /metrics`
+    #[arg(long, value_name = "BOOL")]
+    pub metrics: Option<bool>,
+    /// Answer 429 to clients over their rate limit
+    #[arg(long, value_name = "BOOL")]
+    pub rate_limit: Option<bool>,
+    /// Seconds an Idempotency-Key is remembered
+    #[arg(long, value_name = "SECS")]
+    pub idempotency_ttl: Option<u64>,
+}
+
+/// Why the configuration could not be loaded
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("cannot read {}: {source}", path.display())]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("invalid config file {}: {message}", path.display())]
+    Parse { path: PathBuf, message: String },
+
+    #[error("invalid value for {var}: {message}")]
+    Env { var: &'static str, message: String },
+
+    #[error("invalid configuration:\n  - {}", .0.join("\n  - "))]
+    Invalid(Vec<String>),
+}
+
+impl AppConfig {
+    /// Load the configuration from the file, `env` and `args`, then validate it
+    ///
+    /// `env` looks up an environment variable; pass `|name| std::env::var(name).ok()`.
+    pub fn load(
+        args: &ConfigArgs,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, ConfigError> {
+        let (path, required) = config_file(args, &env);
+        let mut config = Self::from_file(&path, required)?;
+
+        config.apply_env(env)?;
+        config.apply_args(args);
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Parse a TOML file; a missing optional file gives the defaults
+    pub fn from_file(path: &Path, required: bool) -> Result<Self, ConfigError> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if !required && e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Self::default());
+            }
+            Err(source) => {
+                return Err(ConfigError::Read {
+                    path: path.to_path_buf(),
+                    source,
+                });
+            }
+        };
+
+        toml::from_str(&text).map_err(|e| ConfigError::Parse {
+            path: path.to_path_buf(),
+            message: e.message().to_string(),
+        })
+    }
+
+    /// Override settings with the `TASK_API_*` variables (and `DATABASE_URL`)
+    fn apply_env(&mut self, env: impl Fn(&str) -> Option<String>) -> Result<(), ConfigError> {
+        let env = |var| env(var).filter(|value: &String| !value.trim().is_empty());
+
+        if let Some(value) = env("TASK_API_BIND") {
+            self.server.bind = parse_env("TASK_API_BIND", &value)?;
+        }
+        if let Some(value) = env("TASK_API_BODY_LIMIT") {
+            self.server.body_limit = parse_env("TASK_API_BODY_LIMIT", &value)?;
+        }
+        if let Some(value) = env("TASK_API_REQUEST_TIMEOUT_SECS") {
+            self.server.request_timeout_secs = parse_env("TASK_API_REQUEST_TIMEOUT_SECS", &value)?;
+        }
+        if let Some(value) = env("TASK_API_SHUTDOWN_TIMEOUT_SECS") {
+            self.server.shutdown_timeout_secs =
+                parse_env("TASK_API_SHUTDOWN_TIMEOUT_SECS", &value)?;
+        }
+        if let Some(value) = env("DATABASE_URL") {
+            self.database.url = value;
+        }
+        if let Some(value) = env("TASK_API_MAX_CONNECTIONS") {
+            self.database.max_connections = parse_env("TASK_API_MAX_CONNECTIONS", &value)?;
+        }
+        if let Some(value) = env("TASK_API_ACQUIRE_TIMEOUT_SECS") {
+            self.database.acquire_timeout_secs =
+                parse_env("TASK_API_ACQUIRE_TIMEOUT_SECS", &value)?;
+        }
+        if let Some(value) = env("TASK_API_CORS_ORIGINS") {
+            self.cors.allowed_origins = value.split(',').map(|o| o.trim().to_string()).collect();
+        }
+        if let Some(value) = env("TASK_API_LOG_FORMAT") {
+            self.log.format =
+                LogFormat::from_str(&value, true).map_err(|message| ConfigError::Env {
+                    var: "TASK_API_LOG_FORMAT",
+                    message,
+                })?;
+        }
+        if let Some(value) = env("TASK_API_LOG_LEVEL") {
+            self.log.level = value;
+        }
+        if let Some(value) = env("TASK_API_METRICS_ENABLED") {
+            self.metrics.enabled = parse_env("TASK_API_METRICS_ENABLED", &value)?;
+        }
+        if let Some(value) = env("TASK_API_RATE_LIMIT_ENABLED") {
+            self.rate_limit.enabled = parse_env("TASK_API_RATE_LIMIT_ENABLED", &value)?;
+        }
+        if let Some(value) = env("TASK_API_IDEMPOTENCY_TTL_SECS") {
+            self.idempotency.ttl_secs = parse_env("TASK_API_IDEMPOTENCY_TTL_SECS", &value)?;
+        }
+
+        Ok(())
+    }
+
+    /// Override settings with the command-line flags that were given
+    fn apply_args(&mut self, args: &ConfigArgs) {
+        if let Some(bind) = args.bind {
+            self.server.bind = bind;
+        }
+        if let Some(limit) = args.body_limit {
+            self.server.body_limit = limit;
+        }
+        if let Some(secs) = args.request_timeout {
+            self.server.request_timeout_secs = secs;
+        }
+        if let Some(secs) = args.shutdown_timeout {
+            self.server.shutdown_timeout_secs = secs;
+        }
+        if let Some(url) = &args.database_url {
+            self.database.url = url.clone();
+        }
+        if let Some(max) = args.max_connections {
+            self.database.max_connections = max;
+        }
+        if let Some(secs) = args.acquire_timeout {
+            self.database.acquire_timeout_secs = secs;
+        }
+        if !args.cors_origins.is_empty() {
+            self.cors.allowed_origins = args.cors_origins.clone();
+        }
+        if let Some(format) = args.log_format {
+            self.log.format = format;
+        }
+        if let Some(level) = &args.log_level {
+            self.log.level = level.clone();
+        }
+        if let Some(enabled) = args.metrics {
+            self.metrics.enabled = enabled;
+        }
+        if let Some(enabled) = args.rate_limit {
+            self.rate_limit.enabled = enabled;
+        }
+        if let Some(secs) = args.idempotency_ttl {
+            self.idempotency.ttl_secs = secs;
+        }
+    }
+
+    /// Check every setting, reporting all problems at once
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut errors = Vec::new();
+
+        if self.server.body_limit == 0 {
+            errors.push("server.body_limit must be at least 1 byte".to_string());
+        }
+        if self.server.request_timeout_secs == 0 {
+            errors.push("server.request_timeout_secs must be at least 1".to_string());
+        }
+        if !self.database.url.starts_with("sqlite:") {
+            errors.push(format!(
+                "database.url must be a sqlite: URL, got '{}'",
+                self.database.url
+            ));
+        }
+        if self.database.max_connections == 0 {
+            errors.push("database.max_connections must be at least 1".to_string());
+        }
+        if self.database.acquire_timeout_secs == 0 {
+            errors.push("database.acquire_timeout_secs must be at least 1".to_string());
+        }
+
+        let origins = &self.cors.allowed_origins;
+        if origins.is_empty() {
+            errors.push("cors.allowed_origins must not be empty (use [\"*\"] to allow any)".into());
+        } else if self.cors.allows_any() && origins.len() > 1 {
+            errors.push("cors.allowed_origins cannot mix \"*\" with other origins".to_string());
+        }
+        for origin in origins.iter().filter(|origin| *origin != "*") {
+            let valid = (origin.starts_with("http://") || origin.starts_with("https://"))
+                && !origin.ends_with('/')
+                && HeaderValue::from_str(origin).is_ok();
+
+            if !valid {
+                errors.push(format!(
+                    "cors.allowed_origins: '{}' is not an origin like https://example.com",
+                    origin
+                ));
+            }
+        }
+
+        for (group, limit) in [
+            ("auth", self.rate_limit.auth),
+            ("bulk", self.rate_limit.bulk),
+            ("default", self.rate_limit.default),
+        ] {
+            if limit.burst == 0 || limit.per_minute == 0 {
+                errors.push(format!(
+                    "rate_limit.{}: burst and per_minute must be at least 1",
+                    group
+                ));
+            }
+        }
+
+        if self.idempotency.ttl_secs == 0 {
+            errors.push("idempotency.ttl_secs must be at least 1".to_string());
+        }
+
+        if LevelFilter::from_str(&self.log.level).is_err() {
+            errors.push(format!(
+                "log.level must be off, error, warn, info, debug or trace, got '{}'",
+                self.log.level
+            ));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(errors))
+        }
+    }
+}
+
+/// Configuration file to read and whether it must exist
+fn config_file(args: &ConfigArgs, env: impl Fn(&str) -> Option<String>) -> (PathBuf, bool) {
+    if let Some(path) = &args.config {
+        return (path.clone(), true);
+    }
+
+    match env("TASK_API_CONFIG").filter(|path| !path.is_empty()) {
+        Some(path) => (PathBuf::from(path), true),
+        None => (PathBuf::from(DEFAULT_CONFIG_FILE), false),
+    }
+}
+
+fn parse_env<T>(var: &'static str, value: &str) -> Result<T, ConfigError>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    value.trim().parse().map_err(|e: T::Err| ConfigError::Env {
+        var,
+        message: format!("'{}': {}", value, e),
+    })
+}
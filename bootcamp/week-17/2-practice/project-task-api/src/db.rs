@@ -2,50 +2,49 @@
 
 use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
 
+use crate::config::DatabaseConfig;
+use crate::migrations;
+
 /// SQLite connection pool
 pub type DbPool = SqlitePool;
 
-/// Create SQLite connection pool
-pub async fn create_pool() -> Result<DbPool, sqlx::Error> {
-    let database_url = std::env::var("DATABASE_URL")
-        .unwrap_or_else(|_| "sqlite:tasks.db?mode=rwc".to_string());
-
-    let pool = SqlitePoolOptions::new()
-        .max_connections(5)
-        .connect(&database_url)
-        .await?;
-
-    // Create table if not exists
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS tasks (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            title TEXT NOT NULL,
-            description TEXT,
-            completed BOOLEAN NOT NULL DEFAULT FALSE,
-            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-            updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
-        )
-        "#,
-    )
-    .execute(&pool)
-    .await?;
-
-    // Create index for status searches
-    sqlx::query(
-        r#"
-        CREATE INDEX IF NOT EXISTS idx_tasks_completed 
-        ON tasks(completed)
-        "#,
-    )
-    .execute(&pool)
-    .await?;
+/// Create SQLite connection pool and apply pending migrations
+pub async fn create_pool(config: &DatabaseConfig) -> Result<DbPool, sqlx::Error> {
+    let pool = connect_with(config).await?;
+
+    migrations::migrate_up(&pool).await?;
 
     Ok(pool)
 }
 
-#[cfg(test)]
+/// Open a connection pool with default settings, without touching the schema
+pub async fn connect(database_url: &str) -> Result<DbPool, sqlx::Error> {
+    connect_with(&DatabaseConfig::with_url(database_url)).await
+}
+
+/// Open a connection pool without touching the schema
+pub async fn connect_with(config: &DatabaseConfig) -> Result<DbPool, sqlx::Error> {
+    let options = if config.url.contains(":memory:") {
+        // Each in-memory connection is a separate database: keep a single one alive
+        SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+    } else {
+        SqlitePoolOptions::new().max_connections(config.max_connections)
+    };
+
+    options
+        .acquire_timeout(config.acquire_timeout())
+        .connect(&config.url)
+        .await
+}
+
+/// Create a migrated in-memory pool (for tests)
 pub async fn create_test_pool() -> Result<DbPool, sqlx::Error> {
-    std::env::set_var("DATABASE_URL", "sqlite::memory:");
-    create_pool().await
+    let pool = connect("sqlite::memory:").await?;
+
+    migrations::migrate_up(&pool).await?;
+
+    Ok(pool)
 }
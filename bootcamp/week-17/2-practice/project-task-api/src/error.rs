@@ -1,13 +1,27 @@
 //! API Error Handling
+//!
+//! Every error is answered with problem details (RFC 7807) as
+//! `application/problem+json`. Validation errors list each invalid field in
+//! `errors`; `instance` is filled in with the request path by
+//! `problem_instance`.
+
+use std::error::Error as _;
 
 use axum::{
-    http::StatusCode,
+    body::Body,
+    extract::{rejection::JsonRejection, Request},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
     response::{IntoResponse, Response},
     Json,
 };
-use serde_json::json;
 use thiserror::Error;
 
+use crate::models::{ErrorResponse, FieldError};
+
+/// Media type of error responses
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
 /// API Errors
 #[derive(Error, Debug)]
 pub enum ApiError {
@@ -17,6 +31,36 @@ pub enum ApiError {
     #[error("Validation error: {0}")]
     Validation(String),
 
+    /// One or more invalid fields, all reported at once
+    #[error("Validation error: {}", join_messages(.0))]
+    Invalid(Vec<FieldError>),
+
+    /// Request body that could not be read as the expected JSON
+    #[error("Invalid JSON body: {0}")]
+    Json(#[from] JsonRejection),
+
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    /// Authenticated, but the caller's role does not allow the action
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    #[error("Precondition failed: {0}")]
+    PreconditionFailed(String),
+
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    #[error("Unprocessable: {0}")]
+    Unprocessable(String),
+
+    #[error("Payload too large: {0}")]
+    PayloadTooLarge(String),
+
+    #[error("Too many requests: {0}")]
+    TooManyRequests(String),
+
     #[error("Database error")]
     Database(#[from] sqlx::Error),
 
@@ -24,11 +68,26 @@ pub enum ApiError {
     Internal(String),
 }
 
-impl IntoResponse for ApiError {
-    fn into_response(self) -> Response {
-        let (status, message) = match &self {
+impl ApiError {
+    /// HTTP status and client-facing message
+    ///
+    /// Database details are logged and never sent to the client.
+    pub fn status_and_message(&self) -> (StatusCode, String) {
+        match self {
             ApiError::NotFound(msg) => (StatusCode::NOT_FOUND, msg.clone()),
             ApiError::Validation(msg) => (StatusCode::BAD_REQUEST, msg.clone()),
+            ApiError::Invalid(errors) => (StatusCode::BAD_REQUEST, join_messages(errors)),
+            ApiError::Json(rejection) => match rejection_field_error(rejection) {
+                Some(error) => (rejection.status(), error.message),
+                None => (rejection.status(), rejection.body_text()),
+            },
+            ApiError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, msg.clone()),
+            ApiError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg.clone()),
+            ApiError::PreconditionFailed(msg) => (StatusCode::PRECONDITION_FAILED, msg.clone()),
+            ApiError::Conflict(msg) => (StatusCode::CONFLICT, msg.clone()),
+            ApiError::Unprocessable(msg) => (StatusCode::UNPROCESSABLE_ENTITY, msg.clone()),
+            ApiError::PayloadTooLarge(msg) => (StatusCode::PAYLOAD_TOO_LARGE, msg.clone()),
+            ApiError::TooManyRequests(msg) => (StatusCode::TOO_MANY_REQUESTS, msg.clone()),
             ApiError::Database(e) => {
                 tracing::error!("Database error: {:?}", e);
                 (
@@ -37,16 +96,123 @@ impl IntoResponse for ApiError {
                 )
             }
             ApiError::Internal(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg.clone()),
+        }
+    }
+
+    /// Problem details of the error, without `instance`
+    pub fn problem(&self) -> ErrorResponse {
+        let (status, detail) = self.status_and_message();
+        let errors = match self {
+            ApiError::Invalid(errors) => errors.clone(),
+            ApiError::Json(rejection) => rejection_field_error(rejection).into_iter().collect(),
+            _ => Vec::new(),
         };
 
-        let body = json!({
-            "error": message,
-            "code": status.as_u16()
-        });
+        ErrorResponse {
+            problem_type: "about:blank".to_string(),
+            title: status.canonical_reason().unwrap_or("Error").to_string(),
+            status: status.as_u16(),
+            detail,
+            instance: None,
+            errors,
+        }
+    }
+}
+
+fn join_messages(errors: &[FieldError]) -> String {
+    errors
+        .iter()
+        .map(|error| error.message.as_str())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// The field a JSON body failed on, when the JSON itself was well-formed
+fn rejection_field_error(rejection: &JsonRejection) -> Option<FieldError> {
+    let JsonRejection::JsonDataError(data_error) = rejection else {
+        return None;
+    };
+    let error = data_error
+        .source()?
+        .source()?
+        .downcast_ref::<serde_path_to_error::Error<serde_json::Error>>()?;
+
+    // serde_json appends the position, which says nothing about the field
+    let inner = error.inner();
+    let message = inner.to_string();
+    let position = format!(" at line {} column {}", inner.line(), inner.column());
+    let message = message.strip_suffix(&position).unwrap_or(&message);
 
-        (status, Json(body)).into_response()
+    // The path ends at the bad value, or at the object missing a field
+    let path = error.path().to_string();
+    let missing = message
+        .strip_prefix("missing field `")
+        .and_then(|rest| rest.split('`').next());
+
+    let (field, code) = match missing {
+        Some(name) if path == "." => (name.to_string(), "required"),
+        Some(name) => (format!("{}.{}", path, name), "required"),
+        None if message.starts_with("unknown field `") => (path, "unknown_field"),
+        None => (path, "invalid"),
+    };
+
+    Some(FieldError::new(field, code, message))
+}
+
+impl IntoResponse for ErrorResponse {
+    fn into_response(self) -> Response {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let mut response =
+            (status, [(header::CONTENT_TYPE, PROBLEM_JSON)], Json(&self)).into_response();
+
+        // Kept so `with_instance` can fill in the request path later
+        response.extensions_mut().insert(self);
+        response
     }
 }
 
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let mut response = self.problem().into_response();
+
+        if response.status() == StatusCode::UNAUTHORIZED {
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        }
+
+        response
+    }
+}
+
+/// `response` with `instance` set, if it is an error response
+pub fn with_instance(response: Response, instance: &str) -> Response {
+    let Some(problem) = response.extensions().get::<ErrorResponse>() else {
+        return response;
+    };
+
+    let problem = ErrorResponse {
+        instance: Some(instance.to_string()),
+        ..problem.clone()
+    };
+    let Ok(body) = serde_json::to_vec(&problem) else {
+        return response;
+    };
+
+    let (mut parts, _) = response.into_parts();
+    parts.extensions.insert(problem);
+
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// Middleware setting `instance` of error responses to the request path
+///
+/// The query string is left out: it may hold an access token.
+pub async fn problem_instance(request: Request, next: Next) -> Response {
+    let path = request.uri().path().to_string();
+
+    with_instance(next.run(request).await, &path)
+}
+
 /// Result type for handlers
 pub type Result<T> = std::result::Result<T, ApiError>;
@@ -0,0 +1,69 @@
+//! Conditional Requests
+//!
+//! Every task carries a `version` that is bumped on each change and sent as
+//! its `ETag`. Clients send it back in `If-Match` to avoid overwriting someone
+//! else's edit, or in `If-None-Match` to poll cheaply.
+
+use axum::{
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+
+use crate::error::ApiError;
+use crate::models::Task;
+
+/// Entity tag of a task version, e.g. `"3"`
+pub fn etag(task: &Task) -> String {
+    format!("\"{}\"", task.version)
+}
+
+/// Task response with its `ETag` header
+pub struct Versioned(pub Task);
+
+impl IntoResponse for Versioned {
+    fn into_response(self) -> Response {
+        let etag = HeaderValue::from_str(&etag(&self.0)).expect("ETag is valid ASCII");
+
+        ([(header::ETAG, etag)], Json(self.0)).into_response()
+    }
+}
+
+/// Whether a header lists the task's entity tag (or `*`), `None` if absent
+///
+/// `If-Match` uses strong comparison and `If-None-Match` weak comparison,
+/// where a `W/` prefix is ignored (RFC 9110).
+fn matches(headers: &HeaderMap, name: header::HeaderName, task: &Task) -> Option<bool> {
+    let weak = name == header::IF_NONE_MATCH;
+    let value = headers.get(&name)?.to_str().ok()?;
+    let current = etag(task);
+
+    Some(value.split(',').map(str::trim).any(|tag| {
+        let tag = if weak { tag.strip_prefix("W/").unwrap_or(tag) } else { tag };
+        tag == "*" || tag == current
+    }))
+}
+
+/// Reject a write whose `If-Match` does not name the current version
+///
+/// Requests without `If-Match` are unconditional.
+pub fn check_if_match(headers: &HeaderMap, task: &Task) -> Result<(), ApiError> {
+    match matches(headers, header::IF_MATCH, task) {
+        Some(false) => Err(ApiError::PreconditionFailed(format!(
+            "Task {} has changed (current ETag: {})",
+            task.id,
+            etag(task)
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// `304 Not Modified` if `If-None-Match` names the current version
+pub fn not_modified(headers: &HeaderMap, task: &Task) -> Option<Response> {
+    if matches(headers, header::IF_NONE_MATCH, task) != Some(true) {
+        return None;
+    }
+
+    let etag = HeaderValue::from_str(&etag(task)).expect("ETag is valid ASCII");
+    Some((StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response())
+}
@@ -0,0 +1,136 @@
+//! Live Task Change Feed
+//!
+//! Mutating handlers publish every change to a `tokio::sync::broadcast`
+//! channel. Each SSE or WebSocket connection holds a receiver and only sees
+//! the changes of its own user's tasks.
+//!
+//! The webhook worker reads from a separate unbounded queue instead, so
+//! bursts such as bulk writes reach it in full and publishing never waits.
+//! The queue lives in memory only: changes committed but not yet read by the
+//! worker are lost if the process stops, so webhooks are delivered at most
+//! once for that window.
+//!
+//! `EventBus::close` ends every subscription, so open feeds do not hold up a
+//! graceful shutdown.
+
+use std::sync::{Arc, Mutex};
+
+use futures_util::{Stream, StreamExt};
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio_stream::wrappers::{BroadcastStream, UnboundedReceiverStream};
+
+use crate::models::{ChangeEvent, ChangeKind, EventFilters, Task};
+
+/// Changes buffered per subscriber before a slow one starts missing some
+const CAPACITY: usize = 256;
+
+/// Change together with the user it belongs to
+#[derive(Debug, Clone)]
+struct Published {
+    owner_id: i64,
+    event: ChangeEvent,
+}
+
+/// Broadcast channel of task changes
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<Published>,
+    queue: Arc<Mutex<Option<mpsc::UnboundedSender<Published>>>>,
+    closed: Arc<watch::Sender<bool>>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventBus {
+    /// Channel with no subscribers yet
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CAPACITY);
+        let (closed, _) = watch::channel(false);
+
+        Self {
+            sender,
+            queue: Arc::default(),
+            closed: Arc::new(closed),
+        }
+    }
+
+    /// End every subscription, current and future
+    pub fn close(&self) {
+        self.closed.send_replace(true);
+    }
+
+    /// Announce a change of one of `owner_id`'s tasks
+    ///
+    /// Call it once the change is committed. Having no subscribers is fine.
+    pub fn publish(&self, owner_id: i64, kind: ChangeKind, task: Task) {
+        let published = Published {
+            owner_id,
+            event: ChangeEvent { kind, task },
+        };
+
+        if let Some(queue) = &*self.queue.lock().expect("Queue lock poisoned") {
+            // Fails only once the consumer is gone
+            let _ = queue.send(published.clone());
+        }
+
+        let _ = self.sender.send(published);
+    }
+
+    /// Changes of `owner_id`'s tasks that pass `filters`, from now on
+    pub fn subscribe(
+        &self,
+        owner_id: i64,
+        filters: EventFilters,
+    ) -> impl Stream<Item = ChangeEvent> + Send + use<> {
+        self.subscribe_all().filter_map(move |(owner, event)| {
+            std::future::ready((owner == owner_id && filters.matches(&event)).then_some(event))
+        })
+    }
+
+    /// Every change with the user it belongs to, from now on
+    ///
+    /// A subscriber that falls more than the channel capacity behind skips
+    /// the changes it missed. The stream ends once the bus is closed.
+    pub fn subscribe_all(&self) -> impl Stream<Item = (i64, ChangeEvent)> + Send + use<> {
+        let mut closed = self.closed.subscribe();
+        let closed = async move {
+            let _ = closed.wait_for(|closed| *closed).await;
+        };
+
+        BroadcastStream::new(self.sender.subscribe()).filter_map(|received| {
+            let change = match received {
+                Ok(published) => Some((published.owner_id, published.event)),
+                Err(error) => {
+                    tracing::warn!("Change subscriber lagging behind: {}", error);
+                    None
+                }
+            };
+
+            std::future::ready(change)
+        })
+        .take_until(closed)
+    }
+
+    /// Every change with the user it belongs to, from now on, without gaps
+    ///
+    /// There is a single queue: a new call replaces the previous consumer.
+    /// Changes wait in memory until read. The stream ends once the bus is
+    /// closed.
+    pub fn queue_all(&self) -> impl Stream<Item = (i64, ChangeEvent)> + Send + use<> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        *self.queue.lock().expect("Queue lock poisoned") = Some(sender);
+
+        let mut closed = self.closed.subscribe();
+        let closed = async move {
+            let _ = closed.wait_for(|closed| *closed).await;
+        };
+
+        UnboundedReceiverStream::new(receiver)
+            .map(|published| (published.owner_id, published.event))
+            .take_until(closed)
+    }
+}
@@ -1,90 +1,160 @@
 //! Task API Handlers
 
+use std::collections::HashMap;
+use std::io;
+use std::time::Duration;
+
 use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
-    response::Json,
+    body::{Body, Bytes},
+    extract::{
+        ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade},
+        Path, Query, State,
+    },
+    http::{header, HeaderMap, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
 };
-use sqlx::SqlitePool;
+use axum_extra::extract::Query as MultiQuery;
+use futures_util::{Stream, StreamExt};
+use sqlx::{Connection, SqliteConnection, SqlitePool};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
 
+use crate::audit;
+use crate::auth::{self, AuthConfig, CurrentUser};
 use crate::error::{ApiError, Result};
-use crate::models::{CreateTask, Task, TaskFilters, TaskStats, UpdateTask};
+use crate::etag::{self, Versioned};
+use crate::events::EventBus;
+use crate::extract::Json;
+use crate::migrations;
+use crate::models::{
+    AuthToken, BuildInfo, BulkItemResult, BulkMode, BulkOperation, BulkRequest, BulkResponse,
+    ChangeEvent, ChangeKind, CreateProject, CreateTask, CreateWebhook, Credentials, DeliveryQuery,
+    DeliveryStatus, EventFilters, ExportFormat, FormatQuery, HealthStatus, ImportReport,
+    ImportRowError, Liveness, MigrationState, NewWebhook, Occurrence, OccurrenceQuery, Project,
+    ProjectMember, ProjectRole, Readiness, Recurrence, RecurrenceRule, ReplaceTask, SearchQuery,
+    SearchResult, SetMemberRole, TagCount, TagMatch, TagsInput, Task, TaskEvent, TaskEventKind,
+    TaskFilters, TaskList, TaskNode, TaskSort, TaskStats, UpdateOptions, UpdateTask, User, Webhook,
+    WebhookDelivery,
+};
+use crate::projects::{self, ProjectAccess};
+use crate::recurrence::{self, Schedule};
+use crate::repository::sqlite::{
+    attach_tags, fetch_task, fetch_task_in, filtered_tasks_query, insert_task, insert_task_with,
+    order_by, save_task, soft_delete, Scope, SqliteTaskRepository, TASK_COLUMNS,
+};
+use crate::repository::{normalize_tags, TaskChange, TaskRepository, TaskValues};
+use crate::state::AppState;
+use crate::transfer::{self, Encoder, ImportRow, ImportRows};
+use crate::webhooks;
 
 /// List all tasks
 ///
 /// Gets a list of tasks with support for filters and pagination.
+///
+/// Passing `cursor` (empty for the first page) switches to keyset pagination
+/// and returns a `TaskPage` envelope; otherwise `limit`/`offset` apply and a
+/// plain array is returned.
 #[utoipa::path(
     get,
     path = "/tasks",
     params(
         ("completed" = Option<bool>, Query, description = "Filter by completion status"),
-        ("limit" = Option<i64>, Query, description = "Result limit (default: 100)"),
-        ("offset" = Option<i64>, Query, description = "Offset for pagination (default: 0)")
+        ("limit" = Option<i64>, Query, description = "Result limit (default: 100, 1-100 with `cursor`)"),
+        ("offset" = Option<i64>, Query, description = "Offset for pagination (default: 0)"),
+        ("tag" = Option<Vec<String>>, Query, description = "Filter by tag, repeatable (`tag=a&tag=b`)"),
+        ("tag_match" = Option<TagMatch>, Query, description = "Require `any` (default) or `all` of the tags"),
+        ("overdue" = Option<bool>, Query, description = "Only pending tasks past their deadline"),
+        ("sort" = Option<TaskSort>, Query, description = "`created` (default), `due_at` or `priority`"),
+        ("cursor" = Option<String>, Query, description = "Keyset cursor from `next_cursor` (empty for the first page)")
     ),
     responses(
-        (status = 200, description = "List of tasks", body = Vec<Task>),
-        (status = 500, description = "Internal error", body = crate::models::ErrorResponse)
+        (status = 200, description = "List of tasks", body = TaskList),
+        (status = 400, description = "Invalid cursor, limit or tag", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal error", body = crate::models::ErrorResponse, content_type = "application/problem+json")
     ),
+    security(("bearer_auth" = [])),
     tag = "Tasks"
 )]
-pub async fn list_tasks(
-    State(pool): State<SqlitePool>,
-    Query(filters): Query<TaskFilters>,
-) -> Result<Json<Vec<Task>>> {
-    let limit = filters.limit.unwrap_or(100);
-    let offset = filters.offset.unwrap_or(0);
-
-    let tasks = match filters.completed {
-        Some(completed) => {
-            sqlx::query_as::<_, Task>(
-                "SELECT id, title, description, completed, created_at, updated_at FROM tasks WHERE completed = ? ORDER BY id DESC LIMIT ? OFFSET ?",
-            )
-            .bind(completed)
-            .bind(limit)
-            .bind(offset)
-            .fetch_all(&pool)
-            .await?
-        }
-        None => {
-            sqlx::query_as::<_, Task>(
-                "SELECT id, title, description, completed, created_at, updated_at FROM tasks ORDER BY id DESC LIMIT ? OFFSET ?",
-            )
-            .bind(limit)
-            .bind(offset)
-            .fetch_all(&pool)
-            .await?
-        }
-    };
+pub async fn list_tasks<R: TaskRepository>(
+    State(state): State<AppState<R>>,
+    user: CurrentUser,
+    MultiQuery(filters): MultiQuery<TaskFilters>,
+) -> Result<Json<TaskList>> {
+    let tasks = state.tasks.list(user.id, filters).await?;
 
     Ok(Json(tasks))
 }
 
+/// Search tasks
+///
+/// Full-text search over title and description, ranked by relevance.
+/// Every word is matched as a prefix (`rus` finds "Rust").
+#[utoipa::path(
+    get,
+    path = "/tasks/search",
+    params(
+        ("q" = String, Query, description = "Search terms"),
+        ("completed" = Option<bool>, Query, description = "Filter by completion status"),
+        ("limit" = Option<i64>, Query, description = "Result limit (default: 20, at most 100)")
+    ),
+    responses(
+        (status = 200, description = "Matching tasks, most relevant first", body = Vec<SearchResult>),
+        (status = 400, description = "Empty search or invalid limit", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Tasks"
+)]
+pub async fn search_tasks(
+    State(pool): State<SqlitePool>,
+    user: CurrentUser,
+    Query(query): Query<SearchQuery>,
+) -> Result<Json<Vec<SearchResult>>> {
+    let results = SqliteTaskRepository::new(pool)
+        .search(user.id, &query)
+        .await?;
+
+    Ok(Json(results))
+}
+
 /// Get a task by ID
 ///
-/// Returns details of a specific task.
+/// Returns details of a specific task, with its version as `ETag`. Sending
+/// that value in `If-None-Match` returns `304 Not Modified` if it is unchanged.
 #[utoipa::path(
     get,
     path = "/tasks/{id}",
     params(
-        ("id" = i64, Path, description = "Task ID")
+        ("id" = i64, Path, description = "Task ID"),
+        ("If-None-Match" = Option<String>, Header, description = "ETag of a cached copy")
     ),
     responses(
-        (status = 200, description = "Task found", body = Task),
-        (status = 404, description = "Task not found", body = crate::models::ErrorResponse)
+        (status = 200, description = "Task found", body = Task,
+            headers(("ETag" = String, description = "Current task version"))),
+        (status = 304, description = "Task unchanged since the given ETag"),
+        (status = 404, description = "Task not found", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
     ),
+    security(("bearer_auth" = [])),
     tag = "Tasks"
 )]
-pub async fn get_task(
-    State(pool): State<SqlitePool>,
+pub async fn get_task<R: TaskRepository>(
+    State(state): State<AppState<R>>,
+    user: CurrentUser,
     Path(id): Path<i64>,
-) -> Result<Json<Task>> {
-    let task = sqlx::query_as::<_, Task>("SELECT id, title, description, completed, created_at, updated_at FROM tasks WHERE id = ?")
-        .bind(id)
-        .fetch_optional(&pool)
-        .await?
-        .ok_or_else(|| ApiError::NotFound(format!("Task {} not found", id)))?;
+    headers: HeaderMap,
+) -> Result<Response> {
+    let task = state.tasks.get(user.id, id).await?;
 
-    Ok(Json(task))
+    if let Some(response) = etag::not_modified(&headers, &task) {
+        return Ok(response);
+    }
+
+    Ok(Versioned(task).into_response())
 }
 
 /// Create a new task
@@ -94,173 +164,2569 @@ pub async fn get_task(
     post,
     path = "/tasks",
     request_body = CreateTask,
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Replay the stored response when sent again")
+    ),
     responses(
-        (status = 201, description = "Task created successfully", body = Task),
-        (status = 400, description = "Validation error", body = crate::models::ErrorResponse)
+        (status = 201, description = "Task created successfully", body = Task,
+            headers(("ETag" = String, description = "Task version"))),
+        (status = 400, description = "Validation error", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 409, description = "A request with the same Idempotency-Key is still running", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "Idempotency-Key reused for a different request", body = crate::models::ErrorResponse, content_type = "application/problem+json")
     ),
+    security(("bearer_auth" = [])),
     tag = "Tasks"
 )]
-pub async fn create_task(
-    State(pool): State<SqlitePool>,
+pub async fn create_task<R: TaskRepository>(
+    State(state): State<AppState<R>>,
+    user: CurrentUser,
     Json(data): Json<CreateTask>,
-) -> Result<(StatusCode, Json<Task>)> {
-    // Validation
-    if data.title.trim().is_empty() {
-        return Err(ApiError::Validation("Title is required".into()));
-    }
+) -> Result<(StatusCode, Versioned)> {
+    let task = state.tasks.create(&user, data).await?;
+    state.events.publish(user.id, ChangeKind::Created, task.clone());
 
-    if data.title.len() > 200 {
-        return Err(ApiError::Validation(
-            "Title cannot exceed 200 characters".into(),
-        ));
-    }
+    Ok((StatusCode::CREATED, Versioned(task)))
+}
 
-    let result = sqlx::query("INSERT INTO tasks (title, description) VALUES (?, ?)")
-        .bind(&data.title)
-        .bind(&data.description)
-        .execute(&pool)
+/// Replace a task
+///
+/// Overwrites every field of a task; omitted optional fields are cleared.
+/// With `cascade=true`, `completed` is applied to all its subtasks too.
+#[utoipa::path(
+    put,
+    path = "/tasks/{id}",
+    params(
+        ("id" = i64, Path, description = "ID of the task to replace"),
+        ("cascade" = Option<bool>, Query, description = "Propagate `completed` to subtasks"),
+        ("If-Match" = Option<String>, Header, description = "Only replace if the task still has this ETag")
+    ),
+    request_body = ReplaceTask,
+    responses(
+        (status = 200, description = "Task replaced", body = Task,
+            headers(("ETag" = String, description = "New task version"))),
+        (status = 400, description = "Validation error", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Task not found", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 412, description = "Task changed since the given ETag", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Tasks"
+)]
+pub async fn replace_task<R: TaskRepository>(
+    State(state): State<AppState<R>>,
+    user: CurrentUser,
+    Path(id): Path<i64>,
+    Query(options): Query<UpdateOptions>,
+    headers: HeaderMap,
+    Json(data): Json<ReplaceTask>,
+) -> Result<Versioned> {
+    let cascade = options.cascade == Some(true);
+    let if_match = |task: &Task| etag::check_if_match(&headers, task);
+
+    let task = state
+        .tasks
+        .update(&user, id, TaskChange::Replace(data), cascade, &if_match)
         .await?;
+    state.events.publish(user.id, ChangeKind::Updated, task.clone());
 
-    let id = result.last_insert_rowid();
+    Ok(Versioned(task))
+}
 
-    let task = sqlx::query_as::<_, Task>("SELECT id, title, description, completed, created_at, updated_at FROM tasks WHERE id = ?")
-        .bind(id)
-        .fetch_one(&pool)
+/// Update an existing task
+///
+/// Applies a JSON Merge Patch (RFC 7386): only the given fields change and
+/// `null` clears a field. The whole patch is applied atomically. With
+/// `cascade=true`, a change of `completed` is applied to all its subtasks too.
+#[utoipa::path(
+    patch,
+    path = "/tasks/{id}",
+    params(
+        ("id" = i64, Path, description = "ID of the task to update"),
+        ("cascade" = Option<bool>, Query, description = "Propagate `completed` to subtasks"),
+        ("If-Match" = Option<String>, Header, description = "Only update if the task still has this ETag")
+    ),
+    request_body(content = UpdateTask, content_type = "application/merge-patch+json"),
+    responses(
+        (status = 200, description = "Task updated", body = Task,
+            headers(("ETag" = String, description = "New task version"))),
+        (status = 400, description = "Validation error", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Task not found", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 412, description = "Task changed since the given ETag", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Tasks"
+)]
+pub async fn update_task<R: TaskRepository>(
+    State(state): State<AppState<R>>,
+    user: CurrentUser,
+    Path(id): Path<i64>,
+    Query(options): Query<UpdateOptions>,
+    headers: HeaderMap,
+    Json(patch): Json<UpdateTask>,
+) -> Result<Versioned> {
+    let cascade = options.cascade == Some(true) && patch.completed.is_some();
+    let if_match = |task: &Task| etag::check_if_match(&headers, task);
+
+    let task = state
+        .tasks
+        .update(&user, id, TaskChange::Patch(patch), cascade, &if_match)
         .await?;
+    state.events.publish(user.id, ChangeKind::Updated, task.clone());
 
-    Ok((StatusCode::CREATED, Json(task)))
+    Ok(Versioned(task))
 }
 
-/// Update an existing task
+/// Delete a task
 ///
-/// Updates one or more fields of a task.
+/// Soft-deletes a task and its subtasks: they disappear from every listing
+/// but keep their history and can be brought back with `POST /tasks/{id}/restore`.
 #[utoipa::path(
-    put,
+    delete,
     path = "/tasks/{id}",
     params(
-        ("id" = i64, Path, description = "ID of the task to update")
+        ("id" = i64, Path, description = "ID of the task to delete"),
+        ("If-Match" = Option<String>, Header, description = "Only delete if the task still has this ETag")
     ),
-    request_body = UpdateTask,
     responses(
-        (status = 200, description = "Task updated", body = Task),
-        (status = 400, description = "Validation error", body = crate::models::ErrorResponse),
-        (status = 404, description = "Task not found", body = crate::models::ErrorResponse)
+        (status = 204, description = "Task deleted successfully"),
+        (status = 404, description = "Task not found", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 412, description = "Task changed since the given ETag", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
     ),
+    security(("bearer_auth" = [])),
     tag = "Tasks"
 )]
-pub async fn update_task(
-    State(pool): State<SqlitePool>,
+pub async fn delete_task<R: TaskRepository>(
+    State(state): State<AppState<R>>,
+    user: CurrentUser,
     Path(id): Path<i64>,
-    Json(data): Json<UpdateTask>,
-) -> Result<Json<Task>> {
-    // Verify it exists
-    let exists = sqlx::query("SELECT id FROM tasks WHERE id = ?")
-        .bind(id)
-        .fetch_optional(&pool)
-        .await?;;
+    headers: HeaderMap,
+) -> Result<StatusCode> {
+    let if_match = |task: &Task| etag::check_if_match(&headers, task);
+    let task = state.tasks.delete(&user, id, &if_match).await?;
+    state.events.publish(user.id, ChangeKind::Deleted, task);
 
-    if exists.is_none() {
-        return Err(ApiError::NotFound(format!("Task {} not found", id)));
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Maximum number of operations in one bulk request
+const BULK_LIMIT: usize = 1000;
+
+/// Run task operations in bulk
+///
+/// Applies create/update/delete operations in order within one transaction
+/// and reports a result per operation. In `atomic` mode (default) a failure
+/// rolls everything back and the response takes the status of the failing
+/// operation (400 or 404) with a `BulkResponse` body; in `best_effort` mode
+/// failed operations are skipped.
+#[utoipa::path(
+    post,
+    path = "/tasks/bulk",
+    request_body = BulkRequest,
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Replay the stored response when sent again")
+    ),
+    responses(
+        (status = 200, description = "Operations applied, see each result", body = BulkResponse),
+        (status = 400, description = "Empty or oversized request (problem details), or an operation failed validation in atomic mode (`BulkResponse`)",
+            content(
+                (BulkResponse = "application/json"),
+                (crate::models::ErrorResponse = "application/problem+json")
+            )),
+        (status = 404, description = "An operation targets a missing task in atomic mode; nothing was applied", body = BulkResponse),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 409, description = "A request with the same Idempotency-Key is still running", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "Idempotency-Key reused for a different request", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Tasks"
+)]
+pub async fn bulk_tasks(
+    State(pool): State<SqlitePool>,
+    State(events): State<EventBus>,
+    user: CurrentUser,
+    Json(request): Json<BulkRequest>,
+) -> Result<(StatusCode, Json<BulkResponse>)> {
+    let total = request.operations.len();
+
+    if total == 0 {
+        return Err(ApiError::Validation("At least one operation is required".into()));
+    }
+
+    if total > BULK_LIMIT {
+        return Err(ApiError::Validation(format!(
+            "A bulk request accepts at most {} operations",
+            BULK_LIMIT
+        )));
     }
 
-    // Update provided fields
-    if let Some(title) = &data.title {
-        if title.trim().is_empty() {
-            return Err(ApiError::Validation("Title cannot be empty".into()));
+    let mut tx = pool.begin_with("BEGIN IMMEDIATE").await?;
+    let mut results = Vec::with_capacity(total);
+    let mut changes = Vec::new();
+    // Index and status of the operation that aborted an atomic request
+    let mut aborted: Option<(usize, StatusCode)> = None;
+
+    for (index, operation) in request.operations.into_iter().enumerate() {
+        if let Some((failed, _)) = aborted {
+            results.push(BulkItemResult {
+                index,
+                status: StatusCode::FAILED_DEPENDENCY.as_u16(),
+                task: None,
+                error: Some(format!("Not applied: operation {} failed", failed)),
+            });
+            continue;
+        }
+
+        // A savepoint per operation, so a failure only undoes its own changes
+        let mut savepoint = tx.begin().await?;
+
+        match apply_bulk_operation(&mut savepoint, &user, operation).await {
+            Ok((kind, task)) => {
+                savepoint.commit().await?;
+                let status = match kind {
+                    ChangeKind::Created => StatusCode::CREATED,
+                    ChangeKind::Updated => StatusCode::OK,
+                    ChangeKind::Deleted => StatusCode::NO_CONTENT,
+                };
+                results.push(BulkItemResult {
+                    index,
+                    status: status.as_u16(),
+                    task: (kind != ChangeKind::Deleted).then(|| task.clone()),
+                    error: None,
+                });
+                changes.push((kind, task));
+            }
+            Err(error) => {
+                savepoint.rollback().await?;
+                let (status, message) = error.status_and_message();
+                results.push(BulkItemResult {
+                    index,
+                    status: status.as_u16(),
+                    task: None,
+                    error: Some(message),
+                });
+
+                if request.mode == BulkMode::Atomic {
+                    aborted = Some((index, status));
+                }
+            }
         }
-        sqlx::query(
-            "UPDATE tasks SET title = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
-        )
-        .bind(title)
-        .bind(id)
-        .execute(&pool)
-        .await?;
     }
 
-    if let Some(description) = &data.description {
-        sqlx::query(
-            "UPDATE tasks SET description = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
-        )
-        .bind(description)
-        .bind(id)
-        .execute(&pool)
-        .await?;
+    if let Some((failed, status)) = aborted {
+        tx.rollback().await?;
+
+        for result in results.iter_mut().filter(|r| r.index < failed) {
+            result.status = StatusCode::FAILED_DEPENDENCY.as_u16();
+            result.task = None;
+            result.error = Some(format!("Rolled back: operation {} failed", failed));
+        }
+
+        let response = BulkResponse {
+            committed: false,
+            succeeded: 0,
+            failed: total,
+            results,
+        };
+        return Ok((status, Json(response)));
     }
 
-    if let Some(completed) = data.completed {
-        sqlx::query(
-            "UPDATE tasks SET completed = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
-        )
-        .bind(completed)
-        .bind(id)
-        .execute(&pool)
-        .await?;
+    tx.commit().await?;
+
+    for (kind, task) in changes {
+        events.publish(user.id, kind, task);
     }
 
-    // Get updated task
-    let task = sqlx::query_as::<_, Task>("SELECT id, title, description, completed, created_at, updated_at FROM tasks WHERE id = ?")
-        .bind(id)
-        .fetch_one(&pool)
-        .await?;
+    let failed = results.iter().filter(|r| r.error.is_some()).count();
+    Ok((
+        StatusCode::OK,
+        Json(BulkResponse {
+            committed: true,
+            succeeded: total - failed,
+            failed,
+            results,
+        }),
+    ))
+}
 
-    Ok(Json(task))
+/// Apply one bulk operation with the same rules as its single-task endpoint
+///
+/// Returns the change made and the task (as it was, for a delete).
+async fn apply_bulk_operation(
+    conn: &mut SqliteConnection,
+    user: &CurrentUser,
+    operation: BulkOperation,
+) -> Result<(ChangeKind, Task)> {
+    match operation {
+        BulkOperation::Create { task } => {
+            let task = insert_task(conn, user, Scope::Owner(user.id), task).await?;
+            Ok((ChangeKind::Created, task))
+        }
+        BulkOperation::Update { id, patch } => {
+            let before = fetch_task(&mut *conn, id, user.id).await?;
+            let values = TaskValues::merge(&before, patch)?;
+            let task = save_task(conn, user, Scope::Owner(user.id), &before, values, false).await?;
+            Ok((ChangeKind::Updated, task))
+        }
+        BulkOperation::Delete { id } => {
+            let task = fetch_task(&mut *conn, id, user.id).await?;
+            soft_delete(conn, user, id).await?;
+            Ok((ChangeKind::Deleted, task))
+        }
+    }
 }
 
-/// Delete a task
+/// Export tasks
 ///
-/// Permanently removes a task from the database.
+/// Streams the caller's tasks as JSON, NDJSON or CSV, straight from the
+/// database. Takes the same filters as `GET /tasks`; without `limit` every
+/// matching task is exported.
 #[utoipa::path(
-    delete,
-    path = "/tasks/{id}",
+    get,
+    path = "/tasks/export",
     params(
-        ("id" = i64, Path, description = "ID of the task to delete")
+        ("format" = Option<ExportFormat>, Query, description = "`json` (default), `ndjson` or `csv`"),
+        ("completed" = Option<bool>, Query, description = "Filter by completion status"),
+        ("tag" = Option<Vec<String>>, Query, description = "Filter by tag, repeatable (`tag=a&tag=b`)"),
+        ("tag_match" = Option<TagMatch>, Query, description = "Require `any` (default) or `all` of the tags"),
+        ("overdue" = Option<bool>, Query, description = "Only pending tasks past their deadline"),
+        ("sort" = Option<TaskSort>, Query, description = "`created` (default), `due_at` or `priority`"),
+        ("limit" = Option<i64>, Query, description = "Maximum number of tasks, at least 1 (default: all)"),
+        ("offset" = Option<i64>, Query, description = "Tasks to skip, not negative (default: 0)")
     ),
     responses(
-        (status = 204, description = "Task deleted successfully"),
-        (status = 404, description = "Task not found", body = crate::models::ErrorResponse)
+        (status = 200, description = "Exported tasks", content(
+            (Vec<Task> = "application/json"),
+            (String = "application/x-ndjson"),
+            (String = "text/csv")
+        )),
+        (status = 400, description = "Invalid filter or format", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
     ),
+    security(("bearer_auth" = [])),
     tag = "Tasks"
 )]
-pub async fn delete_task(
+pub async fn export_tasks(
     State(pool): State<SqlitePool>,
-    Path(id): Path<i64>,
-) -> Result<StatusCode> {
-    let result = sqlx::query("DELETE FROM tasks WHERE id = ?")
-        .bind(id)
-        .execute(&pool)
-        .await?;
+    user: CurrentUser,
+    MultiQuery(filters): MultiQuery<TaskFilters>,
+    Query(options): Query<FormatQuery>,
+) -> Result<Response> {
+    if filters.cursor.is_some() {
+        return Err(ApiError::Validation(
+            "Exports do not support cursor pagination".into(),
+        ));
+    }
 
-    if result.rows_affected() == 0 {
-        return Err(ApiError::NotFound(format!("Task {} not found", id)));
+    if filters.limit.is_some_and(|limit| limit < 1) {
+        return Err(ApiError::Validation("Limit must be at least 1".into()));
     }
 
-    Ok(StatusCode::NO_CONTENT)
+    if filters.offset.is_some_and(|offset| offset < 0) {
+        return Err(ApiError::Validation("Offset cannot be negative".into()));
+    }
+
+    let format = options.format.unwrap_or_default();
+    let mut query = filtered_tasks_query(Scope::Owner(user.id), &filters)?;
+    query.push(order_by(filters.sort.unwrap_or_default()));
+
+    if filters.limit.is_some() || filters.offset.is_some() {
+        // Without a limit, -1 keeps every row (SQLite needs a LIMIT before OFFSET)
+        query
+            .push(" LIMIT ")
+            .push_bind(filters.limit.unwrap_or(-1))
+            .push(" OFFSET ")
+            .push_bind(filters.offset.unwrap_or(0));
+    }
+
+    // Rows are encoded as they arrive and handed to the body through a channel
+    let (sender, receiver) = mpsc::channel::<io::Result<Bytes>>(32);
+
+    tokio::spawn(async move {
+        let mut encoder = Encoder::new(format);
+
+        if sender.send(encoder.start()).await.is_err() {
+            return;
+        }
+
+        let mut rows = query.build_query_as::<Task>().fetch(&pool);
+
+        while let Some(row) = rows.next().await {
+            let chunk = row
+                .map_err(|e| {
+                    tracing::error!("Export failed: {:?}", e);
+                    io::Error::other("export failed")
+                })
+                .and_then(|task| encoder.task(&task));
+            let failed = chunk.is_err();
+
+            // Stop when the client is gone; an error aborts the body
+            if sender.send(chunk).await.is_err() || failed {
+                return;
+            }
+        }
+
+        let _ = sender.send(Ok(encoder.finish())).await;
+    });
+
+    let disposition = format!(
+        "attachment; filename=\"tasks.{}\"",
+        transfer::extension(format)
+    );
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, transfer::content_type(format).to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        Body::from_stream(ReceiverStream::new(receiver)),
+    )
+        .into_response())
 }
 
-/// Get task statistics
+/// Import tasks
+///
+/// Creates tasks from a JSON, NDJSON or CSV file in the export layout. The
+/// format comes from `format` or else the `Content-Type`. `id`/`parent_id`
+/// link rows of the same file into trees and are replaced by new ids.
 ///
-/// Returns total count, completed and pending tasks.
+/// The import is all-or-nothing: if any row is invalid nothing is created
+/// and the report lists every invalid row.
 #[utoipa::path(
-    get,
-    path = "/tasks/stats",
+    post,
+    path = "/tasks/import",
+    params(
+        ("format" = Option<ExportFormat>, Query, description = "`json`, `ndjson` or `csv` (default: from `Content-Type`)")
+    ),
+    request_body(description = "Tasks in the export layout", content(
+        (Vec<Task> = "application/json"),
+        (String = "application/x-ndjson"),
+        (String = "text/csv")
+    )),
     responses(
-        (status = 200, description = "Task statistics", body = TaskStats)
+        (status = 201, description = "Every task imported", body = ImportReport),
+        (status = 400, description = "Invalid rows, nothing imported", body = ImportReport),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
     ),
-    tag = "Statistics"
+    security(("bearer_auth" = [])),
+    tag = "Tasks"
 )]
-pub async fn get_stats(State(pool): State<SqlitePool>) -> Result<Json<TaskStats>> {
-    let total: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM tasks")
-        .fetch_one(&pool)
-        .await?;
+pub async fn import_tasks(
+    State(pool): State<SqlitePool>,
+    State(events): State<EventBus>,
+    user: CurrentUser,
+    Query(options): Query<FormatQuery>,
+    headers: HeaderMap,
+    body: String,
+) -> Result<(StatusCode, Json<ImportReport>)> {
+    let format = options
+        .format
+        .or_else(|| {
+            headers
+                .get(header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(transfer::format_of_content_type)
+        })
+        .unwrap_or_default();
+
+    let rows = transfer::parse(format, &body).map_err(ApiError::Validation)?;
 
-    let completed_count: (i64,) =
-        sqlx::query_as("SELECT COUNT(*) FROM tasks WHERE completed = TRUE")
-            .fetch_one(&pool)
+    if rows.is_empty() {
+        return Err(ApiError::Validation("The file contains no tasks".into()));
+    }
+
+    let (rows, errors) = validate_import(rows);
+
+    if !errors.is_empty() {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(ImportReport {
+                imported: 0,
+                errors,
+            }),
+        ));
+    }
+
+    let mut tx = pool.begin_with("BEGIN IMMEDIATE").await?;
+
+    // Parents are created before their subtasks, so every task is inserted
+    // once, complete with its parent and completion state
+    let mut new_ids: HashMap<i64, i64> = HashMap::new();
+    let mut created: Vec<Option<Task>> = vec![None; rows.len()];
+    let mut pending: Vec<usize> = (0..rows.len()).collect();
+
+    while !pending.is_empty() {
+        let mut waiting = Vec::new();
+
+        for &index in &pending {
+            let row = &rows[index];
+            let parent_id = match row.parent_id {
+                Some(source) => match new_ids.get(&source) {
+                    Some(&id) => Some(id),
+                    None => {
+                        waiting.push(index);
+                        continue;
+                    }
+                },
+                None => None,
+            };
+
+            let task = insert_task_with(
+                &mut tx,
+                &user,
+                Scope::Owner(user.id),
+                CreateTask {
+                    title: row.title.clone().unwrap_or_default(),
+                    description: row.description.clone(),
+                    due_at: row.due_at,
+                    priority: row.priority,
+                    parent_id,
+                    tags: row.tags.clone(),
+                },
+                row.completed.unwrap_or(false),
+            )
             .await?;
 
-    Ok(Json(TaskStats {
-        total: total.0,
-        completed: completed_count.0,
-        pending: total.0 - completed_count.0,
+            if let Some(id) = row.id {
+                new_ids.insert(id, task.id);
+            }
+            created[index] = Some(task);
+        }
+
+        // validate_import rejects missing parents and loops
+        if waiting.len() == pending.len() {
+            return Err(ApiError::Internal("Import rows have unresolved parents".into()));
+        }
+        pending = waiting;
+    }
+
+    let created: Vec<Task> = created.into_iter().flatten().collect();
+
+    tx.commit().await?;
+
+    let imported = created.len();
+    for task in created {
+        events.publish(user.id, ChangeKind::Created, task);
+    }
+
+    Ok((
+        StatusCode::CREATED,
+        Json(ImportReport {
+            imported,
+            errors: Vec::new(),
+        }),
+    ))
+}
+
+/// Check every import row, including `parent_id` links within the file
+///
+/// Returns the rows when all are valid, otherwise one error per invalid row.
+fn validate_import(rows: ImportRows) -> (Vec<ImportRow>, Vec<ImportRowError>) {
+    let mut errors: Vec<ImportRowError> = Vec::new();
+    let mut valid: Vec<(usize, ImportRow)> = Vec::with_capacity(rows.len());
+    // Source id -> row number
+    let mut ids: HashMap<i64, usize> = HashMap::new();
+
+    for (row, parsed) in rows {
+        let checked = parsed.and_then(|data| {
+            let title = data.title.as_deref().unwrap_or("");
+
+            if title.trim().is_empty() {
+                return Err("Title is required".to_string());
+            }
+
+            if title.len() > 200 {
+                return Err("Title cannot exceed 200 characters".to_string());
+            }
+
+            if data.description.as_ref().is_some_and(|d| d.chars().count() > 2000) {
+                return Err("Description cannot exceed 2000 characters".to_string());
+            }
+
+            normalize_tags("tags", &data.tags).map_err(|e| e.status_and_message().1)?;
+
+            let duplicate = data.id.and_then(|id| Some((id, ids.insert(id, row)?)));
+            if let Some((id, first)) = duplicate {
+                return Err(format!("Duplicate id {} (first used on row {})", id, first));
+            }
+
+            Ok(data)
+        });
+
+        match checked {
+            Ok(data) => valid.push((row, data)),
+            Err(error) => errors.push(ImportRowError { row, error }),
+        }
+    }
+
+    // Parents must be rows of the same file, without loops
+    let parents: HashMap<i64, i64> = valid
+        .iter()
+        .filter_map(|(_, data)| Some((data.id?, data.parent_id?)))
+        .collect();
+
+    for (row, data) in &valid {
+        let Some(parent_id) = data.parent_id else {
+            continue;
+        };
+
+        let error = if !ids.contains_key(&parent_id) {
+            Some(format!("Parent task {} is missing or invalid", parent_id))
+        } else if data.id.is_some_and(|id| has_cycle(&parents, id)) {
+            Some("A task cannot be its own ancestor".to_string())
+        } else {
+            None
+        };
+
+        if let Some(error) = error {
+            errors.push(ImportRowError { row: *row, error });
+        }
+    }
+
+    errors.sort_by_key(|e| e.row);
+
+    (valid.into_iter().map(|(_, data)| data).collect(), errors)
+}
+
+/// Whether following `parent_id` links from `id` leads back to it
+fn has_cycle(parents: &HashMap<i64, i64>, id: i64) -> bool {
+    let mut current = id;
+
+    for _ in 0..parents.len() {
+        match parents.get(&current) {
+            Some(&parent) if parent == id => return true,
+            Some(&parent) => current = parent,
+            None => return false,
+        }
+    }
+
+    false
+}
+
+/// Restore a deleted task
+///
+/// Undoes a soft delete, bringing back the subtasks that were deleted along
+/// with the task. A subtask can only be restored once its parent is.
+#[utoipa::path(
+    post,
+    path = "/tasks/{id}/restore",
+    params(
+        ("id" = i64, Path, description = "ID of the deleted task")
+    ),
+    responses(
+        (status = 200, description = "Task restored", body = Task),
+        (status = 400, description = "Task is not deleted or its parent is", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Task not found", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Tasks"
+)]
+pub async fn restore_task(
+    State(pool): State<SqlitePool>,
+    State(events): State<EventBus>,
+    user: CurrentUser,
+    Path(id): Path<i64>,
+) -> Result<Json<Task>> {
+    let (task, _) = restore(&pool, &user, Scope::Owner(user.id), id).await?;
+
+    // To live subscribers a restored task is a new one
+    events.publish(user.id, ChangeKind::Created, task.clone());
+
+    Ok(Json(task))
+}
+
+/// Restore deleted task `id` of `scope` with its subtasks
+///
+/// Returns the task and the users to tell about it.
+async fn restore(
+    pool: &SqlitePool,
+    user: &CurrentUser,
+    scope: Scope,
+    id: i64,
+) -> Result<(Task, Vec<i64>)> {
+    let mut tx = pool.begin_with("BEGIN IMMEDIATE").await?;
+    scope.authorize(&mut tx).await?;
+
+    let (deleted_at, parent_deleted): (Option<String>, bool) = sqlx::query_as(&format!(
+        r#"
+        SELECT t.deleted_at, COALESCE(p.deleted_at IS NOT NULL, FALSE)
+        FROM tasks t LEFT JOIN tasks p ON p.id = t.parent_id
+        WHERE t.id = ? AND t.{} = ?
+        "#,
+        scope.column()
+    ))
+    .bind(id)
+    .bind(scope.id())
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| ApiError::NotFound(format!("Task {} not found", id)))?;
+
+    let Some(deleted_at) = deleted_at else {
+        return Err(ApiError::Validation(format!("Task {} is not deleted", id)));
+    };
+
+    if parent_deleted {
+        return Err(ApiError::Validation("Restore the parent task first".into()));
+    }
+
+    // Subtasks deleted along with the task carry its ID in deleted_with;
+    // tasks deleted before that column existed fall back to the timestamp
+    let restored: Vec<(i64,)> = sqlx::query_as(
+        r#"
+        WITH RECURSIVE subtree(id) AS (
+            SELECT ?
+            UNION ALL
+            SELECT c.id FROM tasks c JOIN subtree s ON c.parent_id = s.id
+            WHERE c.deleted_with = ?
+               OR (c.deleted_with IS NULL AND c.deleted_at = ?)
+        )
+        UPDATE tasks SET deleted_at = NULL, deleted_with = NULL, version = version + 1
+        WHERE id IN (SELECT id FROM subtree)
+        RETURNING id
+        "#,
+    )
+    .bind(id)
+    .bind(id)
+    .bind(&deleted_at)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    for (task_id,) in restored {
+        audit::record(
+            &mut tx,
+            task_id,
+            user.id,
+            TaskEventKind::Restored,
+            audit::deleted_change(false),
+        )
+        .await?;
+    }
+
+    let task = fetch_task_in(&mut *tx, id, scope).await?;
+    let audience = scope.audience(&mut tx).await?;
+    tx.commit().await?;
+
+    Ok((task, audience))
+}
+
+/// Get task history
+///
+/// Returns every recorded change of a task, oldest first. Deleted tasks
+/// keep their history.
+#[utoipa::path(
+    get,
+    path = "/tasks/{id}/history",
+    params(
+        ("id" = i64, Path, description = "Task ID")
+    ),
+    responses(
+        (status = 200, description = "Task events, oldest first", body = Vec<TaskEvent>),
+        (status = 404, description = "Task not found", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Tasks"
+)]
+pub async fn get_task_history(
+    State(pool): State<SqlitePool>,
+    user: CurrentUser,
+    Path(id): Path<i64>,
+) -> Result<Json<Vec<TaskEvent>>> {
+    Ok(Json(history(&pool, Scope::Owner(user.id), id).await?))
+}
+
+/// Events of task `id` of `scope`, deleted or not
+async fn history(pool: &SqlitePool, scope: Scope, id: i64) -> Result<Vec<TaskEvent>> {
+    let found = sqlx::query(&format!(
+        "SELECT id FROM tasks WHERE id = ? AND {} = ?",
+        scope.column()
+    ))
+    .bind(id)
+    .bind(scope.id())
+    .fetch_optional(pool)
+    .await?;
+
+    if found.is_none() {
+        return Err(ApiError::NotFound(format!("Task {} not found", id)));
+    }
+
+    let events = sqlx::query_as::<_, TaskEvent>(
+        "SELECT id, task_id, actor_id, kind, changes, created_at FROM task_events WHERE task_id = ? ORDER BY id",
+    )
+    .bind(id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(events)
+}
+
+/// List subtasks
+///
+/// Returns the direct subtasks of a task.
+#[utoipa::path(
+    get,
+    path = "/tasks/{id}/subtasks",
+    params(
+        ("id" = i64, Path, description = "Parent task ID")
+    ),
+    responses(
+        (status = 200, description = "Direct subtasks", body = Vec<Task>),
+        (status = 404, description = "Task not found", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Tasks"
+)]
+pub async fn list_subtasks(
+    State(pool): State<SqlitePool>,
+    user: CurrentUser,
+    Path(id): Path<i64>,
+) -> Result<Json<Vec<Task>>> {
+    Ok(Json(subtasks(&pool, Scope::Owner(user.id), id).await?))
+}
+
+/// Live direct subtasks of task `id` of `scope`
+async fn subtasks(pool: &SqlitePool, scope: Scope, id: i64) -> Result<Vec<Task>> {
+    fetch_task_in(pool, id, scope).await?;
+
+    let subtasks = sqlx::query_as::<_, Task>(&format!(
+        "SELECT {} FROM tasks t WHERE t.parent_id = ? AND t.deleted_at IS NULL ORDER BY t.id",
+        TASK_COLUMNS
+    ))
+    .bind(id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(subtasks)
+}
+
+/// Get a task tree
+///
+/// Returns a task with all its subtasks nested at any depth.
+#[utoipa::path(
+    get,
+    path = "/tasks/{id}/tree",
+    params(
+        ("id" = i64, Path, description = "Root task ID")
+    ),
+    responses(
+        (status = 200, description = "Nested task tree", body = TaskNode),
+        (status = 404, description = "Task not found", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Tasks"
+)]
+pub async fn get_task_tree(
+    State(pool): State<SqlitePool>,
+    user: CurrentUser,
+    Path(id): Path<i64>,
+) -> Result<Json<TaskNode>> {
+    Ok(Json(tree(&pool, Scope::Owner(user.id), id).await?))
+}
+
+/// Live task `id` of `scope` with its live subtasks nested
+async fn tree(pool: &SqlitePool, scope: Scope, id: i64) -> Result<TaskNode> {
+    let tasks = sqlx::query_as::<_, Task>(&format!(
+        r#"
+        WITH RECURSIVE subtree(id, depth) AS (
+            SELECT id, 0 FROM tasks WHERE id = ? AND {} = ? AND deleted_at IS NULL
+            UNION ALL
+            SELECT c.id, s.depth + 1 FROM tasks c JOIN subtree s ON c.parent_id = s.id
+            WHERE c.deleted_at IS NULL
+        )
+        SELECT {} FROM subtree JOIN tasks t ON t.id = subtree.id
+        ORDER BY subtree.depth, t.id
+        "#,
+        scope.column(),
+        TASK_COLUMNS
+    ))
+    .bind(id)
+    .bind(scope.id())
+    .fetch_all(pool)
+    .await?;
+
+    let mut tasks = tasks.into_iter();
+    let root = tasks
+        .next()
+        .ok_or_else(|| ApiError::NotFound(format!("Task {} not found", id)))?;
+
+    Ok(build_tree(root, tasks.collect()))
+}
+
+/// Nest `descendants` under `root` using their `parent_id`
+fn build_tree(root: Task, descendants: Vec<Task>) -> TaskNode {
+    let mut children: HashMap<i64, Vec<Task>> = HashMap::new();
+    for task in descendants {
+        if let Some(parent_id) = task.parent_id {
+            children.entry(parent_id).or_default().push(task);
+        }
+    }
+
+    fn attach(task: Task, children: &mut HashMap<i64, Vec<Task>>) -> TaskNode {
+        let nodes = children
+            .remove(&task.id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|child| attach(child, children))
+            .collect();
+
+        TaskNode {
+            task,
+            children: nodes,
+        }
+    }
+
+    attach(root, &mut children)
+}
+
+/// Add tags to a task
+///
+/// Attaches the given tags, creating them if needed. Tags already on the task are ignored.
+#[utoipa::path(
+    post,
+    path = "/tasks/{id}/tags",
+    params(
+        ("id" = i64, Path, description = "Task ID")
+    ),
+    request_body = TagsInput,
+    responses(
+        (status = 200, description = "Task with its tags", body = Task),
+        (status = 400, description = "Invalid tag name", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Task not found", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Tags"
+)]
+pub async fn add_tags(
+    State(pool): State<SqlitePool>,
+    State(events): State<EventBus>,
+    user: CurrentUser,
+    Path(id): Path<i64>,
+    Json(data): Json<TagsInput>,
+) -> Result<Json<Task>> {
+    let (task, audience) = tag(&pool, &user, Scope::Owner(user.id), id, &data).await?;
+
+    if !audience.is_empty() {
+        events.publish(user.id, ChangeKind::Updated, task.clone());
+    }
+
+    Ok(Json(task))
+}
+
+/// Attach `data`'s tags to task `id` of `scope`
+///
+/// Returns the task and the users to tell about the change, nobody if the
+/// task already had the tags.
+async fn tag(
+    pool: &SqlitePool,
+    user: &CurrentUser,
+    scope: Scope,
+    id: i64,
+    data: &TagsInput,
+) -> Result<(Task, Vec<i64>)> {
+    let tags = normalize_tags("tags", &data.tags)?;
+
+    let mut tx = pool.begin_with("BEGIN IMMEDIATE").await?;
+    scope.authorize(&mut tx).await?;
+    let before = fetch_task_in(&mut *tx, id, scope).await?;
+    attach_tags(&mut tx, id, &tags).await?;
+
+    let changed = record_tag_change(&mut tx, user, scope, &before).await?;
+    tx.commit().await?;
+
+    Ok(changed)
+}
+
+/// Remove tags from a task
+///
+/// Detaches the given tags. Tags not on the task are ignored.
+#[utoipa::path(
+    delete,
+    path = "/tasks/{id}/tags",
+    params(
+        ("id" = i64, Path, description = "Task ID")
+    ),
+    request_body = TagsInput,
+    responses(
+        (status = 200, description = "Task with its remaining tags", body = Task),
+        (status = 400, description = "Invalid tag name", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Task not found", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Tags"
+)]
+pub async fn remove_tags(
+    State(pool): State<SqlitePool>,
+    State(events): State<EventBus>,
+    user: CurrentUser,
+    Path(id): Path<i64>,
+    Json(data): Json<TagsInput>,
+) -> Result<Json<Task>> {
+    let (task, audience) = untag(&pool, &user, Scope::Owner(user.id), id, &data).await?;
+
+    if !audience.is_empty() {
+        events.publish(user.id, ChangeKind::Updated, task.clone());
+    }
+
+    Ok(Json(task))
+}
+
+/// Detach `data`'s tags from task `id` of `scope`
+///
+/// Returns the task and the users to tell about the change, like [`tag`].
+async fn untag(
+    pool: &SqlitePool,
+    user: &CurrentUser,
+    scope: Scope,
+    id: i64,
+    data: &TagsInput,
+) -> Result<(Task, Vec<i64>)> {
+    let tags = normalize_tags("tags", &data.tags)?;
+
+    let mut tx = pool.begin_with("BEGIN IMMEDIATE").await?;
+    scope.authorize(&mut tx).await?;
+    let before = fetch_task_in(&mut *tx, id, scope).await?;
+
+    for name in &tags {
+        sqlx::query(
+            "DELETE FROM task_tags WHERE task_id = ? AND tag_id = (SELECT id FROM tags WHERE name = ?)",
+        )
+        .bind(id)
+        .bind(name)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    let changed = record_tag_change(&mut tx, user, scope, &before).await?;
+    tx.commit().await?;
+
+    Ok(changed)
+}
+
+/// Bump the version of a task whose tags changed and log the change
+///
+/// Returns the task as it is now and the audience of the change, empty if
+/// the tags are the same.
+async fn record_tag_change(
+    conn: &mut SqliteConnection,
+    user: &CurrentUser,
+    scope: Scope,
+    before: &Task,
+) -> Result<(Task, Vec<i64>)> {
+    let task = fetch_task_in(&mut *conn, before.id, scope).await?;
+
+    if task.tags == before.tags {
+        return Ok((task, Vec::new()));
+    }
+
+    sqlx::query(
+        "UPDATE tasks SET version = version + 1, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+    )
+    .bind(task.id)
+    .execute(&mut *conn)
+    .await?;
+
+    audit::record(
+        conn,
+        task.id,
+        user.id,
+        TaskEventKind::Updated,
+        audit::diff(Some(before), Some(&task)),
+    )
+    .await?;
+
+    let task = fetch_task_in(&mut *conn, before.id, scope).await?;
+
+    Ok((task, scope.audience(conn).await?))
+}
+
+/// List tags
+///
+/// Returns the tags used by the caller's tasks with their usage counts,
+/// most used first.
+#[utoipa::path(
+    get,
+    path = "/tags",
+    responses(
+        (status = 200, description = "Tags with usage counts", body = Vec<TagCount>),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Tags"
+)]
+pub async fn list_tags(
+    State(pool): State<SqlitePool>,
+    user: CurrentUser,
+) -> Result<Json<Vec<TagCount>>> {
+    let tags = sqlx::query_as::<_, TagCount>(
+        r#"
+        SELECT g.name, COUNT(*) AS count
+        FROM tags g
+        JOIN task_tags tt ON tt.tag_id = g.id
+        JOIN tasks t ON t.id = tt.task_id
+        WHERE t.owner_id = ? AND t.deleted_at IS NULL
+        GROUP BY g.id
+        ORDER BY count DESC, g.name
+        "#,
+    )
+    .bind(user.id)
+    .fetch_all(&pool)
+    .await?;
+
+    Ok(Json(tags))
+}
+
+/// Get task statistics
+///
+/// Returns total count, completed, pending and overdue tasks of the caller,
+/// plus a count per priority and the progress of root tasks with subtasks.
+#[utoipa::path(
+    get,
+    path = "/tasks/stats",
+    responses(
+        (status = 200, description = "Task statistics", body = TaskStats),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Statistics"
+)]
+pub async fn get_stats<R: TaskRepository>(
+    State(state): State<AppState<R>>,
+    user: CurrentUser,
+) -> Result<Json<TaskStats>> {
+    let stats = state.tasks.stats(user.id).await?;
+
+    Ok(Json(stats))
+}
+
+/// Stream task changes
+///
+/// Server-Sent Events feed of the caller's task changes. Each event is named
+/// `task.created`, `task.updated` or `task.deleted` and carries a
+/// `ChangeEvent` as JSON data. `id` and `completed` narrow the feed.
+///
+/// Only the task a request targets is announced: subtasks changed by a
+/// cascade or deleted along with their parent are not.
+#[utoipa::path(
+    get,
+    path = "/tasks/events",
+    params(
+        ("id" = Option<i64>, Query, description = "Only changes of this task"),
+        ("completed" = Option<bool>, Query, description = "Only changes of tasks in this state")
+    ),
+    responses(
+        (status = 200, description = "Event stream of `ChangeEvent`", body = ChangeEvent,
+            content_type = "text/event-stream"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Events"
+)]
+pub async fn task_events(
+    State(events): State<EventBus>,
+    user: CurrentUser,
+    Query(filters): Query<EventFilters>,
+) -> Sse<impl Stream<Item = std::result::Result<Event, axum::Error>>> {
+    let stream = events
+        .subscribe(user.id, filters)
+        .map(|change| Event::default().event(change.kind.as_str()).json_data(&change));
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+/// Task changes over WebSocket
+///
+/// Upgrades to a WebSocket that receives the same `ChangeEvent`s as
+/// `GET /tasks/events`, one JSON text message per change. Messages from the
+/// client are ignored.
+#[utoipa::path(
+    get,
+    path = "/ws",
+    params(
+        ("id" = Option<i64>, Query, description = "Only changes of this task"),
+        ("completed" = Option<bool>, Query, description = "Only changes of tasks in this state")
+    ),
+    responses(
+        (status = 101, description = "Switching to WebSocket, then one `ChangeEvent` per message", body = ChangeEvent),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Events"
+)]
+pub async fn task_socket(
+    State(events): State<EventBus>,
+    user: CurrentUser,
+    Query(filters): Query<EventFilters>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    // Subscribe before upgrading, so no change after the handshake is missed
+    let changes = events.subscribe(user.id, filters);
+
+    upgrade.on_upgrade(|socket| forward_changes(socket, changes))
+}
+
+/// Send changes to a WebSocket until either end closes
+///
+/// When the feed ends because the server is shutting down, the socket is
+/// closed with "going away".
+async fn forward_changes(mut socket: WebSocket, changes: impl Stream<Item = ChangeEvent>) {
+    let mut changes = std::pin::pin!(changes);
+
+    loop {
+        tokio::select! {
+            change = changes.next() => {
+                let Some(change) = change else {
+                    let frame = CloseFrame {
+                        code: close_code::AWAY,
+                        reason: "Server shutting down".into(),
+                    };
+                    let _ = socket.send(Message::Close(Some(frame))).await;
+                    break;
+                };
+                let Ok(text) = serde_json::to_string(&change) else { continue };
+
+                if socket.send(Message::Text(text.into())).await.is_err() {
+                    break;
+                }
+            }
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+                // Pings are answered by axum; anything else is ignored
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+/// Set the recurrence of a task
+///
+/// Makes the task the current occurrence of a series: once it is completed,
+/// or its due date arrives, the next occurrence is created with the same
+/// fields and the rule moves to it. Occurrences are counted from the task's
+/// due date, which is required. Replaces any previous rule of the task.
+#[utoipa::path(
+    put,
+    path = "/tasks/{id}/recurrence",
+    params(
+        ("id" = i64, Path, description = "Task ID")
+    ),
+    request_body = RecurrenceRule,
+    responses(
+        (status = 200, description = "Recurrence set", body = Recurrence),
+        (status = 400, description = "Invalid rule, or task without due date", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Task not found", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Tasks"
+)]
+pub async fn set_recurrence(
+    State(pool): State<SqlitePool>,
+    user: CurrentUser,
+    Path(id): Path<i64>,
+    Json(rule): Json<RecurrenceRule>,
+) -> Result<Json<Recurrence>> {
+    let recurrence = set_rule(&pool, Scope::Owner(user.id), id, rule).await?;
+
+    Ok(Json(recurrence))
+}
+
+/// Make task `id` of `scope` recur by `rule`, from its due date
+async fn set_rule(
+    pool: &SqlitePool,
+    scope: Scope,
+    id: i64,
+    rule: RecurrenceRule,
+) -> Result<Recurrence> {
+    let mut tx = pool.begin_with("BEGIN IMMEDIATE").await?;
+    scope.authorize(&mut tx).await?;
+
+    let task = fetch_task_in(&mut *tx, id, scope).await?;
+    let starts_at = task
+        .due_at
+        .ok_or_else(|| ApiError::Validation("A recurring task needs a due date".into()))?;
+
+    let schedule = Schedule::new(rule, starts_at)?;
+    let recurrence = recurrence::save(&mut *tx, id, &schedule).await?;
+    tx.commit().await?;
+
+    Ok(recurrence)
+}
+
+/// Get the recurrence of a task
+///
+/// Only the latest occurrence of a series holds the rule.
+#[utoipa::path(
+    get,
+    path = "/tasks/{id}/recurrence",
+    params(
+        ("id" = i64, Path, description = "Task ID")
+    ),
+    responses(
+        (status = 200, description = "Recurrence rule", body = Recurrence),
+        (status = 404, description = "Task not found or not recurring", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Tasks"
+)]
+pub async fn get_recurrence(
+    State(pool): State<SqlitePool>,
+    user: CurrentUser,
+    Path(id): Path<i64>,
+) -> Result<Json<Recurrence>> {
+    Ok(Json(rule(&pool, Scope::Owner(user.id), id).await?))
+}
+
+/// Recurrence rule of task `id` of `scope`
+async fn rule(pool: &SqlitePool, scope: Scope, id: i64) -> Result<Recurrence> {
+    fetch_task_in(pool, id, scope).await?;
+
+    recurrence::find(pool, id, scope)
+        .await?
+        .ok_or_else(|| recurrence::no_recurrence(id))
+}
+
+/// Stop a task from recurring
+///
+/// The task itself is kept; no further occurrence is created.
+#[utoipa::path(
+    delete,
+    path = "/tasks/{id}/recurrence",
+    params(
+        ("id" = i64, Path, description = "Task ID")
+    ),
+    responses(
+        (status = 204, description = "Recurrence removed"),
+        (status = 404, description = "Task not found or not recurring", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Tasks"
+)]
+pub async fn delete_recurrence(
+    State(pool): State<SqlitePool>,
+    user: CurrentUser,
+    Path(id): Path<i64>,
+) -> Result<StatusCode> {
+    remove_rule(&pool, Scope::Owner(user.id), id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Stop task `id` of `scope` from recurring
+async fn remove_rule(pool: &SqlitePool, scope: Scope, id: i64) -> Result<()> {
+    let mut tx = pool.begin_with("BEGIN IMMEDIATE").await?;
+    scope.authorize(&mut tx).await?;
+
+    fetch_task_in(&mut *tx, id, scope).await?;
+
+    if !recurrence::remove(&mut *tx, id).await? {
+        return Err(recurrence::no_recurrence(id));
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Preview upcoming occurrences
+///
+/// Returns the due dates of the next occurrences of a recurring task, the
+/// ones not created yet, stopping at the rule's `until`.
+#[utoipa::path(
+    get,
+    path = "/tasks/{id}/occurrences",
+    params(
+        ("id" = i64, Path, description = "Task ID"),
+        ("limit" = Option<usize>, Query, description = "Number of occurrences (1-100, default: 10)")
+    ),
+    responses(
+        (status = 200, description = "Upcoming occurrences, soonest first", body = Vec<Occurrence>),
+        (status = 400, description = "Limit out of range", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Task not found or not recurring", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Tasks"
+)]
+pub async fn list_occurrences(
+    State(pool): State<SqlitePool>,
+    user: CurrentUser,
+    Path(id): Path<i64>,
+    Query(query): Query<OccurrenceQuery>,
+) -> Result<Json<Vec<Occurrence>>> {
+    let occurrences = occurrences(&pool, Scope::Owner(user.id), id, &query).await?;
+
+    Ok(Json(occurrences))
+}
+
+/// Most occurrences one preview returns
+const MAX_OCCURRENCE_LIMIT: usize = 100;
+
+/// Upcoming occurrences of recurring task `id` of `scope`
+async fn occurrences(
+    pool: &SqlitePool,
+    scope: Scope,
+    id: i64,
+    query: &OccurrenceQuery,
+) -> Result<Vec<Occurrence>> {
+    let limit = query.limit.unwrap_or(10);
+    if !(1..=MAX_OCCURRENCE_LIMIT).contains(&limit) {
+        return Err(ApiError::Validation(format!(
+            "Limit must be between 1 and {}",
+            MAX_OCCURRENCE_LIMIT
+        )));
+    }
+
+    let task = fetch_task_in(pool, id, scope).await?;
+    let schedule = recurrence::find(pool, id, scope)
+        .await?
+        .ok_or_else(|| recurrence::no_recurrence(id))?
+        .schedule()?;
+
+    let occurrences = recurrence::next_due(&schedule, &task, chrono::Utc::now())
+        .map(|first| {
+            std::iter::once(first)
+                .chain(schedule.occurrences(first))
+                .take(limit)
+                .map(|due_at| Occurrence { due_at })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(occurrences)
+}
+
+/// Create a project
+///
+/// Creates a project with the caller as its only owner.
+#[utoipa::path(
+    post,
+    path = "/projects",
+    request_body = CreateProject,
+    responses(
+        (status = 201, description = "Project created", body = Project),
+        (status = 400, description = "Validation error", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Projects"
+)]
+pub async fn create_project(
+    State(pool): State<SqlitePool>,
+    user: CurrentUser,
+    Json(data): Json<CreateProject>,
+) -> Result<(StatusCode, Json<Project>)> {
+    let project = projects::create(&pool, &user, data).await?;
+
+    Ok((StatusCode::CREATED, Json(project)))
+}
+
+/// List projects
+///
+/// Returns the projects the caller is a member of, with the caller's role.
+#[utoipa::path(
+    get,
+    path = "/projects",
+    responses(
+        (status = 200, description = "Projects of the caller", body = Vec<Project>),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Projects"
+)]
+pub async fn list_projects(
+    State(pool): State<SqlitePool>,
+    user: CurrentUser,
+) -> Result<Json<Vec<Project>>> {
+    let projects = projects::list(&pool, user.id).await?;
+
+    Ok(Json(projects))
+}
+
+/// Get a project
+///
+/// Any member can read the project.
+#[utoipa::path(
+    get,
+    path = "/projects/{pid}",
+    params(
+        ("pid" = i64, Path, description = "Project ID")
+    ),
+    responses(
+        (status = 200, description = "Project found", body = Project),
+        (status = 404, description = "Project not found or caller not a member", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Projects"
+)]
+pub async fn get_project(
+    State(pool): State<SqlitePool>,
+    access: ProjectAccess,
+) -> Result<Json<Project>> {
+    let project = projects::find(&pool, access.project_id, access.user.id).await?;
+
+    Ok(Json(project))
+}
+
+/// Delete a project
+///
+/// Removes the project with its members and its deleted tasks. Owners only;
+/// refused with 409 while the project still has tasks.
+#[utoipa::path(
+    delete,
+    path = "/projects/{pid}",
+    params(
+        ("pid" = i64, Path, description = "Project ID")
+    ),
+    responses(
+        (status = 204, description = "Project deleted"),
+        (status = 403, description = "Caller is not an owner", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Project not found or caller not a member", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 409, description = "Project still has tasks", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Projects"
+)]
+pub async fn delete_project(
+    State(pool): State<SqlitePool>,
+    access: ProjectAccess,
+) -> Result<StatusCode> {
+    projects::delete(&pool, &access).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// List project members
+///
+/// Returns the members of a project with their roles, owners first.
+#[utoipa::path(
+    get,
+    path = "/projects/{pid}/members",
+    params(
+        ("pid" = i64, Path, description = "Project ID")
+    ),
+    responses(
+        (status = 200, description = "Members of the project", body = Vec<ProjectMember>),
+        (status = 404, description = "Project not found or caller not a member", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Projects"
+)]
+pub async fn list_project_members(
+    State(pool): State<SqlitePool>,
+    access: ProjectAccess,
+) -> Result<Json<Vec<ProjectMember>>> {
+    let members = projects::members(&pool, access.project_id).await?;
+
+    Ok(Json(members))
+}
+
+/// Add a member or change their role
+///
+/// Adds a registered user to the project with the given role, or changes the
+/// role of a member. Owners only; the last owner cannot step down.
+#[utoipa::path(
+    put,
+    path = "/projects/{pid}/members/{username}",
+    params(
+        ("pid" = i64, Path, description = "Project ID"),
+        ("username" = String, Path, description = "User to add or change")
+    ),
+    request_body = SetMemberRole,
+    responses(
+        (status = 200, description = "Member saved", body = ProjectMember),
+        (status = 403, description = "Caller is not an owner", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Project or user not found", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 409, description = "The project would be left without owners", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Projects"
+)]
+pub async fn set_project_member(
+    State(pool): State<SqlitePool>,
+    access: ProjectAccess,
+    Path((_pid, username)): Path<(i64, String)>,
+    Json(data): Json<SetMemberRole>,
+) -> Result<Json<ProjectMember>> {
+    let member = projects::set_member(&pool, &access, &username, data.role).await?;
+
+    Ok(Json(member))
+}
+
+/// Remove a member
+///
+/// Owners can remove anyone; other members can only leave the project
+/// themselves. The last owner cannot be removed.
+#[utoipa::path(
+    delete,
+    path = "/projects/{pid}/members/{username}",
+    params(
+        ("pid" = i64, Path, description = "Project ID"),
+        ("username" = String, Path, description = "Member to remove")
+    ),
+    responses(
+        (status = 204, description = "Member removed"),
+        (status = 403, description = "Caller is not an owner", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Project or member not found", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 409, description = "The project would be left without owners", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Projects"
+)]
+pub async fn remove_project_member(
+    State(pool): State<SqlitePool>,
+    access: ProjectAccess,
+    Path((_pid, username)): Path<(i64, String)>,
+) -> Result<StatusCode> {
+    projects::remove_member(&pool, &access, &username).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// List project tasks
+///
+/// Same filters and pagination as `GET /tasks`, over the tasks of the project.
+#[utoipa::path(
+    get,
+    path = "/projects/{pid}/tasks",
+    params(
+        ("pid" = i64, Path, description = "Project ID"),
+        ("completed" = Option<bool>, Query, description = "Filter by completion status"),
+        ("limit" = Option<i64>, Query, description = "Result limit (default: 100, 1-100 with `cursor`)"),
+        ("offset" = Option<i64>, Query, description = "Offset for pagination (default: 0)"),
+        ("tag" = Option<Vec<String>>, Query, description = "Filter by tag, repeatable (`tag=a&tag=b`)"),
+        ("tag_match" = Option<TagMatch>, Query, description = "Require `any` (default) or `all` of the tags"),
+        ("overdue" = Option<bool>, Query, description = "Only pending tasks past their deadline"),
+        ("sort" = Option<TaskSort>, Query, description = "`created` (default), `due_at` or `priority`"),
+        ("cursor" = Option<String>, Query, description = "Keyset cursor from `next_cursor` (empty for the first page)")
+    ),
+    responses(
+        (status = 200, description = "Tasks of the project", body = TaskList),
+        (status = 400, description = "Invalid cursor, limit or tag", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Project not found or caller not a member", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Projects"
+)]
+pub async fn list_project_tasks(
+    State(pool): State<SqlitePool>,
+    access: ProjectAccess,
+    MultiQuery(filters): MultiQuery<TaskFilters>,
+) -> Result<Json<TaskList>> {
+    let tasks = SqliteTaskRepository::new(pool)
+        .list_in(access.scope(), filters)
+        .await?;
+
+    Ok(Json(tasks))
+}
+
+/// Create a project task
+///
+/// Creates a task in the project. Editors and owners only.
+#[utoipa::path(
+    post,
+    path = "/projects/{pid}/tasks",
+    params(
+        ("pid" = i64, Path, description = "Project ID")
+    ),
+    request_body = CreateTask,
+    responses(
+        (status = 201, description = "Task created", body = Task,
+            headers(("ETag" = String, description = "Task version"))),
+        (status = 400, description = "Validation error", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "Caller is a viewer", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Project not found or caller not a member", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Projects"
+)]
+pub async fn create_project_task(
+    State(pool): State<SqlitePool>,
+    State(events): State<EventBus>,
+    access: ProjectAccess,
+    Json(data): Json<CreateTask>,
+) -> Result<(StatusCode, Versioned)> {
+    let scope = access.write_scope(ProjectRole::Editor)?;
+
+    let (task, members) = SqliteTaskRepository::new(pool)
+        .create_in(&access.user, scope, data)
+        .await?;
+    projects::publish(&events, &members, ChangeKind::Created, task.clone());
+
+    Ok((StatusCode::CREATED, Versioned(task)))
+}
+
+/// Get a project task
+///
+/// Returns a task of the project with its version as `ETag`, honouring
+/// `If-None-Match` like `GET /tasks/{id}`.
+#[utoipa::path(
+    get,
+    path = "/projects/{pid}/tasks/{id}",
+    params(
+        ("pid" = i64, Path, description = "Project ID"),
+        ("id" = i64, Path, description = "Task ID"),
+        ("If-None-Match" = Option<String>, Header, description = "ETag of a cached copy")
+    ),
+    responses(
+        (status = 200, description = "Task found", body = Task,
+            headers(("ETag" = String, description = "Current task version"))),
+        (status = 304, description = "Task unchanged since the given ETag"),
+        (status = 404, description = "Project or task not found", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Projects"
+)]
+pub async fn get_project_task(
+    State(pool): State<SqlitePool>,
+    access: ProjectAccess,
+    Path((_pid, id)): Path<(i64, i64)>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    let task = fetch_task_in(&pool, id, access.scope()).await?;
+
+    if let Some(response) = etag::not_modified(&headers, &task) {
+        return Ok(response);
+    }
+
+    Ok(Versioned(task).into_response())
+}
+
+/// Replace a project task
+///
+/// Same as `PUT /tasks/{id}` for a task of the project. Editors and owners only.
+#[utoipa::path(
+    put,
+    path = "/projects/{pid}/tasks/{id}",
+    params(
+        ("pid" = i64, Path, description = "Project ID"),
+        ("id" = i64, Path, description = "ID of the task to replace"),
+        ("cascade" = Option<bool>, Query, description = "Propagate `completed` to subtasks"),
+        ("If-Match" = Option<String>, Header, description = "Only replace if the task still has this ETag")
+    ),
+    request_body = ReplaceTask,
+    responses(
+        (status = 200, description = "Task replaced", body = Task,
+            headers(("ETag" = String, description = "New task version"))),
+        (status = 400, description = "Validation error", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "Caller is a viewer", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Project or task not found", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 412, description = "Task changed since the given ETag", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Projects"
+)]
+pub async fn replace_project_task(
+    State(pool): State<SqlitePool>,
+    State(events): State<EventBus>,
+    access: ProjectAccess,
+    Path((_pid, id)): Path<(i64, i64)>,
+    Query(options): Query<UpdateOptions>,
+    headers: HeaderMap,
+    Json(data): Json<ReplaceTask>,
+) -> Result<Versioned> {
+    let scope = access.write_scope(ProjectRole::Editor)?;
+    let cascade = options.cascade == Some(true);
+    let if_match = |task: &Task| etag::check_if_match(&headers, task);
+
+    let (task, members) = SqliteTaskRepository::new(pool)
+        .update_in(&access.user, scope, id, TaskChange::Replace(data), cascade, &if_match)
+        .await?;
+    projects::publish(&events, &members, ChangeKind::Updated, task.clone());
+
+    Ok(Versioned(task))
+}
+
+/// Update a project task
+///
+/// Same as `PATCH /tasks/{id}` for a task of the project. Editors and owners only.
+#[utoipa::path(
+    patch,
+    path = "/projects/{pid}/tasks/{id}",
+    params(
+        ("pid" = i64, Path, description = "Project ID"),
+        ("id" = i64, Path, description = "ID of the task to update"),
+        ("cascade" = Option<bool>, Query, description = "Propagate `completed` to subtasks"),
+        ("If-Match" = Option<String>, Header, description = "Only update if the task still has this ETag")
+    ),
+    request_body(content = UpdateTask, content_type = "application/merge-patch+json"),
+    responses(
+        (status = 200, description = "Task updated", body = Task,
+            headers(("ETag" = String, description = "New task version"))),
+        (status = 400, description = "Validation error", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "Caller is a viewer", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Project or task not found", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 412, description = "Task changed since the given ETag", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Projects"
+)]
+pub async fn update_project_task(
+    State(pool): State<SqlitePool>,
+    State(events): State<EventBus>,
+    access: ProjectAccess,
+    Path((_pid, id)): Path<(i64, i64)>,
+    Query(options): Query<UpdateOptions>,
+    headers: HeaderMap,
+    Json(patch): Json<UpdateTask>,
+) -> Result<Versioned> {
+    let scope = access.write_scope(ProjectRole::Editor)?;
+    let cascade = options.cascade == Some(true) && patch.completed.is_some();
+    let if_match = |task: &Task| etag::check_if_match(&headers, task);
+
+    let (task, members) = SqliteTaskRepository::new(pool)
+        .update_in(&access.user, scope, id, TaskChange::Patch(patch), cascade, &if_match)
+        .await?;
+    projects::publish(&events, &members, ChangeKind::Updated, task.clone());
+
+    Ok(Versioned(task))
+}
+
+/// Delete a project task
+///
+/// Soft-deletes a task of the project and its subtasks. Editors and owners only.
+#[utoipa::path(
+    delete,
+    path = "/projects/{pid}/tasks/{id}",
+    params(
+        ("pid" = i64, Path, description = "Project ID"),
+        ("id" = i64, Path, description = "ID of the task to delete"),
+        ("If-Match" = Option<String>, Header, description = "Only delete if the task still has this ETag")
+    ),
+    responses(
+        (status = 204, description = "Task deleted"),
+        (status = 403, description = "Caller is a viewer", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Project or task not found", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 412, description = "Task changed since the given ETag", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Projects"
+)]
+pub async fn delete_project_task(
+    State(pool): State<SqlitePool>,
+    State(events): State<EventBus>,
+    access: ProjectAccess,
+    Path((_pid, id)): Path<(i64, i64)>,
+    headers: HeaderMap,
+) -> Result<StatusCode> {
+    let scope = access.write_scope(ProjectRole::Editor)?;
+    let if_match = |task: &Task| etag::check_if_match(&headers, task);
+
+    let (task, members) = SqliteTaskRepository::new(pool)
+        .delete_in(&access.user, scope, id, &if_match)
+        .await?;
+    projects::publish(&events, &members, ChangeKind::Deleted, task);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Get project statistics
+///
+/// Same counts as `GET /tasks/stats`, over the tasks of the project.
+#[utoipa::path(
+    get,
+    path = "/projects/{pid}/tasks/stats",
+    params(
+        ("pid" = i64, Path, description = "Project ID")
+    ),
+    responses(
+        (status = 200, description = "Task statistics of the project", body = TaskStats),
+        (status = 404, description = "Project not found or caller not a member", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Projects"
+)]
+pub async fn get_project_stats(
+    State(pool): State<SqlitePool>,
+    access: ProjectAccess,
+) -> Result<Json<TaskStats>> {
+    let stats = SqliteTaskRepository::new(pool)
+        .stats_in(access.scope())
+        .await?;
+
+    Ok(Json(stats))
+}
+
+/// Get project task history
+///
+/// Same as `GET /tasks/{id}/history` for a task of the project.
+#[utoipa::path(
+    get,
+    path = "/projects/{pid}/tasks/{id}/history",
+    params(
+        ("pid" = i64, Path, description = "Project ID"),
+        ("id" = i64, Path, description = "Task ID")
+    ),
+    responses(
+        (status = 200, description = "Task events, oldest first", body = Vec<TaskEvent>),
+        (status = 404, description = "Project or task not found", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Projects"
+)]
+pub async fn get_project_task_history(
+    State(pool): State<SqlitePool>,
+    access: ProjectAccess,
+    Path((_pid, id)): Path<(i64, i64)>,
+) -> Result<Json<Vec<TaskEvent>>> {
+    Ok(Json(history(&pool, access.scope(), id).await?))
+}
+
+/// Restore a deleted project task
+///
+/// Same as `POST /tasks/{id}/restore` for a task of the project. Editors and
+/// owners only.
+#[utoipa::path(
+    post,
+    path = "/projects/{pid}/tasks/{id}/restore",
+    params(
+        ("pid" = i64, Path, description = "Project ID"),
+        ("id" = i64, Path, description = "ID of the deleted task")
+    ),
+    responses(
+        (status = 200, description = "Task restored", body = Task),
+        (status = 400, description = "Task is not deleted or its parent is", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "Caller is a viewer", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Project or task not found", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Projects"
+)]
+pub async fn restore_project_task(
+    State(pool): State<SqlitePool>,
+    State(events): State<EventBus>,
+    access: ProjectAccess,
+    Path((_pid, id)): Path<(i64, i64)>,
+) -> Result<Json<Task>> {
+    let scope = access.write_scope(ProjectRole::Editor)?;
+
+    let (task, members) = restore(&pool, &access.user, scope, id).await?;
+    projects::publish(&events, &members, ChangeKind::Created, task.clone());
+
+    Ok(Json(task))
+}
+
+/// List project subtasks
+///
+/// Same as `GET /tasks/{id}/subtasks` for a task of the project.
+#[utoipa::path(
+    get,
+    path = "/projects/{pid}/tasks/{id}/subtasks",
+    params(
+        ("pid" = i64, Path, description = "Project ID"),
+        ("id" = i64, Path, description = "Parent task ID")
+    ),
+    responses(
+        (status = 200, description = "Direct subtasks", body = Vec<Task>),
+        (status = 404, description = "Project or task not found", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Projects"
+)]
+pub async fn list_project_subtasks(
+    State(pool): State<SqlitePool>,
+    access: ProjectAccess,
+    Path((_pid, id)): Path<(i64, i64)>,
+) -> Result<Json<Vec<Task>>> {
+    Ok(Json(subtasks(&pool, access.scope(), id).await?))
+}
+
+/// Get a project task tree
+///
+/// Same as `GET /tasks/{id}/tree` for a task of the project.
+#[utoipa::path(
+    get,
+    path = "/projects/{pid}/tasks/{id}/tree",
+    params(
+        ("pid" = i64, Path, description = "Project ID"),
+        ("id" = i64, Path, description = "Root task ID")
+    ),
+    responses(
+        (status = 200, description = "Nested task tree", body = TaskNode),
+        (status = 404, description = "Project or task not found", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Projects"
+)]
+pub async fn get_project_task_tree(
+    State(pool): State<SqlitePool>,
+    access: ProjectAccess,
+    Path((_pid, id)): Path<(i64, i64)>,
+) -> Result<Json<TaskNode>> {
+    Ok(Json(tree(&pool, access.scope(), id).await?))
+}
+
+/// Add tags to a project task
+///
+/// Same as `POST /tasks/{id}/tags` for a task of the project. Editors and
+/// owners only.
+#[utoipa::path(
+    post,
+    path = "/projects/{pid}/tasks/{id}/tags",
+    params(
+        ("pid" = i64, Path, description = "Project ID"),
+        ("id" = i64, Path, description = "Task ID")
+    ),
+    request_body = TagsInput,
+    responses(
+        (status = 200, description = "Task with its tags", body = Task),
+        (status = 400, description = "Invalid tag name", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "Caller is a viewer", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Project or task not found", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Projects"
+)]
+pub async fn add_project_tags(
+    State(pool): State<SqlitePool>,
+    State(events): State<EventBus>,
+    access: ProjectAccess,
+    Path((_pid, id)): Path<(i64, i64)>,
+    Json(data): Json<TagsInput>,
+) -> Result<Json<Task>> {
+    let scope = access.write_scope(ProjectRole::Editor)?;
+
+    let (task, members) = tag(&pool, &access.user, scope, id, &data).await?;
+    projects::publish(&events, &members, ChangeKind::Updated, task.clone());
+
+    Ok(Json(task))
+}
+
+/// Remove tags from a project task
+///
+/// Same as `DELETE /tasks/{id}/tags` for a task of the project. Editors and
+/// owners only.
+#[utoipa::path(
+    delete,
+    path = "/projects/{pid}/tasks/{id}/tags",
+    params(
+        ("pid" = i64, Path, description = "Project ID"),
+        ("id" = i64, Path, description = "Task ID")
+    ),
+    request_body = TagsInput,
+    responses(
+        (status = 200, description = "Task with its remaining tags", body = Task),
+        (status = 400, description = "Invalid tag name", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "Caller is a viewer", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Project or task not found", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Projects"
+)]
+pub async fn remove_project_tags(
+    State(pool): State<SqlitePool>,
+    State(events): State<EventBus>,
+    access: ProjectAccess,
+    Path((_pid, id)): Path<(i64, i64)>,
+    Json(data): Json<TagsInput>,
+) -> Result<Json<Task>> {
+    let scope = access.write_scope(ProjectRole::Editor)?;
+
+    let (task, members) = untag(&pool, &access.user, scope, id, &data).await?;
+    projects::publish(&events, &members, ChangeKind::Updated, task.clone());
+
+    Ok(Json(task))
+}
+
+/// Set the recurrence of a project task
+///
+/// Same as `PUT /tasks/{id}/recurrence` for a task of the project. Editors
+/// and owners only.
+#[utoipa::path(
+    put,
+    path = "/projects/{pid}/tasks/{id}/recurrence",
+    params(
+        ("pid" = i64, Path, description = "Project ID"),
+        ("id" = i64, Path, description = "Task ID")
+    ),
+    request_body = RecurrenceRule,
+    responses(
+        (status = 200, description = "Recurrence set", body = Recurrence),
+        (status = 400, description = "Invalid rule, or task without due date", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "Caller is a viewer", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Project or task not found", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Projects"
+)]
+pub async fn set_project_recurrence(
+    State(pool): State<SqlitePool>,
+    access: ProjectAccess,
+    Path((_pid, id)): Path<(i64, i64)>,
+    Json(rule): Json<RecurrenceRule>,
+) -> Result<Json<Recurrence>> {
+    let scope = access.write_scope(ProjectRole::Editor)?;
+
+    Ok(Json(set_rule(&pool, scope, id, rule).await?))
+}
+
+/// Get the recurrence of a project task
+///
+/// Same as `GET /tasks/{id}/recurrence` for a task of the project.
+#[utoipa::path(
+    get,
+    path = "/projects/{pid}/tasks/{id}/recurrence",
+    params(
+        ("pid" = i64, Path, description = "Project ID"),
+        ("id" = i64, Path, description = "Task ID")
+    ),
+    responses(
+        (status = 200, description = "Recurrence rule", body = Recurrence),
+        (status = 404, description = "Project or task not found, or task not recurring", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Projects"
+)]
+pub async fn get_project_recurrence(
+    State(pool): State<SqlitePool>,
+    access: ProjectAccess,
+    Path((_pid, id)): Path<(i64, i64)>,
+) -> Result<Json<Recurrence>> {
+    Ok(Json(rule(&pool, access.scope(), id).await?))
+}
+
+/// Stop a project task from recurring
+///
+/// Same as `DELETE /tasks/{id}/recurrence` for a task of the project.
+/// Editors and owners only.
+#[utoipa::path(
+    delete,
+    path = "/projects/{pid}/tasks/{id}/recurrence",
+    params(
+        ("pid" = i64, Path, description = "Project ID"),
+        ("id" = i64, Path, description = "Task ID")
+    ),
+    responses(
+        (status = 204, description = "Recurrence removed"),
+        (status = 403, description = "Caller is a viewer", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Project or task not found, or task not recurring", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Projects"
+)]
+pub async fn delete_project_recurrence(
+    State(pool): State<SqlitePool>,
+    access: ProjectAccess,
+    Path((_pid, id)): Path<(i64, i64)>,
+) -> Result<StatusCode> {
+    let scope = access.write_scope(ProjectRole::Editor)?;
+    remove_rule(&pool, scope, id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Preview upcoming occurrences of a project task
+///
+/// Same as `GET /tasks/{id}/occurrences` for a task of the project.
+#[utoipa::path(
+    get,
+    path = "/projects/{pid}/tasks/{id}/occurrences",
+    params(
+        ("pid" = i64, Path, description = "Project ID"),
+        ("id" = i64, Path, description = "Task ID"),
+        ("limit" = Option<usize>, Query, description = "Number of occurrences (1-100, default: 10)")
+    ),
+    responses(
+        (status = 200, description = "Upcoming occurrences, soonest first", body = Vec<Occurrence>),
+        (status = 400, description = "Limit out of range", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Project or task not found, or task not recurring", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Projects"
+)]
+pub async fn list_project_occurrences(
+    State(pool): State<SqlitePool>,
+    access: ProjectAccess,
+    Path((_pid, id)): Path<(i64, i64)>,
+    Query(query): Query<OccurrenceQuery>,
+) -> Result<Json<Vec<Occurrence>>> {
+    Ok(Json(occurrences(&pool, access.scope(), id, &query).await?))
+}
+
+/// Register a webhook
+///
+/// Subscribes a URL to task events of the caller. Each event is sent as a
+/// `POST` signed with HMAC-SHA256 (see `X-Webhook-Signature`) and retried with
+/// exponential backoff until the endpoint answers 2xx. The secret is only
+/// returned here.
+#[utoipa::path(
+    post,
+    path = "/webhooks",
+    request_body = CreateWebhook,
+    responses(
+        (status = 201, description = "Webhook registered", body = NewWebhook),
+        (status = 400, description = "Invalid URL or event list", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Webhooks"
+)]
+pub async fn create_webhook(
+    State(pool): State<SqlitePool>,
+    user: CurrentUser,
+    Json(data): Json<CreateWebhook>,
+) -> Result<(StatusCode, Json<NewWebhook>)> {
+    let url = reqwest::Url::parse(data.url.trim())
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .ok_or_else(|| ApiError::Validation("URL must be an absolute http or https URL".into()))?;
+
+    let mut events = data.events;
+    events.sort();
+    events.dedup();
+
+    if events.is_empty() {
+        return Err(ApiError::Validation("At least one event type is required".into()));
+    }
+
+    let secret = webhooks::generate_secret();
+
+    let webhook: Webhook = sqlx::query_as(
+        r#"
+        INSERT INTO webhooks (owner_id, url, events, secret) VALUES (?, ?, ?, ?)
+        RETURNING id, url, events, created_at
+        "#,
+    )
+    .bind(user.id)
+    .bind(url.as_str())
+    .bind(sqlx::types::Json(&events))
+    .bind(&secret)
+    .fetch_one(&pool)
+    .await?;
+
+    Ok((StatusCode::CREATED, Json(NewWebhook { webhook, secret })))
+}
+
+/// List webhooks
+///
+/// Returns the caller's webhooks, without their secrets.
+#[utoipa::path(
+    get,
+    path = "/webhooks",
+    responses(
+        (status = 200, description = "Registered webhooks", body = Vec<Webhook>),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Webhooks"
+)]
+pub async fn list_webhooks(
+    State(pool): State<SqlitePool>,
+    user: CurrentUser,
+) -> Result<Json<Vec<Webhook>>> {
+    let webhooks = sqlx::query_as(
+        "SELECT id, url, events, created_at FROM webhooks WHERE owner_id = ? ORDER BY id",
+    )
+    .bind(user.id)
+    .fetch_all(&pool)
+    .await?;
+
+    Ok(Json(webhooks))
+}
+
+/// Delete a webhook
+///
+/// Stops deliveries to the webhook and removes its delivery log.
+#[utoipa::path(
+    delete,
+    path = "/webhooks/{id}",
+    params(
+        ("id" = i64, Path, description = "Webhook ID")
+    ),
+    responses(
+        (status = 204, description = "Webhook deleted"),
+        (status = 404, description = "Webhook not found", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Webhooks"
+)]
+pub async fn delete_webhook(
+    State(pool): State<SqlitePool>,
+    user: CurrentUser,
+    Path(id): Path<i64>,
+) -> Result<StatusCode> {
+    let deleted = sqlx::query("DELETE FROM webhooks WHERE id = ? AND owner_id = ?")
+        .bind(id)
+        .bind(user.id)
+        .execute(&pool)
+        .await?;
+
+    if deleted.rows_affected() == 0 {
+        return Err(webhook_not_found(id));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// List webhook deliveries
+///
+/// Returns the deliveries of a webhook, newest first, with their attempts and
+/// last error. Deliveries that ran out of retries have status `dead`.
+#[utoipa::path(
+    get,
+    path = "/webhooks/{id}/deliveries",
+    params(
+        ("id" = i64, Path, description = "Webhook ID"),
+        ("status" = Option<DeliveryStatus>, Query, description = "Only deliveries in this state")
+    ),
+    responses(
+        (status = 200, description = "Deliveries of the webhook", body = Vec<WebhookDelivery>),
+        (status = 404, description = "Webhook not found", body = crate::models::ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Webhooks"
+)]
+pub async fn list_webhook_deliveries(
+    State(pool): State<SqlitePool>,
+    user: CurrentUser,
+    Path(id): Path<i64>,
+    Query(query): Query<DeliveryQuery>,
+) -> Result<Json<Vec<WebhookDelivery>>> {
+    sqlx::query("SELECT 1 FROM webhooks WHERE id = ? AND owner_id = ?")
+        .bind(id)
+        .bind(user.id)
+        .fetch_optional(&pool)
+        .await?
+        .ok_or_else(|| webhook_not_found(id))?;
+
+    let deliveries = sqlx::query_as(
+        r#"
+        SELECT id, webhook_id, event, payload, status, attempts, next_attempt_at,
+               last_status, last_error, created_at, delivered_at
+        FROM webhook_deliveries
+        WHERE webhook_id = ? AND (? IS NULL OR status = ?)
+        ORDER BY id DESC
+        "#,
+    )
+    .bind(id)
+    .bind(query.status)
+    .bind(query.status)
+    .fetch_all(&pool)
+    .await?;
+
+    Ok(Json(deliveries))
+}
+
+fn webhook_not_found(id: i64) -> ApiError {
+    ApiError::NotFound(format!("Webhook {} not found", id))
+}
+
+/// Liveness probe
+///
+/// Answers as long as the process serves requests; it does not touch the
+/// database.
+#[utoipa::path(
+    get,
+    path = "/health/live",
+    responses(
+        (status = 200, description = "Server is running", body = Liveness)
+    ),
+    tag = "Health"
+)]
+pub async fn health_live() -> Json<Liveness> {
+    Json(Liveness {
+        status: HealthStatus::Ok,
+        build: BuildInfo::current(),
+    })
+}
+
+/// Longest a readiness check waits for the database
+const READY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Readiness probe
+///
+/// Runs `SELECT 1` against the pool and compares the schema version with the
+/// migrations of this build. Answers 503 while the database is unreachable or
+/// has pending migrations.
+#[utoipa::path(
+    get,
+    path = "/health/ready",
+    responses(
+        (status = 200, description = "Ready to serve requests", body = Readiness),
+        (status = 503, description = "Database unreachable or not migrated", body = Readiness)
+    ),
+    tag = "Health"
+)]
+pub async fn health_ready(State(pool): State<SqlitePool>) -> (StatusCode, Json<Readiness>) {
+    let check = async {
+        sqlx::query("SELECT 1").execute(&pool).await?;
+        migrations::current_version(&pool).await
+    };
+
+    let current = match tokio::time::timeout(READY_TIMEOUT, check).await {
+        Ok(Ok(version)) => Some(version),
+        Ok(Err(e)) => {
+            tracing::warn!("Readiness check failed: {}", e);
+            None
+        }
+        Err(_) => {
+            tracing::warn!("Readiness check timed out after {:?}", READY_TIMEOUT);
+            None
+        }
+    };
+
+    let migrations = current.map(|current| {
+        let latest = migrations::latest_version();
+        MigrationState {
+            current,
+            latest,
+            pending: (latest - current).max(0),
+        }
+    });
+    let ready = migrations.as_ref().is_some_and(|m| m.pending == 0);
+
+    let readiness = Readiness {
+        status: if ready {
+            HealthStatus::Ok
+        } else {
+            HealthStatus::Unavailable
+        },
+        database: if current.is_some() {
+            HealthStatus::Ok
+        } else {
+            HealthStatus::Unavailable
+        },
+        migrations,
+        build: BuildInfo::current(),
+    };
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (status, Json(readiness))
+}
+
+/// Register a new user
+///
+/// Creates an account; use `/auth/login` to obtain an access token.
+#[utoipa::path(
+    post,
+    path = "/auth/register",
+    request_body = Credentials,
+    responses(
+        (status = 201, description = "User registered", body = User),
+        (status = 400, description = "Invalid or taken username, weak password", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    tag = "Auth"
+)]
+pub async fn register(
+    State(pool): State<SqlitePool>,
+    Json(data): Json<Credentials>,
+) -> Result<(StatusCode, Json<User>)> {
+    let username = data.username.trim();
+
+    if username.len() < 3 || username.len() > 50 {
+        return Err(ApiError::Validation(
+            "Username must be between 3 and 50 characters".into(),
+        ));
+    }
+
+    if data.password.len() < 8 {
+        return Err(ApiError::Validation(
+            "Password must be at least 8 characters".into(),
+        ));
+    }
+
+    // Argon2 is slow on purpose; keep it off the async workers
+    let password = data.password;
+    let password_hash = tokio::task::spawn_blocking(move || auth::hash_password(&password))
+        .await
+        .map_err(|e| ApiError::Internal(format!("Password hashing failed: {}", e)))??;
+
+    // The UNIQUE constraint decides, so concurrent registrations of the same
+    // name get the same answer as sequential ones
+    let user = sqlx::query_as::<_, User>(
+        r#"
+        INSERT INTO users (username, password_hash) VALUES (?, ?)
+        ON CONFLICT (username) DO NOTHING
+        RETURNING id, username, created_at
+        "#,
+    )
+    .bind(username)
+    .bind(password_hash)
+    .fetch_optional(&pool)
+    .await?
+    .ok_or_else(|| ApiError::Validation("Username is already taken".into()))?;
+
+    Ok((StatusCode::CREATED, Json(user)))
+}
+
+/// Log in
+///
+/// Exchanges username and password for a signed access token.
+#[utoipa::path(
+    post,
+    path = "/auth/login",
+    request_body = Credentials,
+    responses(
+        (status = 200, description = "Access token", body = AuthToken),
+        (status = 401, description = "Invalid username or password", body = crate::models::ErrorResponse, content_type = "application/problem+json")
+    ),
+    tag = "Auth"
+)]
+pub async fn login(
+    State(pool): State<SqlitePool>,
+    State(auth_config): State<AuthConfig>,
+    Json(data): Json<Credentials>,
+) -> Result<Json<AuthToken>> {
+    let user: Option<(i64, String, String)> =
+        sqlx::query_as("SELECT id, username, password_hash FROM users WHERE username = ?")
+            .bind(data.username.trim())
+            .fetch_optional(&pool)
+            .await?;
+
+    // Unknown users are checked against a dummy hash, so they take as long
+    let (user, hash) = match user {
+        Some((id, username, hash)) => (Some((id, username)), hash),
+        None => (None, auth::dummy_hash().to_string()),
+    };
+
+    let password = data.password;
+    let valid = tokio::task::spawn_blocking(move || auth::verify_password(&password, &hash))
+        .await
+        .map_err(|e| ApiError::Internal(format!("Password check failed: {}", e)))?;
+
+    let Some((id, username)) = user.filter(|_| valid) else {
+        return Err(ApiError::Unauthorized("Invalid username or password".into()));
+    };
+
+    Ok(Json(AuthToken {
+        access_token: auth_config.issue_token(id, &username)?,
+        token_type: "Bearer".into(),
+        expires_in: auth_config.token_ttl.as_secs(),
     }))
 }
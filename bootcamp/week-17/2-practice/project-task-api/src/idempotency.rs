@@ -0,0 +1,482 @@
+//! Idempotent Requests
+//!
+//! A `POST` with an `Idempotency-Key` header runs once per key: its response
+//! is stored together with a fingerprint of the request (method, path,
+//! content type and body) and replayed, marked `Idempotent-Replayed: true`,
+//! when the same key comes back. Reusing a key for a different request gets
+//! 422, and a key whose first request is still running gets 409.
+//!
+//! Keys belong to the user of the bearer token; requests without a valid
+//! token pass through untouched and are refused by the handler. Server errors
+//! are not stored, so the client can retry them with the same key. Keys
+//! expire after `idempotency.ttl_secs` and are removed by `spawn_cleanup`.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use axum::{
+    body::{Body, Bytes},
+    extract::Request,
+    http::{header, request, response, HeaderName, HeaderValue, Method, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use sqlx::types::Json;
+use sqlx::SqlitePool;
+use tokio::task::JoinHandle;
+use tower::{Layer, Service};
+
+use crate::auth::{bearer_token, AuthConfig};
+use crate::error::{self, ApiError};
+
+/// Header with the client's key for the request
+pub const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+/// Header marking a response replayed from the store
+pub const REPLAYED_HEADER: HeaderName = HeaderName::from_static("idempotent-replayed");
+
+/// Longest accepted key
+const MAX_KEY_LEN: usize = 255;
+
+/// Response headers kept with the body
+const STORED_HEADERS: [HeaderName; 3] = [header::CONTENT_TYPE, header::ETAG, header::LOCATION];
+
+/// Longest wait between two cleanup rounds
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Keys and responses in the `idempotency_keys` table
+#[derive(Clone)]
+pub struct IdempotencyStore {
+    pool: SqlitePool,
+    ttl: Duration,
+}
+
+/// What to do with a request, given its key
+enum Claim {
+    /// First use: run it
+    New,
+    /// Same request seen before: answer with its response
+    Replay(StoredResponse),
+    /// The first request with the key has not finished yet
+    Running,
+    /// The key was used for a different request
+    Mismatch,
+}
+
+/// Fingerprint, status, headers and body of a stored key
+type KeyRow = (
+    String,
+    Option<i64>,
+    Option<Json<Vec<(String, String)>>>,
+    Option<Vec<u8>>,
+);
+
+/// Status, headers and body of a finished request
+struct StoredResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl StoredResponse {
+    fn new(parts: &response::Parts, body: &[u8]) -> Self {
+        let headers = STORED_HEADERS
+            .iter()
+            .filter_map(|name| {
+                let value = parts.headers.get(name)?.to_str().ok()?;
+                Some((name.to_string(), value.to_string()))
+            })
+            .collect();
+
+        Self {
+            status: parts.status.as_u16(),
+            headers,
+            body: body.to_vec(),
+        }
+    }
+
+    fn into_response(self) -> Response {
+        let mut response = Response::new(Body::from(self.body));
+        *response.status_mut() = StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK);
+
+        for (name, value) in self.headers {
+            if let (Ok(name), Ok(value)) =
+                (HeaderName::try_from(name), HeaderValue::try_from(value))
+            {
+                response.headers_mut().insert(name, value);
+            }
+        }
+        response
+            .headers_mut()
+            .insert(REPLAYED_HEADER, HeaderValue::from_static("true"));
+
+        response
+    }
+}
+
+impl IdempotencyStore {
+    /// Store remembering keys for `ttl`
+    pub fn new(pool: SqlitePool, ttl: Duration) -> Self {
+        Self { pool, ttl }
+    }
+
+    /// Layer applying the keys to every `POST` passing through it
+    ///
+    /// `auth` must be the configuration the handlers use; bodies over
+    /// `body_limit` bytes are refused with 413.
+    pub fn layer(&self, auth: AuthConfig, body_limit: usize) -> IdempotencyLayer {
+        IdempotencyLayer {
+            store: self.clone(),
+            auth,
+            body_limit,
+        }
+    }
+
+    /// Take `key` for a request with `fingerprint`, unless already in use
+    async fn claim(
+        &self,
+        user_id: i64,
+        key: &str,
+        fingerprint: &str,
+    ) -> Result<Claim, sqlx::Error> {
+        let now = Utc::now().timestamp();
+        let ttl = i64::try_from(self.ttl.as_secs()).unwrap_or(i64::MAX);
+
+        // An expired key is free again, even before the cleanup removes it
+        sqlx::query(
+            "DELETE FROM idempotency_keys WHERE user_id = ? AND key = ? AND expires_at <= ?",
+        )
+        .bind(user_id)
+        .bind(key)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        let claimed = sqlx::query(
+            r#"
+            INSERT INTO idempotency_keys (user_id, key, fingerprint, expires_at)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT (user_id, key) DO NOTHING
+            "#,
+        )
+        .bind(user_id)
+        .bind(key)
+        .bind(fingerprint)
+        .bind(now.saturating_add(ttl))
+        .execute(&self.pool)
+        .await?
+        .rows_affected()
+            == 1;
+
+        if claimed {
+            return Ok(Claim::New);
+        }
+
+        let stored: Option<KeyRow> = sqlx::query_as(
+            "SELECT fingerprint, status, headers, body FROM idempotency_keys WHERE user_id = ? AND key = ?",
+        )
+        .bind(user_id)
+        .bind(key)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(match stored {
+            // Released since the insert: the first request failed and is
+            // being retried by someone else
+            None | Some((_, None, _, _)) => Claim::Running,
+            Some((stored, ..)) if stored != fingerprint => Claim::Mismatch,
+            Some((_, Some(status), headers, body)) => Claim::Replay(StoredResponse {
+                status: u16::try_from(status).unwrap_or(500),
+                headers: headers.map(|Json(headers)| headers).unwrap_or_default(),
+                body: body.unwrap_or_default(),
+            }),
+        })
+    }
+
+    /// Save the response of the request holding `key`
+    async fn complete(
+        &self,
+        user_id: i64,
+        key: &str,
+        response: &StoredResponse,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE idempotency_keys SET status = ?, headers = ?, body = ? WHERE user_id = ? AND key = ?",
+        )
+        .bind(i64::from(response.status))
+        .bind(Json(&response.headers))
+        .bind(&response.body)
+        .bind(user_id)
+        .bind(key)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Forget `key`, so the request can be sent again
+    async fn release(&self, user_id: i64, key: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM idempotency_keys WHERE user_id = ? AND key = ?")
+            .bind(user_id)
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// A claimed key, released again if dropped before `complete`
+///
+/// Requests cut short (e.g. by the request timeout) must not keep their key
+/// busy until it expires.
+struct Pending {
+    store: IdempotencyStore,
+    user_id: i64,
+    key: Option<String>,
+}
+
+impl Pending {
+    async fn complete(mut self, response: &StoredResponse) {
+        let Some(key) = self.key.take() else {
+            return;
+        };
+
+        if let Err(e) = self.store.complete(self.user_id, &key, response).await {
+            tracing::error!("Could not store the response for an idempotency key: {}", e);
+            self.key = Some(key);
+            self.release().await;
+        }
+    }
+
+    async fn release(mut self) {
+        if let Some(key) = self.key.take()
+            && let Err(e) = self.store.release(self.user_id, &key).await
+        {
+            tracing::error!("Could not release an idempotency key: {}", e);
+        }
+    }
+}
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            let store = self.store.clone();
+            let user_id = self.user_id;
+
+            tokio::spawn(async move {
+                if let Err(e) = store.release(user_id, &key).await {
+                    tracing::error!("Could not release an idempotency key: {}", e);
+                }
+            });
+        }
+    }
+}
+
+/// Delete every expired key; returns how many were removed
+pub async fn remove_expired(pool: &SqlitePool) -> Result<u64, sqlx::Error> {
+    let removed = sqlx::query("DELETE FROM idempotency_keys WHERE expires_at <= ?")
+        .bind(Utc::now().timestamp())
+        .execute(pool)
+        .await?
+        .rows_affected();
+
+    Ok(removed)
+}
+
+/// Start the task that removes expired keys
+///
+/// It runs every `ttl`, or every ten minutes for longer TTLs.
+pub fn spawn_cleanup(pool: SqlitePool, ttl: Duration) -> JoinHandle<()> {
+    let period = ttl.min(CLEANUP_INTERVAL);
+
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(period);
+
+        loop {
+            ticks.tick().await;
+
+            match remove_expired(&pool).await {
+                Ok(0) => {}
+                Ok(removed) => tracing::debug!("Removed {} expired idempotency keys", removed),
+                Err(e) => tracing::error!("Could not remove expired idempotency keys: {}", e),
+            }
+        }
+    })
+}
+
+/// SHA-256 of what makes two requests the same
+fn fingerprint(parts: &request::Parts, body: &[u8]) -> String {
+    let content_type = parts
+        .headers
+        .get(header::CONTENT_TYPE)
+        .map_or(&[][..], HeaderValue::as_bytes);
+    let target = parts
+        .uri
+        .path_and_query()
+        .map_or("", |target| target.as_str());
+
+    let mut hasher = Sha256::new();
+    for part in [
+        parts.method.as_str().as_bytes(),
+        target.as_bytes(),
+        content_type,
+    ] {
+        hasher.update(part);
+        hasher.update(b"\n");
+    }
+    hasher.update(body);
+
+    hex::encode(hasher.finalize())
+}
+
+type BoxFuture<E> = Pin<Box<dyn Future<Output = Result<Response, E>> + Send>>;
+
+/// Tower layer produced by `IdempotencyStore::layer`
+#[derive(Clone)]
+pub struct IdempotencyLayer {
+    store: IdempotencyStore,
+    auth: AuthConfig,
+    body_limit: usize,
+}
+
+impl<S> Layer<S> for IdempotencyLayer {
+    type Service = IdempotencyService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        IdempotencyService {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+/// Service produced by `IdempotencyLayer`
+#[derive(Clone)]
+pub struct IdempotencyService<S> {
+    inner: S,
+    layer: IdempotencyLayer,
+}
+
+impl<S> Service<Request> for IdempotencyService<S>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    S::Error: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<S::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let key = (request.method() == Method::POST)
+            .then(|| request.headers().get(IDEMPOTENCY_KEY).cloned())
+            .flatten();
+        let user = key
+            .as_ref()
+            .and_then(|_| bearer_token(request.headers()))
+            .and_then(|token| self.layer.auth.verify_token(token).ok());
+
+        let (Some(key), Some(claims)) = (key, user) else {
+            return Box::pin(self.inner.call(request));
+        };
+
+        // The service that was polled ready handles the request
+        let clone = self.inner.clone();
+        let ready = std::mem::replace(&mut self.inner, clone);
+        let store = self.layer.store.clone();
+        let body_limit = self.layer.body_limit;
+
+        Box::pin(run_once(ready, store, claims.sub, key, request, body_limit))
+    }
+}
+
+/// Run `request` unless its key was already used, storing the response
+async fn run_once<S>(
+    mut inner: S,
+    store: IdempotencyStore,
+    user_id: i64,
+    key: HeaderValue,
+    request: Request,
+    body_limit: usize,
+) -> Result<Response, S::Error>
+where
+    S: Service<Request, Response = Response>,
+{
+    let path = request.uri().path().to_string();
+    let refuse = |error: ApiError| error::with_instance(error.into_response(), &path);
+
+    let Some(key) = key
+        .to_str()
+        .ok()
+        .filter(|key| !key.is_empty() && key.len() <= MAX_KEY_LEN)
+        .map(str::to_string)
+    else {
+        return Ok(refuse(ApiError::Validation(format!(
+            "Idempotency-Key must be 1 to {} ASCII characters",
+            MAX_KEY_LEN
+        ))));
+    };
+
+    let (parts, body) = request.into_parts();
+    let Ok(body) = axum::body::to_bytes(body, body_limit).await else {
+        return Ok(refuse(ApiError::PayloadTooLarge(format!(
+            "Request body exceeds the limit of {} bytes",
+            body_limit
+        ))));
+    };
+
+    let pending = match store
+        .claim(user_id, &key, &fingerprint(&parts, &body))
+        .await
+    {
+        Ok(Claim::New) => Pending {
+            store,
+            user_id,
+            key: Some(key),
+        },
+        Ok(Claim::Replay(stored)) => return Ok(stored.into_response()),
+        Ok(Claim::Running) => {
+            return Ok(refuse(ApiError::Conflict(
+                "A request with this Idempotency-Key is still in progress".into(),
+            )));
+        }
+        Ok(Claim::Mismatch) => {
+            return Ok(refuse(ApiError::Unprocessable(
+                "This Idempotency-Key was already used for a different request".into(),
+            )));
+        }
+        Err(e) => return Ok(refuse(e.into())),
+    };
+
+    let response = inner
+        .call(Request::from_parts(parts, Body::from(body)))
+        .await?;
+
+    if response.status().is_server_error() {
+        pending.release().await;
+        return Ok(response);
+    }
+
+    let (parts, body) = response.into_parts();
+    let body: Bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => {
+            pending.release().await;
+            return Ok(refuse(ApiError::Internal(format!(
+                "Could not read the response: {}",
+                e
+            ))));
+        }
+    };
+
+    pending.complete(&StoredResponse::new(&parts, &body)).await;
+
+    Ok(Response::from_parts(parts, Body::from(body)))
+}
@@ -2,8 +2,24 @@
 //!
 //! Complete REST API with SQLite for task management.
 
+pub mod audit;
+pub mod auth;
+pub mod config;
 pub mod db;
 pub mod error;
+pub mod etag;
+pub mod events;
+pub mod extract;
 pub mod handlers;
+pub mod idempotency;
+pub mod limits;
+pub mod metrics;
+pub mod migrations;
 pub mod models;
+pub mod projects;
+pub mod recurrence;
+pub mod repository;
 pub mod routes;
+pub mod state;
+pub mod transfer;
+pub mod webhooks;
@@ -0,0 +1,400 @@
+//! Request Limits
+//!
+//! `RateLimitLayer` throttles each client with a token bucket per route
+//! group: a bucket holds up to `burst` tokens, refilled at `per_minute`, and
+//! every request takes one. Responses of limited routes carry `RateLimit-Limit`,
+//! `RateLimit-Remaining` and `RateLimit-Reset`; a request finding the bucket
+//! empty gets 429 with `Retry-After`.
+//!
+//! `BodyLimitLayer` rejects bodies over the configured size with 413, as
+//! problem details like every other error.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use axum::{
+    extract::{ConnectInfo, Request},
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use tokio::time::Instant;
+use tower::{Layer, Service};
+
+use crate::auth::{bearer_token, AuthConfig};
+use crate::config::{RateLimit, RateLimitConfig};
+use crate::error::{self, ApiError};
+
+/// Header with the bucket size
+pub const LIMIT_HEADER: HeaderName = HeaderName::from_static("ratelimit-limit");
+/// Header with the requests left right now
+pub const REMAINING_HEADER: HeaderName = HeaderName::from_static("ratelimit-remaining");
+/// Header with the seconds until the bucket is full again
+pub const RESET_HEADER: HeaderName = HeaderName::from_static("ratelimit-reset");
+
+/// How often buckets that refilled completely are forgotten
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Routes sharing a limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum RouteGroup {
+    Auth,
+    Bulk,
+    Default,
+}
+
+impl RouteGroup {
+    /// Group of `path`, or `None` for routes that are never limited
+    fn of(path: &str) -> Option<Self> {
+        if path.starts_with("/health/") || path == "/metrics" {
+            None
+        } else if path.starts_with("/auth/") {
+            Some(Self::Auth)
+        } else if matches!(path, "/tasks/bulk" | "/tasks/import" | "/tasks/export") {
+            Some(Self::Bulk)
+        } else {
+            Some(Self::Default)
+        }
+    }
+}
+
+/// Who a bucket belongs to
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Client {
+    User(i64),
+    Ip(IpAddr),
+    /// No token and no peer address (e.g. requests built in tests)
+    Unknown,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn full(limit: RateLimit, now: Instant) -> Self {
+        Self {
+            tokens: f64::from(limit.burst),
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, limit: RateLimit, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+
+        self.tokens = (self.tokens + elapsed * per_second(limit)).min(f64::from(limit.burst));
+        self.updated = now;
+    }
+}
+
+fn per_second(limit: RateLimit) -> f64 {
+    f64::from(limit.per_minute) / 60.0
+}
+
+/// Outcome of taking a token
+#[derive(Debug)]
+struct Decision {
+    allowed: bool,
+    limit: u32,
+    remaining: u32,
+    /// Until the bucket is full again
+    reset: Duration,
+    /// Until the next token, when the request was refused
+    retry_after: Option<Duration>,
+}
+
+struct Buckets {
+    buckets: HashMap<(RouteGroup, Client), Bucket>,
+    last_sweep: Instant,
+}
+
+/// Token buckets of every client, shared by all copies of the layer
+#[derive(Clone)]
+pub struct RateLimiter {
+    config: Arc<RateLimitConfig>,
+    auth: AuthConfig,
+    state: Arc<Mutex<Buckets>>,
+}
+
+impl RateLimiter {
+    /// Limiter with the configured limits; `auth` identifies token holders
+    pub fn new(config: RateLimitConfig, auth: AuthConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+            auth,
+            state: Arc::new(Mutex::new(Buckets {
+                buckets: HashMap::new(),
+                last_sweep: Instant::now(),
+            })),
+        }
+    }
+
+    /// Layer applying the limits to every request passing through it
+    pub fn layer(&self) -> RateLimitLayer {
+        RateLimitLayer {
+            limiter: self.clone(),
+        }
+    }
+
+    fn limit(&self, group: RouteGroup) -> RateLimit {
+        match group {
+            RouteGroup::Auth => self.config.auth,
+            RouteGroup::Bulk => self.config.bulk,
+            RouteGroup::Default => self.config.default,
+        }
+    }
+
+    /// The client a request counts against
+    ///
+    /// A valid bearer token identifies the user; otherwise (and always for
+    /// `/auth/*`, to slow down password guessing) the IP address does.
+    fn client(&self, group: RouteGroup, request: &Request) -> Client {
+        if group != RouteGroup::Auth {
+            let user = bearer_token(request.headers())
+                .and_then(|token| self.auth.verify_token(token).ok());
+
+            if let Some(claims) = user {
+                return Client::User(claims.sub);
+            }
+        }
+
+        let forwarded = self
+            .config
+            .trust_forwarded_for
+            .then(|| forwarded_for(request.headers()))
+            .flatten();
+        let peer = request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(address)| address.ip());
+
+        forwarded.or(peer).map_or(Client::Unknown, Client::Ip)
+    }
+
+    /// Take a token from the bucket of `client` in `group`
+    fn check(&self, group: RouteGroup, client: Client) -> Decision {
+        let limit = self.limit(group);
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+
+        if now.duration_since(state.last_sweep) >= SWEEP_INTERVAL {
+            // A full bucket is no different from a missing one
+            state.buckets.retain(|(group, _), bucket| {
+                bucket.refill(self.limit(*group), now);
+                bucket.tokens < f64::from(self.limit(*group).burst)
+            });
+            state.last_sweep = now;
+        }
+
+        let bucket = state
+            .buckets
+            .entry((group, client))
+            .or_insert_with(|| Bucket::full(limit, now));
+        bucket.refill(limit, now);
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+
+        let rate = per_second(limit);
+        Decision {
+            allowed,
+            limit: limit.burst,
+            remaining: bucket.tokens.floor() as u32,
+            reset: Duration::from_secs_f64((f64::from(limit.burst) - bucket.tokens) / rate),
+            retry_after: (!allowed).then(|| Duration::from_secs_f64((1.0 - bucket.tokens) / rate)),
+        }
+    }
+}
+
+/// First address of `X-Forwarded-For`, i.e. the original client
+fn forwarded_for(headers: &HeaderMap) -> Option<IpAddr> {
+    headers
+        .get("x-forwarded-for")?
+        .to_str()
+        .ok()?
+        .split(',')
+        .next()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Whole seconds, rounded up so clients never retry too early
+fn ceil_secs(duration: Duration) -> u64 {
+    duration.as_secs_f64().ceil() as u64
+}
+
+fn add_rate_limit_headers(headers: &mut HeaderMap, decision: &Decision) {
+    headers.insert(LIMIT_HEADER, HeaderValue::from(decision.limit));
+    headers.insert(REMAINING_HEADER, HeaderValue::from(decision.remaining));
+    headers.insert(RESET_HEADER, HeaderValue::from(ceil_secs(decision.reset)));
+
+    if let Some(retry_after) = decision.retry_after {
+        headers.insert(
+            header::RETRY_AFTER,
+            HeaderValue::from(ceil_secs(retry_after)),
+        );
+    }
+}
+
+type BoxFuture<E> = Pin<Box<dyn Future<Output = Result<Response, E>> + Send>>;
+
+/// Tower layer enforcing a `RateLimiter`
+#[derive(Clone)]
+pub struct RateLimitLayer {
+    limiter: RateLimiter,
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimitService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimitService {
+            inner,
+            limiter: self.limiter.clone(),
+        }
+    }
+}
+
+/// Service produced by `RateLimitLayer`
+#[derive(Clone)]
+pub struct RateLimitService<S> {
+    inner: S,
+    limiter: RateLimiter,
+}
+
+impl<S> Service<Request> for RateLimitService<S>
+where
+    S: Service<Request, Response = Response> + Send + 'static,
+    S::Future: Send + 'static,
+    S::Error: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<S::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let Some(group) = RouteGroup::of(request.uri().path()) else {
+            return Box::pin(self.inner.call(request));
+        };
+
+        let client = self.limiter.client(group, &request);
+        let decision = self.limiter.check(group, client);
+
+        if !decision.allowed {
+            let error = ApiError::TooManyRequests(format!(
+                "Rate limit exceeded, retry after {}s",
+                decision.retry_after.map_or(1, ceil_secs)
+            ));
+            let mut response = error::with_instance(error.into_response(), request.uri().path());
+            add_rate_limit_headers(response.headers_mut(), &decision);
+
+            return Box::pin(std::future::ready(Ok(response)));
+        }
+
+        let response = self.inner.call(request);
+
+        Box::pin(async move {
+            let mut response = response.await?;
+            add_rate_limit_headers(response.headers_mut(), &decision);
+
+            Ok(response)
+        })
+    }
+}
+
+/// Tower layer rejecting request bodies larger than `limit` bytes
+///
+/// Bodies announcing a larger `Content-Length` are refused before reaching the
+/// handler. Bodies without one are cut off by `DefaultBodyLimit` while being
+/// read; whatever 413 the extractor answers with is replaced here, so the
+/// message is always the same.
+#[derive(Debug, Clone, Copy)]
+pub struct BodyLimitLayer {
+    limit: usize,
+}
+
+impl BodyLimitLayer {
+    pub fn new(limit: usize) -> Self {
+        Self { limit }
+    }
+}
+
+impl<S> Layer<S> for BodyLimitLayer {
+    type Service = BodyLimitService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        BodyLimitService {
+            inner,
+            limit: self.limit,
+        }
+    }
+}
+
+/// Service produced by `BodyLimitLayer`
+#[derive(Debug, Clone)]
+pub struct BodyLimitService<S> {
+    inner: S,
+    limit: usize,
+}
+
+fn too_large(limit: usize, path: &str) -> Response {
+    let error =
+        ApiError::PayloadTooLarge(format!("Request body exceeds the limit of {} bytes", limit));
+
+    error::with_instance(error.into_response(), path)
+}
+
+impl<S> Service<Request> for BodyLimitService<S>
+where
+    S: Service<Request, Response = Response> + Send + 'static,
+    S::Future: Send + 'static,
+    S::Error: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<S::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let length = request
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+
+        let path = request.uri().path().to_string();
+
+        if length.is_some_and(|length| length > self.limit as u64) {
+            return Box::pin(std::future::ready(Ok(too_large(self.limit, &path))));
+        }
+
+        let limit = self.limit;
+        let response = self.inner.call(request);
+
+        Box::pin(async move {
+            let response = response.await?;
+
+            if response.status() == StatusCode::PAYLOAD_TOO_LARGE {
+                return Ok(too_large(limit, &path));
+            }
+
+            Ok(response)
+        })
+    }
+}
@@ -6,12 +6,92 @@
 //!
 //! | Method | Route | Description |
 //! |--------|------|-------------|
+//! | GET | /health/live | Liveness probe |
+//! | GET | /health/ready | Readiness probe (database + migrations) |
+//! | GET | /metrics | Prometheus metrics (unless `metrics.enabled = false`) |
+//! | POST | /auth/register | Register a user |
+//! | POST | /auth/login | Get an access token |
 //! | GET | /tasks | List all tasks |
 //! | POST | /tasks | Create new task |
+//! | POST | /tasks/bulk | Create/update/delete many tasks at once |
+//! | GET | /tasks/export | Download tasks as JSON, NDJSON or CSV |
+//! | POST | /tasks/import | Create tasks from a JSON, NDJSON or CSV file |
 //! | GET | /tasks/:id | Get task by ID |
-//! | PUT | /tasks/:id | Update task |
-//! | DELETE | /tasks/:id | Delete task |
+//! | PUT | /tasks/:id | Replace task |
+//! | PATCH | /tasks/:id | Update task (JSON Merge Patch) |
+//! | DELETE | /tasks/:id | Delete task (soft delete) |
+//! | POST | /tasks/:id/restore | Undo a delete |
+//! | GET | /tasks/:id/history | Change history |
 //! | GET | /tasks/stats | Statistics |
+//! | GET | /tasks/events | Live task changes (Server-Sent Events) |
+//! | GET | /ws | Live task changes (WebSocket) |
+//! | GET | /tasks/search?q= | Full-text search |
+//! | GET | /tasks/:id/subtasks | Direct subtasks |
+//! | GET | /tasks/:id/tree | Task with nested subtasks |
+//! | POST | /tasks/:id/tags | Add tags |
+//! | DELETE | /tasks/:id/tags | Remove tags |
+//! | GET | /tags | Tags with usage counts |
+//! | PUT | /tasks/:id/recurrence | Make a task recurring |
+//! | GET | /tasks/:id/recurrence | Recurrence rule of a task |
+//! | DELETE | /tasks/:id/recurrence | Stop a task from recurring |
+//! | GET | /tasks/:id/occurrences | Preview upcoming occurrences |
+//! | POST | /webhooks | Register a webhook |
+//! | GET | /webhooks | List webhooks |
+//! | DELETE | /webhooks/:id | Delete a webhook |
+//! | GET | /webhooks/:id/deliveries | Delivery log of a webhook |
+//! | POST | /projects | Create a project |
+//! | GET | /projects | Projects of the caller |
+//! | GET | /projects/:pid | Get a project |
+//! | DELETE | /projects/:pid | Delete a project |
+//! | GET | /projects/:pid/members | Members and roles |
+//! | PUT | /projects/:pid/members/:username | Add a member or change their role |
+//! | DELETE | /projects/:pid/members/:username | Remove a member |
+//! | GET/POST | /projects/:pid/tasks | List or create project tasks |
+//! | GET/PUT/PATCH/DELETE | /projects/:pid/tasks/:id | Project task |
+//! | GET | /projects/:pid/tasks/stats | Project statistics |
+//!
+//! ## Authentication
+//!
+//! Task routes require `Authorization: Bearer <token>`; each user only sees
+//! their own tasks. Tokens are signed with the `JWT_SECRET` environment variable.
+//!
+//! ## Concurrency
+//!
+//! Task responses carry an `ETag` with the task version. `If-Match` on
+//! PUT/PATCH/DELETE rejects stale writes with 412, and `If-None-Match` on
+//! GET returns 304 while the task is unchanged.
+//!
+//! ## Idempotency
+//!
+//! A POST with an `Idempotency-Key` header runs only once per key: retries
+//! get the stored response back, marked `Idempotent-Replayed: true`, and a
+//! key reused for a different request gets 422. Keys are kept for
+//! `idempotency.ttl_secs` (see `idempotency`).
+//!
+//! ## Webhooks
+//!
+//! A background worker sends task events to registered webhooks, signed with
+//! HMAC-SHA256 and retried with exponential backoff (see `webhooks`).
+//!
+//! ## Recurring tasks
+//!
+//! A recurring task is re-created (daily, weekly, monthly or on a cron-like
+//! schedule, until an optional end date) once it is completed or its due
+//! date arrives. The scheduler works from the database only, so occurrences
+//! due while the server was down are created on the next start (see
+//! `recurrence`).
+//!
+//! ## Projects
+//!
+//! Projects hold tasks shared by their members, who are owners, editors or
+//! viewers. Viewers only read, editors also write and owners manage members;
+//! a missing role gives 403 and a project the caller is not in gives 404
+//! (see `projects`).
+//!
+//! ## Storage
+//!
+//! Core task routes run over any `TaskRepository` (SQLite, in-memory, or
+//! PostgreSQL with the `postgres` feature); this binary uses SQLite.
 //!
 //! ## Documentation
 //!
@@ -23,13 +103,51 @@
 //! - `?completed=false` - Only pending tasks
 //! - `?limit=10` - Limit results
 //! - `?offset=0` - Pagination
+//! - `?overdue=true` - Pending tasks past their deadline
+//! - `?sort=created|due_at|priority` - Sort order
+//! - `?tag=a&tag=b&tag_match=any|all` - Filter by tags
+//! - `?cursor=` - Keyset pagination, pass `next_cursor` to get the next page
+//!
+//! ## Configuration
+//!
+//! Settings come from a TOML file (`--config`, `TASK_API_CONFIG` or
+//! `config.toml`), then `TASK_API_*` environment variables, then command-line
+//! flags; see `config` for every option. Invalid settings stop the server at
+//! startup with a list of the problems.
+//!
+//! ## Shutdown
+//!
+//! On SIGINT or SIGTERM the server stops accepting connections, closes the
+//! live change feeds and waits up to `server.shutdown_timeout_secs` for
+//! in-flight requests before closing the database pool.
+//!
+//! ## Migrations
+//!
+//! Pending migrations are applied on startup. They can also be managed by hand:
+//!
+//! - `project-task-api migrate up` - Apply pending migrations
+//! - `project-task-api migrate down [N]` - Revert the last N migrations (default: 1)
+//! - `project-task-api migrate status` - Show applied/pending migrations
+
+use std::net::SocketAddr;
 
 use axum::Router;
-use tower_http::{cors::CorsLayer, trace::TraceLayer};
-use utoipa::OpenApi;
+use clap::{Parser, Subcommand};
+use tower_http::trace::TraceLayer;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
 use utoipa_swagger_ui::SwaggerUi;
 
-use project_task_api::{db, handlers, models, routes};
+use project_task_api::{
+    auth::AuthConfig,
+    config::{AppConfig, ConfigArgs, LogConfig, LogFormat},
+    db, handlers, idempotency, metrics, migrations, models,
+    recurrence::{self, SchedulerConfig},
+    repository::SqliteTaskRepository,
+    routes,
+    state::AppState,
+    webhooks::{self, WebhookConfig},
+};
 
 /// Task API OpenAPI Documentation
 #[derive(OpenApi)]
@@ -38,23 +156,134 @@ use project_task_api::{db, handlers, models, routes};
         handlers::list_tasks,
         handlers::get_task,
         handlers::create_task,
+        handlers::bulk_tasks,
+        handlers::export_tasks,
+        handlers::import_tasks,
+        handlers::replace_task,
         handlers::update_task,
         handlers::delete_task,
+        handlers::restore_task,
+        handlers::get_task_history,
         handlers::get_stats,
+        handlers::task_events,
+        handlers::task_socket,
+        handlers::search_tasks,
+        handlers::list_subtasks,
+        handlers::get_task_tree,
+        handlers::add_tags,
+        handlers::remove_tags,
+        handlers::list_tags,
+        handlers::set_recurrence,
+        handlers::get_recurrence,
+        handlers::delete_recurrence,
+        handlers::list_occurrences,
+        handlers::create_webhook,
+        handlers::list_webhooks,
+        handlers::delete_webhook,
+        handlers::list_webhook_deliveries,
+        handlers::create_project,
+        handlers::list_projects,
+        handlers::get_project,
+        handlers::delete_project,
+        handlers::list_project_members,
+        handlers::set_project_member,
+        handlers::remove_project_member,
+        handlers::list_project_tasks,
+        handlers::create_project_task,
+        handlers::get_project_task,
+        handlers::replace_project_task,
+        handlers::update_project_task,
+        handlers::delete_project_task,
+        handlers::get_project_stats,
+        handlers::get_project_task_history,
+        handlers::restore_project_task,
+        handlers::list_project_subtasks,
+        handlers::get_project_task_tree,
+        handlers::add_project_tags,
+        handlers::remove_project_tags,
+        handlers::set_project_recurrence,
+        handlers::get_project_recurrence,
+        handlers::delete_project_recurrence,
+        handlers::list_project_occurrences,
+        handlers::register,
+        handlers::login,
+        handlers::health_live,
+        handlers::health_ready,
+        metrics::get_metrics,
     ),
     components(
         schemas(
             models::Task,
             models::CreateTask,
+            models::BulkRequest,
+            models::BulkOperation,
+            models::BulkMode,
+            models::BulkResponse,
+            models::BulkItemResult,
+            models::ExportFormat,
+            models::ImportReport,
+            models::ImportRowError,
+            models::ReplaceTask,
             models::UpdateTask,
             models::TaskFilters,
+            models::TaskPage,
+            models::TaskList,
+            models::TagMatch,
+            models::TagsInput,
+            models::TagCount,
             models::TaskStats,
+            models::PriorityCounts,
+            models::Priority,
+            models::TaskSort,
+            models::TaskNode,
+            models::UpdateOptions,
+            models::RootProgress,
+            models::TaskEvent,
+            models::TaskEventKind,
+            models::ChangeEvent,
+            models::ChangeKind,
+            models::EventFilters,
+            models::Frequency,
+            models::RecurrenceRule,
+            models::Recurrence,
+            models::Occurrence,
+            models::OccurrenceQuery,
+            models::CreateWebhook,
+            models::Webhook,
+            models::NewWebhook,
+            models::WebhookDelivery,
+            models::DeliveryStatus,
+            models::DeliveryQuery,
+            models::ProjectRole,
+            models::CreateProject,
+            models::Project,
+            models::ProjectMember,
+            models::SetMemberRole,
+            models::SearchQuery,
+            models::SearchResult,
             models::ErrorResponse,
+            models::FieldError,
+            models::User,
+            models::Credentials,
+            models::AuthToken,
+            models::HealthStatus,
+            models::BuildInfo,
+            models::Liveness,
+            models::Readiness,
+            models::MigrationState,
         )
     ),
+    modifiers(&SecurityAddon),
     tags(
         (name = "Tasks", description = "Task management endpoints"),
-        (name = "Statistics", description = "Statistics endpoints")
+        (name = "Tags", description = "Task labels"),
+        (name = "Statistics", description = "Statistics endpoints"),
+        (name = "Events", description = "Live task changes"),
+        (name = "Webhooks", description = "Task events pushed to external systems"),
+        (name = "Projects", description = "Shared task lists with member roles"),
+        (name = "Auth", description = "Registration and login"),
+        (name = "Health", description = "Liveness and readiness probes"),
+        (name = "Metrics", description = "Prometheus metrics")
     ),
     info(
         title = "Task API",
@@ -72,50 +301,288 @@ use project_task_api::{db, handlers, models, routes};
 )]
 struct ApiDoc;
 
+/// Registers the JWT bearer security scheme
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "bearer_auth",
+                SecurityScheme::Http(
+                    HttpBuilder::new()
+                        .scheme(HttpAuthScheme::Bearer)
+                        .bearer_format("JWT")
+                        .build(),
+                ),
+            );
+        }
+    }
+}
+
+/// Task API server
+#[derive(Parser)]
+#[command(name = "project-task-api", version, about)]
+struct Cli {
+    #[command(flatten)]
+    config: ConfigArgs,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Manage schema migrations
+    Migrate {
+        #[command(subcommand)]
+        action: MigrateAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum MigrateAction {
+    /// Apply pending migrations
+    Up,
+    /// Revert the last N migrations
+    Down {
+        #[arg(default_value_t = 1)]
+        steps: usize,
+    },
+    /// Show applied/pending migrations
+    Status,
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = Cli::parse();
+
+    // Configuration problems are reported before anything starts
+    let config = match AppConfig::load(&cli.config, |name| std::env::var(name).ok()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            std::process::exit(2);
+        }
+    };
+
     // Initialize logging
-    tracing_subscriber::fmt()
-        .with_target(false)
-        .compact()
-        .init();
+    init_logging(&config.log);
+
+    // Migration subcommand: `migrate up|down [N]|status`
+    if let Some(Command::Migrate { action }) = cli.command {
+        return run_migrate(&config, action).await;
+    }
 
     tracing::info!("🚀 Starting Task API...");
 
     // Create SQLite connection pool
-    let pool = db::create_pool().await?;
-    tracing::info!("✅ SQLite connection established");
+    let pool = db::create_pool(&config.database).await?;
+    tracing::info!(
+        "✅ SQLite connection established (pool size {})",
+        config.database.max_connections
+    );
+
+    let auth = AuthConfig::from_env();
+    let state = AppState::new(SqliteTaskRepository::new(pool.clone()), auth.clone());
+
+    // Deliver task events to webhooks in the background
+    let worker = webhooks::spawn_worker(pool.clone(), &state.events, WebhookConfig::default());
+    tracing::info!("📮 Webhook worker started");
+
+    // Create the next occurrence of recurring tasks in the background
+    let scheduler =
+        recurrence::spawn_scheduler(pool.clone(), &state.events, SchedulerConfig::default());
+    tracing::info!("🔁 Recurrence scheduler started");
+
+    // Forget expired idempotency keys in the background
+    let cleanup = config
+        .idempotency
+        .enabled
+        .then(|| idempotency::spawn_cleanup(pool.clone(), config.idempotency.ttl()));
 
     // Build application
     let app = Router::new()
-        .merge(routes::create_routes())
-        .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()))
-        .layer(CorsLayer::permissive())
+        .merge(routes::create_routes::<SqliteTaskRepository>())
+        .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()));
+    let events = state.events.clone();
+    let app = routes::with_idempotency(app, &config, pool.clone(), auth.clone());
+    let app = routes::with_middleware(app, &config);
+    let app = routes::with_rate_limit(app, &config.rate_limit, auth);
+    let app = routes::with_metrics(app, &config.metrics, pool.clone())
         .layer(TraceLayer::new_for_http())
-        .with_state(pool);
+        .with_state(state);
 
     // Start server
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await?;
+    let listener = tokio::net::TcpListener::bind(config.server.bind).await?;
+    let address = listener.local_addr()?;
 
-    tracing::info!("🌐 Server listening on http://localhost:3000");
+    tracing::info!("🌐 Server listening on http://{}", address);
     tracing::info!("");
-    tracing::info!("📚 Swagger UI: http://localhost:3000/swagger-ui");
-    tracing::info!("📄 OpenAPI JSON: http://localhost:3000/api-docs/openapi.json");
+    tracing::info!("📚 Swagger UI: http://{}/swagger-ui", address);
+    tracing::info!("📄 OpenAPI JSON: http://{}/api-docs/openapi.json", address);
+    tracing::info!("");
+    tracing::info!("❤️  Health: http://{0}/health/live, http://{0}/health/ready", address);
+    if config.metrics.enabled {
+        tracing::info!("📈 Metrics: http://{}/metrics", address);
+    }
     tracing::info!("");
     tracing::info!("📝 Available endpoints:");
+    tracing::info!("   POST   /auth/register - Register user");
+    tracing::info!("   POST   /auth/login    - Get access token");
     tracing::info!("   GET    /tasks         - List tasks");
     tracing::info!("   POST   /tasks         - Create task");
+    tracing::info!("   POST   /tasks/bulk    - Bulk create/update/delete");
+    tracing::info!("   GET    /tasks/export  - Export tasks (?format=json|ndjson|csv)");
+    tracing::info!("   POST   /tasks/import  - Import tasks");
     tracing::info!("   GET    /tasks/:id     - Get task");
-    tracing::info!("   PUT    /tasks/:id     - Update task");
+    tracing::info!("   PUT    /tasks/:id     - Replace task");
+    tracing::info!("   PATCH  /tasks/:id     - Update task (merge patch)");
     tracing::info!("   DELETE /tasks/:id     - Delete task");
+    tracing::info!("   POST   /tasks/:id/restore - Restore deleted task");
+    tracing::info!("   GET    /tasks/:id/history - Change history");
     tracing::info!("   GET    /tasks/stats   - Statistics");
+    tracing::info!("   GET    /tasks/events  - Live changes (SSE)");
+    tracing::info!("   GET    /ws            - Live changes (WebSocket)");
+    tracing::info!("   GET    /tasks/search  - Full-text search (?q=...)");
+    tracing::info!("   GET    /tasks/:id/subtasks - Direct subtasks");
+    tracing::info!("   GET    /tasks/:id/tree - Nested subtasks");
+    tracing::info!("   POST   /tasks/:id/tags - Add tags");
+    tracing::info!("   DELETE /tasks/:id/tags - Remove tags");
+    tracing::info!("   GET    /tags          - Tags with usage counts");
+    tracing::info!("   PUT    /tasks/:id/recurrence - Make task recurring");
+    tracing::info!("   GET    /tasks/:id/occurrences - Upcoming occurrences");
+    tracing::info!("   POST   /webhooks      - Register webhook");
+    tracing::info!("   GET    /webhooks/:id/deliveries - Delivery log");
+    tracing::info!("   POST   /projects      - Create project");
+    tracing::info!("   PUT    /projects/:pid/members/:username - Add member");
+    tracing::info!("   GET    /projects/:pid/tasks - Project tasks");
     tracing::info!("");
-    tracing::info!("🔍 Filters: ?completed=true|false&limit=N&offset=N");
+    tracing::info!("🔍 Filters: ?completed=true|false&overdue=true&tag=a&sort=created|due_at|priority&limit=N&offset=N|cursor=...");
     tracing::info!("");
     tracing::info!("💡 Try:");
-    tracing::info!(r#"   curl -X POST localhost:3000/tasks -H "Content-Type: application/json" -d '{{"title":"My task"}}'"#);
+    tracing::info!(r#"   curl -X POST localhost:3000/auth/register -H "Content-Type: application/json" -d '{{"username":"ferris","password":"crab-secret"}}'"#);
+    tracing::info!(r#"   curl -X POST localhost:3000/auth/login -H "Content-Type: application/json" -d '{{"username":"ferris","password":"crab-secret"}}'"#);
+    tracing::info!(r#"   curl -X POST localhost:3000/tasks -H "Authorization: Bearer $TOKEN" -H "Content-Type: application/json" -d '{{"title":"My task"}}'"#);
+
+    // Serve until SIGINT/SIGTERM, then drain in-flight requests
+    let (stopping_tx, stopping) = tokio::sync::oneshot::channel();
+    // Peer addresses identify anonymous clients for rate limiting
+    let app = app.into_make_service_with_connect_info::<SocketAddr>();
+    let server = axum::serve(listener, app).with_graceful_shutdown(async move {
+        shutdown_signal().await;
+        tracing::info!("🛑 Shutting down, draining in-flight requests...");
+        // End SSE/WebSocket feeds, which would otherwise never finish
+        events.close();
+        let _ = stopping_tx.send(());
+    });
+    let drain_deadline = async {
+        if stopping.await.is_ok() {
+            tokio::time::sleep(config.server.shutdown_timeout()).await;
+        } else {
+            std::future::pending::<()>().await;
+        }
+    };
+
+    tokio::select! {
+        result = server => result?,
+        _ = drain_deadline => tracing::warn!(
+            "⏱️  Requests still running after {}s, closing anyway",
+            config.server.shutdown_timeout_secs
+        ),
+    }
+
+    // Pending webhook deliveries stay queued and are sent on the next start
+    worker.abort();
+    let _ = worker.await;
+    // Due occurrences are created from the database on the next start
+    scheduler.abort();
+    let _ = scheduler.await;
+    if let Some(cleanup) = cleanup {
+        cleanup.abort();
+    }
+    pool.close().await;
+    tracing::info!("👋 Shutdown complete");
+
+    Ok(())
+}
+
+/// Resolve on Ctrl+C (SIGINT) or, on Unix, SIGTERM
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Could not listen for Ctrl+C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("Could not listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+/// Set up the log subscriber with the configured format and level
+fn init_logging(config: &LogConfig) {
+    let builder = tracing_subscriber::fmt()
+        .with_target(false)
+        .with_max_level(config.level_filter());
+
+    match config.format {
+        LogFormat::Compact => builder.compact().init(),
+        LogFormat::Full => builder.init(),
+        LogFormat::Pretty => builder.pretty().init(),
+    }
+}
+
+/// Run the `migrate` subcommand
+async fn run_migrate(
+    config: &AppConfig,
+    action: MigrateAction,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let pool = db::connect_with(&config.database).await?;
+
+    match action {
+        MigrateAction::Up => {
+            let applied = migrations::migrate_up(&pool).await?;
+            println!("Applied {} migration(s): {:?}", applied.len(), applied);
+        }
+        MigrateAction::Down { steps } => {
+            let reverted = migrations::migrate_down(&pool, steps).await?;
+            println!("Reverted {} migration(s): {:?}", reverted.len(), reverted);
+        }
+        MigrateAction::Status => {
+            println!(
+                "Schema version: {} (latest: {})",
+                migrations::current_version(&pool).await?,
+                migrations::latest_version()
+            );
+            for m in migrations::status(&pool).await? {
+                let state = m.applied_at.as_deref().unwrap_or("pending");
+                println!("  {:>4}  {:<30} {}", m.version, m.name, state);
+            }
+        }
+    }
 
-    axum::serve(listener, app).await?;
+    pool.close().await;
 
     Ok(())
 }
@@ -0,0 +1,287 @@
+//! Prometheus Metrics
+//!
+//! `MetricsLayer` counts and times every request, labelled with its method,
+//! matched route and status code. `GET /metrics` renders those in the
+//! Prometheus text format, together with gauges read at scrape time: SQLite
+//! pool usage and task counts (the same queries as `GET /tasks/stats`, over
+//! every user).
+//!
+//! Routes are labelled with their pattern (`/tasks/{id}`), never the actual
+//! path, so the number of series stays bounded; requests that match no route
+//! are labelled `unmatched`.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::header,
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+use sqlx::SqlitePool;
+use tower::{Layer, Service};
+
+use crate::error::{ApiError, Result};
+use crate::repository::sqlite::task_counts;
+
+/// Prefix of every metric name
+const NAMESPACE: &str = "task_api";
+
+/// Metrics registry shared by the layer and `GET /metrics`
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    duration: HistogramVec,
+    pool_connections: IntGaugeVec,
+    pool_max_connections: IntGauge,
+    tasks: IntGaugeVec,
+    tasks_by_priority: IntGaugeVec,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    /// Registry with every metric at zero
+    pub fn new() -> Self {
+        let registry = Registry::new();
+
+        let requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests handled").namespace(NAMESPACE),
+            &["method", "route", "status"],
+        )
+        .expect("valid metric");
+        let duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Time to produce the response headers",
+            )
+            .namespace(NAMESPACE),
+            &["method", "route", "status"],
+        )
+        .expect("valid metric");
+        let pool_connections = IntGaugeVec::new(
+            Opts::new("db_pool_connections", "Open SQLite connections").namespace(NAMESPACE),
+            &["state"],
+        )
+        .expect("valid metric");
+        let pool_max_connections = IntGauge::with_opts(
+            Opts::new("db_pool_max_connections", "Size limit of the SQLite pool")
+                .namespace(NAMESPACE),
+        )
+        .expect("valid metric");
+        let tasks = IntGaugeVec::new(
+            Opts::new("tasks", "Live tasks of every user by state").namespace(NAMESPACE),
+            &["state"],
+        )
+        .expect("valid metric");
+        let tasks_by_priority = IntGaugeVec::new(
+            Opts::new("tasks_by_priority", "Live tasks of every user by priority")
+                .namespace(NAMESPACE),
+            &["priority"],
+        )
+        .expect("valid metric");
+
+        for collector in [
+            Box::new(requests.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(duration.clone()),
+            Box::new(pool_connections.clone()),
+            Box::new(pool_max_connections.clone()),
+            Box::new(tasks.clone()),
+            Box::new(tasks_by_priority.clone()),
+        ] {
+            registry
+                .register(collector)
+                .expect("metric names are unique");
+        }
+
+        Self {
+            registry,
+            requests,
+            duration,
+            pool_connections,
+            pool_max_connections,
+            tasks,
+            tasks_by_priority,
+        }
+    }
+
+    /// Layer recording every request passing through it
+    pub fn layer(&self) -> MetricsLayer {
+        MetricsLayer {
+            metrics: self.clone(),
+        }
+    }
+
+    fn record(&self, method: &str, route: &str, status: &str, started: Instant) {
+        let labels = [method, route, status];
+
+        self.requests.with_label_values(&labels).inc();
+        self.duration
+            .with_label_values(&labels)
+            .observe(started.elapsed().as_secs_f64());
+    }
+
+    /// Refresh the pool and task gauges from `pool`
+    async fn refresh(&self, pool: &SqlitePool) -> Result<()> {
+        let open = i64::from(pool.size());
+        let idle = pool.num_idle() as i64;
+        self.pool_connections.with_label_values(&["idle"]).set(idle);
+        self.pool_connections
+            .with_label_values(&["in_use"])
+            .set(open - idle);
+        self.pool_max_connections
+            .set(i64::from(pool.options().get_max_connections()));
+
+        let counts = task_counts(pool, None).await?;
+        for (state, count) in [
+            ("total", counts.total),
+            ("completed", counts.completed),
+            ("pending", counts.total - counts.completed),
+            ("overdue", counts.overdue),
+        ] {
+            self.tasks.with_label_values(&[state]).set(count);
+        }
+
+        let priorities = counts.by_priority;
+        for (priority, count) in [
+            ("low", priorities.low),
+            ("medium", priorities.medium),
+            ("high", priorities.high),
+            ("urgent", priorities.urgent),
+        ] {
+            self.tasks_by_priority
+                .with_label_values(&[priority])
+                .set(count);
+        }
+
+        Ok(())
+    }
+
+    /// Every metric in the Prometheus text format
+    pub fn render(&self) -> Result<String> {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .map_err(|e| ApiError::Internal(format!("Could not encode metrics: {}", e)))?;
+
+        String::from_utf8(buffer)
+            .map_err(|e| ApiError::Internal(format!("Could not encode metrics: {}", e)))
+    }
+}
+
+/// `GET /metrics` over `pool`, with the state it needs already attached
+pub fn routes<S>(metrics: Metrics, pool: SqlitePool) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/metrics", get(get_metrics))
+        .with_state(MetricsState { metrics, pool })
+}
+
+/// State of `GET /metrics`
+#[derive(Clone)]
+pub struct MetricsState {
+    metrics: Metrics,
+    pool: SqlitePool,
+}
+
+/// Prometheus metrics
+///
+/// Request counters and latency histograms per route and status, SQLite
+/// pool usage and task counts of every user, in the Prometheus text format.
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    responses(
+        (status = 200, description = "Metrics in the Prometheus text format", body = String, content_type = "text/plain")
+    ),
+    tag = "Metrics"
+)]
+pub async fn get_metrics(State(state): State<MetricsState>) -> Result<Response> {
+    // Request metrics are still worth serving when the database is down
+    if let Err(e) = state.metrics.refresh(&state.pool).await {
+        tracing::warn!("Could not refresh database metrics: {}", e);
+    }
+
+    let body = state.metrics.render()?;
+
+    Ok((
+        [(header::CONTENT_TYPE, TextEncoder::new().format_type())],
+        body,
+    )
+        .into_response())
+}
+
+/// Tower layer recording request counts and latencies into `Metrics`
+///
+/// Add it with `Router::layer`, so the matched route is known when a request
+/// reaches it.
+#[derive(Clone)]
+pub struct MetricsLayer {
+    metrics: Metrics,
+}
+
+impl<S> Layer<S> for MetricsLayer {
+    type Service = MetricsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        MetricsService {
+            inner,
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+/// Service produced by `MetricsLayer`
+#[derive(Clone)]
+pub struct MetricsService<S> {
+    inner: S,
+    metrics: Metrics,
+}
+
+impl<S> Service<Request> for MetricsService<S>
+where
+    S: Service<Request, Response = Response> + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = std::result::Result<Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<std::result::Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let started = Instant::now();
+        let method = request.method().to_string();
+        let route = request
+            .extensions()
+            .get::<MatchedPath>()
+            .map_or("unmatched", MatchedPath::as_str)
+            .to_string();
+        let metrics = self.metrics.clone();
+        let response = self.inner.call(request);
+
+        Box::pin(async move {
+            let response = response.await?;
+            metrics.record(&method, &route, response.status().as_str(), started);
+
+            Ok(response)
+        })
+    }
+}
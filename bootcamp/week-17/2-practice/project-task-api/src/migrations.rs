@@ -0,0 +1,452 @@
+//! Versioned Schema Migrations
+//!
+//! Migrations are applied in order and tracked in the `schema_migrations`
+//! table, so existing databases only receive the steps they are missing.
+
+use sqlx::SqlitePool;
+
+/// A single numbered schema change
+#[derive(Debug, Clone, Copy)]
+pub struct Migration {
+    /// Sequential version number (starts at 1)
+    pub version: i64,
+    /// Short description
+    pub name: &'static str,
+    /// SQL applied when migrating up
+    pub up: &'static str,
+    /// SQL that reverts `up`
+    pub down: &'static str,
+}
+
+/// State of a migration in the current database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub name: &'static str,
+    /// When it was applied, `None` if still pending
+    pub applied_at: Option<String>,
+}
+
+/// All known migrations, ordered by version
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "create_tasks",
+        // IF NOT EXISTS lets databases created before migrations adopt this step
+        up: r#"
+            CREATE TABLE IF NOT EXISTS tasks (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                title TEXT NOT NULL,
+                description TEXT,
+                completed BOOLEAN NOT NULL DEFAULT FALSE,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );
+            CREATE INDEX IF NOT EXISTS idx_tasks_completed ON tasks(completed);
+        "#,
+        down: r#"
+            DROP INDEX IF EXISTS idx_tasks_completed;
+            DROP TABLE IF EXISTS tasks;
+        "#,
+    },
+    Migration {
+        version: 2,
+        name: "create_tasks_fts",
+        // External-content FTS5 index over tasks, kept in sync by triggers
+        up: r#"
+            CREATE VIRTUAL TABLE tasks_fts USING fts5(
+                title,
+                description,
+                content = 'tasks',
+                content_rowid = 'id',
+                tokenize = 'unicode61 remove_diacritics 2'
+            );
+
+            CREATE TRIGGER tasks_fts_insert AFTER INSERT ON tasks BEGIN
+                INSERT INTO tasks_fts (rowid, title, description)
+                VALUES (new.id, new.title, new.description);
+            END;
+
+            CREATE TRIGGER tasks_fts_delete AFTER DELETE ON tasks BEGIN
+                INSERT INTO tasks_fts (tasks_fts, rowid, title, description)
+                VALUES ('delete', old.id, old.title, old.description);
+            END;
+
+            CREATE TRIGGER tasks_fts_update AFTER UPDATE OF title, description ON tasks BEGIN
+                INSERT INTO tasks_fts (tasks_fts, rowid, title, description)
+                VALUES ('delete', old.id, old.title, old.description);
+                INSERT INTO tasks_fts (rowid, title, description)
+                VALUES (new.id, new.title, new.description);
+            END;
+
+            INSERT INTO tasks_fts (tasks_fts) VALUES ('rebuild');
+        "#,
+        down: r#"
+            DROP TRIGGER IF EXISTS tasks_fts_update;
+            DROP TRIGGER IF EXISTS tasks_fts_delete;
+            DROP TRIGGER IF EXISTS tasks_fts_insert;
+            DROP TABLE IF EXISTS tasks_fts;
+        "#,
+    },
+    Migration {
+        version: 3,
+        name: "create_users_and_task_owner",
+        // Tasks created before this migration have no owner and are not visible to anyone
+        up: r#"
+            CREATE TABLE users (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                username TEXT NOT NULL UNIQUE COLLATE NOCASE,
+                password_hash TEXT NOT NULL,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );
+
+            ALTER TABLE tasks ADD COLUMN owner_id INTEGER REFERENCES users(id) ON DELETE CASCADE;
+            CREATE INDEX idx_tasks_owner ON tasks(owner_id);
+        "#,
+        down: r#"
+            DROP INDEX IF EXISTS idx_tasks_owner;
+            ALTER TABLE tasks DROP COLUMN owner_id;
+            DROP TABLE IF EXISTS users;
+        "#,
+    },
+    Migration {
+        version: 4,
+        name: "create_tags",
+        up: r#"
+            CREATE TABLE tags (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE COLLATE NOCASE
+            );
+
+            CREATE TABLE task_tags (
+                task_id INTEGER NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+                tag_id INTEGER NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+                PRIMARY KEY (task_id, tag_id)
+            );
+            CREATE INDEX idx_task_tags_tag ON task_tags(tag_id);
+        "#,
+        down: r#"
+            DROP INDEX IF EXISTS idx_task_tags_tag;
+            DROP TABLE IF EXISTS task_tags;
+            DROP TABLE IF EXISTS tags;
+        "#,
+    },
+    Migration {
+        version: 5,
+        name: "add_task_due_at_and_priority",
+        // priority: 0 = low, 1 = medium, 2 = high, 3 = urgent
+        up: r#"
+            ALTER TABLE tasks ADD COLUMN due_at DATETIME;
+            ALTER TABLE tasks ADD COLUMN priority INTEGER NOT NULL DEFAULT 1;
+            CREATE INDEX idx_tasks_due_at ON tasks(due_at);
+            CREATE INDEX idx_tasks_priority ON tasks(priority);
+        "#,
+        down: r#"
+            DROP INDEX IF EXISTS idx_tasks_priority;
+            DROP INDEX IF EXISTS idx_tasks_due_at;
+            ALTER TABLE tasks DROP COLUMN priority;
+            ALTER TABLE tasks DROP COLUMN due_at;
+        "#,
+    },
+    Migration {
+        version: 6,
+        name: "add_task_parent",
+        // Deleting a task deletes its subtasks
+        up: r#"
+            ALTER TABLE tasks ADD COLUMN parent_id INTEGER REFERENCES tasks(id) ON DELETE CASCADE;
+            CREATE INDEX idx_tasks_parent ON tasks(parent_id);
+        "#,
+        down: r#"
+            DROP INDEX IF EXISTS idx_tasks_parent;
+            ALTER TABLE tasks DROP COLUMN parent_id;
+        "#,
+    },
+    Migration {
+        version: 7,
+        name: "create_task_events_and_soft_delete",
+        // changes: JSON object {field: {before, after}}
+        up: r#"
+            ALTER TABLE tasks ADD COLUMN deleted_at DATETIME;
+            CREATE INDEX idx_tasks_deleted_at ON tasks(deleted_at);
+
+            CREATE TABLE task_events (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                task_id INTEGER NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+                actor_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+                kind TEXT NOT NULL,
+                changes TEXT NOT NULL DEFAULT '{}',
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );
+            CREATE INDEX idx_task_events_task ON task_events(task_id);
+        "#,
+        down: r#"
+            DROP INDEX IF EXISTS idx_task_events_task;
+            DROP TABLE IF EXISTS task_events;
+            DROP INDEX IF EXISTS idx_tasks_deleted_at;
+            ALTER TABLE tasks DROP COLUMN deleted_at;
+        "#,
+    },
+    Migration {
+        version: 8,
+        name: "add_task_version",
+        // Bumped on every change, exposed as the task's ETag
+        up: r#"
+            ALTER TABLE tasks ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+        "#,
+        down: r#"
+            ALTER TABLE tasks DROP COLUMN version;
+        "#,
+    },
+    Migration {
+        version: 9,
+        name: "create_webhooks",
+        // events: JSON array of event types; delivery status: pending, delivered or dead
+        up: r#"
+            CREATE TABLE webhooks (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                owner_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                url TEXT NOT NULL,
+                events TEXT NOT NULL,
+                secret TEXT NOT NULL,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );
+            CREATE INDEX idx_webhooks_owner ON webhooks(owner_id);
+
+            CREATE TABLE webhook_deliveries (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                webhook_id INTEGER NOT NULL REFERENCES webhooks(id) ON DELETE CASCADE,
+                event TEXT NOT NULL,
+                payload TEXT NOT NULL,
+                status TEXT NOT NULL DEFAULT 'pending',
+                attempts INTEGER NOT NULL DEFAULT 0,
+                next_attempt_at TEXT,
+                last_status INTEGER,
+                last_error TEXT,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                delivered_at DATETIME
+            );
+            CREATE INDEX idx_webhook_deliveries_webhook ON webhook_deliveries(webhook_id);
+            CREATE INDEX idx_webhook_deliveries_due ON webhook_deliveries(status, next_attempt_at);
+
+            CREATE TABLE webhook_dead_letters (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                delivery_id INTEGER NOT NULL REFERENCES webhook_deliveries(id) ON DELETE CASCADE,
+                webhook_id INTEGER NOT NULL REFERENCES webhooks(id) ON DELETE CASCADE,
+                event TEXT NOT NULL,
+                payload TEXT NOT NULL,
+                attempts INTEGER NOT NULL,
+                last_error TEXT,
+                failed_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );
+        "#,
+        down: r#"
+            DROP TABLE IF EXISTS webhook_dead_letters;
+            DROP INDEX IF EXISTS idx_webhook_deliveries_due;
+            DROP INDEX IF EXISTS idx_webhook_deliveries_webhook;
+            DROP TABLE IF EXISTS webhook_deliveries;
+            DROP INDEX IF EXISTS idx_webhooks_owner;
+            DROP TABLE IF EXISTS webhooks;
+        "#,
+    },
+    Migration {
+        version: 10,
+        name: "create_idempotency_keys",
+        // status is NULL while the first request with the key is still running;
+        // expires_at is a Unix timestamp
+        up: r#"
+            CREATE TABLE idempotency_keys (
+                user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                key TEXT NOT NULL,
+                fingerprint TEXT NOT NULL,
+                status INTEGER,
+                headers TEXT,
+                body BLOB,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                expires_at INTEGER NOT NULL,
+                PRIMARY KEY (user_id, key)
+            );
+            CREATE INDEX idx_idempotency_keys_expires ON idempotency_keys(expires_at);
+        "#,
+        down: r#"
+            DROP INDEX IF EXISTS idx_idempotency_keys_expires;
+            DROP TABLE IF EXISTS idempotency_keys;
+        "#,
+    },
+    Migration {
+        version: 11,
+        name: "create_task_recurrences",
+        // task_id is the latest occurrence of the series: the rule moves to
+        // each new occurrence; interval is NULL for cron rules
+        up: r#"
+            CREATE TABLE task_recurrences (
+                task_id INTEGER PRIMARY KEY REFERENCES tasks(id) ON DELETE CASCADE,
+                frequency TEXT NOT NULL,
+                interval INTEGER,
+                cron TEXT,
+                until TEXT,
+                starts_at TEXT NOT NULL,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );
+        "#,
+        down: r#"
+            DROP TABLE IF EXISTS task_recurrences;
+        "#,
+    },
+    Migration {
+        version: 12,
+        name: "create_projects",
+        // Project tasks have a project_id and no owner_id, so queries scoped
+        // to an owner never see them; role: owner, editor or viewer
+        up: r#"
+            CREATE TABLE projects (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL,
+                description TEXT,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );
+
+            CREATE TABLE project_members (
+                project_id INTEGER NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+                user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                role TEXT NOT NULL,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (project_id, user_id)
+            );
+            CREATE INDEX idx_project_members_user ON project_members(user_id);
+
+            ALTER TABLE tasks ADD COLUMN project_id INTEGER REFERENCES projects(id) ON DELETE CASCADE;
+            CREATE INDEX idx_tasks_project ON tasks(project_id);
+        "#,
+        down: r#"
+            DROP INDEX IF EXISTS idx_tasks_project;
+            DELETE FROM tasks WHERE project_id IS NOT NULL;
+            ALTER TABLE tasks DROP COLUMN project_id;
+            DROP INDEX IF EXISTS idx_project_members_user;
+            DROP TABLE IF EXISTS project_members;
+            DROP TABLE IF EXISTS projects;
+        "#,
+    },
+    Migration {
+        version: 13,
+        name: "add_task_deleted_with",
+        // ID of the task whose deletion also deleted this one, so a restore
+        // brings back that batch only; NULL for tasks deleted before
+        up: r#"
+            ALTER TABLE tasks ADD COLUMN deleted_with INTEGER;
+        "#,
+        down: r#"
+            ALTER TABLE tasks DROP COLUMN deleted_with;
+        "#,
+    },
+];
+
+/// Latest schema version known by this binary
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+/// Create the version tracking table if needed
+async fn ensure_version_table(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            applied_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Current schema version (0 for an empty database)
+pub async fn current_version(pool: &SqlitePool) -> Result<i64, sqlx::Error> {
+    ensure_version_table(pool).await?;
+
+    let version: (Option<i64>,) = sqlx::query_as("SELECT MAX(version) FROM schema_migrations")
+        .fetch_one(pool)
+        .await?;
+
+    Ok(version.0.unwrap_or(0))
+}
+
+/// Apply every pending migration
+///
+/// Each migration runs in its own transaction. Returns the applied versions.
+pub async fn migrate_up(pool: &SqlitePool) -> Result<Vec<i64>, sqlx::Error> {
+    let current = current_version(pool).await?;
+    let mut applied = Vec::new();
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        let mut tx = pool.begin().await?;
+
+        sqlx::raw_sql(migration.up).execute(&mut *tx).await?;
+        sqlx::query("INSERT INTO schema_migrations (version, name) VALUES (?, ?)")
+            .bind(migration.version)
+            .bind(migration.name)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        tracing::info!("Applied migration {} ({})", migration.version, migration.name);
+        applied.push(migration.version);
+    }
+
+    Ok(applied)
+}
+
+/// Revert the last `steps` applied migrations
+///
+/// Returns the reverted versions, newest first.
+pub async fn migrate_down(pool: &SqlitePool, steps: usize) -> Result<Vec<i64>, sqlx::Error> {
+    let current = current_version(pool).await?;
+    let mut reverted = Vec::new();
+
+    for migration in MIGRATIONS
+        .iter()
+        .rev()
+        .filter(|m| m.version <= current)
+        .take(steps)
+    {
+        let mut tx = pool.begin().await?;
+
+        sqlx::raw_sql(migration.down).execute(&mut *tx).await?;
+        sqlx::query("DELETE FROM schema_migrations WHERE version = ?")
+            .bind(migration.version)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        tracing::info!("Reverted migration {} ({})", migration.version, migration.name);
+        reverted.push(migration.version);
+    }
+
+    Ok(reverted)
+}
+
+/// Applied/pending state of every known migration
+pub async fn status(pool: &SqlitePool) -> Result<Vec<MigrationStatus>, sqlx::Error> {
+    ensure_version_table(pool).await?;
+
+    let applied: Vec<(i64, String)> =
+        sqlx::query_as("SELECT version, applied_at FROM schema_migrations")
+            .fetch_all(pool)
+            .await?;
+
+    Ok(MIGRATIONS
+        .iter()
+        .map(|m| MigrationStatus {
+            version: m.version,
+            name: m.name,
+            applied_at: applied
+                .iter()
+                .find(|(version, _)| *version == m.version)
+                .map(|(_, at)| at.clone()),
+        })
+        .collect())
+}
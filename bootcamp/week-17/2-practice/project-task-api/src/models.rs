@@ -1,6 +1,10 @@
 //! Data models
+//!
+//! Shared with `task-api-client`: request bodies are `Serialize` and
+//! responses `Deserialize` as well, so both sides use the same types.
 
-use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
 use sqlx::FromRow;
 use utoipa::ToSchema;
 
@@ -25,10 +29,25 @@ pub struct Task {
     /// Last update timestamp
     #[schema(example = "2025-01-15 12:00:00")]
     pub updated_at: String,
+    /// Optional deadline (RFC 3339)
+    #[schema(example = "2025-02-01T18:00:00Z")]
+    pub due_at: Option<DateTime<Utc>>,
+    /// Priority level
+    pub priority: Priority,
+    /// Parent task, `None` for root tasks
+    #[schema(example = json!(null))]
+    pub parent_id: Option<i64>,
+    /// Revision number, increased on every change (sent as the `ETag`)
+    #[schema(example = 3)]
+    pub version: i64,
+    /// Labels attached to the task, sorted by name
+    #[schema(example = json!(["backend", "urgent"]))]
+    #[sqlx(json)]
+    pub tags: Vec<String>,
 }
 
 /// DTO for creating a task
-#[derive(Debug, Deserialize, ToSchema)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
 pub struct CreateTask {
     /// Task title (required)
     #[schema(example = "Learn Rust")]
@@ -36,38 +55,374 @@ pub struct CreateTask {
     /// Optional task description
     #[schema(example = "Complete the bootcamp")]
     pub description: Option<String>,
+    /// Optional deadline (RFC 3339)
+    #[schema(example = "2025-02-01T18:00:00Z")]
+    pub due_at: Option<DateTime<Utc>>,
+    /// Priority level (default: medium)
+    pub priority: Option<Priority>,
+    /// Parent task, to create a subtask
+    #[schema(example = json!(null))]
+    pub parent_id: Option<i64>,
+    /// Optional labels
+    #[schema(example = json!(["learning"]))]
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
-/// DTO for updating a task
-#[derive(Debug, Deserialize, ToSchema)]
-pub struct UpdateTask {
-    /// New title (optional)
+/// DTO for replacing a task (`PUT`)
+///
+/// Every field is written: omitted optional fields are cleared.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ReplaceTask {
+    /// Task title (required)
     #[schema(example = "Master Rust")]
-    pub title: Option<String>,
-    /// New description (optional)
+    pub title: String,
+    /// Task description
     #[schema(example = "Now I'm an expert")]
     pub description: Option<String>,
-    /// New completion status (optional)
+    /// Completion status (default: false)
     #[schema(example = true)]
-    pub completed: Option<bool>,
+    #[serde(default)]
+    pub completed: bool,
+    /// Deadline (RFC 3339)
+    #[schema(example = "2025-02-01T18:00:00Z")]
+    pub due_at: Option<DateTime<Utc>>,
+    /// Priority level (default: medium)
+    pub priority: Option<Priority>,
+    /// Parent task, `None` for a root task
+    #[schema(example = json!(null))]
+    pub parent_id: Option<i64>,
+    /// Labels, replacing the current ones
+    #[schema(example = json!(["learning"]))]
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// JSON Merge Patch (RFC 7386) for a task (`PATCH`)
+///
+/// Omitted fields are left unchanged and `null` clears a field.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct UpdateTask {
+    /// New title (cannot be null)
+    #[schema(value_type = Option<String>, example = "Master Rust")]
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    pub title: Option<Option<String>>,
+    /// New description, `null` to clear
+    #[schema(value_type = Option<String>, nullable, example = "Now I'm an expert")]
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    pub description: Option<Option<String>>,
+    /// New completion status (cannot be null)
+    #[schema(value_type = Option<bool>, example = true)]
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    pub completed: Option<Option<bool>>,
+    /// New deadline (RFC 3339), `null` to clear
+    #[schema(value_type = Option<DateTime<Utc>>, nullable, example = "2025-02-01T18:00:00Z")]
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    pub due_at: Option<Option<DateTime<Utc>>>,
+    /// New priority (cannot be null)
+    #[schema(value_type = Option<Priority>)]
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Option<Priority>>,
+    /// Move under another task, `null` to make it a root task
+    #[schema(value_type = Option<i64>, nullable, example = 3)]
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<Option<i64>>,
+    /// Replace the labels, `null` to remove them all
+    #[schema(value_type = Option<Vec<String>>, nullable, example = json!(["backend"]))]
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Option<Vec<String>>>,
+}
+
+/// Deserialize a present field as `Some`, keeping an explicit `null` as `Some(None)`
+fn nullable<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// Query options for updating a task
+#[derive(Debug, Clone, Serialize, Deserialize, Default, ToSchema)]
+pub struct UpdateOptions {
+    /// Apply a `completed` change to every subtask as well
+    #[schema(example = true)]
+    pub cascade: Option<bool>,
+}
+
+/// Task with its nested subtasks
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TaskNode {
+    #[serde(flatten)]
+    pub task: Task,
+    /// Direct subtasks, each with their own subtasks
+    #[schema(no_recursion)]
+    pub children: Vec<TaskNode>,
+}
+
+/// One operation of a bulk request
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum BulkOperation {
+    /// Create a task, with the same rules as `POST /tasks`
+    Create { task: CreateTask },
+    /// Apply a JSON Merge Patch, like `PATCH /tasks/{id}`
+    Update { id: i64, patch: UpdateTask },
+    /// Soft-delete a task and its subtasks, like `DELETE /tasks/{id}`
+    Delete { id: i64 },
+}
+
+/// How a bulk request handles failing operations
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkMode {
+    /// Any failure rolls back every operation
+    #[default]
+    Atomic,
+    /// Failed operations are skipped, the rest are committed
+    BestEffort,
+}
+
+/// Body of `POST /tasks/bulk`
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BulkRequest {
+    /// Failure handling (default: atomic)
+    #[serde(default)]
+    pub mode: BulkMode,
+    /// Operations, applied in order (at most 1000)
+    #[schema(example = json!([
+        {"op": "create", "task": {"title": "Write docs"}},
+        {"op": "update", "id": 1, "patch": {"completed": true}},
+        {"op": "delete", "id": 2}
+    ]))]
+    pub operations: Vec<BulkOperation>,
+}
+
+/// Outcome of one bulk operation
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BulkItemResult {
+    /// Position of the operation in the request
+    #[schema(example = 0)]
+    pub index: usize,
+    /// HTTP status the operation would have had on its own
+    /// (424 if it was rolled back or skipped because another one failed)
+    #[schema(example = 201)]
+    pub status: u16,
+    /// Created or updated task
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub task: Option<Task>,
+    /// Why the operation failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Response of `POST /tasks/bulk`
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BulkResponse {
+    /// Whether the changes were saved
+    #[schema(example = true)]
+    pub committed: bool,
+    /// Operations applied
+    #[schema(example = 3)]
+    pub succeeded: usize,
+    /// Operations that failed or were rolled back
+    #[schema(example = 0)]
+    pub failed: usize,
+    /// One result per operation, in request order
+    pub results: Vec<BulkItemResult>,
+}
+
+/// Task priority, stored as an integer so it sorts naturally
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, sqlx::Type, ToSchema,
+)]
+#[serde(rename_all = "lowercase")]
+#[repr(i64)]
+pub enum Priority {
+    Low = 0,
+    #[default]
+    Medium = 1,
+    High = 2,
+    Urgent = 3,
+}
+
+/// Sort order for task lists
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskSort {
+    /// Newest first
+    #[default]
+    Created,
+    /// Soonest deadline first, tasks without deadline last
+    DueAt,
+    /// Most urgent first
+    Priority,
 }
 
 /// Query filters
-#[derive(Debug, Deserialize, Default, ToSchema)]
+#[derive(Debug, Clone, Deserialize, Default, ToSchema)]
 pub struct TaskFilters {
     /// Filter by completion status
     #[schema(example = false)]
     pub completed: Option<bool>,
-    /// Results limit
+    /// Results limit (default 100, 1-100 with `cursor`)
     #[schema(example = 10)]
     pub limit: Option<i64>,
     /// Pagination offset
     #[schema(example = 0)]
     pub offset: Option<i64>,
+    /// Only tasks with these tags (repeat the parameter: `tag=a&tag=b`)
+    #[schema(example = json!(["backend"]))]
+    #[serde(default)]
+    pub tag: Vec<String>,
+    /// Whether tasks need any (default) or all of the given tags
+    pub tag_match: Option<TagMatch>,
+    /// Only pending tasks whose deadline has passed
+    #[schema(example = true)]
+    pub overdue: Option<bool>,
+    /// Sort order (default: created)
+    pub sort: Option<TaskSort>,
+    /// Keyset pagination cursor (empty for the first page)
+    #[schema(example = "aWQ6NDI")]
+    #[serde(default, deserialize_with = "present_string")]
+    pub cursor: Option<String>,
+}
+
+/// Deserialize a present parameter as `Some`, even when empty (`?cursor=`)
+fn present_string<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    String::deserialize(deserializer).map(Some)
+}
+
+/// How multiple `tag` filters are combined
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TagMatch {
+    /// Tasks with at least one of the tags
+    #[default]
+    Any,
+    /// Tasks with every tag
+    All,
+}
+
+/// Tags to attach to or detach from a task
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TagsInput {
+    /// Tag names (case-insensitive)
+    #[schema(example = json!(["backend", "urgent"]))]
+    pub tags: Vec<String>,
+}
+
+/// Tag with the number of tasks using it
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct TagCount {
+    /// Tag name
+    #[schema(example = "backend")]
+    pub name: String,
+    /// Number of the caller's tasks with this tag
+    #[schema(example = 3)]
+    pub count: i64,
+}
+
+/// One page of tasks in cursor (keyset) mode
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TaskPage {
+    /// Tasks in this page, newest first
+    pub items: Vec<Task>,
+    /// Cursor for the next page, `None` on the last page
+    #[schema(example = "aWQ6NDI")]
+    pub next_cursor: Option<String>,
+    /// Whether more tasks follow this page
+    #[schema(example = true)]
+    pub has_more: bool,
+}
+
+/// Response of `GET /tasks`: a page envelope in cursor mode, a plain array in offset mode
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum TaskList {
+    Page(TaskPage),
+    Items(Vec<Task>),
+}
+
+/// File format for task export and import
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// One JSON array
+    #[default]
+    Json,
+    /// One JSON object per line
+    Ndjson,
+    /// Comma-separated values with a header row, tags joined with `;`
+    Csv,
 }
 
-/// Task statistics
-#[derive(Debug, Serialize, ToSchema)]
+/// `format` query parameter of export and import
+#[derive(Debug, Clone, Serialize, Deserialize, Default, ToSchema)]
+pub struct FormatQuery {
+    /// File format (default: json, or the request `Content-Type` on import)
+    pub format: Option<ExportFormat>,
+}
+
+/// Outcome of `POST /tasks/import`
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ImportReport {
+    /// Tasks created (0 when any row is invalid)
+    #[schema(example = 12)]
+    pub imported: usize,
+    /// Problems found, one per invalid row
+    pub errors: Vec<ImportRowError>,
+}
+
+/// Validation error of one imported row
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ImportRowError {
+    /// Line number (CSV, NDJSON) or 1-based array position (JSON)
+    #[schema(example = 3)]
+    pub row: usize,
+    /// What is wrong with the row
+    #[schema(example = "Title is required")]
+    pub error: String,
+}
+
+/// Full-text search query
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SearchQuery {
+    /// Words to search in title and description (prefix matching)
+    #[schema(example = "rus")]
+    pub q: String,
+    /// Filter by completion status
+    #[schema(example = false)]
+    pub completed: Option<bool>,
+    /// Results limit (1-100, default 20)
+    #[schema(example = 20)]
+    pub limit: Option<i64>,
+}
+
+/// Task matching a search, with relevance and highlighted text
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct SearchResult {
+    /// Matching task
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub task: Task,
+    /// BM25 relevance (lower is more relevant)
+    #[schema(example = -1.5)]
+    pub rank: f64,
+    /// Title with matches wrapped in `<mark>` tags
+    #[schema(example = "Learn <mark>Rust</mark>")]
+    pub title_highlight: String,
+    /// Description fragment around the matches, wrapped in `<mark>` tags
+    #[schema(example = "Complete the <mark>Rust</mark> bootcamp")]
+    pub snippet: Option<String>,
+}
+
+/// Task statistics of a user or of a project
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct TaskStats {
     /// Total tasks
     #[schema(example = 100)]
@@ -78,15 +433,548 @@ pub struct TaskStats {
     /// Pending tasks
     #[schema(example = 25)]
     pub pending: i64,
+    /// Pending tasks past their deadline
+    #[schema(example = 4)]
+    pub overdue: i64,
+    /// Task count per priority
+    pub by_priority: PriorityCounts,
+    /// Progress of every root task that has subtasks
+    pub roots: Vec<RootProgress>,
+}
+
+/// Completion of the subtasks below a root task
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct RootProgress {
+    /// Root task ID
+    #[schema(example = 1)]
+    pub id: i64,
+    /// Root task title
+    #[schema(example = "Launch website")]
+    pub title: String,
+    /// Number of subtasks at any depth
+    #[schema(example = 4)]
+    pub subtasks: i64,
+    /// Completed subtasks
+    #[schema(example = 3)]
+    pub completed: i64,
+    /// Completion percentage (0-100)
+    #[schema(example = 75.0)]
+    pub percent: f64,
+}
+
+/// Task count per priority
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct PriorityCounts {
+    #[schema(example = 10)]
+    pub low: i64,
+    #[schema(example = 60)]
+    pub medium: i64,
+    #[schema(example = 25)]
+    pub high: i64,
+    #[schema(example = 5)]
+    pub urgent: i64,
+}
+
+impl PriorityCounts {
+    /// Collect `(priority, count)` pairs; missing priorities count 0
+    pub fn from_counts(counts: impl IntoIterator<Item = (Priority, i64)>) -> Self {
+        let mut by_priority = Self::default();
+
+        for (priority, count) in counts {
+            match priority {
+                Priority::Low => by_priority.low = count,
+                Priority::Medium => by_priority.medium = count,
+                Priority::High => by_priority.high = count,
+                Priority::Urgent => by_priority.urgent = count,
+            }
+        }
+
+        by_priority
+    }
+}
+
+/// Kind of change recorded in a task's history
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+pub enum TaskEventKind {
+    Created,
+    Updated,
+    Deleted,
+    Restored,
+}
+
+/// Entry of a task's audit log
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct TaskEvent {
+    /// Unique event ID (increasing)
+    #[schema(example = 7)]
+    pub id: i64,
+    /// Affected task
+    #[schema(example = 1)]
+    pub task_id: i64,
+    /// User who made the change, `None` if the account was removed
+    #[schema(example = 1)]
+    pub actor_id: Option<i64>,
+    /// What happened
+    pub kind: TaskEventKind,
+    /// Changed fields, each with its `before` and `after` value
+    #[schema(value_type = Object, example = json!({"completed": {"before": false, "after": true}}))]
+    #[sqlx(json)]
+    pub changes: serde_json::Value,
+    /// When the change was made
+    #[schema(example = "2025-01-15 12:00:00")]
+    pub created_at: String,
+}
+
+/// Kind of change pushed to live subscribers and webhooks
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, sqlx::Type, ToSchema,
+)]
+#[sqlx(type_name = "TEXT")]
+pub enum ChangeKind {
+    /// A task was created (or restored)
+    #[serde(rename = "task.created")]
+    #[sqlx(rename = "task.created")]
+    Created,
+    /// A task was modified
+    #[serde(rename = "task.updated")]
+    #[sqlx(rename = "task.updated")]
+    Updated,
+    /// A task was deleted
+    #[serde(rename = "task.deleted")]
+    #[sqlx(rename = "task.deleted")]
+    Deleted,
+}
+
+impl ChangeKind {
+    /// Name used as the SSE event type
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ChangeKind::Created => "task.created",
+            ChangeKind::Updated => "task.updated",
+            ChangeKind::Deleted => "task.deleted",
+        }
+    }
+}
+
+/// Change event sent by `GET /tasks/events` and `/ws`
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ChangeEvent {
+    /// What happened
+    #[serde(rename = "type")]
+    #[schema(example = "task.updated")]
+    pub kind: ChangeKind,
+    /// The task after the change (for deletions, as it was when deleted)
+    pub task: Task,
+}
+
+/// Query parameters of the live change feeds
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct EventFilters {
+    /// Only events about this task
+    #[schema(example = 1)]
+    pub id: Option<i64>,
+    /// Only events about tasks in this completion state
+    pub completed: Option<bool>,
+}
+
+impl EventFilters {
+    /// Whether an event passes the filters
+    pub fn matches(&self, event: &ChangeEvent) -> bool {
+        self.id.is_none_or(|id| event.task.id == id)
+            && self.completed.is_none_or(|completed| event.task.completed == completed)
+    }
+}
+
+/// Webhook registration
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CreateWebhook {
+    /// Endpoint that receives a `POST` per event (http or https)
+    #[schema(example = "https://example.com/hooks/tasks")]
+    pub url: String,
+    /// Event types to deliver
+    #[schema(example = json!(["task.created", "task.deleted"]))]
+    pub events: Vec<ChangeKind>,
+}
+
+/// Registered webhook
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct Webhook {
+    /// Unique webhook ID
+    #[schema(example = 1)]
+    pub id: i64,
+    /// Endpoint receiving the events
+    #[schema(example = "https://example.com/hooks/tasks")]
+    pub url: String,
+    /// Event types delivered
+    #[sqlx(json)]
+    pub events: Vec<ChangeKind>,
+    /// Registration timestamp
+    #[schema(example = "2025-01-15 10:30:00")]
+    pub created_at: String,
+}
+
+/// Webhook just registered, with its signing secret
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NewWebhook {
+    #[serde(flatten)]
+    pub webhook: Webhook,
+    /// HMAC-SHA256 key of the `X-Webhook-Signature` header, only shown here
+    #[schema(example = "3f1c9a...")]
+    pub secret: String,
+}
+
+/// State of a webhook delivery
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    /// Waiting for its first attempt or a retry
+    Pending,
+    /// Accepted by the endpoint with a 2xx response
+    Delivered,
+    /// Gave up after the last retry, copied to the dead-letter table
+    Dead,
+}
+
+/// One event sent (or to be sent) to a webhook
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct WebhookDelivery {
+    /// Unique delivery ID, sent as `X-Webhook-Delivery`
+    #[schema(example = 12)]
+    pub id: i64,
+    /// Webhook it belongs to
+    #[schema(example = 1)]
+    pub webhook_id: i64,
+    /// Event type
+    pub event: ChangeKind,
+    /// Request body sent, a `ChangeEvent`
+    #[schema(value_type = Object)]
+    #[sqlx(json)]
+    pub payload: serde_json::Value,
+    /// Delivery state
+    pub status: DeliveryStatus,
+    /// Attempts made so far
+    #[schema(example = 1)]
+    pub attempts: i64,
+    /// When the next attempt is due (pending deliveries only)
+    #[schema(example = "2025-01-15 10:30:04.000")]
+    pub next_attempt_at: Option<String>,
+    /// HTTP status of the last attempt, `None` if no response was received
+    #[schema(example = 200)]
+    pub last_status: Option<i64>,
+    /// Why the last attempt failed
+    pub last_error: Option<String>,
+    /// When the event was queued
+    #[schema(example = "2025-01-15 10:30:00")]
+    pub created_at: String,
+    /// When the endpoint accepted it
+    pub delivered_at: Option<String>,
+}
+
+/// Query parameters of `GET /webhooks/{id}/deliveries`
+#[derive(Debug, Clone, Serialize, Deserialize, Default, ToSchema)]
+pub struct DeliveryQuery {
+    /// Only deliveries in this state
+    pub status: Option<DeliveryStatus>,
+}
+
+/// How often a recurring task repeats
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+pub enum Frequency {
+    /// Every `interval` days
+    Daily,
+    /// Every `interval` weeks
+    Weekly,
+    /// Every `interval` months, on the day of the first due date (or the
+    /// last day of shorter months)
+    Monthly,
+    /// At the times matching the `cron` expression
+    Cron,
+}
+
+/// Recurrence rule of a task (`PUT /tasks/{id}/recurrence`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct RecurrenceRule {
+    pub frequency: Frequency,
+    /// Repeat every N days, weeks or months (default: 1); not used with `cron`
+    #[schema(example = 1)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval: Option<u32>,
+    /// Five-field expression (`minute hour day month weekday`, in UTC),
+    /// required with `frequency: cron`
+    #[schema(example = "0 9 * * 1-5")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cron: Option<String>,
+    /// No occurrence is due after this date
+    #[schema(example = "2025-12-31T23:59:59Z")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<DateTime<Utc>>,
+}
+
+/// Recurrence of a task series
+///
+/// The rule lives on the latest occurrence: once the next one is created,
+/// it moves there.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Recurrence {
+    /// Task holding the rule, the latest occurrence of the series
+    #[schema(example = 1)]
+    pub task_id: i64,
+    #[serde(flatten)]
+    pub rule: RecurrenceRule,
+    /// Due date of the first occurrence; the others are counted from it
+    #[schema(example = "2025-01-06T09:00:00Z")]
+    pub starts_at: DateTime<Utc>,
+}
+
+/// Upcoming occurrence of a recurring task, not created yet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Occurrence {
+    /// When it will be due
+    #[schema(example = "2025-01-13T09:00:00Z")]
+    pub due_at: DateTime<Utc>,
+}
+
+/// Query parameters of `GET /tasks/{id}/occurrences`
+#[derive(Debug, Clone, Serialize, Deserialize, Default, ToSchema)]
+pub struct OccurrenceQuery {
+    /// Number of occurrences (1-100, default: 10)
+    #[schema(example = 10)]
+    pub limit: Option<usize>,
+}
+
+/// Role of a project member, from least to most privileged
+///
+/// Viewers read the project's tasks, editors also change them, and owners
+/// also manage the project and its members.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, sqlx::Type, ToSchema,
+)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+pub enum ProjectRole {
+    Viewer,
+    Editor,
+    Owner,
+}
+
+impl ProjectRole {
+    /// Name used in JSON and in the database
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ProjectRole::Viewer => "viewer",
+            ProjectRole::Editor => "editor",
+            ProjectRole::Owner => "owner",
+        }
+    }
+}
+
+/// Data for creating a project; the caller becomes its owner
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CreateProject {
+    /// Project name (1-100 characters)
+    #[schema(example = "Website relaunch")]
+    pub name: String,
+    /// Optional description
+    #[schema(example = "Everything for the new site")]
+    pub description: Option<String>,
+}
+
+/// Project the caller is a member of
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct Project {
+    /// Unique project ID
+    #[schema(example = 1)]
+    pub id: i64,
+    #[schema(example = "Website relaunch")]
+    pub name: String,
+    pub description: Option<String>,
+    /// Role of the caller in the project
+    pub role: ProjectRole,
+    /// Creation timestamp
+    #[schema(example = "2025-01-15 10:30:00")]
+    pub created_at: String,
+}
+
+/// Member of a project
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct ProjectMember {
+    #[schema(example = 2)]
+    pub user_id: i64,
+    #[schema(example = "ferris")]
+    pub username: String,
+    pub role: ProjectRole,
+    /// When the user joined the project
+    #[schema(example = "2025-01-15 10:30:00")]
+    pub added_at: String,
+}
+
+/// Role given to a member (`PUT /projects/{pid}/members/{username}`)
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SetMemberRole {
+    pub role: ProjectRole,
+}
+
+/// Registered user (without credentials)
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct User {
+    /// Unique user ID
+    #[schema(example = 1)]
+    pub id: i64,
+    /// Login name
+    #[schema(example = "ferris")]
+    pub username: String,
+    /// Registration timestamp
+    #[schema(example = "2025-01-15 10:30:00")]
+    pub created_at: String,
+}
+
+/// Username and password, used to register and to log in
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Credentials {
+    /// Login name (3-50 characters)
+    #[schema(example = "ferris")]
+    pub username: String,
+    /// Password (at least 8 characters)
+    #[schema(example = "correct horse battery")]
+    pub password: String,
+}
+
+/// Access token returned by login
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AuthToken {
+    /// Signed JWT to send as `Authorization: Bearer <token>`
+    pub access_token: String,
+    /// Always `Bearer`
+    #[schema(example = "Bearer")]
+    pub token_type: String,
+    /// Seconds until the token expires
+    #[schema(example = 86400)]
+    pub expires_in: u64,
+}
+
+/// Outcome of a health check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Ok,
+    Unavailable,
+}
+
+/// Version of the running server
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BuildInfo {
+    /// Crate name
+    #[schema(example = "project-task-api")]
+    pub name: String,
+    /// Crate version
+    #[schema(example = "0.1.0")]
+    pub version: String,
+    /// Git commit, from the `GIT_COMMIT` variable at build time
+    #[schema(example = "0d166b0")]
+    pub commit: Option<String>,
+    /// `debug` or `release`
+    #[schema(example = "release")]
+    pub profile: String,
+}
+
+impl BuildInfo {
+    /// Build info of this binary
+    pub fn current() -> Self {
+        Self {
+            name: env!("CARGO_PKG_NAME").to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            commit: option_env!("GIT_COMMIT").map(str::to_string),
+            profile: if cfg!(debug_assertions) { "debug" } else { "release" }.to_string(),
+        }
+    }
+}
+
+/// Response of `GET /health/live`
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Liveness {
+    /// Always `ok` while the process answers
+    pub status: HealthStatus,
+    pub build: BuildInfo,
+}
+
+/// Schema version of the database
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct MigrationState {
+    /// Latest applied migration
+    #[schema(example = 10)]
+    pub current: i64,
+    /// Latest migration known to this build
+    #[schema(example = 10)]
+    pub latest: i64,
+    /// Migrations not applied yet
+    #[schema(example = 0)]
+    pub pending: i64,
+}
+
+/// Response of `GET /health/ready`
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Readiness {
+    /// `ok` when the database answers and has no pending migrations
+    pub status: HealthStatus,
+    /// Result of `SELECT 1` against the pool
+    pub database: HealthStatus,
+    /// Missing when the database could not be read
+    pub migrations: Option<MigrationState>,
+    pub build: BuildInfo,
 }
 
-/// API error response
-#[derive(Debug, Serialize, ToSchema)]
+/// API error response: problem details (RFC 7807), sent as
+/// `application/problem+json`
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ErrorResponse {
-    /// Error message
-    #[schema(example = "Task not found")]
-    pub error: String,
+    /// Problem type; `about:blank` means `title` says it all
+    #[serde(rename = "type")]
+    #[schema(example = "about:blank")]
+    pub problem_type: String,
+    /// HTTP status phrase
+    #[schema(example = "Bad Request")]
+    pub title: String,
     /// HTTP status code
-    #[schema(example = 404)]
-    pub code: u16,
+    #[schema(example = 400)]
+    pub status: u16,
+    /// What went wrong
+    #[schema(example = "Title is required")]
+    pub detail: String,
+    /// Path of the request that failed
+    #[schema(example = "/tasks")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    /// Every invalid field, for validation errors
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+}
+
+/// Problem with one field of the request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct FieldError {
+    /// Field path, e.g. `title` or `tags[1]`
+    #[schema(example = "title")]
+    pub field: String,
+    /// `required`, `too_long`, `invalid` or `unknown_field`
+    #[schema(example = "required")]
+    pub code: String,
+    /// Human-readable description
+    #[schema(example = "Title is required")]
+    pub message: String,
+}
+
+impl FieldError {
+    /// Problem with `field`, identified by `code`
+    pub fn new(field: impl Into<String>, code: &str, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            code: code.to_string(),
+            message: message.into(),
+        }
+    }
 }
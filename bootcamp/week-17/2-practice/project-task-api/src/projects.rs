@@ -0,0 +1,452 @@
+//! Projects
+//!
+//! A project groups tasks shared by its members. Every member has a
+//! `ProjectRole`: viewers read the tasks, editors also change them and owners
+//! also manage the project and its members. A project always keeps at least
+//! one owner.
+//!
+//! Routes under `/projects/{pid}` take a `ProjectAccess`, which loads the
+//! caller's role; each handler then `require`s the role its action needs.
+//! Writes check the role again inside their transaction (see
+//! `ProjectAccess::write_scope` and `ProjectAccess::require_in`), so a role
+//! change that lands in between is not missed. Non-members get 404, as if
+//! the project did not exist, and members without the role get 403.
+
+use axum::{
+    extract::{FromRef, FromRequestParts, Path},
+    http::request::Parts,
+};
+use serde::Deserialize;
+use sqlx::{Executor, Sqlite, SqliteConnection, SqlitePool};
+
+use crate::auth::{AuthConfig, CurrentUser};
+use crate::error::{ApiError, Result};
+use crate::events::EventBus;
+use crate::models::{ChangeKind, CreateProject, Project, ProjectMember, ProjectRole, Task};
+use crate::repository::sqlite::Scope;
+use crate::repository::FieldErrors;
+
+/// The caller's membership of the project named in the path
+#[derive(Debug, Clone)]
+pub struct ProjectAccess {
+    pub project_id: i64,
+    pub role: ProjectRole,
+    pub user: CurrentUser,
+}
+
+impl ProjectAccess {
+    /// Fail with 403 unless the caller has `role` or a higher one
+    ///
+    /// Checks the role read with the request; writes check it again with
+    /// `require_in` or through `write_scope`.
+    pub fn require(&self, role: ProjectRole) -> Result<()> {
+        ensure_role(self.project_id, self.role, role)
+    }
+
+    /// Same as `require`, with the caller's role as of now in `conn`
+    ///
+    /// Call it inside the write's `BEGIN IMMEDIATE` transaction.
+    pub async fn require_in(&self, conn: &mut SqliteConnection, role: ProjectRole) -> Result<()> {
+        require_role(conn, self.project_id, self.user.id, role).await
+    }
+
+    /// Tasks of the project
+    pub(crate) fn scope(&self) -> Scope {
+        Scope::Project(self.project_id)
+    }
+
+    /// Tasks of the project, for a write that needs `role`
+    ///
+    /// Fails early like `require`; the write checks the role again in its
+    /// transaction (see `Scope::authorize`).
+    pub(crate) fn write_scope(&self, role: ProjectRole) -> Result<Scope> {
+        self.require(role)?;
+
+        Ok(Scope::ProjectWrite {
+            project_id: self.project_id,
+            user_id: self.user.id,
+            role,
+        })
+    }
+}
+
+/// The `{pid}` segment of project routes
+#[derive(Deserialize)]
+struct ProjectPath {
+    pid: i64,
+}
+
+impl<S> FromRequestParts<S> for ProjectAccess
+where
+    AuthConfig: FromRef<S>,
+    SqlitePool: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &S,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        let user = CurrentUser::from_request_parts(parts, state).await?;
+        let Path(ProjectPath { pid }) = Path::<ProjectPath>::from_request_parts(parts, state)
+            .await
+            .map_err(|rejection| ApiError::Validation(rejection.body_text()))?;
+
+        let role = member_role(&SqlitePool::from_ref(state), pid, user.id)
+            .await?
+            .ok_or_else(|| project_not_found(pid))?;
+
+        Ok(Self {
+            project_id: pid,
+            role,
+            user,
+        })
+    }
+}
+
+/// Role of `user_id` in a project, `None` if not a member
+pub async fn member_role<'e, E>(
+    executor: E,
+    project_id: i64,
+    user_id: i64,
+) -> Result<Option<ProjectRole>>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let role =
+        sqlx::query_scalar("SELECT role FROM project_members WHERE project_id = ? AND user_id = ?")
+            .bind(project_id)
+            .bind(user_id)
+            .fetch_optional(executor)
+            .await?;
+
+    Ok(role)
+}
+
+/// Fail unless `user_id` is a member of a project with `role` or a higher one
+pub async fn require_role(
+    conn: &mut SqliteConnection,
+    project_id: i64,
+    user_id: i64,
+    role: ProjectRole,
+) -> Result<()> {
+    let current = member_role(conn, project_id, user_id)
+        .await?
+        .ok_or_else(|| project_not_found(project_id))?;
+
+    ensure_role(project_id, current, role)
+}
+
+fn ensure_role(project_id: i64, current: ProjectRole, role: ProjectRole) -> Result<()> {
+    if current >= role {
+        return Ok(());
+    }
+
+    Err(ApiError::Forbidden(format!(
+        "This action needs the {} role in project {}",
+        role.as_str(),
+        project_id
+    )))
+}
+
+/// Create a project owned by `user`
+pub async fn create(pool: &SqlitePool, user: &CurrentUser, data: CreateProject) -> Result<Project> {
+    let name = data.name.trim();
+    let mut errors = FieldErrors::default();
+
+    if name.is_empty() {
+        errors.add("name", "required", "Project name is required");
+    } else if name.chars().count() > 100 {
+        errors.add(
+            "name",
+            "too_long",
+            "Project name cannot exceed 100 characters",
+        );
+    }
+
+    if data
+        .description
+        .as_deref()
+        .is_some_and(|description| description.chars().count() > 2000)
+    {
+        errors.add(
+            "description",
+            "too_long",
+            "Description cannot exceed 2000 characters",
+        );
+    }
+
+    errors.into_result(())?;
+
+    let mut tx = pool.begin().await?;
+
+    let id: i64 =
+        sqlx::query_scalar("INSERT INTO projects (name, description) VALUES (?, ?) RETURNING id")
+            .bind(name)
+            .bind(&data.description)
+            .fetch_one(&mut *tx)
+            .await?;
+
+    sqlx::query("INSERT INTO project_members (project_id, user_id, role) VALUES (?, ?, ?)")
+        .bind(id)
+        .bind(user.id)
+        .bind(ProjectRole::Owner)
+        .execute(&mut *tx)
+        .await?;
+
+    let project = find(&mut *tx, id, user.id).await?;
+    tx.commit().await?;
+
+    Ok(project)
+}
+
+/// Projects `user_id` is a member of, oldest first
+pub async fn list(pool: &SqlitePool, user_id: i64) -> Result<Vec<Project>> {
+    let projects = sqlx::query_as::<_, Project>(
+        r#"
+        SELECT p.id, p.name, p.description, m.role, p.created_at
+        FROM projects p JOIN project_members m ON m.project_id = p.id
+        WHERE m.user_id = ?
+        ORDER BY p.id
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(projects)
+}
+
+/// A project as seen by one of its members
+pub async fn find<'e, E>(executor: E, project_id: i64, user_id: i64) -> Result<Project>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    sqlx::query_as::<_, Project>(
+        r#"
+        SELECT p.id, p.name, p.description, m.role, p.created_at
+        FROM projects p JOIN project_members m ON m.project_id = p.id
+        WHERE p.id = ? AND m.user_id = ?
+        "#,
+    )
+    .bind(project_id)
+    .bind(user_id)
+    .fetch_optional(executor)
+    .await?
+    .ok_or_else(|| project_not_found(project_id))
+}
+
+/// Delete the caller's project with its memberships; owners only
+///
+/// Refused while the project has live tasks: removing the project removes
+/// its tasks with their history, so they must be deleted (and announced)
+/// one by one first. Tasks already in the trash go with the project.
+pub async fn delete(pool: &SqlitePool, access: &ProjectAccess) -> Result<()> {
+    let mut tx = pool.begin_with("BEGIN IMMEDIATE").await?;
+    access.require_in(&mut tx, ProjectRole::Owner).await?;
+
+    let (live,): (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM tasks WHERE project_id = ? AND deleted_at IS NULL",
+    )
+    .bind(access.project_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if live > 0 {
+        return Err(ApiError::Conflict(format!(
+            "Project {} still has {} tasks; delete them first",
+            access.project_id, live
+        )));
+    }
+
+    sqlx::query("DELETE FROM projects WHERE id = ?")
+        .bind(access.project_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Members of a project, owners first
+pub async fn members<'e, E>(executor: E, project_id: i64) -> Result<Vec<ProjectMember>>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let members = sqlx::query_as::<_, ProjectMember>(
+        r#"
+        SELECT m.user_id, u.username, m.role, m.created_at AS added_at
+        FROM project_members m JOIN users u ON u.id = m.user_id
+        WHERE m.project_id = ?
+        ORDER BY CASE m.role WHEN 'owner' THEN 0 WHEN 'editor' THEN 1 ELSE 2 END, u.username
+        "#,
+    )
+    .bind(project_id)
+    .fetch_all(executor)
+    .await?;
+
+    Ok(members)
+}
+
+/// Add `username` to the caller's project, or change their role; owners only
+pub async fn set_member(
+    pool: &SqlitePool,
+    access: &ProjectAccess,
+    username: &str,
+    role: ProjectRole,
+) -> Result<ProjectMember> {
+    let project_id = access.project_id;
+
+    // IMMEDIATE, so two owners cannot demote each other at the same time
+    let mut tx = pool.begin_with("BEGIN IMMEDIATE").await?;
+    access.require_in(&mut tx, ProjectRole::Owner).await?;
+
+    let user_id = user_id(&mut *tx, username).await?;
+    if role != ProjectRole::Owner {
+        ensure_other_owner(&mut tx, project_id, user_id).await?;
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO project_members (project_id, user_id, role) VALUES (?, ?, ?)
+        ON CONFLICT (project_id, user_id) DO UPDATE SET role = excluded.role
+        "#,
+    )
+    .bind(project_id)
+    .bind(user_id)
+    .bind(role)
+    .execute(&mut *tx)
+    .await?;
+
+    let member = members(&mut *tx, project_id)
+        .await?
+        .into_iter()
+        .find(|member| member.user_id == user_id)
+        .ok_or_else(|| ApiError::Internal("Member not saved".into()))?;
+    tx.commit().await?;
+
+    Ok(member)
+}
+
+/// Remove `username` from the caller's project; returns their user ID
+///
+/// Owners can remove anyone, other members only themselves. The caller is
+/// matched by user ID, as usernames are compared case-insensitively.
+pub async fn remove_member(
+    pool: &SqlitePool,
+    access: &ProjectAccess,
+    username: &str,
+) -> Result<i64> {
+    let project_id = access.project_id;
+    let mut tx = pool.begin_with("BEGIN IMMEDIATE").await?;
+
+    let user_id = find_user_id(&mut *tx, username).await?;
+    if user_id != Some(access.user.id) {
+        access.require_in(&mut tx, ProjectRole::Owner).await?;
+    }
+    let user_id = user_id.ok_or_else(|| user_not_found(username))?;
+    ensure_other_owner(&mut tx, project_id, user_id).await?;
+
+    let removed = sqlx::query("DELETE FROM project_members WHERE project_id = ? AND user_id = ?")
+        .bind(project_id)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    if removed.rows_affected() == 0 {
+        return Err(ApiError::NotFound(format!(
+            "{} is not a member of project {}",
+            username, project_id
+        )));
+    }
+
+    tx.commit().await?;
+
+    Ok(user_id)
+}
+
+/// ID of a registered user
+async fn user_id<'e, E>(executor: E, username: &str) -> Result<i64>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    find_user_id(executor, username)
+        .await?
+        .ok_or_else(|| user_not_found(username))
+}
+
+/// ID of a user, `None` if nobody has registered `username`
+async fn find_user_id<'e, E>(executor: E, username: &str) -> Result<Option<i64>>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let id = sqlx::query_scalar("SELECT id FROM users WHERE username = ?")
+        .bind(username.trim())
+        .fetch_optional(executor)
+        .await?;
+
+    Ok(id)
+}
+
+fn user_not_found(username: &str) -> ApiError {
+    ApiError::NotFound(format!("User {} not found", username))
+}
+
+/// Reject a change that would leave the project without owners, i.e. one
+/// that takes `user_id`'s role away when they are the only owner
+async fn ensure_other_owner(
+    tx: &mut sqlx::SqliteConnection,
+    project_id: i64,
+    user_id: i64,
+) -> Result<()> {
+    let other_owners: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM project_members WHERE project_id = ? AND role = ? AND user_id != ?",
+    )
+    .bind(project_id)
+    .bind(ProjectRole::Owner)
+    .bind(user_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if other_owners == 0
+        && member_role(&mut *tx, project_id, user_id).await? == Some(ProjectRole::Owner)
+    {
+        return Err(ApiError::Conflict(
+            "A project needs at least one owner; add another owner first".into(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// IDs of the members of a project
+pub async fn member_ids<'e, E>(executor: E, project_id: i64) -> Result<Vec<i64>>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let members = sqlx::query_scalar("SELECT user_id FROM project_members WHERE project_id = ?")
+        .bind(project_id)
+        .fetch_all(executor)
+        .await?;
+
+    Ok(members)
+}
+
+/// Announce a change of a project task to `members`
+///
+/// Live feeds and webhooks are per user, so each member gets the event as
+/// for one of their own tasks. Runs after the write has committed, so like
+/// `EventBus::publish` it cannot fail; the members come from the write's
+/// transaction.
+pub fn publish(events: &EventBus, members: &[i64], kind: ChangeKind, task: Task) {
+    for &member in members {
+        events.publish(member, kind, task.clone());
+    }
+}
+
+/// Error for a project that does not exist or the caller is not a member of
+pub fn project_not_found(project_id: i64) -> ApiError {
+    ApiError::NotFound(format!("Project {} not found", project_id))
+}
@@ -0,0 +1,645 @@
+//! Recurring Tasks
+//!
+//! A task with a `RecurrenceRule` is the current occurrence of a series. Once
+//! it is completed, or its due date arrives, a background scheduler creates
+//! the next occurrence (a copy of the task due at the next date of the rule)
+//! and moves the rule to it.
+//!
+//! Every occurrence is counted from `starts_at`, the due date of the task
+//! when the rule was set, so monthly series keep their day even after a
+//! shorter month.
+//!
+//! ## Restarts
+//!
+//! The scheduler keeps no state of its own: the new task and the move of the
+//! rule are written in one transaction, so an occurrence is created exactly
+//! once even if the server stops halfway. On start it catches up with the
+//! series that fell due while it was down, creating only the next future
+//! occurrence of each, not one per missed date.
+//!
+//! ## Cron expressions
+//!
+//! Five fields, `minute hour day month weekday`, evaluated in UTC. Each field
+//! is `*` or a list of numbers and ranges, optionally with a step: `0 9 * * 1-5`
+//! (weekdays at 09:00), `*/15 * * * *`, `0 0 1,15 * *`. Weekdays go from 0
+//! (Sunday) to 6, 7 is Sunday too. As in cron, when both day and weekday are
+//! restricted a date matching either one is due.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, Months, NaiveDate, TimeDelta, Timelike, Utc};
+use futures_util::{Stream, StreamExt};
+use sqlx::{Executor, FromRow, Sqlite, SqlitePool};
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+
+use crate::auth::CurrentUser;
+use crate::error::{ApiError, Result};
+use crate::events::EventBus;
+use crate::models::{
+    ChangeEvent, ChangeKind, CreateTask, Frequency, ProjectRole, Recurrence, RecurrenceRule, Task,
+};
+use crate::projects;
+use crate::repository::sqlite::{fetch_task_in, insert_task, Scope};
+use crate::repository::FieldErrors;
+
+/// Largest `interval` accepted
+pub const MAX_INTERVAL: u32 = 1000;
+
+/// Days searched for the next match of a cron expression (covers `29 2`)
+const CRON_SEARCH_DAYS: u32 = 8 * 366;
+
+/// Scheduler settings
+#[derive(Debug, Clone)]
+pub struct SchedulerConfig {
+    /// How often to look for due occurrences when nothing wakes the scheduler
+    pub poll_interval: Duration,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Due dates of a series, computed from a validated rule
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    rule: RecurrenceRule,
+    starts_at: DateTime<Utc>,
+    cron: Option<Cron>,
+}
+
+impl Schedule {
+    /// Check `rule` for a series whose first occurrence is due at `starts_at`
+    ///
+    /// Every invalid field is reported at once. The rule is normalized:
+    /// `interval` defaults to 1 and the cron expression is trimmed.
+    pub fn new(mut rule: RecurrenceRule, starts_at: DateTime<Utc>) -> Result<Self> {
+        let mut errors = FieldErrors::default();
+        let mut cron = None;
+
+        if rule.frequency == Frequency::Cron {
+            if rule.interval.is_some() {
+                errors.add(
+                    "interval",
+                    "invalid",
+                    "interval is not used with frequency cron",
+                );
+            }
+
+            match rule.cron.as_deref().map(str::trim) {
+                None | Some("") => {
+                    errors.add("cron", "required", "cron is required with frequency cron")
+                }
+                Some(expression) => match Cron::parse(expression) {
+                    Ok(parsed) if parsed.next_after(starts_at).is_none() => {
+                        errors.add("cron", "invalid", "cron expression never matches")
+                    }
+                    Ok(parsed) => {
+                        rule.cron = Some(expression.to_string());
+                        cron = Some(parsed);
+                    }
+                    Err(message) => errors.add("cron", "invalid", message),
+                },
+            }
+        } else {
+            if rule.cron.is_some() {
+                errors.add("cron", "invalid", "cron is only used with frequency cron");
+            }
+
+            let interval = rule.interval.unwrap_or(1);
+            if !(1..=MAX_INTERVAL).contains(&interval) {
+                errors.add(
+                    "interval",
+                    "invalid",
+                    format!("interval must be between 1 and {}", MAX_INTERVAL),
+                );
+            }
+            rule.interval = Some(interval);
+        }
+
+        if rule.until.is_some_and(|until| until < starts_at) {
+            errors.add(
+                "until",
+                "invalid",
+                "until cannot be before the task's due date",
+            );
+        }
+
+        errors.into_result(Self {
+            rule,
+            starts_at,
+            cron,
+        })
+    }
+
+    /// The normalized rule
+    pub fn rule(&self) -> &RecurrenceRule {
+        &self.rule
+    }
+
+    /// Due date of the first occurrence
+    pub fn starts_at(&self) -> DateTime<Utc> {
+        self.starts_at
+    }
+
+    /// First due date strictly after `after`, `None` once the series is over
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let next = if after < self.starts_at {
+            Some(self.starts_at)
+        } else {
+            let interval = self.rule.interval.unwrap_or(1);
+
+            match self.rule.frequency {
+                Frequency::Daily => self.next_by_days(after, i64::from(interval)),
+                Frequency::Weekly => self.next_by_days(after, 7 * i64::from(interval)),
+                Frequency::Monthly => self.next_by_months(after, interval),
+                Frequency::Cron => self.cron.as_ref()?.next_after(after),
+            }
+        };
+
+        next.filter(|next| self.rule.until.is_none_or(|until| *next <= until))
+    }
+
+    /// Due dates after `after`, in order, until the series is over
+    pub fn occurrences(&self, after: DateTime<Utc>) -> impl Iterator<Item = DateTime<Utc>> + '_ {
+        std::iter::successors(self.next_after(after), |due| self.next_after(*due))
+    }
+
+    fn next_by_days(&self, after: DateTime<Utc>, days: i64) -> Option<DateTime<Utc>> {
+        let step = TimeDelta::try_days(days)?;
+        let steps = (after - self.starts_at).num_seconds() / step.num_seconds() + 1;
+
+        self.starts_at.checked_add_signed(step.checked_mul(i32::try_from(steps).ok()?)?)
+    }
+
+    fn next_by_months(&self, after: DateTime<Utc>, interval: u32) -> Option<DateTime<Utc>> {
+        let months = (after.year() - self.starts_at.year()) * 12 + after.month() as i32
+            - self.starts_at.month() as i32;
+        // Start one step early: a shorter month can put an occurrence before `after`
+        let mut step = (months.max(0) as u32 / interval).saturating_sub(1);
+
+        loop {
+            let next = self
+                .starts_at
+                .checked_add_months(Months::new(step.checked_mul(interval)?))?;
+            if next > after {
+                return Some(next);
+            }
+            step += 1;
+        }
+    }
+}
+
+/// Parsed five-field cron expression; each field is a bit set of the values
+#[derive(Debug, Clone)]
+struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Cron {
+    fn parse(expression: &str) -> std::result::Result<Self, String> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!(
+                "cron expression needs 5 fields (minute hour day month weekday), got {}",
+                fields.len()
+            ));
+        };
+
+        let mut weekdays = cron_field(weekday, "weekday", 0, 7)?;
+        // 7 is Sunday too
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays & !(1 << 7)) | 1;
+        }
+
+        Ok(Self {
+            minutes: cron_field(minute, "minute", 0, 59)?,
+            hours: cron_field(hour, "hour", 0, 23)?,
+            days: cron_field(day, "day", 1, 31)?,
+            months: cron_field(month, "month", 1, 12)?,
+            weekdays,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        })
+    }
+
+    /// First matching minute strictly after `after`
+    fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let start = after.with_second(0)?.with_nanosecond(0)? + TimeDelta::minutes(1);
+        let mut date = start.date_naive();
+
+        for _ in 0..CRON_SEARCH_DAYS {
+            if self.matches_date(date) {
+                let from = if date == start.date_naive() {
+                    (start.hour(), start.minute())
+                } else {
+                    (0, 0)
+                };
+
+                let time = (from.0..24)
+                    .filter(|hour| self.hours & (1 << hour) != 0)
+                    .flat_map(|hour| {
+                        let first = if hour == from.0 { from.1 } else { 0 };
+                        (first..60)
+                            .filter(|minute| self.minutes & (1 << minute) != 0)
+                            .map(move |minute| (hour, minute))
+                    })
+                    .next();
+
+                if let Some((hour, minute)) = time {
+                    return Some(date.and_hms_opt(hour, minute, 0)?.and_utc());
+                }
+            }
+
+            date = date.succ_opt()?;
+        }
+
+        None
+    }
+
+    fn matches_date(&self, date: NaiveDate) -> bool {
+        if self.months & (1 << date.month()) == 0 {
+            return false;
+        }
+
+        let day = self.days & (1 << date.day()) != 0;
+        let weekday = self.weekdays & (1 << date.weekday().num_days_from_sunday()) != 0;
+
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (true, false) => weekday,
+            (false, true) => day,
+            (false, false) => day || weekday,
+        }
+    }
+}
+
+/// Bit set of the values of one cron field
+fn cron_field(text: &str, name: &str, min: u32, max: u32) -> std::result::Result<u64, String> {
+    let invalid = || format!("invalid {} field '{}'", name, text);
+    let number = |value: &str| -> std::result::Result<u32, String> {
+        value
+            .parse()
+            .ok()
+            .filter(|value| (min..=max).contains(value))
+            .ok_or_else(|| format!("{} must be between {} and {}, got '{}'", name, min, max, value))
+    };
+
+    let mut bits = 0u64;
+
+    for part in text.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step.parse().ok().filter(|step| *step > 0).ok_or_else(invalid)?;
+                (range, step)
+            }
+            None => (part, 1),
+        };
+
+        let (first, last) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((first, last)) => (number(first)?, number(last)?),
+                // `5/10` starts at 5 and runs to the end of the range
+                None if step > 1 => (number(range)?, max),
+                None => (number(range)?, number(range)?),
+            },
+        };
+
+        if first > last {
+            return Err(invalid());
+        }
+
+        for value in (first..=last).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+
+    Ok(bits)
+}
+
+/// Stored rule of a series
+#[derive(FromRow)]
+struct RuleRow {
+    task_id: i64,
+    frequency: Frequency,
+    interval: Option<i64>,
+    cron: Option<String>,
+    until: Option<DateTime<Utc>>,
+    starts_at: DateTime<Utc>,
+}
+
+impl From<RuleRow> for Recurrence {
+    fn from(row: RuleRow) -> Self {
+        Self {
+            task_id: row.task_id,
+            rule: RecurrenceRule {
+                frequency: row.frequency,
+                interval: row.interval.and_then(|interval| u32::try_from(interval).ok()),
+                cron: row.cron,
+                until: row.until,
+            },
+            starts_at: row.starts_at,
+        }
+    }
+}
+
+impl Recurrence {
+    /// Schedule of the stored rule
+    pub fn schedule(&self) -> Result<Schedule> {
+        Schedule::new(self.rule.clone(), self.starts_at)
+    }
+}
+
+/// Rule of one of the live tasks of `scope`, if it has one
+pub(crate) async fn find<'e, E>(
+    executor: E,
+    task_id: i64,
+    scope: Scope,
+) -> Result<Option<Recurrence>>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let row: Option<RuleRow> = sqlx::query_as(&format!(
+        r#"
+        SELECT r.task_id, r.frequency, r.interval, r.cron, r.until, r.starts_at
+        FROM task_recurrences r JOIN tasks t ON t.id = r.task_id
+        WHERE r.task_id = ? AND t.{} = ? AND t.deleted_at IS NULL
+        "#,
+        scope.column()
+    ))
+    .bind(task_id)
+    .bind(scope.id())
+    .fetch_optional(executor)
+    .await?;
+
+    Ok(row.map(Recurrence::from))
+}
+
+/// Set (or replace) the rule of a task
+pub async fn save<'e, E>(executor: E, task_id: i64, schedule: &Schedule) -> Result<Recurrence>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let rule = schedule.rule();
+
+    let row: RuleRow = sqlx::query_as(
+        r#"
+        INSERT INTO task_recurrences (task_id, frequency, interval, cron, until, starts_at)
+        VALUES (?, ?, ?, ?, ?, ?)
+        ON CONFLICT (task_id) DO UPDATE SET
+            frequency = excluded.frequency, interval = excluded.interval, cron = excluded.cron,
+            until = excluded.until, starts_at = excluded.starts_at
+        RETURNING task_id, frequency, interval, cron, until, starts_at
+        "#,
+    )
+    .bind(task_id)
+    .bind(rule.frequency)
+    .bind(rule.interval)
+    .bind(&rule.cron)
+    .bind(rule.until)
+    .bind(schedule.starts_at())
+    .fetch_one(executor)
+    .await?;
+
+    Ok(row.into())
+}
+
+/// Remove the rule of a task; `false` if it had none
+pub async fn remove<'e, E>(executor: E, task_id: i64) -> Result<bool>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let removed = sqlx::query("DELETE FROM task_recurrences WHERE task_id = ?")
+        .bind(task_id)
+        .execute(executor)
+        .await?;
+
+    Ok(removed.rows_affected() > 0)
+}
+
+/// Due date of the occurrence that follows `task`, the current one of the series
+///
+/// Dates that passed while nobody completed the task are skipped.
+pub fn next_due(schedule: &Schedule, task: &Task, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let after = task.due_at.map_or(now, |due_at| due_at.max(now));
+
+    schedule.next_after(after)
+}
+
+/// Error for a task without recurrence rule
+pub fn no_recurrence(task_id: i64) -> ApiError {
+    ApiError::NotFound(format!("Task {} has no recurrence rule", task_id))
+}
+
+/// Start the scheduler that creates the next occurrences
+///
+/// It subscribes to `events` before returning, so completions published
+/// after this call wake it right away. Series that fell due before the start
+/// are handled on its first round.
+pub fn spawn_scheduler(pool: SqlitePool, events: &EventBus, config: SchedulerConfig) -> JoinHandle<()> {
+    let changes = events.subscribe_all();
+    let events = events.clone();
+
+    tokio::spawn(async move {
+        let wake = Notify::new();
+
+        tokio::join!(
+            watch_completions(changes, &wake),
+            schedule(&pool, &events, &config, &wake),
+        );
+    })
+}
+
+/// Wake the scheduler whenever a task is completed
+async fn watch_completions(changes: impl Stream<Item = (i64, ChangeEvent)>, wake: &Notify) {
+    let mut changes = std::pin::pin!(changes);
+
+    while let Some((_, change)) = changes.next().await {
+        if change.kind == ChangeKind::Updated && change.task.completed {
+            wake.notify_one();
+        }
+    }
+}
+
+/// Create due occurrences, then sleep until the next one is due or a task is completed
+async fn schedule(pool: &SqlitePool, events: &EventBus, config: &SchedulerConfig, wake: &Notify) {
+    loop {
+        if let Err(e) = materialize_due(pool, events, Utc::now()).await {
+            tracing::error!("Could not create recurring task occurrences: {}", e);
+        }
+
+        let wait = match next_wake(pool).await {
+            Ok(Some(wait)) => wait.min(config.poll_interval),
+            Ok(None) => config.poll_interval,
+            Err(e) => {
+                tracing::error!("Could not read recurring tasks: {}", e);
+                config.poll_interval
+            }
+        };
+
+        tokio::select! {
+            _ = wake.notified() => {}
+            _ = tokio::time::sleep(wait) => {}
+        }
+    }
+}
+
+/// Create the next occurrence of every series whose current task is
+/// completed or due at `now`, returning the new tasks
+///
+/// Each new task is published to `events` as created, to its owner or to the
+/// members of its project. A series that fails is
+/// logged and retried on the next round; the others go on.
+pub async fn materialize_due(
+    pool: &SqlitePool,
+    events: &EventBus,
+    now: DateTime<Utc>,
+) -> Result<Vec<Task>> {
+    let due: Vec<i64> = sqlx::query_scalar(
+        r#"
+        SELECT r.task_id FROM task_recurrences r JOIN tasks t ON t.id = r.task_id
+        WHERE t.deleted_at IS NULL AND (t.completed = TRUE OR t.due_at <= ?)
+        ORDER BY r.task_id
+        "#,
+    )
+    .bind(now)
+    .fetch_all(pool)
+    .await?;
+
+    let mut created = Vec::new();
+
+    for task_id in due {
+        match materialize(pool, task_id, now).await {
+            Ok(Some((audience, task))) => {
+                projects::publish(events, &audience, ChangeKind::Created, task.clone());
+                created.push(task);
+            }
+            Ok(None) => {}
+            Err(e) => tracing::error!(
+                "Could not create the next occurrence of task {}: {}",
+                task_id,
+                e
+            ),
+        }
+    }
+
+    Ok(created)
+}
+
+/// Create the occurrence after task `task_id` and move the rule to it
+///
+/// Returns the users to tell about it and the new task; `None` if there was
+/// nothing to do, e.g. another run got there first, or the series just ended.
+///
+/// A personal task is copied on behalf of its owner. A project task is
+/// copied on behalf of the last member who changed it (usually the one who
+/// completed it), or of a project owner if that user is gone.
+async fn materialize(
+    pool: &SqlitePool,
+    task_id: i64,
+    now: DateTime<Utc>,
+) -> Result<Option<(Vec<i64>, Task)>> {
+    let mut tx = pool.begin_with("BEGIN IMMEDIATE").await?;
+
+    // Checked again under the write lock
+    let series: Option<(Option<i64>, Option<i64>, i64, String)> = sqlx::query_as(
+        r#"
+        SELECT t.owner_id, t.project_id, u.id, u.username
+        FROM task_recurrences r JOIN tasks t ON t.id = r.task_id
+        JOIN users u ON u.id = COALESCE(
+            t.owner_id,
+            (SELECT e.actor_id FROM task_events e JOIN users a ON a.id = e.actor_id
+             WHERE e.task_id = t.id ORDER BY e.id DESC LIMIT 1),
+            (SELECT m.user_id FROM project_members m
+             WHERE m.project_id = t.project_id AND m.role = ? ORDER BY m.user_id LIMIT 1)
+        )
+        WHERE r.task_id = ? AND t.deleted_at IS NULL AND (t.completed = TRUE OR t.due_at <= ?)
+        "#,
+    )
+    .bind(ProjectRole::Owner)
+    .bind(task_id)
+    .bind(now)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some((owner_id, project_id, actor_id, username)) = series else {
+        return Ok(None);
+    };
+    let scope = match (owner_id, project_id) {
+        (Some(owner_id), _) => Scope::Owner(owner_id),
+        (None, Some(project_id)) => Scope::Project(project_id),
+        (None, None) => return Err(ApiError::Internal(format!("Task {} has no scope", task_id))),
+    };
+    let user = CurrentUser {
+        id: actor_id,
+        username,
+    };
+
+    let current = fetch_task_in(&mut *tx, task_id, scope).await?;
+    let recurrence = find(&mut *tx, task_id, scope)
+        .await?
+        .ok_or_else(|| no_recurrence(task_id))?;
+
+    let Some(due_at) = next_due(&recurrence.schedule()?, &current, now) else {
+        // Past `until`: the series is over
+        remove(&mut *tx, task_id).await?;
+        tx.commit().await?;
+        return Ok(None);
+    };
+
+    let task = insert_task(
+        &mut tx,
+        &user,
+        scope,
+        CreateTask {
+            title: current.title.clone(),
+            description: current.description.clone(),
+            due_at: Some(due_at),
+            priority: Some(current.priority),
+            parent_id: current.parent_id,
+            tags: current.tags.clone(),
+        },
+    )
+    .await?;
+
+    sqlx::query("UPDATE task_recurrences SET task_id = ? WHERE task_id = ?")
+        .bind(task.id)
+        .bind(task_id)
+        .execute(&mut *tx)
+        .await?;
+
+    let audience = scope.audience(&mut tx).await?;
+    tx.commit().await?;
+
+    Ok(Some((audience, task)))
+}
+
+/// Time until the earliest pending current occurrence falls due
+///
+/// Occurrences already due are left to the poll interval, so a series that
+/// keeps failing is not retried in a busy loop.
+async fn next_wake(pool: &SqlitePool) -> Result<Option<Duration>> {
+    let now = Utc::now();
+    let next: Option<DateTime<Utc>> = sqlx::query_scalar(
+        r#"
+        SELECT MIN(t.due_at) FROM task_recurrences r JOIN tasks t ON t.id = r.task_id
+        WHERE t.deleted_at IS NULL AND t.completed = FALSE AND t.due_at > ?
+        "#,
+    )
+    .bind(now)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(next.map(|next| (next - now).to_std().unwrap_or_default()))
+}
@@ -0,0 +1,323 @@
+//! In-Memory Task Repository
+//!
+//! Keeps tasks in a map behind a mutex. It follows the same rules as the SQL
+//! backends, which makes it handy for tests and demos, but everything is lost
+//! on restart and no history is kept.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use chrono::Utc;
+
+use super::{
+    compare, cycle_error, normalize_tags, parent_not_found, task_not_found, validate_new, Page,
+    Precondition, TaskChange, TaskRepository,
+};
+use crate::auth::CurrentUser;
+use crate::error::Result;
+use crate::models::{
+    CreateTask, PriorityCounts, RootProgress, TagMatch, Task, TaskFilters, TaskList, TaskStats,
+};
+
+/// Tasks kept in process memory
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryTaskRepository {
+    store: Arc<Mutex<Store>>,
+}
+
+#[derive(Debug, Default)]
+struct Store {
+    tasks: BTreeMap<i64, StoredTask>,
+    last_id: i64,
+}
+
+#[derive(Debug)]
+struct StoredTask {
+    task: Task,
+    owner_id: i64,
+    deleted: bool,
+}
+
+impl InMemoryTaskRepository {
+    /// Empty repository
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every operation holds the lock for its whole duration, like a transaction
+    fn lock(&self) -> MutexGuard<'_, Store> {
+        self.store.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Timestamp in the format SQLite's `CURRENT_TIMESTAMP` uses
+fn timestamp() -> String {
+    Utc::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+impl Store {
+    /// Live tasks owned by `owner_id`
+    fn owned(&self, owner_id: i64) -> impl Iterator<Item = &Task> {
+        self.tasks
+            .values()
+            .filter(move |stored| !stored.deleted && stored.owner_id == owner_id)
+            .map(|stored| &stored.task)
+    }
+
+    /// A live task owned by `owner_id`
+    fn live(&self, owner_id: i64, id: i64) -> Result<&Task> {
+        self.tasks
+            .get(&id)
+            .filter(|stored| !stored.deleted && stored.owner_id == owner_id)
+            .map(|stored| &stored.task)
+            .ok_or_else(|| task_not_found(id))
+    }
+
+    fn task_mut(&mut self, id: i64) -> &mut Task {
+        &mut self
+            .tasks
+            .get_mut(&id)
+            .expect("task ids come from the store")
+            .task
+    }
+
+    /// Live subtasks of `id` at any depth
+    fn descendants(&self, id: i64) -> Vec<i64> {
+        let mut children: HashMap<i64, Vec<i64>> = HashMap::new();
+        for stored in self.tasks.values().filter(|stored| !stored.deleted) {
+            if let Some(parent_id) = stored.task.parent_id {
+                children.entry(parent_id).or_default().push(stored.task.id);
+            }
+        }
+
+        let mut found = Vec::new();
+        let mut pending = vec![id];
+        while let Some(current) = pending.pop() {
+            for &child in children.get(&current).into_iter().flatten() {
+                found.push(child);
+                pending.push(child);
+            }
+        }
+
+        found
+    }
+
+    /// Whether `ancestor` is `id` or one of its ancestors
+    fn is_ancestor_or_self(&self, ancestor: i64, id: i64) -> bool {
+        let mut current = Some(id);
+
+        while let Some(task_id) = current {
+            if task_id == ancestor {
+                return true;
+            }
+            current = self.tasks.get(&task_id).and_then(|stored| stored.task.parent_id);
+        }
+
+        false
+    }
+}
+
+impl TaskRepository for InMemoryTaskRepository {
+    async fn list(&self, owner_id: i64, filters: TaskFilters) -> Result<TaskList> {
+        let sort = filters.sort.unwrap_or_default();
+        let tags = normalize_tags("tag", &filters.tag)?;
+        let match_all = filters.tag_match.unwrap_or_default() == TagMatch::All;
+        let page = Page::from_filters(&filters)?;
+        let now = Utc::now();
+
+        let store = self.lock();
+        let mut rows: Vec<Task> = store
+            .owned(owner_id)
+            .filter(|task| filters.completed.is_none_or(|completed| task.completed == completed))
+            .filter(|task| {
+                let has = |tag: &String| task.tags.contains(tag);
+                tags.is_empty() || if match_all { tags.iter().all(has) } else { tags.iter().any(has) }
+            })
+            .filter(|task| {
+                filters.overdue != Some(true)
+                    || (!task.completed && task.due_at.is_some_and(|due_at| due_at < now))
+            })
+            .filter(|task| page.cursor().is_none_or(|cursor| cursor.admits(task)))
+            .cloned()
+            .collect();
+        drop(store);
+
+        rows.sort_by(|a, b| compare(sort, a, b));
+
+        let limit = page
+            .fetch_limit()
+            .map_or(usize::MAX, |limit| usize::try_from(limit).unwrap_or(usize::MAX));
+        let offset = usize::try_from(page.offset()).unwrap_or(usize::MAX);
+        let rows = rows.into_iter().skip(offset).take(limit).collect();
+
+        Ok(page.finish(rows, sort))
+    }
+
+    async fn get(&self, owner_id: i64, id: i64) -> Result<Task> {
+        self.lock().live(owner_id, id).cloned()
+    }
+
+    async fn create(&self, user: &CurrentUser, data: CreateTask) -> Result<Task> {
+        let mut tags = validate_new(&data)?;
+        tags.sort();
+
+        let mut store = self.lock();
+
+        if let Some(parent_id) = data.parent_id {
+            store.live(user.id, parent_id).map_err(|_| parent_not_found(parent_id))?;
+        }
+
+        store.last_id += 1;
+        let now = timestamp();
+        let task = Task {
+            id: store.last_id,
+            title: data.title,
+            description: data.description,
+            completed: false,
+            created_at: now.clone(),
+            updated_at: now,
+            due_at: data.due_at,
+            priority: data.priority.unwrap_or_default(),
+            parent_id: data.parent_id,
+            version: 1,
+            tags,
+        };
+
+        store.tasks.insert(
+            task.id,
+            StoredTask {
+                task: task.clone(),
+                owner_id: user.id,
+                deleted: false,
+            },
+        );
+
+        Ok(task)
+    }
+
+    async fn update(
+        &self,
+        user: &CurrentUser,
+        id: i64,
+        change: TaskChange,
+        cascade: bool,
+        precondition: Precondition<'_>,
+    ) -> Result<Task> {
+        let mut store = self.lock();
+
+        let before = store.live(user.id, id)?;
+        precondition(before)?;
+        let values = change.apply(before)?;
+        let tags = values.validate()?;
+
+        // Only a new parent needs checking
+        if let Some(parent_id) = values.parent_id.filter(|p| Some(*p) != before.parent_id) {
+            store.live(user.id, parent_id).map_err(|_| parent_not_found(parent_id))?;
+
+            if store.is_ancestor_or_self(id, parent_id) {
+                return Err(cycle_error());
+            }
+        }
+
+        let now = timestamp();
+        let subtasks = if cascade { store.descendants(id) } else { Vec::new() };
+
+        let task = store.task_mut(id);
+        task.title = values.title;
+        task.description = values.description;
+        task.completed = values.completed;
+        task.due_at = values.due_at;
+        task.priority = values.priority;
+        task.parent_id = values.parent_id;
+        task.version += 1;
+        task.updated_at = now.clone();
+        if let Some(mut tags) = tags {
+            tags.sort();
+            task.tags = tags;
+        }
+        let task = task.clone();
+
+        for subtask_id in subtasks {
+            let subtask = store.task_mut(subtask_id);
+            if subtask.completed != values.completed {
+                subtask.completed = values.completed;
+                subtask.version += 1;
+                subtask.updated_at = now.clone();
+            }
+        }
+
+        Ok(task)
+    }
+
+    async fn delete(&self, user: &CurrentUser, id: i64, precondition: Precondition<'_>) -> Result<Task> {
+        let mut store = self.lock();
+
+        let task = store.live(user.id, id)?.clone();
+        precondition(&task)?;
+
+        let mut subtree = store.descendants(id);
+        subtree.push(id);
+
+        for task_id in subtree {
+            let stored = store.tasks.get_mut(&task_id).expect("task ids come from the store");
+            stored.deleted = true;
+            stored.task.version += 1;
+        }
+
+        Ok(task)
+    }
+
+    async fn stats(&self, owner_id: i64) -> Result<TaskStats> {
+        let store = self.lock();
+        let now = Utc::now();
+
+        let mut total = 0;
+        let mut completed = 0;
+        let mut overdue = 0;
+        let mut priorities = BTreeMap::new();
+
+        for task in store.owned(owner_id) {
+            total += 1;
+            if task.completed {
+                completed += 1;
+            } else if task.due_at.is_some_and(|due_at| due_at < now) {
+                overdue += 1;
+            }
+            *priorities.entry(task.priority).or_insert(0) += 1;
+        }
+
+        let roots = store
+            .owned(owner_id)
+            .filter(|task| task.parent_id.is_none())
+            .filter_map(|root| {
+                let subtasks = store.descendants(root.id);
+                if subtasks.is_empty() {
+                    return None;
+                }
+
+                let done = subtasks
+                    .iter()
+                    .filter(|id| store.tasks[id].task.completed)
+                    .count() as i64;
+                let count = subtasks.len() as i64;
+
+                Some(RootProgress {
+                    id: root.id,
+                    title: root.title.clone(),
+                    subtasks: count,
+                    completed: done,
+                    percent: (1000.0 * done as f64 / count as f64).round() / 10.0,
+                })
+            })
+            .collect();
+
+        Ok(TaskStats {
+            total,
+            completed,
+            pending: total - completed,
+            overdue,
+            by_priority: PriorityCounts::from_counts(priorities),
+            roots,
+        })
+    }
+}
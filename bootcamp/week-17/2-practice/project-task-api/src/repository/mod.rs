@@ -0,0 +1,479 @@
+//! Task Storage
+//!
+//! `TaskRepository` is the storage behind the core task routes (list, get,
+//! create, update, delete and stats), so they can run on SQLite, in memory or
+//! on PostgreSQL. The validation and pagination rules every backend shares
+//! live here, so all of them answer the same requests the same way.
+
+use std::cmp::Ordering;
+use std::future::Future;
+
+use axum::Router;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+
+use crate::auth::CurrentUser;
+use crate::error::{ApiError, Result};
+use crate::models::{
+    CreateTask, FieldError, Priority, ReplaceTask, Task, TaskFilters, TaskList, TaskPage, TaskSort,
+    TaskStats, UpdateTask,
+};
+use crate::routes;
+use crate::state::AppState;
+
+mod memory;
+#[cfg(feature = "postgres")]
+mod postgres;
+pub(crate) mod sqlite;
+
+pub use memory::InMemoryTaskRepository;
+#[cfg(feature = "postgres")]
+pub use postgres::PostgresTaskRepository;
+pub use sqlite::SqliteTaskRepository;
+
+/// Most tasks one keyset page returns, and the default page size
+const MAX_PAGE_LIMIT: i64 = 100;
+
+/// Check run on the current task inside the write, e.g. `If-Match`
+pub type Precondition<'a> = &'a (dyn Fn(&Task) -> Result<()> + Send + Sync);
+
+/// Storage of tasks, scoped to their owner
+///
+/// Every method only sees the live (not deleted) tasks of the given user;
+/// other tasks are reported as not found.
+pub trait TaskRepository: Clone + Send + Sync + 'static {
+    /// Tasks matching `filters`: a plain list, or a page when `cursor` is set
+    fn list(&self, owner_id: i64, filters: TaskFilters) -> impl Future<Output = Result<TaskList>> + Send;
+
+    /// A single task
+    fn get(&self, owner_id: i64, id: i64) -> impl Future<Output = Result<Task>> + Send;
+
+    /// Validate and store a new task owned by `user`
+    fn create(&self, user: &CurrentUser, data: CreateTask) -> impl Future<Output = Result<Task>> + Send;
+
+    /// Apply `change` to a task, bumping its version
+    ///
+    /// `precondition` sees the current task before anything is written. With
+    /// `cascade`, the new `completed` value is applied to every subtask too.
+    fn update(
+        &self,
+        user: &CurrentUser,
+        id: i64,
+        change: TaskChange,
+        cascade: bool,
+        precondition: Precondition<'_>,
+    ) -> impl Future<Output = Result<Task>> + Send;
+
+    /// Delete a task and its subtasks, returning the task as it was
+    fn delete(
+        &self,
+        user: &CurrentUser,
+        id: i64,
+        precondition: Precondition<'_>,
+    ) -> impl Future<Output = Result<Task>> + Send;
+
+    /// Counts of the caller's tasks
+    fn stats(&self, owner_id: i64) -> impl Future<Output = Result<TaskStats>> + Send;
+
+    /// Routes for features beyond this trait, merged by `routes::create_routes`
+    ///
+    /// By default the SQLite-only paths answer `404`.
+    fn extra_routes() -> Router<AppState<Self>> {
+        routes::unsupported_routes()
+    }
+}
+
+/// New content for an existing task
+#[derive(Debug)]
+pub enum TaskChange {
+    /// Overwrite every field (`PUT`)
+    Replace(ReplaceTask),
+    /// Apply a JSON Merge Patch (`PATCH`)
+    Patch(UpdateTask),
+}
+
+impl TaskChange {
+    /// Values of `task` once the change is applied
+    pub(crate) fn apply(self, task: &Task) -> Result<TaskValues> {
+        match self {
+            TaskChange::Replace(data) => Ok(data.into()),
+            TaskChange::Patch(patch) => TaskValues::merge(task, patch),
+        }
+    }
+}
+
+/// Field values written by an update
+pub(crate) struct TaskValues {
+    pub(crate) title: String,
+    pub(crate) description: Option<String>,
+    pub(crate) completed: bool,
+    pub(crate) due_at: Option<DateTime<Utc>>,
+    pub(crate) priority: Priority,
+    pub(crate) parent_id: Option<i64>,
+    /// `None` keeps the current tags
+    pub(crate) tags: Option<Vec<String>>,
+}
+
+impl TaskValues {
+    /// Apply a JSON Merge Patch on top of the current task
+    ///
+    /// The merged values are checked here too, so `null` in a required field
+    /// is reported together with every other problem.
+    pub(crate) fn merge(task: &Task, patch: UpdateTask) -> Result<Self> {
+        let mut errors = FieldErrors::default();
+
+        let values = Self {
+            title: required("title", patch.title, &task.title, &mut errors),
+            description: patch.description.unwrap_or_else(|| task.description.clone()),
+            completed: required("completed", patch.completed, &task.completed, &mut errors),
+            due_at: patch.due_at.unwrap_or(task.due_at),
+            priority: required("priority", patch.priority, &task.priority, &mut errors),
+            parent_id: patch.parent_id.unwrap_or(task.parent_id),
+            tags: patch.tags.map(Option::unwrap_or_default),
+        };
+
+        values.check(&mut errors);
+        errors.into_result(values)
+    }
+
+    /// Check the new values; returns the normalized tags if they are replaced
+    pub(crate) fn validate(&self) -> Result<Option<Vec<String>>> {
+        let mut errors = FieldErrors::default();
+        let tags = self.check(&mut errors);
+
+        errors.into_result(tags)
+    }
+
+    fn check(&self, errors: &mut FieldErrors) -> Option<Vec<String>> {
+        check_text(
+            &self.title,
+            self.description.as_deref(),
+            "Title cannot be empty",
+            errors,
+        );
+
+        self.tags
+            .as_deref()
+            .map(|tags| check_tags("tags", tags, errors))
+    }
+}
+
+impl From<ReplaceTask> for TaskValues {
+    fn from(data: ReplaceTask) -> Self {
+        Self {
+            title: data.title,
+            description: data.description,
+            completed: data.completed,
+            due_at: data.due_at,
+            priority: data.priority.unwrap_or_default(),
+            parent_id: data.parent_id,
+            tags: Some(data.tags),
+        }
+    }
+}
+
+/// Invalid fields found while checking a request, reported all at once
+#[derive(Debug, Default)]
+pub(crate) struct FieldErrors(Vec<FieldError>);
+
+impl FieldErrors {
+    pub(crate) fn add(&mut self, field: impl Into<String>, code: &str, message: impl Into<String>) {
+        self.0.push(FieldError::new(field, code, message));
+    }
+
+    /// `value` when nothing was found, `ApiError::Invalid` otherwise
+    pub(crate) fn into_result<T>(self, value: T) -> Result<T> {
+        if self.0.is_empty() {
+            Ok(value)
+        } else {
+            Err(ApiError::Invalid(self.0))
+        }
+    }
+}
+
+/// Patched value of a field that cannot be null; keeps `current` when omitted
+fn required<T: Clone>(
+    field: &str,
+    value: Option<Option<T>>,
+    current: &T,
+    errors: &mut FieldErrors,
+) -> T {
+    match value {
+        Some(Some(value)) => value,
+        Some(None) => {
+            errors.add(field, "required", format!("{} cannot be null", field));
+            current.clone()
+        }
+        None => current.clone(),
+    }
+}
+
+/// Check the title and description of a task
+fn check_text(title: &str, description: Option<&str>, empty: &str, errors: &mut FieldErrors) {
+    if title.trim().is_empty() {
+        errors.add("title", "required", empty);
+    } else if title.len() > 200 {
+        errors.add("title", "too_long", "Title cannot exceed 200 characters");
+    }
+
+    if description.is_some_and(|description| description.chars().count() > 2000) {
+        errors.add(
+            "description",
+            "too_long",
+            "Description cannot exceed 2000 characters",
+        );
+    }
+}
+
+/// Check the fields of a new task; returns its normalized tags
+pub(crate) fn validate_new(data: &CreateTask) -> Result<Vec<String>> {
+    let mut errors = FieldErrors::default();
+
+    check_text(
+        &data.title,
+        data.description.as_deref(),
+        "Title is required",
+        &mut errors,
+    );
+    let tags = check_tags("tags", &data.tags, &mut errors);
+
+    errors.into_result(tags)
+}
+
+/// Validate tag names: trimmed, lowercased and deduplicated
+///
+/// `field` names the list in errors, e.g. `tags[2]`.
+pub(crate) fn normalize_tags(field: &str, tags: &[String]) -> Result<Vec<String>> {
+    let mut errors = FieldErrors::default();
+    let names = check_tags(field, tags, &mut errors);
+
+    errors.into_result(names)
+}
+
+fn check_tags(field: &str, tags: &[String], errors: &mut FieldErrors) -> Vec<String> {
+    let mut names: Vec<String> = Vec::with_capacity(tags.len());
+
+    for (index, tag) in tags.iter().enumerate() {
+        let name = tag.trim().to_lowercase();
+        let field = || format!("{}[{}]", field, index);
+
+        if name.is_empty() {
+            errors.add(field(), "required", "Tag names cannot be empty");
+        } else if name.chars().count() > 50 {
+            errors.add(field(), "too_long", "Tag names cannot exceed 50 characters");
+        } else if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+
+    names
+}
+
+/// Error for a missing parent task
+pub(crate) fn parent_not_found(parent_id: i64) -> ApiError {
+    ApiError::Validation(format!("Parent task {} not found", parent_id))
+}
+
+/// Error for a move that would make a task its own ancestor
+pub(crate) fn cycle_error() -> ApiError {
+    ApiError::Validation("A task cannot be moved under itself or one of its subtasks".into())
+}
+
+/// Error for a task that does not exist or is not visible to the caller
+pub(crate) fn task_not_found(id: i64) -> ApiError {
+    ApiError::NotFound(format!("Task {} not found", id))
+}
+
+/// Page of a task listing requested by the filters
+#[derive(Debug)]
+pub(crate) enum Page {
+    /// `limit`/`offset` pagination, answered with a plain array
+    /// (`limit` is `None` when the caller asked for every row)
+    Offset { limit: Option<i64>, offset: i64 },
+    /// Keyset pagination, starting after `cursor` (`None` for the first page)
+    Keyset { limit: i64, cursor: Option<Cursor> },
+}
+
+impl Page {
+    /// Read the pagination parameters; `cursor` switches to keyset mode
+    pub(crate) fn from_filters(filters: &TaskFilters) -> Result<Self> {
+        let Some(cursor) = filters.cursor.as_deref() else {
+            // Offset mode keeps its original semantics: any limit, a
+            // negative one meaning no limit, and negative offsets read as 0
+            return Ok(Page::Offset {
+                limit: Some(filters.limit.unwrap_or(MAX_PAGE_LIMIT)).filter(|limit| *limit >= 0),
+                offset: filters.offset.unwrap_or(0).max(0),
+            });
+        };
+
+        let limit = filters.limit.unwrap_or(MAX_PAGE_LIMIT);
+        if !(1..=MAX_PAGE_LIMIT).contains(&limit) {
+            return Err(ApiError::Validation(format!(
+                "Limit must be between 1 and {}",
+                MAX_PAGE_LIMIT
+            )));
+        }
+
+        Ok(Page::Keyset {
+            limit,
+            cursor: Cursor::decode(cursor, filters.sort.unwrap_or_default())?,
+        })
+    }
+
+    /// Rows to fetch (`None` for all): one extra in keyset mode, to know
+    /// whether another page follows
+    pub(crate) fn fetch_limit(&self) -> Option<i64> {
+        match self {
+            Page::Offset { limit, .. } => *limit,
+            Page::Keyset { limit, .. } => Some(limit + 1),
+        }
+    }
+
+    /// Rows to skip before the first one
+    pub(crate) fn offset(&self) -> i64 {
+        match self {
+            Page::Offset { offset, .. } => *offset,
+            Page::Keyset { .. } => 0,
+        }
+    }
+
+    /// Keyset position the rows must come after
+    pub(crate) fn cursor(&self) -> Option<&Cursor> {
+        match self {
+            Page::Offset { .. } => None,
+            Page::Keyset { cursor, .. } => cursor.as_ref(),
+        }
+    }
+
+    /// Response for the rows fetched with `fetch_limit`
+    pub(crate) fn finish(self, mut rows: Vec<Task>, sort: TaskSort) -> TaskList {
+        let Page::Keyset { limit, .. } = self else {
+            return TaskList::Items(rows);
+        };
+
+        let has_more = rows.len() as i64 > limit;
+        rows.truncate(limit as usize);
+
+        let next_cursor = if has_more {
+            rows.last().map(|task| Cursor::after(task, sort).encode())
+        } else {
+            None
+        };
+
+        TaskList::Page(TaskPage {
+            items: rows,
+            next_cursor,
+            has_more,
+        })
+    }
+}
+
+/// Keyset position after the last task of a page, for each sort order
+#[derive(Debug)]
+pub(crate) enum Cursor {
+    Created { id: i64 },
+    DueAt { due_at: Option<DateTime<Utc>>, id: i64 },
+    Priority { priority: i64, id: i64 },
+}
+
+impl Cursor {
+    /// Position right after `task`
+    fn after(task: &Task, sort: TaskSort) -> Self {
+        match sort {
+            TaskSort::Created => Cursor::Created { id: task.id },
+            TaskSort::DueAt => Cursor::DueAt {
+                due_at: task.due_at,
+                id: task.id,
+            },
+            TaskSort::Priority => Cursor::Priority {
+                priority: task.priority as i64,
+                id: task.id,
+            },
+        }
+    }
+
+    /// Encode as an opaque string
+    fn encode(&self) -> String {
+        let raw = match self {
+            Cursor::Created { id } => format!("id:{}", id),
+            Cursor::DueAt { due_at, id } => format!(
+                "due:{}:{}",
+                id,
+                due_at.map(|d| d.to_rfc3339()).unwrap_or_default()
+            ),
+            Cursor::Priority { priority, id } => format!("priority:{}:{}", id, priority),
+        };
+
+        URL_SAFE_NO_PAD.encode(raw)
+    }
+
+    /// Decode a cursor issued for `sort`; an empty cursor means "first page"
+    fn decode(cursor: &str, sort: TaskSort) -> Result<Option<Self>> {
+        if cursor.is_empty() {
+            return Ok(None);
+        }
+
+        let invalid = || ApiError::Validation("Invalid cursor".into());
+
+        let bytes = URL_SAFE_NO_PAD.decode(cursor).map_err(|_| invalid())?;
+        let raw = String::from_utf8(bytes).map_err(|_| invalid())?;
+        let mut parts = raw.splitn(3, ':');
+        let kind = parts.next().unwrap_or_default();
+        let id: i64 = parts
+            .next()
+            .and_then(|id| id.parse().ok())
+            .ok_or_else(invalid)?;
+        let value = parts.next();
+
+        let cursor = match (sort, kind, value) {
+            (TaskSort::Created, "id", None) => Cursor::Created { id },
+            (TaskSort::DueAt, "due", Some("")) => Cursor::DueAt { due_at: None, id },
+            (TaskSort::DueAt, "due", Some(due_at)) => Cursor::DueAt {
+                due_at: Some(
+                    DateTime::parse_from_rfc3339(due_at)
+                        .map_err(|_| invalid())?
+                        .with_timezone(&Utc),
+                ),
+                id,
+            },
+            (TaskSort::Priority, "priority", Some(priority)) => Cursor::Priority {
+                priority: priority.parse().map_err(|_| invalid())?,
+                id,
+            },
+            _ => return Err(invalid()),
+        };
+
+        Ok(Some(cursor))
+    }
+
+    /// Whether `task` comes after this position (for backends without SQL)
+    pub(crate) fn admits(&self, task: &Task) -> bool {
+        match *self {
+            Cursor::Created { id } => task.id < id,
+            // Tasks without deadline come last, ordered by id
+            Cursor::DueAt { due_at: None, id } => task.due_at.is_none() && task.id > id,
+            Cursor::DueAt {
+                due_at: Some(due_at),
+                id,
+            } => match task.due_at {
+                None => true,
+                Some(task_due_at) => task_due_at > due_at || (task_due_at == due_at && task.id > id),
+            },
+            Cursor::Priority { priority, id } => (task.priority as i64, task.id) < (priority, id),
+        }
+    }
+}
+
+/// Order of two tasks in a listing (for backends without SQL)
+pub(crate) fn compare(sort: TaskSort, a: &Task, b: &Task) -> Ordering {
+    match sort {
+        TaskSort::Created => b.id.cmp(&a.id),
+        TaskSort::DueAt => match (a.due_at, b.due_at) {
+            (Some(x), Some(y)) => x.cmp(&y).then(a.id.cmp(&b.id)),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => a.id.cmp(&b.id),
+        },
+        TaskSort::Priority => b.priority.cmp(&a.priority).then(b.id.cmp(&a.id)),
+    }
+}
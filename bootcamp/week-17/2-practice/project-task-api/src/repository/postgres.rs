@@ -0,0 +1,473 @@
+//! PostgreSQL Task Repository
+//!
+//! Enabled with the `postgres` cargo feature. It stores the core task fields
+//! and tags; accounts, search and history stay on the SQLite backend.
+
+use chrono::Utc;
+use sqlx::postgres::{PgConnection, PgPool, PgPoolOptions};
+use sqlx::{Executor, Postgres, QueryBuilder};
+
+use super::{
+    cycle_error, normalize_tags, parent_not_found, task_not_found, validate_new, Cursor, Page,
+    Precondition, TaskChange, TaskRepository,
+};
+use crate::auth::CurrentUser;
+use crate::error::Result;
+use crate::models::{
+    CreateTask, Priority, PriorityCounts, RootProgress, TagMatch, Task, TaskFilters, TaskList,
+    TaskSort, TaskStats,
+};
+
+/// Tables used by the repository, created by `PostgresTaskRepository::migrate`
+const SCHEMA: &str = r#"
+    CREATE TABLE IF NOT EXISTS tasks (
+        id BIGSERIAL PRIMARY KEY,
+        owner_id BIGINT NOT NULL,
+        title TEXT NOT NULL,
+        description TEXT,
+        completed BOOLEAN NOT NULL DEFAULT FALSE,
+        due_at TIMESTAMPTZ,
+        priority BIGINT NOT NULL DEFAULT 1,
+        parent_id BIGINT REFERENCES tasks(id) ON DELETE CASCADE,
+        version BIGINT NOT NULL DEFAULT 1,
+        deleted_at TIMESTAMPTZ,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+        updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+    );
+    CREATE INDEX IF NOT EXISTS idx_tasks_owner ON tasks(owner_id) WHERE deleted_at IS NULL;
+    CREATE INDEX IF NOT EXISTS idx_tasks_parent ON tasks(parent_id);
+
+    CREATE TABLE IF NOT EXISTS tags (
+        id BIGSERIAL PRIMARY KEY,
+        name TEXT NOT NULL UNIQUE
+    );
+
+    CREATE TABLE IF NOT EXISTS task_tags (
+        task_id BIGINT NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+        tag_id BIGINT NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+        PRIMARY KEY (task_id, tag_id)
+    );
+    CREATE INDEX IF NOT EXISTS idx_task_tags_tag ON task_tags(tag_id);
+"#;
+
+/// Columns selected for a `Task`, with timestamps formatted like SQLite's
+const TASK_COLUMNS: &str = "t.id, t.title, t.description, t.completed, \
+    to_char(t.created_at AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS') AS created_at, \
+    to_char(t.updated_at AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS') AS updated_at, \
+    t.due_at, t.priority, t.parent_id, t.version, \
+    (SELECT COALESCE(json_agg(g.name ORDER BY g.name), '[]'::json) \
+        FROM task_tags tt JOIN tags g ON g.id = tt.tag_id WHERE tt.task_id = t.id) AS tags";
+
+/// Tasks stored in PostgreSQL
+#[derive(Debug, Clone)]
+pub struct PostgresTaskRepository {
+    pool: PgPool,
+}
+
+impl PostgresTaskRepository {
+    /// Repository over an existing pool (see `migrate`)
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Connect to `database_url` and create the tables if needed
+    pub async fn connect(database_url: &str) -> Result<Self> {
+        let pool = PgPoolOptions::new()
+            .max_connections(5)
+            .connect(database_url)
+            .await?;
+
+        let repository = Self::new(pool);
+        repository.migrate().await?;
+
+        Ok(repository)
+    }
+
+    /// Create the tables used by the repository
+    pub async fn migrate(&self) -> Result<()> {
+        sqlx::raw_sql(SCHEMA).execute(&self.pool).await?;
+
+        Ok(())
+    }
+
+    /// Underlying connection pool
+    pub fn pool(&self) -> &PgPool {
+        &self.pool
+    }
+}
+
+impl TaskRepository for PostgresTaskRepository {
+    async fn list(&self, owner_id: i64, filters: TaskFilters) -> Result<TaskList> {
+        let sort = filters.sort.unwrap_or_default();
+        let mut query = QueryBuilder::<Postgres>::new(format!(
+            "SELECT {} FROM tasks t WHERE t.deleted_at IS NULL AND t.owner_id = ",
+            TASK_COLUMNS
+        ));
+        query.push_bind(owner_id);
+
+        if let Some(completed) = filters.completed {
+            query.push(" AND t.completed = ").push_bind(completed);
+        }
+
+        if !filters.tag.is_empty() {
+            let tags = normalize_tags("tag", &filters.tag)?;
+
+            query
+                .push(
+                    " AND t.id IN (SELECT tt.task_id FROM task_tags tt \
+                     JOIN tags g ON g.id = tt.tag_id WHERE g.name = ANY(",
+                )
+                .push_bind(tags.clone())
+                .push(")");
+
+            if filters.tag_match.unwrap_or_default() == TagMatch::All {
+                query
+                    .push(" GROUP BY tt.task_id HAVING COUNT(*) = ")
+                    .push_bind(tags.len() as i64);
+            }
+            query.push(")");
+        }
+
+        if filters.overdue == Some(true) {
+            query
+                .push(" AND t.completed = FALSE AND t.due_at < ")
+                .push_bind(Utc::now());
+        }
+
+        let page = Page::from_filters(&filters)?;
+
+        if let Some(cursor) = page.cursor() {
+            push_cursor(&mut query, cursor);
+        }
+
+        query
+            .push(order_by(sort))
+            .push(" LIMIT ")
+            // NULL means no limit
+            .push_bind(page.fetch_limit())
+            .push(" OFFSET ")
+            .push_bind(page.offset());
+
+        let rows = query.build_query_as::<Task>().fetch_all(&self.pool).await?;
+
+        Ok(page.finish(rows, sort))
+    }
+
+    async fn get(&self, owner_id: i64, id: i64) -> Result<Task> {
+        fetch_task(&self.pool, id, owner_id, false).await
+    }
+
+    async fn create(&self, user: &CurrentUser, data: CreateTask) -> Result<Task> {
+        let tags = validate_new(&data)?;
+        let mut tx = self.pool.begin().await?;
+
+        if let Some(parent_id) = data.parent_id {
+            ensure_parent(&mut *tx, parent_id, user.id).await?;
+        }
+
+        let (id,): (i64,) = sqlx::query_as(
+            "INSERT INTO tasks (title, description, due_at, priority, parent_id, owner_id) \
+             VALUES ($1, $2, $3, $4, $5, $6) RETURNING id",
+        )
+        .bind(&data.title)
+        .bind(&data.description)
+        .bind(data.due_at)
+        .bind(data.priority.unwrap_or_default())
+        .bind(data.parent_id)
+        .bind(user.id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        attach_tags(&mut tx, id, &tags).await?;
+        let task = fetch_task(&mut *tx, id, user.id, false).await?;
+
+        tx.commit().await?;
+
+        Ok(task)
+    }
+
+    async fn update(
+        &self,
+        user: &CurrentUser,
+        id: i64,
+        change: TaskChange,
+        cascade: bool,
+        precondition: Precondition<'_>,
+    ) -> Result<Task> {
+        let mut tx = self.pool.begin().await?;
+
+        // FOR UPDATE makes concurrent updates of the task queue up
+        let before = fetch_task(&mut *tx, id, user.id, true).await?;
+        precondition(&before)?;
+        let values = change.apply(&before)?;
+        let tags = values.validate()?;
+
+        // Only a new parent needs checking
+        if let Some(parent_id) = values.parent_id.filter(|p| Some(*p) != before.parent_id) {
+            ensure_parent(&mut *tx, parent_id, user.id).await?;
+            ensure_no_cycle(&mut *tx, id, parent_id).await?;
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE tasks
+            SET title = $1, description = $2, completed = $3, due_at = $4, priority = $5,
+                parent_id = $6, version = version + 1, updated_at = now()
+            WHERE id = $7
+            "#,
+        )
+        .bind(&values.title)
+        .bind(&values.description)
+        .bind(values.completed)
+        .bind(values.due_at)
+        .bind(values.priority)
+        .bind(values.parent_id)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+        if let Some(tags) = tags {
+            sqlx::query("DELETE FROM task_tags WHERE task_id = $1")
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+            attach_tags(&mut tx, id, &tags).await?;
+        }
+
+        if cascade {
+            sqlx::query(
+                r#"
+                WITH RECURSIVE descendants(id) AS (
+                    SELECT id FROM tasks WHERE parent_id = $1 AND deleted_at IS NULL
+                    UNION ALL
+                    SELECT c.id FROM tasks c JOIN descendants d ON c.parent_id = d.id
+                    WHERE c.deleted_at IS NULL
+                )
+                UPDATE tasks SET completed = $2, version = version + 1, updated_at = now()
+                WHERE id IN (SELECT id FROM descendants) AND completed != $2
+                "#,
+            )
+            .bind(id)
+            .bind(values.completed)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let task = fetch_task(&mut *tx, id, user.id, false).await?;
+        tx.commit().await?;
+
+        Ok(task)
+    }
+
+    async fn delete(&self, user: &CurrentUser, id: i64, precondition: Precondition<'_>) -> Result<Task> {
+        let mut tx = self.pool.begin().await?;
+
+        let task = fetch_task(&mut *tx, id, user.id, true).await?;
+        precondition(&task)?;
+
+        sqlx::query(
+            r#"
+            WITH RECURSIVE subtree(id) AS (
+                SELECT $1::BIGINT
+                UNION ALL
+                SELECT c.id FROM tasks c JOIN subtree s ON c.parent_id = s.id
+                WHERE c.deleted_at IS NULL
+            )
+            UPDATE tasks SET deleted_at = now(), version = version + 1
+            WHERE id IN (SELECT id FROM subtree)
+            "#,
+        )
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(task)
+    }
+
+    async fn stats(&self, owner_id: i64) -> Result<TaskStats> {
+        let (total, completed, overdue): (i64, i64, i64) = sqlx::query_as(
+            r#"
+            SELECT COUNT(*),
+                   COUNT(*) FILTER (WHERE completed),
+                   COUNT(*) FILTER (WHERE NOT completed AND due_at < $2)
+            FROM tasks WHERE owner_id = $1 AND deleted_at IS NULL
+            "#,
+        )
+        .bind(owner_id)
+        .bind(Utc::now())
+        .fetch_one(&self.pool)
+        .await?;
+
+        let priorities: Vec<(Priority, i64)> = sqlx::query_as(
+            "SELECT priority, COUNT(*) FROM tasks WHERE owner_id = $1 AND deleted_at IS NULL GROUP BY priority",
+        )
+        .bind(owner_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let roots = sqlx::query_as::<_, RootProgress>(
+            r#"
+            WITH RECURSIVE tree(root_id, id) AS (
+                SELECT id, id FROM tasks
+                WHERE owner_id = $1 AND parent_id IS NULL AND deleted_at IS NULL
+                UNION ALL
+                SELECT tree.root_id, c.id FROM tasks c JOIN tree ON c.parent_id = tree.id
+                WHERE c.deleted_at IS NULL
+            )
+            SELECT r.id, r.title,
+                   COUNT(*) AS subtasks,
+                   COUNT(*) FILTER (WHERE t.completed) AS completed,
+                   ROUND(100.0 * COUNT(*) FILTER (WHERE t.completed) / COUNT(*), 1)::FLOAT8 AS percent
+            FROM tree
+            JOIN tasks t ON t.id = tree.id
+            JOIN tasks r ON r.id = tree.root_id
+            WHERE tree.id != tree.root_id
+            GROUP BY r.id, r.title
+            ORDER BY r.id
+            "#,
+        )
+        .bind(owner_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(TaskStats {
+            total,
+            completed,
+            pending: total - completed,
+            overdue,
+            by_priority: PriorityCounts::from_counts(priorities),
+            roots,
+        })
+    }
+}
+
+/// Fetch a live task owned by `owner_id`, optionally locking its row
+async fn fetch_task<'e, E>(executor: E, id: i64, owner_id: i64, for_update: bool) -> Result<Task>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    sqlx::query_as::<_, Task>(&format!(
+        "SELECT {} FROM tasks t WHERE t.id = $1 AND t.owner_id = $2 AND t.deleted_at IS NULL{}",
+        TASK_COLUMNS,
+        if for_update { " FOR UPDATE OF t" } else { "" }
+    ))
+    .bind(id)
+    .bind(owner_id)
+    .fetch_optional(executor)
+    .await?
+    .ok_or_else(|| task_not_found(id))
+}
+
+/// `ORDER BY` clause for a sort order
+fn order_by(sort: TaskSort) -> &'static str {
+    match sort {
+        TaskSort::Created => " ORDER BY t.id DESC",
+        TaskSort::DueAt => " ORDER BY t.due_at IS NULL, t.due_at, t.id",
+        TaskSort::Priority => " ORDER BY t.priority DESC, t.id DESC",
+    }
+}
+
+/// Restrict a query to the rows after a keyset position
+fn push_cursor(query: &mut QueryBuilder<'_, Postgres>, cursor: &Cursor) {
+    match *cursor {
+        Cursor::Created { id } => {
+            query.push(" AND t.id < ").push_bind(id);
+        }
+        Cursor::DueAt { due_at: None, id } => {
+            query.push(" AND t.due_at IS NULL AND t.id > ").push_bind(id);
+        }
+        Cursor::DueAt {
+            due_at: Some(due_at),
+            id,
+        } => {
+            query
+                .push(" AND (t.due_at IS NULL OR t.due_at > ")
+                .push_bind(due_at)
+                .push(" OR (t.due_at = ")
+                .push_bind(due_at)
+                .push(" AND t.id > ")
+                .push_bind(id)
+                .push("))");
+        }
+        Cursor::Priority { priority, id } => {
+            query
+                .push(" AND (t.priority, t.id) < (")
+                .push_bind(priority)
+                .push(", ")
+                .push_bind(id)
+                .push(")");
+        }
+    }
+}
+
+/// Check that a parent task exists and belongs to the caller
+async fn ensure_parent<'e, E>(executor: E, parent_id: i64, owner_id: i64) -> Result<()>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    let parent = sqlx::query(
+        "SELECT id FROM tasks WHERE id = $1 AND owner_id = $2 AND deleted_at IS NULL",
+    )
+    .bind(parent_id)
+    .bind(owner_id)
+    .fetch_optional(executor)
+    .await?;
+
+    if parent.is_none() {
+        return Err(parent_not_found(parent_id));
+    }
+
+    Ok(())
+}
+
+/// Reject moving task `id` under `parent_id` if that parent is the task itself
+/// or one of its descendants
+async fn ensure_no_cycle<'e, E>(executor: E, id: i64, parent_id: i64) -> Result<()>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    let cycle = sqlx::query(
+        r#"
+        WITH RECURSIVE ancestors(id) AS (
+            SELECT $1::BIGINT
+            UNION
+            SELECT t.parent_id FROM tasks t JOIN ancestors a ON t.id = a.id
+            WHERE t.parent_id IS NOT NULL
+        )
+        SELECT 1 FROM ancestors WHERE id = $2
+        "#,
+    )
+    .bind(parent_id)
+    .bind(id)
+    .fetch_optional(executor)
+    .await?;
+
+    if cycle.is_some() {
+        return Err(cycle_error());
+    }
+
+    Ok(())
+}
+
+/// Attach tags to a task, creating the missing ones
+async fn attach_tags(conn: &mut PgConnection, task_id: i64, tags: &[String]) -> Result<()> {
+    for name in tags {
+        sqlx::query("INSERT INTO tags (name) VALUES ($1) ON CONFLICT (name) DO NOTHING")
+            .bind(name)
+            .execute(&mut *conn)
+            .await?;
+
+        sqlx::query(
+            "INSERT INTO task_tags (task_id, tag_id) SELECT $1, id FROM tags WHERE name = $2 \
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(task_id)
+        .bind(name)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,779 @@
+//! SQLite Task Repository
+//!
+//! The default backend. Besides implementing `TaskRepository`, its helpers are
+//! shared with the handlers of the SQLite-only features (bulk, import,
+//! history...), which run them inside their own transactions.
+
+use axum::Router;
+use chrono::Utc;
+use sqlx::{Executor, QueryBuilder, Sqlite, SqliteConnection, SqlitePool};
+
+use super::{
+    cycle_error, normalize_tags, parent_not_found, task_not_found, validate_new, Cursor, Page,
+    Precondition, TaskChange, TaskRepository, TaskValues,
+};
+use crate::audit;
+use crate::auth::CurrentUser;
+use crate::error::{ApiError, Result};
+use crate::models::{
+    CreateTask, Priority, PriorityCounts, ProjectRole, RootProgress, SearchQuery, SearchResult,
+    TagMatch, Task, TaskEventKind, TaskFilters, TaskList, TaskSort, TaskStats,
+};
+use crate::projects;
+use crate::routes;
+use crate::state::AppState;
+
+/// Columns selected for a `Task`, with the `tasks` table aliased as `t`
+pub(crate) const TASK_COLUMNS: &str = "t.id, t.title, t.description, t.completed, t.created_at, t.updated_at, \
+    t.due_at, t.priority, t.parent_id, t.version, \
+    (SELECT json_group_array(name) FROM ( \
+        SELECT g.name FROM task_tags tt JOIN tags g ON g.id = tt.tag_id \
+        WHERE tt.task_id = t.id ORDER BY g.name \
+    )) AS tags";
+
+/// Most results one search returns
+const MAX_SEARCH_LIMIT: i64 = 100;
+
+/// Tasks a query can see: the personal tasks of a user, or a project's
+///
+/// Project tasks have no owner, so they never show up in a user's scope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Scope {
+    /// Tasks of a user outside any project
+    Owner(i64),
+    /// Tasks of a project, shared by its members
+    Project(i64),
+    /// Tasks of a project, changed by `user_id` who needs `role` for it
+    ProjectWrite {
+        project_id: i64,
+        user_id: i64,
+        role: ProjectRole,
+    },
+}
+
+impl Scope {
+    /// Column of `tasks` holding the scope's ID
+    pub(crate) fn column(self) -> &'static str {
+        match self {
+            Scope::Owner(_) => "owner_id",
+            Scope::Project(_) | Scope::ProjectWrite { .. } => "project_id",
+        }
+    }
+
+    pub(crate) fn id(self) -> i64 {
+        match self {
+            Scope::Owner(id) | Scope::Project(id) => id,
+            Scope::ProjectWrite { project_id, .. } => project_id,
+        }
+    }
+
+    /// `owner_id` and `project_id` of a task created in this scope
+    fn columns(self) -> (Option<i64>, Option<i64>) {
+        match self {
+            Scope::Owner(id) => (Some(id), None),
+            Scope::Project(_) | Scope::ProjectWrite { .. } => (None, Some(self.id())),
+        }
+    }
+
+    /// Check that the writer still has the role the write needs
+    ///
+    /// Call it first thing in the write's `BEGIN IMMEDIATE` transaction, so
+    /// a role change committed after the request was let in still counts.
+    pub(crate) async fn authorize(self, conn: &mut SqliteConnection) -> Result<()> {
+        match self {
+            Scope::Owner(_) | Scope::Project(_) => Ok(()),
+            Scope::ProjectWrite {
+                project_id,
+                user_id,
+                role,
+            } => projects::require_role(conn, project_id, user_id, role).await,
+        }
+    }
+
+    /// Users who see the scope's tasks and so get events about them
+    pub(crate) async fn audience(self, conn: &mut SqliteConnection) -> Result<Vec<i64>> {
+        match self {
+            Scope::Owner(id) => Ok(vec![id]),
+            Scope::Project(_) | Scope::ProjectWrite { .. } => {
+                projects::member_ids(conn, self.id()).await
+            }
+        }
+    }
+}
+
+/// Tasks stored in SQLite, with history kept in `task_events`
+#[derive(Debug, Clone)]
+pub struct SqliteTaskRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteTaskRepository {
+    /// Repository over a migrated pool
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Underlying connection pool
+    pub fn pool(&self) -> &SqlitePool {
+        &self.pool
+    }
+
+    /// Full-text search over the owner's live tasks, most relevant first
+    pub async fn search(&self, owner_id: i64, query: &SearchQuery) -> Result<Vec<SearchResult>> {
+        let fts_query = fts_prefix_query(&query.q)
+            .ok_or_else(|| ApiError::Validation("Search query is required".into()))?;
+        let limit = query.limit.unwrap_or(20);
+        if !(1..=MAX_SEARCH_LIMIT).contains(&limit) {
+            return Err(ApiError::Validation(format!(
+                "Limit must be between 1 and {}",
+                MAX_SEARCH_LIMIT
+            )));
+        }
+
+        let results = sqlx::query_as::<_, SearchResult>(&format!(
+            r#"
+            SELECT {},
+                   bm25(tasks_fts) AS rank,
+                   highlight(tasks_fts, 0, '<mark>', '</mark>') AS title_highlight,
+                   snippet(tasks_fts, 1, '<mark>', '</mark>', '…', 16) AS snippet
+            FROM tasks_fts
+            JOIN tasks t ON t.id = tasks_fts.rowid
+            WHERE tasks_fts MATCH ? AND t.owner_id = ? AND t.deleted_at IS NULL
+              AND (? IS NULL OR t.completed = ?)
+            ORDER BY rank
+            LIMIT ?
+            "#,
+            TASK_COLUMNS
+        ))
+        .bind(fts_query)
+        .bind(owner_id)
+        .bind(query.completed)
+        .bind(query.completed)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(results)
+    }
+}
+
+/// Build an FTS5 query where every word is a quoted prefix term
+///
+/// Quoting keeps user input from being parsed as FTS5 syntax.
+fn fts_prefix_query(input: &str) -> Option<String> {
+    let terms: Vec<String> = input
+        .split_whitespace()
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect();
+
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+impl SqliteTaskRepository {
+    /// Tasks of `scope` matching `filters`
+    pub(crate) async fn list_in(&self, scope: Scope, filters: TaskFilters) -> Result<TaskList> {
+        let sort = filters.sort.unwrap_or_default();
+        let mut query = filtered_tasks_query(scope, &filters)?;
+        let page = Page::from_filters(&filters)?;
+
+        if let Some(cursor) = page.cursor() {
+            push_cursor(&mut query, cursor);
+        }
+
+        query
+            .push(order_by(sort))
+            .push(" LIMIT ")
+            // SQLite reads a negative limit as no limit
+            .push_bind(page.fetch_limit().unwrap_or(-1))
+            .push(" OFFSET ")
+            .push_bind(page.offset());
+
+        let rows = query.build_query_as::<Task>().fetch_all(&self.pool).await?;
+
+        Ok(page.finish(rows, sort))
+    }
+
+    /// Create a task in `scope`, logged as done by `user`
+    ///
+    /// Like the other `*_in` writes, also returns the scope's audience as of
+    /// the write, read in its transaction so nothing can fail once committed.
+    pub(crate) async fn create_in(
+        &self,
+        user: &CurrentUser,
+        scope: Scope,
+        data: CreateTask,
+    ) -> Result<(Task, Vec<i64>)> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        scope.authorize(&mut tx).await?;
+        let task = insert_task(&mut tx, user, scope, data).await?;
+        let audience = scope.audience(&mut tx).await?;
+        tx.commit().await?;
+
+        Ok((task, audience))
+    }
+
+    /// Apply `change` to a task of `scope`, see `TaskRepository::update`
+    pub(crate) async fn update_in(
+        &self,
+        user: &CurrentUser,
+        scope: Scope,
+        id: i64,
+        change: TaskChange,
+        cascade: bool,
+        precondition: Precondition<'_>,
+    ) -> Result<(Task, Vec<i64>)> {
+        // IMMEDIATE takes the write lock up front, so concurrent updates queue
+        // instead of merging into a stale copy of the task
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        scope.authorize(&mut tx).await?;
+
+        let before = fetch_task_in(&mut *tx, id, scope).await?;
+        precondition(&before)?;
+        let values = change.apply(&before)?;
+        let task = save_task(&mut tx, user, scope, &before, values, cascade).await?;
+        let audience = scope.audience(&mut tx).await?;
+
+        tx.commit().await?;
+
+        Ok((task, audience))
+    }
+
+    /// Delete a task of `scope` and its subtasks
+    pub(crate) async fn delete_in(
+        &self,
+        user: &CurrentUser,
+        scope: Scope,
+        id: i64,
+        precondition: Precondition<'_>,
+    ) -> Result<(Task, Vec<i64>)> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        scope.authorize(&mut tx).await?;
+
+        let task = fetch_task_in(&mut *tx, id, scope).await?;
+        precondition(&task)?;
+        soft_delete(&mut tx, user, id).await?;
+        let audience = scope.audience(&mut tx).await?;
+
+        tx.commit().await?;
+
+        Ok((task, audience))
+    }
+
+    /// Counts of the tasks of `scope`
+    pub(crate) async fn stats_in(&self, scope: Scope) -> Result<TaskStats> {
+        let pool = &self.pool;
+
+        let counts = task_counts(pool, Some(scope)).await?;
+
+        let roots = sqlx::query_as::<_, RootProgress>(&format!(
+            r#"
+            WITH RECURSIVE tree(root_id, id) AS (
+                SELECT id, id FROM tasks
+                WHERE {} = ? AND parent_id IS NULL AND deleted_at IS NULL
+                UNION ALL
+                SELECT tree.root_id, c.id FROM tasks c JOIN tree ON c.parent_id = tree.id
+                WHERE c.deleted_at IS NULL
+            )
+            SELECT r.id, r.title,
+                   COUNT(*) AS subtasks,
+                   SUM(t.completed) AS completed,
+                   ROUND(100.0 * SUM(t.completed) / COUNT(*), 1) AS percent
+            FROM tree
+            JOIN tasks t ON t.id = tree.id
+            JOIN tasks r ON r.id = tree.root_id
+            WHERE tree.id != tree.root_id
+            GROUP BY r.id
+            ORDER BY r.id
+            "#,
+            scope.column()
+        ))
+        .bind(scope.id())
+        .fetch_all(pool)
+        .await?;
+
+        Ok(TaskStats {
+            total: counts.total,
+            completed: counts.completed,
+            pending: counts.total - counts.completed,
+            overdue: counts.overdue,
+            by_priority: counts.by_priority,
+            roots,
+        })
+    }
+}
+
+impl TaskRepository for SqliteTaskRepository {
+    async fn list(&self, owner_id: i64, filters: TaskFilters) -> Result<TaskList> {
+        self.list_in(Scope::Owner(owner_id), filters).await
+    }
+
+    async fn get(&self, owner_id: i64, id: i64) -> Result<Task> {
+        fetch_task(&self.pool, id, owner_id).await
+    }
+
+    async fn create(&self, user: &CurrentUser, data: CreateTask) -> Result<Task> {
+        let (task, _) = self.create_in(user, Scope::Owner(user.id), data).await?;
+
+        Ok(task)
+    }
+
+    async fn update(
+        &self,
+        user: &CurrentUser,
+        id: i64,
+        change: TaskChange,
+        cascade: bool,
+        precondition: Precondition<'_>,
+    ) -> Result<Task> {
+        let (task, _) = self
+            .update_in(user, Scope::Owner(user.id), id, change, cascade, precondition)
+            .await?;
+
+        Ok(task)
+    }
+
+    async fn delete(&self, user: &CurrentUser, id: i64, precondition: Precondition<'_>) -> Result<Task> {
+        let (task, _) = self
+            .delete_in(user, Scope::Owner(user.id), id, precondition)
+            .await?;
+
+        Ok(task)
+    }
+
+    async fn stats(&self, owner_id: i64) -> Result<TaskStats> {
+        self.stats_in(Scope::Owner(owner_id)).await
+    }
+
+    fn extra_routes() -> Router<AppState<Self>> {
+        routes::sqlite_routes()
+    }
+}
+
+/// Live task counts behind `GET /tasks/stats` and the `/metrics` gauges
+#[derive(Debug, Default)]
+pub(crate) struct TaskCounts {
+    pub total: i64,
+    pub completed: i64,
+    /// Pending tasks past their deadline
+    pub overdue: i64,
+    pub by_priority: PriorityCounts,
+}
+
+/// Count the live tasks of `scope`, or every task with `None`
+pub(crate) async fn task_counts(pool: &SqlitePool, scope: Option<Scope>) -> Result<TaskCounts> {
+    let column = scope.map_or("owner_id", Scope::column);
+    let id = scope.map(Scope::id);
+
+    let (total, completed): (i64, i64) = sqlx::query_as(&format!(
+        "SELECT COUNT(*), COALESCE(SUM(completed), 0) FROM tasks \
+         WHERE (? IS NULL OR {} = ?) AND deleted_at IS NULL",
+        column
+    ))
+    .bind(id)
+    .bind(id)
+    .fetch_one(pool)
+    .await?;
+
+    let (overdue,): (i64,) = sqlx::query_as(&format!(
+        "SELECT COUNT(*) FROM tasks \
+         WHERE (? IS NULL OR {} = ?) AND deleted_at IS NULL AND completed = FALSE AND due_at < ?",
+        column
+    ))
+    .bind(id)
+    .bind(id)
+    .bind(Utc::now())
+    .fetch_one(pool)
+    .await?;
+
+    let priorities: Vec<(Priority, i64)> = sqlx::query_as(&format!(
+        "SELECT priority, COUNT(*) FROM tasks \
+         WHERE (? IS NULL OR {} = ?) AND deleted_at IS NULL GROUP BY priority",
+        column
+    ))
+    .bind(id)
+    .bind(id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(TaskCounts {
+        total,
+        completed,
+        overdue,
+        by_priority: PriorityCounts::from_counts(priorities),
+    })
+}
+
+/// Fetch a live (not deleted) task owned by `owner_id`
+pub(crate) async fn fetch_task<'e, E>(executor: E, id: i64, owner_id: i64) -> Result<Task>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    fetch_task_in(executor, id, Scope::Owner(owner_id)).await
+}
+
+/// Fetch a live (not deleted) task of `scope`
+pub(crate) async fn fetch_task_in<'e, E>(executor: E, id: i64, scope: Scope) -> Result<Task>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    sqlx::query_as::<_, Task>(&format!(
+        "SELECT {} FROM tasks t WHERE t.id = ? AND t.{} = ? AND t.deleted_at IS NULL",
+        TASK_COLUMNS,
+        scope.column()
+    ))
+    .bind(id)
+    .bind(scope.id())
+    .fetch_optional(executor)
+    .await?
+    .ok_or_else(|| task_not_found(id))
+}
+
+/// Query over the live tasks of `scope` matching `filters`
+///
+/// Ordering and pagination (`limit`, `offset`, `cursor`) are left to the caller.
+pub(crate) fn filtered_tasks_query(
+    scope: Scope,
+    filters: &TaskFilters,
+) -> Result<QueryBuilder<'static, Sqlite>> {
+    let mut query = QueryBuilder::<Sqlite>::new(format!(
+        "SELECT {} FROM tasks t WHERE t.deleted_at IS NULL AND t.{} = ",
+        TASK_COLUMNS,
+        scope.column()
+    ));
+    query.push_bind(scope.id());
+
+    if let Some(completed) = filters.completed {
+        query.push(" AND t.completed = ").push_bind(completed);
+    }
+
+    if !filters.tag.is_empty() {
+        let tags = normalize_tags("tag", &filters.tag)?;
+
+        query.push(
+            " AND t.id IN (SELECT tt.task_id FROM task_tags tt \
+             JOIN tags g ON g.id = tt.tag_id WHERE g.name IN (",
+        );
+        let mut names = query.separated(", ");
+        for tag in &tags {
+            names.push_bind(tag.clone());
+        }
+        names.push_unseparated(")");
+
+        if filters.tag_match.unwrap_or_default() == TagMatch::All {
+            query
+                .push(" GROUP BY tt.task_id HAVING COUNT(*) = ")
+                .push_bind(tags.len() as i64);
+        }
+        query.push(")");
+    }
+
+    if filters.overdue == Some(true) {
+        query
+            .push(" AND t.completed = FALSE AND t.due_at < ")
+            .push_bind(Utc::now());
+    }
+
+    Ok(query)
+}
+
+/// `ORDER BY` clause for a sort order
+pub(crate) fn order_by(sort: TaskSort) -> &'static str {
+    match sort {
+        TaskSort::Created => " ORDER BY t.id DESC",
+        TaskSort::DueAt => " ORDER BY t.due_at IS NULL, t.due_at, t.id",
+        TaskSort::Priority => " ORDER BY t.priority DESC, t.id DESC",
+    }
+}
+
+/// Restrict a query to the rows after a keyset position
+fn push_cursor(query: &mut QueryBuilder<'_, Sqlite>, cursor: &Cursor) {
+    match *cursor {
+        Cursor::Created { id } => {
+            query.push(" AND t.id < ").push_bind(id);
+        }
+        // Tasks without deadline come last, ordered by id
+        Cursor::DueAt { due_at: None, id } => {
+            query.push(" AND t.due_at IS NULL AND t.id > ").push_bind(id);
+        }
+        Cursor::DueAt {
+            due_at: Some(due_at),
+            id,
+        } => {
+            query
+                .push(" AND (t.due_at IS NULL OR t.due_at > ")
+                .push_bind(due_at)
+                .push(" OR (t.due_at = ")
+                .push_bind(due_at)
+                .push(" AND t.id > ")
+                .push_bind(id)
+                .push("))");
+        }
+        Cursor::Priority { priority, id } => {
+            query
+                .push(" AND (t.priority, t.id) < (")
+                .push_bind(priority)
+                .push(", ")
+                .push_bind(id)
+                .push(")");
+        }
+    }
+}
+
+/// Validate and insert a new task of `scope`, logging its creation by `user`
+///
+/// Runs inside the caller's transaction; returns the created task.
+pub(crate) async fn insert_task(
+    conn: &mut SqliteConnection,
+    user: &CurrentUser,
+    scope: Scope,
+    data: CreateTask,
+) -> Result<Task> {
+    insert_task_with(conn, user, scope, data, false).await
+}
+
+/// `insert_task` for a task that may already be completed (imports)
+pub(crate) async fn insert_task_with(
+    conn: &mut SqliteConnection,
+    user: &CurrentUser,
+    scope: Scope,
+    data: CreateTask,
+    completed: bool,
+) -> Result<Task> {
+    let tags = validate_new(&data)?;
+
+    if let Some(parent_id) = data.parent_id {
+        ensure_parent(&mut *conn, parent_id, scope).await?;
+    }
+
+    let (owner_id, project_id) = scope.columns();
+    let result = sqlx::query(
+        "INSERT INTO tasks (title, description, completed, due_at, priority, parent_id, owner_id, project_id) \
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(&data.title)
+    .bind(&data.description)
+    .bind(completed)
+    .bind(data.due_at)
+    .bind(data.priority.unwrap_or_default())
+    .bind(data.parent_id)
+    .bind(owner_id)
+    .bind(project_id)
+    .execute(&mut *conn)
+    .await?;
+
+    let id = result.last_insert_rowid();
+    attach_tags(conn, id, &tags).await?;
+
+    let task = fetch_task_in(&mut *conn, id, scope).await?;
+    audit::record(
+        conn,
+        id,
+        user.id,
+        TaskEventKind::Created,
+        audit::diff(None, Some(&task)),
+    )
+    .await?;
+
+    Ok(task)
+}
+
+/// Write the new values of a task in one statement and log the change
+///
+/// Runs inside the caller's transaction; returns the updated task.
+pub(crate) async fn save_task(
+    conn: &mut SqliteConnection,
+    user: &CurrentUser,
+    scope: Scope,
+    before: &Task,
+    values: TaskValues,
+    cascade: bool,
+) -> Result<Task> {
+    let id = before.id;
+    let tags = values.validate()?;
+
+    // Only a new parent needs checking
+    if let Some(parent_id) = values.parent_id.filter(|p| Some(*p) != before.parent_id) {
+        ensure_parent(&mut *conn, parent_id, scope).await?;
+        ensure_no_cycle(&mut *conn, id, parent_id).await?;
+    }
+
+    sqlx::query(
+        r#"
+        UPDATE tasks
+        SET title = ?, description = ?, completed = ?, due_at = ?, priority = ?, parent_id = ?,
+            version = version + 1, updated_at = CURRENT_TIMESTAMP
+        WHERE id = ?
+        "#,
+    )
+    .bind(&values.title)
+    .bind(&values.description)
+    .bind(values.completed)
+    .bind(values.due_at)
+    .bind(values.priority)
+    .bind(values.parent_id)
+    .bind(id)
+    .execute(&mut *conn)
+    .await?;
+
+    if let Some(tags) = tags {
+        sqlx::query("DELETE FROM task_tags WHERE task_id = ?")
+            .bind(id)
+            .execute(&mut *conn)
+            .await?;
+        attach_tags(conn, id, &tags).await?;
+    }
+
+    if cascade {
+        let completed = values.completed;
+        let changed: Vec<(i64,)> = sqlx::query_as(
+            r#"
+            WITH RECURSIVE descendants(id) AS (
+                SELECT id FROM tasks WHERE parent_id = ? AND deleted_at IS NULL
+                UNION ALL
+                SELECT c.id FROM tasks c JOIN descendants d ON c.parent_id = d.id
+                WHERE c.deleted_at IS NULL
+            )
+            UPDATE tasks SET completed = ?, version = version + 1, updated_at = CURRENT_TIMESTAMP
+            WHERE id IN (SELECT id FROM descendants) AND completed != ?
+            RETURNING id
+            "#,
+        )
+        .bind(id)
+        .bind(completed)
+        .bind(completed)
+        .fetch_all(&mut *conn)
+        .await?;
+
+        for (subtask_id,) in changed {
+            let mut changes = serde_json::Map::new();
+            changes.insert(
+                "completed".into(),
+                serde_json::json!({ "before": !completed, "after": completed }),
+            );
+            audit::record(conn, subtask_id, user.id, TaskEventKind::Updated, changes).await?;
+        }
+    }
+
+    let task = fetch_task_in(&mut *conn, id, scope).await?;
+    audit::record(
+        conn,
+        id,
+        user.id,
+        TaskEventKind::Updated,
+        audit::diff(Some(before), Some(&task)),
+    )
+    .await?;
+
+    Ok(task)
+}
+
+/// Check that a parent task exists in the same scope
+pub(crate) async fn ensure_parent<'e, E>(executor: E, parent_id: i64, scope: Scope) -> Result<()>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let parent = sqlx::query(&format!(
+        "SELECT id FROM tasks WHERE id = ? AND {} = ? AND deleted_at IS NULL",
+        scope.column()
+    ))
+    .bind(parent_id)
+    .bind(scope.id())
+    .fetch_optional(executor)
+    .await?;
+
+    if parent.is_none() {
+        return Err(parent_not_found(parent_id));
+    }
+
+    Ok(())
+}
+
+/// Reject moving task `id` under `parent_id` if that parent is the task itself
+/// or one of its descendants
+async fn ensure_no_cycle<'e, E>(executor: E, id: i64, parent_id: i64) -> Result<()>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let cycle = sqlx::query(
+        r#"
+        WITH RECURSIVE ancestors(id) AS (
+            SELECT ?
+            UNION
+            SELECT t.parent_id FROM tasks t JOIN ancestors a ON t.id = a.id
+            WHERE t.parent_id IS NOT NULL
+        )
+        SELECT 1 FROM ancestors WHERE id = ?
+        "#,
+    )
+    .bind(parent_id)
+    .bind(id)
+    .fetch_optional(executor)
+    .await?;
+
+    if cycle.is_some() {
+        return Err(cycle_error());
+    }
+
+    Ok(())
+}
+
+/// Mark a task and its live subtasks as deleted, logging each one
+///
+/// They all get `deleted_with = id`, which `restore_task` uses to bring back
+/// exactly this batch.
+pub(crate) async fn soft_delete(conn: &mut SqliteConnection, user: &CurrentUser, id: i64) -> Result<()> {
+    let deleted: Vec<(i64,)> = sqlx::query_as(
+        r#"
+        WITH RECURSIVE subtree(id) AS (
+            SELECT ?
+            UNION ALL
+            SELECT c.id FROM tasks c JOIN subtree s ON c.parent_id = s.id
+            WHERE c.deleted_at IS NULL
+        )
+        UPDATE tasks SET deleted_at = CURRENT_TIMESTAMP, deleted_with = ?, version = version + 1
+        WHERE id IN (SELECT id FROM subtree)
+        RETURNING id
+        "#,
+    )
+    .bind(id)
+    .bind(id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    for (task_id,) in deleted {
+        audit::record(
+            conn,
+            task_id,
+            user.id,
+            TaskEventKind::Deleted,
+            audit::deleted_change(true),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Attach tags to a task, creating the missing ones
+pub(crate) async fn attach_tags(conn: &mut SqliteConnection, task_id: i64, tags: &[String]) -> Result<()> {
+    for name in tags {
+        sqlx::query("INSERT INTO tags (name) VALUES (?) ON CONFLICT (name) DO NOTHING")
+            .bind(name)
+            .execute(&mut *conn)
+            .await?;
+
+        sqlx::query(
+            "INSERT OR IGNORE INTO task_tags (task_id, tag_id) SELECT ?, id FROM tags WHERE name = ?",
+        )
+        .bind(task_id)
+        .bind(name)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok(())
+}
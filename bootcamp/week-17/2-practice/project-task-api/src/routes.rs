@@ -1,19 +1,298 @@
 //! API Routes Definition
 
-use axum::{routing::get, Router};
+use axum::{
+    extract::DefaultBodyLimit,
+    http::{HeaderValue, StatusCode, Uri},
+    middleware,
+    routing::{any, delete, get, post, put},
+    Router,
+};
 use sqlx::SqlitePool;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+use tower_http::timeout::TimeoutLayer;
 
+use crate::auth::AuthConfig;
+use crate::config::{AppConfig, CorsConfig, MetricsConfig, RateLimitConfig};
+use crate::error::{self, ApiError};
 use crate::handlers;
+use crate::idempotency::IdempotencyStore;
+use crate::limits::{BodyLimitLayer, RateLimiter};
+use crate::metrics::{self, Metrics};
+use crate::repository::TaskRepository;
+use crate::state::AppState;
 
 /// Create API router
-pub fn create_routes() -> Router<SqlitePool> {
+///
+/// The core task routes (`/health/live`, `/tasks`, `/tasks/stats`,
+/// `/tasks/{id}` and the `/tasks/events` and `/ws` feeds) work with any
+/// repository; the repository adds the routes of the extra features it
+/// supports (see `TaskRepository::extra_routes`). SQLite serves every route;
+/// the other backends answer `404` on the paths of `SQLITE_ONLY_PATHS`.
+/// Error responses of every route carry the request path as `instance`.
+pub fn create_routes<R: TaskRepository>() -> Router<AppState<R>> {
     Router::new()
-        .route("/tasks", get(handlers::list_tasks).post(handlers::create_task))
-        .route("/tasks/stats", get(handlers::get_stats))
+        .route("/health/live", get(handlers::health_live))
+        .route(
+            "/tasks",
+            get(handlers::list_tasks::<R>).post(handlers::create_task::<R>),
+        )
+        .route("/tasks/stats", get(handlers::get_stats::<R>))
+        .route("/tasks/events", get(handlers::task_events))
+        .route("/ws", get(handlers::task_socket))
         .route(
             "/tasks/{id}",
-            get(handlers::get_task)
-                .put(handlers::update_task)
-                .delete(handlers::delete_task),
+            get(handlers::get_task::<R>)
+                .put(handlers::replace_task::<R>)
+                .patch(handlers::update_task::<R>)
+                .delete(handlers::delete_task::<R>),
+        )
+        .merge(R::extra_routes())
+        .layer(middleware::from_fn(error::problem_instance))
+}
+
+/// Paths of `sqlite_routes`
+pub const SQLITE_ONLY_PATHS: &[&str] = &[
+    "/health/ready",
+    "/auth/register",
+    "/auth/login",
+    "/tasks/bulk",
+    "/tasks/export",
+    "/tasks/import",
+    "/tasks/search",
+    "/tasks/{id}/subtasks",
+    "/tasks/{id}/tree",
+    "/tasks/{id}/history",
+    "/tasks/{id}/restore",
+    "/tasks/{id}/tags",
+    "/tags",
+    "/tasks/{id}/recurrence",
+    "/tasks/{id}/occurrences",
+    "/webhooks",
+    "/webhooks/{id}",
+    "/webhooks/{id}/deliveries",
+    "/projects",
+    "/projects/{pid}",
+    "/projects/{pid}/members",
+    "/projects/{pid}/members/{username}",
+    "/projects/{pid}/tasks",
+    "/projects/{pid}/tasks/stats",
+    "/projects/{pid}/tasks/{id}",
+    "/projects/{pid}/tasks/{id}/subtasks",
+    "/projects/{pid}/tasks/{id}/tree",
+    "/projects/{pid}/tasks/{id}/history",
+    "/projects/{pid}/tasks/{id}/restore",
+    "/projects/{pid}/tasks/{id}/tags",
+    "/projects/{pid}/tasks/{id}/recurrence",
+    "/projects/{pid}/tasks/{id}/occurrences",
+];
+
+/// Routes of backends without the SQLite-only features
+///
+/// Every path of `SQLITE_ONLY_PATHS` answers `404` to any method, rather
+/// than falling through to a core route (`/tasks/search` would otherwise
+/// reach `/tasks/{id}`).
+pub fn unsupported_routes<S>() -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    SQLITE_ONLY_PATHS
+        .iter()
+        .fold(Router::new(), |router, path| router.route(path, any(unsupported)))
+}
+
+async fn unsupported(uri: Uri) -> ApiError {
+    ApiError::NotFound(format!(
+        "{} is not available with this storage backend",
+        uri.path()
+    ))
+}
+
+/// Routes that need the SQLite backend: readiness, accounts, bulk,
+/// export/import, search, subtasks, history, tags, recurrence, webhooks and
+/// projects
+pub fn sqlite_routes() -> Router<AppState> {
+    Router::new()
+        .route("/health/ready", get(handlers::health_ready))
+        .route("/auth/register", post(handlers::register))
+        .route("/auth/login", post(handlers::login))
+        .route("/tasks/bulk", post(handlers::bulk_tasks))
+        .route("/tasks/export", get(handlers::export_tasks))
+        .route("/tasks/import", post(handlers::import_tasks))
+        .route("/tasks/search", get(handlers::search_tasks))
+        .route("/tasks/{id}/subtasks", get(handlers::list_subtasks))
+        .route("/tasks/{id}/tree", get(handlers::get_task_tree))
+        .route("/tasks/{id}/history", get(handlers::get_task_history))
+        .route("/tasks/{id}/restore", post(handlers::restore_task))
+        .route(
+            "/tasks/{id}/tags",
+            post(handlers::add_tags).delete(handlers::remove_tags),
+        )
+        .route("/tags", get(handlers::list_tags))
+        .route(
+            "/tasks/{id}/recurrence",
+            get(handlers::get_recurrence)
+                .put(handlers::set_recurrence)
+                .delete(handlers::delete_recurrence),
+        )
+        .route("/tasks/{id}/occurrences", get(handlers::list_occurrences))
+        .route(
+            "/webhooks",
+            get(handlers::list_webhooks).post(handlers::create_webhook),
         )
+        .route("/webhooks/{id}", delete(handlers::delete_webhook))
+        .route(
+            "/webhooks/{id}/deliveries",
+            get(handlers::list_webhook_deliveries),
+        )
+        .route(
+            "/projects",
+            get(handlers::list_projects).post(handlers::create_project),
+        )
+        .route(
+            "/projects/{pid}",
+            get(handlers::get_project).delete(handlers::delete_project),
+        )
+        .route(
+            "/projects/{pid}/members",
+            get(handlers::list_project_members),
+        )
+        .route(
+            "/projects/{pid}/members/{username}",
+            put(handlers::set_project_member).delete(handlers::remove_project_member),
+        )
+        .route(
+            "/projects/{pid}/tasks",
+            get(handlers::list_project_tasks).post(handlers::create_project_task),
+        )
+        .route("/projects/{pid}/tasks/stats", get(handlers::get_project_stats))
+        .route(
+            "/projects/{pid}/tasks/{id}",
+            get(handlers::get_project_task)
+                .put(handlers::replace_project_task)
+                .patch(handlers::update_project_task)
+                .delete(handlers::delete_project_task),
+        )
+        .route(
+            "/projects/{pid}/tasks/{id}/subtasks",
+            get(handlers::list_project_subtasks),
+        )
+        .route(
+            "/projects/{pid}/tasks/{id}/tree",
+            get(handlers::get_project_task_tree),
+        )
+        .route(
+            "/projects/{pid}/tasks/{id}/history",
+            get(handlers::get_project_task_history),
+        )
+        .route(
+            "/projects/{pid}/tasks/{id}/restore",
+            post(handlers::restore_project_task),
+        )
+        .route(
+            "/projects/{pid}/tasks/{id}/tags",
+            post(handlers::add_project_tags).delete(handlers::remove_project_tags),
+        )
+        .route(
+            "/projects/{pid}/tasks/{id}/recurrence",
+            get(handlers::get_project_recurrence)
+                .put(handlers::set_project_recurrence)
+                .delete(handlers::delete_project_recurrence),
+        )
+        .route(
+            "/projects/{pid}/tasks/{id}/occurrences",
+            get(handlers::list_project_occurrences),
+        )
+}
+
+/// Add the middleware set up by the configuration: body size limit, request
+/// timeout and CORS
+pub fn with_middleware<S>(router: Router<S>, config: &AppConfig) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    router
+        .layer(DefaultBodyLimit::max(config.server.body_limit))
+        .layer(BodyLimitLayer::new(config.server.body_limit))
+        .layer(TimeoutLayer::with_status_code(
+            StatusCode::REQUEST_TIMEOUT,
+            config.server.request_timeout(),
+        ))
+        .layer(cors_layer(&config.cors))
+}
+
+/// Throttle clients over their rate limit, unless disabled
+///
+/// `auth` must be the configuration the handlers use, so that requests with a
+/// valid token count against their user.
+pub fn with_rate_limit<S>(
+    router: Router<S>,
+    config: &RateLimitConfig,
+    auth: AuthConfig,
+) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    if !config.enabled {
+        return router;
+    }
+
+    router.layer(RateLimiter::new(config.clone(), auth).layer())
+}
+
+/// Run `POST` requests with an `Idempotency-Key` only once, unless disabled
+///
+/// Call it before `with_middleware`, so requests cut short by the timeout
+/// release their key. Expired keys are removed by `idempotency::spawn_cleanup`.
+pub fn with_idempotency<S>(
+    router: Router<S>,
+    config: &AppConfig,
+    pool: SqlitePool,
+    auth: AuthConfig,
+) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    if !config.idempotency.enabled {
+        return router;
+    }
+
+    let store = IdempotencyStore::new(pool, config.idempotency.ttl());
+
+    router.layer(store.layer(auth, config.server.body_limit))
+}
+
+/// Record request metrics and serve them at `GET /metrics`, unless disabled
+///
+/// Call it once every route is in place, so all of them are measured.
+pub fn with_metrics<S>(router: Router<S>, config: &MetricsConfig, pool: SqlitePool) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    if !config.enabled {
+        return router;
+    }
+
+    let metrics = Metrics::new();
+
+    router
+        .merge(metrics::routes(metrics.clone(), pool))
+        .layer(metrics.layer())
+}
+
+fn cors_layer(config: &CorsConfig) -> CorsLayer {
+    if config.allows_any() {
+        return CorsLayer::permissive();
+    }
+
+    // Origins are checked when the configuration is validated
+    let origins = config
+        .allowed_origins
+        .iter()
+        .filter_map(|origin| HeaderValue::from_str(origin).ok());
+
+    CorsLayer::new()
+        .allow_origin(AllowOrigin::list(origins))
+        .allow_methods(Any)
+        .allow_headers(Any)
+        .expose_headers(Any)
 }
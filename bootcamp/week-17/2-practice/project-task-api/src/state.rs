@@ -0,0 +1,49 @@
+//! Shared Application State
+
+use axum::extract::FromRef;
+use sqlx::SqlitePool;
+
+use crate::auth::AuthConfig;
+use crate::events::EventBus;
+use crate::repository::SqliteTaskRepository;
+
+/// State shared by every handler, generic over the task storage
+#[derive(Clone)]
+pub struct AppState<R = SqliteTaskRepository> {
+    /// Task repository
+    pub tasks: R,
+    /// JWT signing configuration
+    pub auth: AuthConfig,
+    /// Live change feed
+    pub events: EventBus,
+}
+
+impl<R> AppState<R> {
+    /// Create application state
+    pub fn new(tasks: R, auth: AuthConfig) -> Self {
+        Self {
+            tasks,
+            auth,
+            events: EventBus::new(),
+        }
+    }
+}
+
+/// Handlers of the SQLite-only features take the pool directly
+impl FromRef<AppState> for SqlitePool {
+    fn from_ref(state: &AppState) -> Self {
+        state.tasks.pool().clone()
+    }
+}
+
+impl<R> FromRef<AppState<R>> for AuthConfig {
+    fn from_ref(state: &AppState<R>) -> Self {
+        state.auth.clone()
+    }
+}
+
+impl<R> FromRef<AppState<R>> for EventBus {
+    fn from_ref(state: &AppState<R>) -> Self {
+        state.events.clone()
+    }
+}
@@ -0,0 +1,256 @@
+//! Task Export and Import Formats
+//!
+//! Exports are encoded one task at a time so they can be streamed straight
+//! from the database. Imports are parsed into rows, each with its own error.
+
+use std::io;
+
+use axum::body::Bytes;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::models::{ExportFormat, Priority, Task};
+
+/// Columns of the CSV format, in order
+const CSV_HEADER: [&str; 10] = [
+    "id",
+    "title",
+    "description",
+    "completed",
+    "due_at",
+    "priority",
+    "parent_id",
+    "tags",
+    "created_at",
+    "updated_at",
+];
+
+/// Separator of the tag names in a CSV cell
+const CSV_TAG_SEPARATOR: &str = ";";
+
+/// `Content-Type` of a format
+pub fn content_type(format: ExportFormat) -> &'static str {
+    match format {
+        ExportFormat::Json => "application/json",
+        ExportFormat::Ndjson => "application/x-ndjson",
+        ExportFormat::Csv => "text/csv; charset=utf-8",
+    }
+}
+
+/// File extension of a format
+pub fn extension(format: ExportFormat) -> &'static str {
+    match format {
+        ExportFormat::Json => "json",
+        ExportFormat::Ndjson => "ndjson",
+        ExportFormat::Csv => "csv",
+    }
+}
+
+/// Format matching a request `Content-Type`, if any
+pub fn format_of_content_type(content_type: &str) -> Option<ExportFormat> {
+    let mime = content_type.split(';').next()?.trim();
+
+    match mime {
+        "application/json" => Some(ExportFormat::Json),
+        "application/x-ndjson" | "application/ndjson" => Some(ExportFormat::Ndjson),
+        "text/csv" => Some(ExportFormat::Csv),
+        _ => None,
+    }
+}
+
+/// A task as a CSV record
+#[derive(Serialize)]
+struct CsvRow<'a> {
+    id: i64,
+    title: &'a str,
+    description: Option<&'a str>,
+    completed: bool,
+    due_at: Option<DateTime<Utc>>,
+    priority: Priority,
+    parent_id: Option<i64>,
+    tags: String,
+    created_at: &'a str,
+    updated_at: &'a str,
+}
+
+/// Incremental encoder of an export
+pub struct Encoder {
+    format: ExportFormat,
+    written: usize,
+}
+
+impl Encoder {
+    pub fn new(format: ExportFormat) -> Self {
+        Self { format, written: 0 }
+    }
+
+    /// Bytes that open the file
+    pub fn start(&mut self) -> io::Result<Bytes> {
+        match self.format {
+            ExportFormat::Json => Ok(Bytes::from_static(b"[")),
+            ExportFormat::Ndjson => Ok(Bytes::new()),
+            ExportFormat::Csv => csv_record(|writer| writer.write_record(CSV_HEADER)),
+        }
+    }
+
+    /// Bytes of one task
+    pub fn task(&mut self, task: &Task) -> io::Result<Bytes> {
+        self.written += 1;
+
+        match self.format {
+            ExportFormat::Json => {
+                let mut bytes = if self.written > 1 { b",\n".to_vec() } else { b"\n".to_vec() };
+                serde_json::to_writer(&mut bytes, task)?;
+                Ok(bytes.into())
+            }
+            ExportFormat::Ndjson => {
+                let mut bytes = serde_json::to_vec(task)?;
+                bytes.push(b'\n');
+                Ok(bytes.into())
+            }
+            ExportFormat::Csv => csv_record(|writer| {
+                writer.serialize(CsvRow {
+                    id: task.id,
+                    title: &task.title,
+                    description: task.description.as_deref(),
+                    completed: task.completed,
+                    due_at: task.due_at,
+                    priority: task.priority,
+                    parent_id: task.parent_id,
+                    tags: task.tags.join(CSV_TAG_SEPARATOR),
+                    created_at: &task.created_at,
+                    updated_at: &task.updated_at,
+                })
+            }),
+        }
+    }
+
+    /// Bytes that close the file
+    pub fn finish(&mut self) -> Bytes {
+        match self.format {
+            ExportFormat::Json if self.written > 0 => Bytes::from_static(b"\n]\n"),
+            ExportFormat::Json => Bytes::from_static(b"]\n"),
+            ExportFormat::Ndjson | ExportFormat::Csv => Bytes::new(),
+        }
+    }
+}
+
+/// Bytes of one CSV record
+fn csv_record<F>(write: F) -> io::Result<Bytes>
+where
+    F: FnOnce(&mut csv::Writer<Vec<u8>>) -> csv::Result<()>,
+{
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(Vec::new());
+    write(&mut writer)?;
+
+    writer
+        .into_inner()
+        .map(Bytes::from)
+        .map_err(|e| e.into_error())
+}
+
+/// One task read from an import file
+///
+/// `id` and `parent_id` refer to ids inside the same file, so a task tree
+/// can be moved between databases. Exported fields such as `created_at` are
+/// ignored.
+#[derive(Debug, Deserialize)]
+pub struct ImportRow {
+    pub id: Option<i64>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub completed: Option<bool>,
+    pub due_at: Option<DateTime<Utc>>,
+    pub priority: Option<Priority>,
+    pub parent_id: Option<i64>,
+    #[serde(default, deserialize_with = "tag_list")]
+    pub tags: Vec<String>,
+}
+
+/// Tags as a JSON array or as a `;`-separated CSV cell
+fn tag_list<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Tags {
+        List(Vec<String>),
+        Joined(String),
+    }
+
+    Ok(match Option::<Tags>::deserialize(deserializer)? {
+        Some(Tags::List(tags)) => tags,
+        Some(Tags::Joined(tags)) => tags
+            .split(CSV_TAG_SEPARATOR)
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(String::from)
+            .collect(),
+        None => Vec::new(),
+    })
+}
+
+/// Parsed rows of an import file, each with its row number
+pub type ImportRows = Vec<(usize, Result<ImportRow, String>)>;
+
+/// Split an import file into rows
+///
+/// Fails only if the file as a whole is unreadable; row errors are kept
+/// next to their row.
+pub fn parse(format: ExportFormat, body: &str) -> Result<ImportRows, String> {
+    match format {
+        ExportFormat::Json => {
+            let values: Vec<serde_json::Value> = serde_json::from_str(body)
+                .map_err(|e| format!("Expected a JSON array of tasks: {}", e))?;
+
+            Ok(values
+                .into_iter()
+                .enumerate()
+                .map(|(i, value)| (i + 1, serde_json::from_value(value).map_err(|e| e.to_string())))
+                .collect())
+        }
+        ExportFormat::Ndjson => Ok(body
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| (i + 1, serde_json::from_str(line).map_err(|e| e.to_string())))
+            .collect()),
+        ExportFormat::Csv => {
+            let mut reader = csv::Reader::from_reader(body.as_bytes());
+            reader
+                .headers()
+                .map_err(|e| format!("Invalid CSV header: {}", e))?;
+
+            Ok(reader
+                .deserialize()
+                .enumerate()
+                .map(|(i, row)| {
+                    let line = match &row {
+                        Err(e) => e.position().map(|p| p.line() as usize),
+                        Ok(_) => None,
+                    };
+                    // Header is line 1
+                    (line.unwrap_or(i + 2), row.map_err(|e| csv_error(&e)))
+                })
+                .collect())
+        }
+    }
+}
+
+/// CSV error message without the position, which is reported as the row
+fn csv_error(error: &csv::Error) -> String {
+    match error.kind() {
+        csv::ErrorKind::Deserialize { err, .. } => match err.field() {
+            Some(field) => format!(
+                "Invalid value in column '{}': {}",
+                CSV_HEADER.get(field as usize).copied().unwrap_or("?"),
+                err.kind()
+            ),
+            None => err.kind().to_string(),
+        },
+        _ => error.to_string(),
+    }
+}
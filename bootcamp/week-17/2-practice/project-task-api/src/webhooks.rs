@@ -0,0 +1,367 @@
+//! Webhook Delivery
+//!
+//! Every task change is queued in `webhook_deliveries` once per matching
+//! subscription and sent by a background worker as a signed `POST`. Failed
+//! attempts are retried with exponential backoff; a delivery that still fails
+//! after the last attempt is marked `dead` and copied to `webhook_dead_letters`.
+//!
+//! Changes reach the worker through an in-memory queue, so a change committed
+//! just before the process stops may never be queued: delivery is at most
+//! once for that window, and at least once (with retries) after it.
+//!
+//! ## Request format
+//!
+//! The body is the `ChangeEvent` as JSON, with these headers:
+//!
+//! - `X-Webhook-Id` / `X-Webhook-Delivery` - Webhook and delivery IDs
+//! - `X-Webhook-Event` - Event type, e.g. `task.created`
+//! - `X-Webhook-Timestamp` - Unix time of the attempt
+//! - `X-Webhook-Signature` - `sha256=` + hex HMAC-SHA256 of
+//!   `"{timestamp}.{body}"` keyed with the webhook secret
+
+use std::time::Duration;
+
+use chrono::{NaiveDateTime, TimeDelta, Utc};
+use futures_util::{Stream, StreamExt};
+use hmac::{Hmac, Mac};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use reqwest::header::CONTENT_TYPE;
+use sha2::Sha256;
+use sqlx::types::Json;
+use sqlx::{FromRow, SqlitePool};
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+
+use crate::events::EventBus;
+use crate::models::{ChangeEvent, ChangeKind};
+
+/// Header with the HMAC-SHA256 signature of a delivery
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+/// Header with the Unix time included in the signature
+pub const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+
+/// Format of `next_attempt_at`; milliseconds keep short backoffs exact
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.3f";
+
+/// Deliveries attempted per round
+const BATCH_SIZE: i64 = 50;
+
+/// Delivery worker settings
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    /// Attempts before a delivery is dead-lettered
+    pub max_attempts: u32,
+    /// Wait before the first retry; doubled on every further one
+    pub base_delay: Duration,
+    /// Longest wait between retries
+    pub max_delay: Duration,
+    /// How often to look for due deliveries when nothing wakes the worker
+    pub poll_interval: Duration,
+    /// Timeout of each HTTP request
+    pub timeout: Duration,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 8,
+            base_delay: Duration::from_secs(2),
+            max_delay: Duration::from_secs(60 * 60),
+            poll_interval: Duration::from_secs(5),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl WebhookConfig {
+    /// Wait before the next attempt after `attempts` failed ones
+    pub fn backoff(&self, attempts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+        self.base_delay.saturating_mul(factor).min(self.max_delay)
+    }
+}
+
+/// Random secret for a new webhook
+pub fn generate_secret() -> String {
+    let mut secret = [0u8; 32];
+    OsRng.fill_bytes(&mut secret);
+    hex::encode(secret)
+}
+
+/// Value of `X-Webhook-Signature` for a body sent at `timestamp`
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Start the worker that queues and sends webhook deliveries
+///
+/// It takes the queue of `events` before returning, so no change published
+/// after this call is missed while the process runs, even during bulk
+/// writes. Deliveries left pending by a previous run are picked up too;
+/// changes still in the in-memory queue at shutdown are not (see `events`).
+pub fn spawn_worker(pool: SqlitePool, events: &EventBus, config: WebhookConfig) -> JoinHandle<()> {
+    let changes = events.queue_all();
+
+    tokio::spawn(async move {
+        let wake = Notify::new();
+        let client = reqwest::Client::new();
+
+        tokio::join!(
+            queue_changes(&pool, changes, &wake),
+            dispatch(&pool, &client, &config, &wake),
+        );
+    })
+}
+
+/// Queue a delivery per matching webhook for every change
+async fn queue_changes(
+    pool: &SqlitePool,
+    changes: impl Stream<Item = (i64, ChangeEvent)>,
+    wake: &Notify,
+) {
+    let mut changes = std::pin::pin!(changes);
+
+    while let Some((owner_id, change)) = changes.next().await {
+        match enqueue(pool, owner_id, &change).await {
+            Ok(0) => {}
+            Ok(_) => wake.notify_one(),
+            Err(e) => tracing::error!("Could not queue webhook deliveries: {}", e),
+        }
+    }
+}
+
+async fn enqueue(
+    pool: &SqlitePool,
+    owner_id: i64,
+    change: &ChangeEvent,
+) -> Result<u64, sqlx::Error> {
+    let queued = sqlx::query(
+        r#"
+        INSERT INTO webhook_deliveries (webhook_id, event, payload, next_attempt_at)
+        SELECT w.id, ?, ?, ?
+        FROM webhooks w
+        WHERE w.owner_id = ? AND EXISTS (SELECT 1 FROM json_each(w.events) WHERE value = ?)
+        "#,
+    )
+    .bind(change.kind)
+    .bind(Json(change))
+    .bind(Utc::now().format(TIME_FORMAT).to_string())
+    .bind(owner_id)
+    .bind(change.kind)
+    .execute(pool)
+    .await?;
+
+    Ok(queued.rows_affected())
+}
+
+/// Send due deliveries, then sleep until the next one is due or a new one is queued
+async fn dispatch(
+    pool: &SqlitePool,
+    client: &reqwest::Client,
+    config: &WebhookConfig,
+    wake: &Notify,
+) {
+    loop {
+        if let Err(e) = deliver_due(pool, client, config).await {
+            tracing::error!("Webhook delivery failed: {}", e);
+        }
+
+        let wait = match next_due(pool).await {
+            Ok(Some(wait)) => wait.min(config.poll_interval),
+            Ok(None) => config.poll_interval,
+            Err(e) => {
+                tracing::error!("Could not read pending webhook deliveries: {}", e);
+                config.poll_interval
+            }
+        };
+
+        tokio::select! {
+            _ = wake.notified() => {}
+            _ = tokio::time::sleep(wait) => {}
+        }
+    }
+}
+
+/// Delivery with what is needed to send it
+#[derive(FromRow)]
+struct DueDelivery {
+    id: i64,
+    webhook_id: i64,
+    event: ChangeKind,
+    payload: String,
+    attempts: i64,
+    url: String,
+    secret: String,
+}
+
+/// Attempt every due delivery, oldest first and in batches
+async fn deliver_due(
+    pool: &SqlitePool,
+    client: &reqwest::Client,
+    config: &WebhookConfig,
+) -> Result<(), sqlx::Error> {
+    loop {
+        let due: Vec<DueDelivery> = sqlx::query_as(
+            r#"
+            SELECT d.id, d.webhook_id, d.event, d.payload, d.attempts, w.url, w.secret
+            FROM webhook_deliveries d JOIN webhooks w ON w.id = d.webhook_id
+            WHERE d.status = 'pending' AND d.next_attempt_at <= ?
+            ORDER BY d.next_attempt_at, d.id
+            LIMIT ?
+            "#,
+        )
+        .bind(Utc::now().format(TIME_FORMAT).to_string())
+        .bind(BATCH_SIZE)
+        .fetch_all(pool)
+        .await?;
+
+        if due.is_empty() {
+            return Ok(());
+        }
+
+        let attempts = due
+            .iter()
+            .map(|delivery| attempt(pool, client, config, delivery));
+
+        for result in futures_util::future::join_all(attempts).await {
+            result?;
+        }
+    }
+}
+
+/// Time until the earliest pending delivery is due
+async fn next_due(pool: &SqlitePool) -> Result<Option<Duration>, sqlx::Error> {
+    let (next,): (Option<String>,) = sqlx::query_as(
+        "SELECT MIN(next_attempt_at) FROM webhook_deliveries WHERE status = 'pending'",
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(next
+        .and_then(|next| NaiveDateTime::parse_from_str(&next, TIME_FORMAT).ok())
+        .map(|next| (next.and_utc() - Utc::now()).to_std().unwrap_or_default()))
+}
+
+/// Send a delivery once and record the outcome
+async fn attempt(
+    pool: &SqlitePool,
+    client: &reqwest::Client,
+    config: &WebhookConfig,
+    delivery: &DueDelivery,
+) -> Result<(), sqlx::Error> {
+    let timestamp = Utc::now().timestamp();
+
+    let response = client
+        .post(&delivery.url)
+        .timeout(config.timeout)
+        .header(CONTENT_TYPE, "application/json")
+        .header("X-Webhook-Id", delivery.webhook_id)
+        .header("X-Webhook-Delivery", delivery.id)
+        .header("X-Webhook-Event", delivery.event.as_str())
+        .header(TIMESTAMP_HEADER, timestamp)
+        .header(
+            SIGNATURE_HEADER,
+            sign(&delivery.secret, timestamp, delivery.payload.as_bytes()),
+        )
+        .body(delivery.payload.clone())
+        .send()
+        .await;
+
+    let (status, error) = match response {
+        Ok(response) if response.status().is_success() => (Some(response.status().as_u16()), None),
+        Ok(response) => (
+            Some(response.status().as_u16()),
+            Some(format!("Endpoint responded with {}", response.status())),
+        ),
+        Err(e) => (None, Some(e.to_string())),
+    };
+    let attempts = delivery.attempts + 1;
+
+    let Some(error) = error else {
+        sqlx::query(
+            r#"
+            UPDATE webhook_deliveries
+            SET status = 'delivered', attempts = ?, last_status = ?, last_error = NULL,
+                next_attempt_at = NULL, delivered_at = CURRENT_TIMESTAMP
+            WHERE id = ?
+            "#,
+        )
+        .bind(attempts)
+        .bind(status)
+        .bind(delivery.id)
+        .execute(pool)
+        .await?;
+
+        return Ok(());
+    };
+
+    if attempts >= i64::from(config.max_attempts) {
+        tracing::warn!(
+            "Webhook {} delivery {} failed {} times, dead-lettered: {}",
+            delivery.webhook_id,
+            delivery.id,
+            attempts,
+            error
+        );
+
+        let mut tx = pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            UPDATE webhook_deliveries
+            SET status = 'dead', attempts = ?, last_status = ?, last_error = ?, next_attempt_at = NULL
+            WHERE id = ?
+            "#,
+        )
+        .bind(attempts)
+        .bind(status)
+        .bind(&error)
+        .bind(delivery.id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO webhook_dead_letters (delivery_id, webhook_id, event, payload, attempts, last_error)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(delivery.id)
+        .bind(delivery.webhook_id)
+        .bind(delivery.event)
+        .bind(&delivery.payload)
+        .bind(attempts)
+        .bind(&error)
+        .execute(&mut *tx)
+        .await?;
+
+        return tx.commit().await;
+    }
+
+    let retry_at =
+        Utc::now() + TimeDelta::from_std(config.backoff(attempts as u32)).unwrap_or_default();
+
+    sqlx::query(
+        r#"
+        UPDATE webhook_deliveries
+        SET attempts = ?, last_status = ?, last_error = ?, next_attempt_at = ?
+        WHERE id = ?
+        "#,
+    )
+    .bind(attempts)
+    .bind(status)
+    .bind(&error)
+    .bind(retry_at.format(TIME_FORMAT).to_string())
+    .bind(delivery.id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
@@ -2,48 +2,150 @@
 //!
 //! Run with: `cargo test`
 
+mod common;
+
 use axum::{
     body::Body,
-    http::\{Request, StatusCode},
+    http::{HeaderMap, Request, StatusCode},
     Router,
 };
-use proyecto_api_tareas::{db, models::Task, routes};
+use std::path::PathBuf;
+
+use common::{body_bytes, test_pool, TestApp, TEST_SECRET};
+use project_task_api::{
+    auth::{self, AuthConfig},
+    db, migrations,
+    models::{HealthStatus, Liveness, Priority, Readiness, Task},
+};
 use serde_json::json;
+use sqlx::SqlitePool;
 use tower::ServiceExt;
 use tower_http::trace::TraceLayer;
 
 /// Helper to create test application
+///
+/// The database starts with user 1 (`tester`), used by [`request`].
 async fn create_app() -> Router {
-    let pool = db::create_pool().await.expect("Error creating pool");
+    app_with_pool(test_pool().await).await
+}
+
+/// SQLite file removed when the test ends
+struct TempDb(PathBuf);
+
+impl TempDb {
+    fn remove(&self) {
+        for suffix in ["", "-wal", "-shm", "-journal"] {
+            let mut path = self.0.clone().into_os_string();
+            path.push(suffix);
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+impl Drop for TempDb {
+    fn drop(&mut self) {
+        self.remove();
+    }
+}
+
+/// Helper to create a test application backed by a file database
+///
+/// Unlike the in-memory database it has several connections, so concurrent
+/// requests really run in parallel.
+async fn create_file_app(name: &str) -> (Router, TempDb) {
+    let path = std::env::temp_dir().join(format!("task-api-{}-{}.db", name, std::process::id()));
+    let temp = TempDb(path);
+    temp.remove();
+
+    let pool = db::connect(&format!("sqlite:{}?mode=rwc", temp.0.display()))
+        .await
+        .expect("Error creating pool");
+    migrations::migrate_up(&pool).await.expect("Error migrating");
+
+    (app_with_pool(pool).await, temp)
+}
 
-    Router::new()
-        .merge(routes::create_routes())
-        .layer(TraceLayer::new_for_http())
-        .with_state(pool)
+/// Helper to build the router around a migrated pool with user 1 (`tester`)
+async fn app_with_pool(pool: SqlitePool) -> Router {
+    TestApp::from_pool(pool, &["tester"], |routes, _| {
+        routes.layer(TraceLayer::new_for_http())
+    })
+    .await
+    .router
 }
 
-/// Helper to make requests
+/// Helper to create an access token for a user
+fn token_for(user_id: i64, username: &str) -> String {
+    AuthConfig::new(TEST_SECRET)
+        .issue_token(user_id, username)
+        .unwrap()
+}
+
+/// Helper to make requests as the test user
 async fn request(app: Router, method: &str, uri: &str, body: Option<serde_json::Value>) -> (StatusCode, String) {
+    let token = token_for(1, "tester");
+    request_as(app, method, uri, body, Some(&token)).await
+}
+
+/// Helper to make requests with an optional bearer token
+async fn request_as(
+    app: Router,
+    method: &str,
+    uri: &str,
+    body: Option<serde_json::Value>,
+    token: Option<&str>,
+) -> (StatusCode, String) {
+    let (status, _, body) = send(app, method, uri, body, token, &[]).await;
+    (status, body)
+}
+
+/// Helper to make requests as the test user with extra headers, returning the response headers
+async fn request_with_headers(
+    app: Router,
+    method: &str,
+    uri: &str,
+    body: Option<serde_json::Value>,
+    headers: &[(&str, &str)],
+) -> (StatusCode, HeaderMap, String) {
+    let token = token_for(1, "tester");
+    send(app, method, uri, body, Some(&token), headers).await
+}
+
+/// Helper to send a request and collect status, headers and body
+async fn send(
+    app: Router,
+    method: &str,
+    uri: &str,
+    body: Option<serde_json::Value>,
+    token: Option<&str>,
+    headers: &[(&str, &str)],
+) -> (StatusCode, HeaderMap, String) {
     let body = match body {
         Some(json) => Body::from(serde_json::to_string(&json).unwrap()),
         None => Body::empty(),
     };
 
-    let request = Request::builder()
+    let mut request = Request::builder()
         .method(method)
         .uri(uri)
-        .header("content-type", "application/json")
-        .body(body)
-        .unwrap();
+        .header("content-type", "application/json");
+
+    if let Some(token) = token {
+        request = request.header("authorization", format!("Bearer {}", token));
+    }
+
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+
+    let request = request.body(body).unwrap();
 
     let response = app.oneshot(request).await.unwrap();
     let status = response.status();
-    let body = axum::body::to_bytes(response.into_body(), 1024 * 1024)
-        .await
-        .unwrap();
-    let body = String::from_utf8(body.to_vec()).unwrap();
+    let headers = response.headers().clone();
+    let body = String::from_utf8(body_bytes(response).await.to_vec()).unwrap();
 
-    (status, body)
+    (status, headers, body)
 }
 
 // ============================================================
@@ -129,6 +231,167 @@ async fn test_create_task_whitespace_only_title() {
     assert_eq!(status, StatusCode::BAD_REQUEST);
 }
 
+// ============================================================
+// Error Response Tests
+// ============================================================
+
+/// Helper to post a raw body to `/tasks` as the test user
+async fn post_raw(app: Router, content_type: &str, body: &str) -> (StatusCode, HeaderMap, serde_json::Value) {
+    let request = Request::post("/tasks")
+        .header("content-type", content_type)
+        .header("authorization", format!("Bearer {}", token_for(1, "tester")))
+        .body(Body::from(body.to_string()))
+        .unwrap();
+
+    let response = app.oneshot(request).await.unwrap();
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = axum::body::to_bytes(response.into_body(), 1024 * 1024)
+        .await
+        .unwrap();
+
+    (status, headers, serde_json::from_slice(&body).unwrap())
+}
+
+/// `(field, code)` of every entry in `errors`
+fn field_errors(problem: &serde_json::Value) -> Vec<(String, String)> {
+    problem["errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|error| {
+            (
+                error["field"].as_str().unwrap().to_string(),
+                error["code"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_errors_are_problem_details() {
+    let app = create_app().await;
+
+    let (status, headers, body) =
+        request_with_headers(app, "GET", "/tasks/42?cascade=true", None, &[]).await;
+
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(headers["content-type"], "application/problem+json");
+    let problem: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(
+        problem,
+        json!({
+            "type": "about:blank",
+            "title": "Not Found",
+            "status": 404,
+            "detail": "Task 42 not found",
+            "instance": "/tasks/42"
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_create_reports_every_invalid_field() {
+    let app = create_app().await;
+
+    let (status, body) = request(
+        app,
+        "POST",
+        "/tasks",
+        Some(json!({
+            "title": " ",
+            "description": "x".repeat(2001),
+            "tags": ["ok", "", "y".repeat(51)]
+        })),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let problem: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(problem["status"], 400);
+    assert_eq!(problem["instance"], "/tasks");
+    assert_eq!(
+        field_errors(&problem),
+        [
+            ("title".to_string(), "required".to_string()),
+            ("description".to_string(), "too_long".to_string()),
+            ("tags[1]".to_string(), "required".to_string()),
+            ("tags[2]".to_string(), "too_long".to_string()),
+        ]
+    );
+    assert_eq!(problem["errors"][0]["message"], "Title is required");
+}
+
+#[tokio::test]
+async fn test_patch_reports_null_and_invalid_fields_together() {
+    let app = create_app().await;
+    let (_, body) = request(app.clone(), "POST", "/tasks", Some(json!({ "title": "Keep" }))).await;
+    let task: Task = serde_json::from_str(&body).unwrap();
+
+    let (status, body) = request(
+        app,
+        "PATCH",
+        &format!("/tasks/{}", task.id),
+        Some(json!({ "completed": null, "priority": null, "tags": [" "] })),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let problem: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(
+        field_errors(&problem),
+        [
+            ("completed".to_string(), "required".to_string()),
+            ("priority".to_string(), "required".to_string()),
+            ("tags[0]".to_string(), "required".to_string()),
+        ]
+    );
+    assert_eq!(problem["errors"][0]["message"], "completed cannot be null");
+}
+
+#[tokio::test]
+async fn test_json_rejections_are_problem_details() {
+    let app = create_app().await;
+
+    let (status, headers, problem) = post_raw(app.clone(), "application/json", "{\"title\":").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(headers["content-type"], "application/problem+json");
+    assert!(problem["detail"].as_str().unwrap().contains("JSON"), "{}", problem);
+    assert!(problem.get("errors").is_none());
+
+    let (status, _, problem) = post_raw(app.clone(), "text/plain", r#"{"title":"Plain"}"#).await;
+    assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    assert_eq!(problem["status"], 415);
+    assert_eq!(problem["instance"], "/tasks");
+
+    let (status, _, problem) = post_raw(app.clone(), "application/json", r#"{"title":5}"#).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(field_errors(&problem), [("title".to_string(), "invalid".to_string())]);
+    assert_eq!(
+        problem["errors"][0]["message"],
+        "invalid type: integer `5`, expected a string"
+    );
+
+    let (_, _, problem) = post_raw(app.clone(), "application/json", r#"{"tags":[]}"#).await;
+    assert_eq!(field_errors(&problem), [("title".to_string(), "required".to_string())]);
+
+    let (_, _, problem) = post_raw(app, "application/json", r#"{"title":"Ok","tags":["a",1]}"#).await;
+    assert_eq!(field_errors(&problem), [("tags[1]".to_string(), "invalid".to_string())]);
+}
+
+#[tokio::test]
+async fn test_unknown_patch_field_is_named() {
+    let app = create_app().await;
+    let (_, body) = request(app.clone(), "POST", "/tasks", Some(json!({ "title": "Keep" }))).await;
+    let task: Task = serde_json::from_str(&body).unwrap();
+
+    let (status, body) = request(app, "PATCH", &format!("/tasks/{}", task.id), Some(json!({ "titel": "Typo" }))).await;
+
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    let problem: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(field_errors(&problem), [("titel".to_string(), "unknown_field".to_string())]);
+}
+
 // ============================================================
 // Read Tests
 // ============================================================
@@ -142,8 +405,7 @@ async fn test_list_tasks_empty() {
     assert_eq!(status, StatusCode::OK);
 
     let tasks: Vec<Task> = serde_json::from_str(&body).unwrap();
-    // May be empty or have tasks from previous tests
-    assert!(tasks.len() >= 0);
+    assert!(tasks.is_empty());
 }
 
 #[tokio::test]
@@ -208,7 +470,7 @@ async fn test_update_task_title() {
     // Update title
     let (status, body) = request(
         app,
-        "PUT",
+        "PATCH",
         &format!("/tasks/{}", id),
         Some(json!({
             "title": "Updated title"
@@ -243,7 +505,7 @@ async fn test_update_task_complete() {
     // Mark as completed
     let (status, body) = request(
         app,
-        "PUT",
+        "PATCH",
         &format!("/tasks/{}", id),
         Some(json!({
             "completed": true
@@ -262,7 +524,7 @@ async fn test_update_task_not_found() {
 
     let (status, _body) = request(
         app,
-        "PUT",
+        "PATCH",
         "/tasks/999999",
         Some(json!({
             "title": "New title"
@@ -273,6 +535,163 @@ async fn test_update_task_not_found() {
     assert_eq!(status, StatusCode::NOT_FOUND);
 }
 
+#[tokio::test]
+async fn test_patch_null_clears_fields() {
+    let app = create_app().await;
+    let id = create_task_with(
+        &app,
+        json!({ "title": "Full", "description": "Text", "due_at": "2030-01-01T00:00:00Z", "tags": ["a"] }),
+    )
+    .await;
+
+    let (status, body) = request(
+        app,
+        "PATCH",
+        &format!("/tasks/{}", id),
+        Some(json!({ "description": null, "due_at": null, "tags": null })),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+    let task: Task = serde_json::from_str(&body).unwrap();
+    assert_eq!(task.title, "Full");
+    assert_eq!(task.description, None);
+    assert_eq!(task.due_at, None);
+    assert!(task.tags.is_empty());
+}
+
+#[tokio::test]
+async fn test_patch_rejects_null_required_and_unknown_fields() {
+    let app = create_app().await;
+    let id = create_task_with(&app, json!({ "title": "Keep" })).await;
+    let uri = format!("/tasks/{}", id);
+
+    let (status, _) = request(app.clone(), "PATCH", &uri, Some(json!({ "title": null }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = request(app, "PATCH", &uri, Some(json!({ "titel": "Typo" }))).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn test_failed_patch_changes_nothing() {
+    let app = create_app().await;
+    let id = create_task_with(&app, json!({ "title": "Before" })).await;
+
+    // The title is valid but the parent is not: nothing must be written
+    let (status, _) = request(
+        app.clone(),
+        "PATCH",
+        &format!("/tasks/{}", id),
+        Some(json!({ "title": "After", "parent_id": 9999 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (_, body) = request(app, "GET", &format!("/tasks/{}", id), None).await;
+    let task: Task = serde_json::from_str(&body).unwrap();
+    assert_eq!(task.title, "Before");
+}
+
+#[tokio::test]
+async fn test_put_replaces_whole_task() {
+    let app = create_app().await;
+    let id = create_task_with(
+        &app,
+        json!({ "title": "Old", "description": "Text", "priority": "urgent", "tags": ["a"] }),
+    )
+    .await;
+
+    let (status, body) = request(
+        app.clone(),
+        "PUT",
+        &format!("/tasks/{}", id),
+        Some(json!({ "title": "New", "completed": true, "tags": ["b"] })),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+    let task: Task = serde_json::from_str(&body).unwrap();
+    assert_eq!(task.title, "New");
+    assert!(task.completed);
+    assert_eq!(task.description, None);
+    assert_eq!(task.priority, Priority::Medium);
+    assert_eq!(task.tags, ["b"]);
+
+    // The title is required in a replacement
+    let (status, _) = request(app, "PUT", &format!("/tasks/{}", id), Some(json!({ "completed": false }))).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_concurrent_patches_keep_every_field() {
+    let (app, _db) = create_file_app("patch-fields").await;
+    let id = create_task_with(&app, json!({ "title": "Shared" })).await;
+    let uri = format!("/tasks/{}", id);
+
+    let patches = [
+        json!({ "title": "Renamed" }),
+        json!({ "description": "Edited" }),
+        json!({ "completed": true }),
+        json!({ "priority": "urgent" }),
+        json!({ "tags": ["raced"] }),
+    ];
+    let handles: Vec<_> = patches
+        .into_iter()
+        .map(|patch| {
+            let (app, uri) = (app.clone(), uri.clone());
+            tokio::spawn(async move { request(app, "PATCH", &uri, Some(patch)).await })
+        })
+        .collect();
+    for handle in handles {
+        assert_eq!(handle.await.unwrap().0, StatusCode::OK);
+    }
+
+    let (_, body) = request(app, "GET", &uri, None).await;
+    let task: Task = serde_json::from_str(&body).unwrap();
+    assert_eq!(task.title, "Renamed");
+    assert_eq!(task.description.as_deref(), Some("Edited"));
+    assert!(task.completed);
+    assert_eq!(task.priority, Priority::Urgent);
+    assert_eq!(task.tags, ["raced"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_concurrent_updates_are_serialized() {
+    let (app, _db) = create_file_app("put-patch").await;
+    let id = create_task_with(&app, json!({ "title": "Start" })).await;
+    let uri = format!("/tasks/{}", id);
+
+    let handles: Vec<_> = (0..10)
+        .map(|i| {
+            let (method, body) = if i % 2 == 0 {
+                ("PUT", json!({ "title": format!("Put {}", i), "priority": "low" }))
+            } else {
+                ("PATCH", json!({ "title": format!("Patch {}", i), "priority": "high" }))
+            };
+            let (app, uri) = (app.clone(), uri.clone());
+            tokio::spawn(async move { request(app, method, &uri, Some(body)).await })
+        })
+        .collect();
+    for handle in handles {
+        assert_eq!(handle.await.unwrap().0, StatusCode::OK);
+    }
+
+    // Each update saw the result of the previous one: the history is a chain
+    let (_, body) = request(app.clone(), "GET", &format!("{}/history", uri), None).await;
+    let events: Vec<serde_json::Value> = serde_json::from_str(&body).unwrap();
+    let titles: Vec<&serde_json::Value> = events[1..].iter().map(|e| &e["changes"]["title"]).collect();
+    assert_eq!(titles.len(), 10);
+    assert_eq!(titles[0]["before"], "Start");
+    for pair in titles.windows(2) {
+        assert_eq!(pair[0]["after"], pair[1]["before"]);
+    }
+
+    let (_, body) = request(app, "GET", &uri, None).await;
+    let task: Task = serde_json::from_str(&body).unwrap();
+    assert_eq!(json!(task.title), titles[9]["after"]);
+}
+
 // ============================================================
 // Delete Tests
 // ============================================================
@@ -315,90 +734,1620 @@ async fn test_delete_task_not_found() {
 }
 
 // ============================================================
-// Filter Tests
+// Bulk Tests
 // ============================================================
 
+/// Helper to run a bulk request and parse its response
+async fn bulk(app: &Router, body: serde_json::Value) -> (StatusCode, serde_json::Value) {
+    let (status, body) = request(app.clone(), "POST", "/tasks/bulk", Some(body)).await;
+    (status, serde_json::from_str(&body).unwrap())
+}
+
+/// Helper to list the status of each bulk result
+fn statuses(response: &serde_json::Value) -> Vec<u64> {
+    response["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["status"].as_u64().unwrap())
+        .collect()
+}
+
 #[tokio::test]
-async fn test_filter_completed_tasks() {
+async fn test_bulk_mixed_operations() {
     let app = create_app().await;
-
-    // Create task
-    let (_, body) = request(
-        app.clone(),
-        "POST",
-        "/tasks",
-        Some(json!({
-            "title": "Completed task for filter"
-        })),
+    let keep = create_task_with(&app, json!({ "title": "Keep" })).await;
+    let drop = create_task_with(&app, json!({ "title": "Drop" })).await;
+
+    let (status, response) = bulk(
+        &app,
+        json!({ "operations": [
+            { "op": "create", "task": { "title": "New A", "tags": ["bulk"] } },
+            { "op": "create", "task": { "title": "New B" } },
+            { "op": "update", "id": keep, "patch": { "completed": true } },
+            { "op": "delete", "id": drop }
+        ]}),
     )
     .await;
 
-    let task: Task = serde_json::from_str(&body).unwrap();
-    let id = task.id;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response["committed"], true);
+    assert_eq!(response["succeeded"], 4);
+    assert_eq!(statuses(&response), [201, 201, 200, 204]);
+    assert_eq!(response["results"][0]["task"]["tags"], json!(["bulk"]));
+    assert_eq!(response["results"][2]["task"]["completed"], true);
 
-    // Mark as completed
-    let _ = request(
-        app.clone(),
-        "PUT",
-        &format!("/tasks/{}", id),
-        Some(json!({
-            "completed": true
-        })),
+    assert_eq!(list_titles(&app, "/tasks").await, ["New B", "New A", "Keep"]);
+}
+
+#[tokio::test]
+async fn test_bulk_atomic_rolls_back_on_failure() {
+    let app = create_app().await;
+
+    let (status, response) = bulk(
+        &app,
+        json!({ "mode": "atomic", "operations": [
+            { "op": "create", "task": { "title": "Valid" } },
+            { "op": "create", "task": { "title": "   " } },
+            { "op": "create", "task": { "title": "Never run" } }
+        ]}),
     )
     .await;
 
-    // Filter completed
-    let (status, body) = request(app, "GET", "/tasks?completed=true", None).await;
-
-    assert_eq!(status, StatusCode::OK);
-    let tasks: Vec<Task> = serde_json::from_str(&body).unwrap();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(response["committed"], false);
+    assert_eq!(response["succeeded"], 0);
+    assert_eq!(statuses(&response), [424, 400, 424]);
+    assert_eq!(response["results"][1]["error"], "Title is required");
 
-    // All must be completed
-    for task in &tasks {
-        assert!(task.completed);
-    }
+    assert!(list_titles(&app, "/tasks").await.is_empty());
 }
 
 #[tokio::test]
-async fn test_filter_tasks_limit() {
+async fn test_bulk_best_effort_skips_failures() {
     let app = create_app().await;
 
-    // Create several tasks
-    for i in 0..5 {
-        let _ = request(
-            app.clone(),
-            "POST",
-            "/tasks",
-            Some(json!({
-                "title": format!("Limit task {}", i)
-            })),
-        )
-        .await;
-    }
-
-    // Filter with limit
-    let (status, body) = request(app, "GET", "/tasks?limit=3", None).await;
+    let (status, response) = bulk(
+        &app,
+        json!({ "mode": "best_effort", "operations": [
+            { "op": "create", "task": { "title": "First" } },
+            { "op": "update", "id": 9999, "patch": { "title": "Ghost" } },
+            { "op": "create", "task": { "title": "x".repeat(201) } },
+            { "op": "create", "task": { "title": "Second" } }
+        ]}),
+    )
+    .await;
 
     assert_eq!(status, StatusCode::OK);
-    let tasks: Vec<Task> = serde_json::from_str(&body).unwrap();
+    assert_eq!(response["committed"], true);
+    assert_eq!(response["succeeded"], 2);
+    assert_eq!(response["failed"], 2);
+    assert_eq!(statuses(&response), [201, 404, 400, 201]);
 
-    assert!(tasks.len() <= 3);
+    assert_eq!(list_titles(&app, "/tasks").await, ["Second", "First"]);
+}
+
+#[tokio::test]
+async fn test_bulk_rejects_empty_and_unknown_operations() {
+    let app = create_app().await;
+
+    let (status, _) = request(app.clone(), "POST", "/tasks/bulk", Some(json!({ "operations": [] }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = request(
+        app,
+        "POST",
+        "/tasks/bulk",
+        Some(json!({ "operations": [{ "op": "archive", "id": 1 }] })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
 }
 
 // ============================================================
-// Statistics Tests
+// Export and Import Tests
 // ============================================================
 
+/// Helper to upload an import file as the test user
+async fn import(app: &Router, uri: &str, content_type: &str, file: &str) -> (StatusCode, serde_json::Value) {
+    let request = Request::builder()
+        .method("POST")
+        .uri(uri)
+        .header("content-type", content_type)
+        .header("authorization", format!("Bearer {}", token_for(1, "tester")))
+        .body(Body::from(file.to_string()))
+        .unwrap();
+
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), 1024 * 1024)
+        .await
+        .unwrap();
+
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+/// Helper to create a small tree: "Parent" (tagged, due) with a completed "Child"
+async fn create_export_fixture(app: &Router) -> (i64, i64) {
+    let parent = create_task_with(
+        app,
+        json!({
+            "title": "Parent",
+            "description": "Has, commas",
+            "due_at": "2030-01-01T09:00:00Z",
+            "priority": "high",
+            "tags": ["work", "q1"]
+        }),
+    )
+    .await;
+    let child = create_task_with(app, json!({ "title": "Child", "parent_id": parent })).await;
+    request(app.clone(), "PATCH", &format!("/tasks/{}", child), Some(json!({ "completed": true }))).await;
+
+    (parent, child)
+}
+
 #[tokio::test]
-async fn test_statistics() {
+async fn test_export_formats() {
     let app = create_app().await;
+    let (parent, child) = create_export_fixture(&app).await;
 
-    let (status, body) = request(app, "GET", "/tasks/stats", None).await;
-
+    let (status, headers, body) = request_with_headers(app.clone(), "GET", "/tasks/export", None, &[]).await;
     assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers["content-type"], "application/json");
+    assert_eq!(headers["content-disposition"], "attachment; filename=\"tasks.json\"");
+    let tasks: Vec<Task> = serde_json::from_str(&body).unwrap();
+    assert_eq!(tasks.iter().map(|t| t.id).collect::<Vec<_>>(), [child, parent]);
+
+    let (status, headers, body) =
+        request_with_headers(app.clone(), "GET", "/tasks/export?format=ndjson", None, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers["content-type"], "application/x-ndjson");
+    let lines: Vec<Task> = body.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[1].tags, ["q1", "work"]);
+
+    let (status, headers, body) = request_with_headers(app, "GET", "/tasks/export?format=csv", None, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers["content-type"], "text/csv; charset=utf-8");
+    let lines: Vec<&str> = body.lines().collect();
+    assert_eq!(
+        lines[0],
+        "id,title,description,completed,due_at,priority,parent_id,tags,created_at,updated_at"
+    );
+    assert!(lines[1].starts_with(&format!("{},Child,,true,,medium,{},,", child, parent)));
+    assert!(lines[2].starts_with(&format!(
+        "{},Parent,\"Has, commas\",false,2030-01-01T09:00:00Z,high,,q1;work,",
+        parent
+    )));
+}
+
+#[tokio::test]
+async fn test_export_empty() {
+    let app = create_app().await;
+
+    let (_, _, body) = request_with_headers(app.clone(), "GET", "/tasks/export", None, &[]).await;
+    assert_eq!(serde_json::from_str::<Vec<Task>>(&body).unwrap().len(), 0);
+
+    let (_, _, body) = request_with_headers(app, "GET", "/tasks/export?format=csv", None, &[]).await;
+    assert_eq!(body.lines().count(), 1);
+}
+
+#[tokio::test]
+async fn test_export_honours_filters() {
+    let app = create_app().await;
+    create_export_fixture(&app).await;
+    create_task_with(&app, json!({ "title": "Other", "tags": ["home"] })).await;
+
+    let (_, _, body) = request_with_headers(app.clone(), "GET", "/tasks/export?tag=work", None, &[]).await;
+    let tasks: Vec<Task> = serde_json::from_str(&body).unwrap();
+    assert_eq!(tasks.iter().map(|t| t.title.as_str()).collect::<Vec<_>>(), ["Parent"]);
+
+    let (_, _, body) =
+        request_with_headers(app.clone(), "GET", "/tasks/export?completed=false&sort=priority&limit=1", None, &[])
+            .await;
+    let tasks: Vec<Task> = serde_json::from_str(&body).unwrap();
+    assert_eq!(tasks.iter().map(|t| t.title.as_str()).collect::<Vec<_>>(), ["Parent"]);
+
+    let (status, _) = request(app.clone(), "GET", "/tasks/export?cursor=", None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    for query in ["limit=0", "limit=-1", "offset=-1"] {
+        let uri = format!("/tasks/export?{}", query);
+        let (status, body) = request(app.clone(), "GET", &uri, None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}: {}", query, body);
+    }
+
+    let (status, _) = request(app, "GET", "/tasks/export?format=xml", None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_export_import_roundtrip() {
+    for format in ["json", "ndjson", "csv"] {
+        let source = create_app().await;
+        create_export_fixture(&source).await;
+        let (_, headers, file) =
+            request_with_headers(source, "GET", &format!("/tasks/export?format={}", format), None, &[]).await;
+
+        // Another database, with a task already taking id 1
+        let target = create_app().await;
+        create_task_with(&target, json!({ "title": "Existing" })).await;
+
+        let content_type = headers["content-type"].to_str().unwrap();
+        let (status, report) = import(&target, "/tasks/import", content_type, &file).await;
+        assert_eq!(status, StatusCode::CREATED, "{}: {}", format, report);
+        assert_eq!(report["imported"], 2);
+
+        let (_, body) = request(target.clone(), "GET", "/tasks?sort=priority", None).await;
+        let tasks: Vec<Task> = serde_json::from_str(&body).unwrap();
+        let parent = tasks.iter().find(|t| t.title == "Parent").unwrap();
+        let child = tasks.iter().find(|t| t.title == "Child").unwrap();
+
+        assert_eq!(parent.description.as_deref(), Some("Has, commas"), "{}", format);
+        assert_eq!(parent.priority, Priority::High);
+        assert_eq!(parent.tags, ["q1", "work"]);
+        assert!(parent.due_at.is_some());
+        assert!(child.completed);
+        assert_eq!(child.parent_id, Some(parent.id));
+
+        // Imported in one write, not created and then updated
+        assert_eq!(child.version, 1, "{}", format);
+        let events = history(&target, child.id).await;
+        assert_eq!(events.len(), 1, "{}", format);
+        assert_eq!(events[0]["kind"], "created");
+    }
+}
+
+#[tokio::test]
+async fn test_import_reports_every_invalid_row() {
+    let app = create_app().await;
+    let file = "id,title,parent_id,tags,priority\n\
+                1,Valid,,,\n\
+                2,,,,\n\
+                3,Orphan,99,,\n\
+                4,Bad priority,,,extreme\n\
+                1,Duplicate,,,\n";
+
+    let (status, report) = import(&app, "/tasks/import?format=csv", "text/plain", file).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(report["imported"], 0);
+    let rows: Vec<u64> = report["errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["row"].as_u64().unwrap())
+        .collect();
+    assert_eq!(rows, [3, 4, 5, 6]);
+    assert_eq!(report["errors"][0]["error"], "Title is required");
+    assert_eq!(report["errors"][1]["error"], "Parent task 99 is missing or invalid");
+
+    // Nothing was imported
+    assert!(list_titles(&app, "/tasks").await.is_empty());
+}
+
+#[tokio::test]
+async fn test_import_rejects_cycles_and_bad_files() {
+    let app = create_app().await;
+    let file = "{\"id\": 1, \"title\": \"A\", \"parent_id\": 2}\n\n{\"id\": 2, \"title\": \"B\", \"parent_id\": 1}\n";
+
+    let (status, report) = import(&app, "/tasks/import", "application/x-ndjson", file).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(report["errors"][0]["row"], 1);
+    assert_eq!(report["errors"][1]["row"], 3);
+
+    let (status, _) = import(&app, "/tasks/import", "application/json", "{\"title\": \"Not an array\"}").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = import(&app, "/tasks/import", "application/json", "[]").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+// ============================================================
+// Filter Tests
+// ============================================================
+
+#[tokio::test]
+async fn test_filter_completed_tasks() {
+    let app = create_app().await;
+
+    // Create task
+    let (_, body) = request(
+        app.clone(),
+        "POST",
+        "/tasks",
+        Some(json!({
+            "title": "Completed task for filter"
+        })),
+    )
+    .await;
+
+    let task: Task = serde_json::from_str(&body).unwrap();
+    let id = task.id;
+
+    // Mark as completed
+    let _ = request(
+        app.clone(),
+        "PATCH",
+        &format!("/tasks/{}", id),
+        Some(json!({
+            "completed": true
+        })),
+    )
+    .await;
+
+    // Filter completed
+    let (status, body) = request(app, "GET", "/tasks?completed=true", None).await;
+
+    assert_eq!(status, StatusCode::OK);
+    let tasks: Vec<Task> = serde_json::from_str(&body).unwrap();
+
+    // All must be completed
+    for task in &tasks {
+        assert!(task.completed);
+    }
+}
+
+#[tokio::test]
+async fn test_filter_tasks_limit() {
+    let app = create_app().await;
+
+    // Create several tasks
+    for i in 0..5 {
+        let _ = request(
+            app.clone(),
+            "POST",
+            "/tasks",
+            Some(json!({
+                "title": format!("Limit task {}", i)
+            })),
+        )
+        .await;
+    }
+
+    // Filter with limit
+    let (status, body) = request(app, "GET", "/tasks?limit=3", None).await;
+
+    assert_eq!(status, StatusCode::OK);
+    let tasks: Vec<Task> = serde_json::from_str(&body).unwrap();
+
+    assert!(tasks.len() <= 3);
+}
+
+#[tokio::test]
+async fn test_cursor_pagination() {
+    let app = create_app().await;
+
+    for i in 0..5 {
+        let _ = request(
+            app.clone(),
+            "POST",
+            "/tasks",
+            Some(json!({ "title": format!("Page task {}", i) })),
+        )
+        .await;
+    }
+
+    let mut titles = Vec::new();
+    let mut cursor = String::new();
+
+    loop {
+        let (status, body) = request(app.clone(), "GET", &format!("/tasks?limit=2&cursor={}", cursor), None).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let page: serde_json::Value = serde_json::from_str(&body).unwrap();
+        for item in page["items"].as_array().unwrap() {
+            titles.push(item["title"].as_str().unwrap().to_string());
+        }
+
+        if !page["has_more"].as_bool().unwrap() {
+            assert!(page["next_cursor"].is_null());
+            break;
+        }
+        cursor = page["next_cursor"].as_str().unwrap().to_string();
+    }
+
+    assert_eq!(
+        titles,
+        ["Page task 4", "Page task 3", "Page task 2", "Page task 1", "Page task 0"]
+    );
+}
+
+#[tokio::test]
+async fn test_cursor_pagination_stable_under_inserts() {
+    let app = create_app().await;
+
+    for i in 0..4 {
+        let _ = request(
+            app.clone(),
+            "POST",
+            "/tasks",
+            Some(json!({ "title": format!("Stable task {}", i) })),
+        )
+        .await;
+    }
+
+    let (_, body) = request(app.clone(), "GET", "/tasks?limit=2&cursor=", None).await;
+    let page: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let cursor = page["next_cursor"].as_str().unwrap().to_string();
+
+    // A new task does not shift the following page
+    let _ = request(
+        app.clone(),
+        "POST",
+        "/tasks",
+        Some(json!({ "title": "Inserted meanwhile" })),
+    )
+    .await;
+
+    let (_, body) = request(app, "GET", &format!("/tasks?limit=2&cursor={}", cursor), None).await;
+    let page: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let titles: Vec<&str> = page["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["title"].as_str().unwrap())
+        .collect();
+
+    assert_eq!(titles, ["Stable task 1", "Stable task 0"]);
+    assert_eq!(page["has_more"], false);
+}
+
+#[tokio::test]
+async fn test_invalid_cursor() {
+    let app = create_app().await;
+
+    let (status, _body) = request(app, "GET", "/tasks?cursor=not-a-cursor", None).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_list_rejects_invalid_limit() {
+    let app = create_app().await;
+    create_task_with(&app, json!({ "title": "Only task" })).await;
+
+    for query in [
+        "limit=0&cursor=",
+        "limit=-1&cursor=",
+        "limit=101&cursor=",
+        "limit=9223372036854775807&cursor=",
+    ] {
+        let uri = format!("/tasks?{}", query);
+        let (status, body) = request(app.clone(), "GET", &uri, None).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}: {}", query, body);
+    }
+
+    let (status, _body) = request(app, "GET", "/tasks?limit=100&cursor=", None).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_offset_pagination_accepts_any_limit() {
+    let app = create_app().await;
+    for i in 0..102 {
+        create_task_with(&app, json!({ "title": format!("Task {}", i) })).await;
+    }
+
+    for (query, expected) in [
+        ("", 100),
+        ("limit=0", 0),
+        ("limit=-1", 102),
+        ("limit=101", 101),
+        ("limit=9223372036854775807", 102),
+        ("limit=5&offset=-1", 5),
+        ("offset=100", 2),
+    ] {
+        let uri = format!("/tasks?{}", query);
+        let (status, body) = request(app.clone(), "GET", &uri, None).await;
+
+        assert_eq!(status, StatusCode::OK, "{}: {}", query, body);
+        let tasks: Vec<Task> = serde_json::from_str(&body).unwrap();
+        assert_eq!(tasks.len(), expected, "{}", query);
+    }
+}
+
+// ============================================================
+// Subtask Tests
+// ============================================================
+
+#[tokio::test]
+async fn test_create_and_list_subtasks() {
+    let app = create_app().await;
+    let parent = create_task_with(&app, json!({ "title": "Parent" })).await;
+    let child = create_task_with(&app, json!({ "title": "Child", "parent_id": parent })).await;
+    create_task_with(&app, json!({ "title": "Grandchild", "parent_id": child })).await;
+
+    let (status, body) = request(app, "GET", &format!("/tasks/{}/subtasks", parent), None).await;
+
+    assert_eq!(status, StatusCode::OK);
+    let subtasks: Vec<Task> = serde_json::from_str(&body).unwrap();
+    assert_eq!(subtasks.len(), 1);
+    assert_eq!(subtasks[0].title, "Child");
+    assert_eq!(subtasks[0].parent_id, Some(parent));
+}
+
+#[tokio::test]
+async fn test_create_subtask_unknown_parent() {
+    let app = create_app().await;
+
+    let (status, _body) = request(
+        app,
+        "POST",
+        "/tasks",
+        Some(json!({ "title": "Orphan", "parent_id": 999999 })),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_task_tree() {
+    let app = create_app().await;
+    let root = create_task_with(&app, json!({ "title": "Root" })).await;
+    let a = create_task_with(&app, json!({ "title": "A", "parent_id": root })).await;
+    create_task_with(&app, json!({ "title": "B", "parent_id": root })).await;
+    create_task_with(&app, json!({ "title": "A1", "parent_id": a })).await;
+
+    let (status, body) = request(app, "GET", &format!("/tasks/{}/tree", root), None).await;
+
+    assert_eq!(status, StatusCode::OK);
+    let tree: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(tree["title"], "Root");
+    assert_eq!(tree["children"][0]["title"], "A");
+    assert_eq!(tree["children"][0]["children"][0]["title"], "A1");
+    assert_eq!(tree["children"][1]["title"], "B");
+    assert_eq!(tree["children"][1]["children"], json!([]));
+}
+
+#[tokio::test]
+async fn test_move_under_descendant_is_rejected() {
+    let app = create_app().await;
+    let root = create_task_with(&app, json!({ "title": "Root" })).await;
+    let child = create_task_with(&app, json!({ "title": "Child", "parent_id": root })).await;
+    let grandchild = create_task_with(&app, json!({ "title": "Grandchild", "parent_id": child })).await;
+
+    // Under itself or under its own grandchild
+    for parent_id in [root, grandchild] {
+        let (status, _) = request(
+            app.clone(),
+            "PATCH",
+            &format!("/tasks/{}", root),
+            Some(json!({ "parent_id": parent_id })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    // Moving within the tree without a cycle is fine
+    let (status, body) = request(
+        app,
+        "PATCH",
+        &format!("/tasks/{}", grandchild),
+        Some(json!({ "parent_id": root })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let task: Task = serde_json::from_str(&body).unwrap();
+    assert_eq!(task.parent_id, Some(root));
+}
+
+#[tokio::test]
+async fn test_complete_parent_with_cascade() {
+    let app = create_app().await;
+    let root = create_task_with(&app, json!({ "title": "Root" })).await;
+    let child = create_task_with(&app, json!({ "title": "Child", "parent_id": root })).await;
+    let grandchild = create_task_with(&app, json!({ "title": "Grandchild", "parent_id": child })).await;
+
+    // Without cascade only the parent changes
+    let _ = request(app.clone(), "PATCH", &format!("/tasks/{}", child), Some(json!({ "completed": true }))).await;
+    let (_, body) = request(app.clone(), "GET", &format!("/tasks/{}", grandchild), None).await;
+    let task: Task = serde_json::from_str(&body).unwrap();
+    assert!(!task.completed);
+
+    let _ = request(
+        app.clone(),
+        "PATCH",
+        &format!("/tasks/{}?cascade=true", root),
+        Some(json!({ "completed": true })),
+    )
+    .await;
+
+    for id in [child, grandchild] {
+        let (_, body) = request(app.clone(), "GET", &format!("/tasks/{}", id), None).await;
+        let task: Task = serde_json::from_str(&body).unwrap();
+        assert!(task.completed);
+    }
+}
+
+#[tokio::test]
+async fn test_delete_parent_deletes_subtasks() {
+    let app = create_app().await;
+    let root = create_task_with(&app, json!({ "title": "Root" })).await;
+    let child = create_task_with(&app, json!({ "title": "Child", "parent_id": root })).await;
+
+    let _ = request(app.clone(), "DELETE", &format!("/tasks/{}", root), None).await;
+
+    let (status, _) = request(app, "GET", &format!("/tasks/{}", child), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+// ============================================================
+// Tag Tests
+// ============================================================
+
+/// Helper to create a task with tags, returning its ID
+async fn create_tagged_task(app: &Router, title: &str, tags: &[&str]) -> i64 {
+    let (status, body) = request(
+        app.clone(),
+        "POST",
+        "/tasks",
+        Some(json!({ "title": title, "tags": tags })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let task: Task = serde_json::from_str(&body).unwrap();
+    task.id
+}
+
+/// Helper to list task titles for a query string
+async fn list_titles(app: &Router, uri: &str) -> Vec<String> {
+    let (status, body) = request(app.clone(), "GET", uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let tasks: Vec<Task> = serde_json::from_str(&body).unwrap();
+    tasks.into_iter().map(|task| task.title).collect()
+}
+
+#[tokio::test]
+async fn test_create_task_with_tags() {
+    let app = create_app().await;
+
+    let (status, body) = request(
+        app,
+        "POST",
+        "/tasks",
+        Some(json!({ "title": "Tagged", "tags": ["Urgent", "backend", "urgent "] })),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::CREATED);
+    let task: Task = serde_json::from_str(&body).unwrap();
+    assert_eq!(task.tags, ["backend", "urgent"]);
+}
+
+#[tokio::test]
+async fn test_add_and_remove_tags() {
+    let app = create_app().await;
+    let id = create_tagged_task(&app, "Tag me", &[]).await;
+    let uri = format!("/tasks/{}/tags", id);
+
+    let (status, body) = request(app.clone(), "POST", &uri, Some(json!({ "tags": ["ops", "docs"] }))).await;
+
+    assert_eq!(status, StatusCode::OK);
+    let task: Task = serde_json::from_str(&body).unwrap();
+    assert_eq!(task.tags, ["docs", "ops"]);
+
+    let (status, body) = request(app.clone(), "DELETE", &uri, Some(json!({ "tags": ["OPS", "missing"] }))).await;
+
+    assert_eq!(status, StatusCode::OK);
+    let task: Task = serde_json::from_str(&body).unwrap();
+    assert_eq!(task.tags, ["docs"]);
+}
+
+#[tokio::test]
+async fn test_add_tags_invalid_name() {
+    let app = create_app().await;
+    let id = create_tagged_task(&app, "Bad tags", &[]).await;
+
+    let (status, _body) = request(
+        app,
+        "POST",
+        &format!("/tasks/{}/tags", id),
+        Some(json!({ "tags": ["  "] })),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_add_tags_task_not_found() {
+    let app = create_app().await;
+
+    let (status, _body) = request(app, "POST", "/tasks/999999/tags", Some(json!({ "tags": ["x"] }))).await;
+
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_filter_by_tags_any_and_all() {
+    let app = create_app().await;
+    create_tagged_task(&app, "Both", &["a", "b"]).await;
+    create_tagged_task(&app, "Only a", &["a"]).await;
+    create_tagged_task(&app, "Only b", &["b"]).await;
+    create_tagged_task(&app, "None", &[]).await;
+
+    assert_eq!(list_titles(&app, "/tasks?tag=a").await, ["Only a", "Both"]);
+    assert_eq!(
+        list_titles(&app, "/tasks?tag=a&tag=b").await,
+        ["Only b", "Only a", "Both"]
+    );
+    assert_eq!(
+        list_titles(&app, "/tasks?tag=a&tag=B&tag_match=all").await,
+        ["Both"]
+    );
+}
+
+#[tokio::test]
+async fn test_list_tags_with_counts() {
+    let app = create_app().await;
+    create_tagged_task(&app, "One", &["backend", "urgent"]).await;
+    create_tagged_task(&app, "Two", &["backend"]).await;
+    let deleted = create_tagged_task(&app, "Three", &["gone"]).await;
+    let _ = request(app.clone(), "DELETE", &format!("/tasks/{}", deleted), None).await;
+
+    let (status, body) = request(app, "GET", "/tags", None).await;
+
+    assert_eq!(status, StatusCode::OK);
+    let tags: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(
+        tags,
+        json!([
+            { "name": "backend", "count": 2 },
+            { "name": "urgent", "count": 1 }
+        ])
+    );
+}
+
+// ============================================================
+// Due Date and Priority Tests
+// ============================================================
+
+/// Helper to create a task from a JSON body, returning its ID
+async fn create_task_with(app: &Router, body: serde_json::Value) -> i64 {
+    let (status, body) = request(app.clone(), "POST", "/tasks", Some(body)).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let task: Task = serde_json::from_str(&body).unwrap();
+    task.id
+}
+
+#[tokio::test]
+async fn test_create_task_with_due_date_and_priority() {
+    let app = create_app().await;
+
+    let (status, body) = request(
+        app,
+        "POST",
+        "/tasks",
+        Some(json!({
+            "title": "Ship release",
+            "due_at": "2030-05-01T12:00:00+02:00",
+            "priority": "urgent"
+        })),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::CREATED);
+    let task: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(task["due_at"], "2030-05-01T10:00:00Z");
+    assert_eq!(task["priority"], "urgent");
+}
+
+#[tokio::test]
+async fn test_default_priority_is_medium() {
+    let app = create_app().await;
+
+    let (_, body) = request(app, "POST", "/tasks", Some(json!({ "title": "Plain" }))).await;
+
+    let task: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(task["priority"], "medium");
+    assert!(task["due_at"].is_null());
+}
+
+#[tokio::test]
+async fn test_invalid_priority_and_due_date() {
+    let app = create_app().await;
+
+    let (status, _) = request(
+        app.clone(),
+        "POST",
+        "/tasks",
+        Some(json!({ "title": "Bad", "priority": "critical" })),
+    )
+    .await;
+    assert!(status.is_client_error());
+
+    let (status, _) = request(
+        app,
+        "POST",
+        "/tasks",
+        Some(json!({ "title": "Bad", "due_at": "next tuesday" })),
+    )
+    .await;
+    assert!(status.is_client_error());
+}
+
+#[tokio::test]
+async fn test_update_due_date_and_priority() {
+    let app = create_app().await;
+    let id = create_task_with(&app, json!({ "title": "Reschedule me" })).await;
+
+    let (status, body) = request(
+        app,
+        "PATCH",
+        &format!("/tasks/{}", id),
+        Some(json!({ "due_at": "2031-01-01T00:00:00Z", "priority": "low" })),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+    let task: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(task["due_at"], "2031-01-01T00:00:00Z");
+    assert_eq!(task["priority"], "low");
+}
+
+#[tokio::test]
+async fn test_overdue_filter() {
+    let app = create_app().await;
+    create_task_with(&app, json!({ "title": "Late", "due_at": "2000-01-01T00:00:00Z" })).await;
+    create_task_with(&app, json!({ "title": "Future", "due_at": "2999-01-01T00:00:00Z" })).await;
+    create_task_with(&app, json!({ "title": "No deadline" })).await;
+    let done = create_task_with(&app, json!({ "title": "Late but done", "due_at": "2000-01-01T00:00:00Z" })).await;
+    let _ = request(app.clone(), "PATCH", &format!("/tasks/{}", done), Some(json!({ "completed": true }))).await;
+
+    assert_eq!(list_titles(&app, "/tasks?overdue=true").await, ["Late"]);
+}
+
+#[tokio::test]
+async fn test_sort_by_due_date_and_priority() {
+    let app = create_app().await;
+    create_task_with(&app, json!({ "title": "Later", "due_at": "2030-06-01T00:00:00Z", "priority": "low" })).await;
+    create_task_with(&app, json!({ "title": "Undated", "priority": "urgent" })).await;
+    create_task_with(&app, json!({ "title": "Sooner", "due_at": "2030-01-01T00:00:00Z", "priority": "high" })).await;
+
+    assert_eq!(
+        list_titles(&app, "/tasks?sort=due_at").await,
+        ["Sooner", "Later", "Undated"]
+    );
+    assert_eq!(
+        list_titles(&app, "/tasks?sort=priority").await,
+        ["Undated", "Sooner", "Later"]
+    );
+}
+
+#[tokio::test]
+async fn test_cursor_pagination_with_sort() {
+    let app = create_app().await;
+    for (title, due_at, priority) in [
+        ("A", Some("2030-01-03T00:00:00Z"), "low"),
+        ("B", None, "high"),
+        ("C", Some("2030-01-01T00:00:00Z"), "high"),
+        ("D", Some("2030-01-01T00:00:00Z"), "urgent"),
+        ("E", None, "low"),
+    ] {
+        create_task_with(&app, json!({ "title": title, "due_at": due_at, "priority": priority })).await;
+    }
+
+    for (sort, expected) in [
+        ("due_at", ["C", "D", "A", "B", "E"]),
+        ("priority", ["D", "C", "B", "E", "A"]),
+    ] {
+        let mut titles = Vec::new();
+        let mut cursor = String::new();
+
+        loop {
+            let uri = format!("/tasks?sort={}&limit=2&cursor={}", sort, cursor);
+            let (status, body) = request(app.clone(), "GET", &uri, None).await;
+            assert_eq!(status, StatusCode::OK);
+
+            let page: serde_json::Value = serde_json::from_str(&body).unwrap();
+            for item in page["items"].as_array().unwrap() {
+                titles.push(item["title"].as_str().unwrap().to_string());
+            }
+
+            match page["next_cursor"].as_str() {
+                Some(next) => cursor = next.to_string(),
+                None => break,
+            }
+        }
+
+        assert_eq!(titles, expected, "sort={}", sort);
+    }
+}
+
+#[tokio::test]
+async fn test_cursor_from_other_sort_is_rejected() {
+    let app = create_app().await;
+    for i in 0..3 {
+        create_task_with(&app, json!({ "title": format!("Task {}", i) })).await;
+    }
+
+    let (_, body) = request(app.clone(), "GET", "/tasks?limit=1&cursor=", None).await;
+    let page: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let cursor = page["next_cursor"].as_str().unwrap();
+
+    let (status, _) = request(app, "GET", &format!("/tasks?sort=priority&cursor={}", cursor), None).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+// ============================================================
+// Statistics Tests
+// ============================================================
+
+#[tokio::test]
+async fn test_statistics() {
+    let app = create_app().await;
+
+    let (status, body) = request(app, "GET", "/tasks/stats", None).await;
+
+    assert_eq!(status, StatusCode::OK);
+
+    let stats: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert!(stats.get("total").is_some());
+    assert!(stats.get("completed").is_some());
+    assert!(stats.get("pending").is_some());
+    assert!(stats.get("overdue").is_some());
+    assert!(stats.get("by_priority").is_some());
+}
+
+#[tokio::test]
+async fn test_statistics_overdue_and_priority_counts() {
+    let app = create_app().await;
+    create_task_with(&app, json!({ "title": "Late", "due_at": "2000-01-01T00:00:00Z", "priority": "high" })).await;
+    create_task_with(&app, json!({ "title": "Urgent", "priority": "urgent" })).await;
+    create_task_with(&app, json!({ "title": "Normal" })).await;
+
+    let (_, body) = request(app, "GET", "/tasks/stats", None).await;
+
+    let stats: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(stats["total"], 3);
+    assert_eq!(stats["overdue"], 1);
+    assert_eq!(
+        stats["by_priority"],
+        json!({ "low": 0, "medium": 1, "high": 1, "urgent": 1 })
+    );
+}
+
+#[tokio::test]
+async fn test_statistics_root_progress() {
+    let app = create_app().await;
+    let root = create_task_with(&app, json!({ "title": "Launch" })).await;
+    let a = create_task_with(&app, json!({ "title": "A", "parent_id": root })).await;
+    create_task_with(&app, json!({ "title": "B", "parent_id": root })).await;
+    create_task_with(&app, json!({ "title": "A1", "parent_id": a })).await;
+    create_task_with(&app, json!({ "title": "Standalone" })).await;
+    let _ = request(app.clone(), "PATCH", &format!("/tasks/{}", a), Some(json!({ "completed": true }))).await;
+
+    let (_, body) = request(app, "GET", "/tasks/stats", None).await;
+
+    let stats: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(
+        stats["roots"],
+        json!([{ "id": root, "title": "Launch", "subtasks": 3, "completed": 1, "percent": 33.3 }])
+    );
+}
+
+// ============================================================
+// History and Restore Tests
+// ============================================================
+
+/// Helper to get the history of a task as JSON events
+async fn history(app: &Router, id: i64) -> Vec<serde_json::Value> {
+    let (status, body) = request(app.clone(), "GET", &format!("/tasks/{}/history", id), None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    serde_json::from_str(&body).unwrap()
+}
+
+#[tokio::test]
+async fn test_history_records_field_changes() {
+    let app = create_app().await;
+    let id = create_task_with(&app, json!({ "title": "Draft", "tags": ["docs"] })).await;
+
+    let _ = request(
+        app.clone(),
+        "PATCH",
+        &format!("/tasks/{}", id),
+        Some(json!({ "title": "Final", "completed": true })),
+    )
+    .await;
+    let _ = request(app.clone(), "POST", &format!("/tasks/{}/tags", id), Some(json!({ "tags": ["review"] }))).await;
+
+    let events = history(&app, id).await;
+
+    let kinds: Vec<&str> = events.iter().map(|e| e["kind"].as_str().unwrap()).collect();
+    assert_eq!(kinds, ["created", "updated", "updated"]);
+    assert_eq!(events[0]["actor_id"], 1);
+    assert_eq!(events[0]["changes"]["title"], json!({ "before": null, "after": "Draft" }));
+    assert_eq!(
+        events[1]["changes"],
+        json!({
+            "title": { "before": "Draft", "after": "Final" },
+            "completed": { "before": false, "after": true }
+        })
+    );
+    assert_eq!(
+        events[2]["changes"]["tags"],
+        json!({ "before": ["docs"], "after": ["docs", "review"] })
+    );
+}
+
+#[tokio::test]
+async fn test_update_without_changes_is_not_recorded() {
+    let app = create_app().await;
+    let id = create_task_with(&app, json!({ "title": "Same" })).await;
+
+    let _ = request(app.clone(), "PATCH", &format!("/tasks/{}", id), Some(json!({ "title": "Same" }))).await;
+
+    assert_eq!(history(&app, id).await.len(), 1);
+}
+
+#[tokio::test]
+async fn test_deleted_task_is_hidden_but_keeps_history() {
+    let app = create_app().await;
+    let id = create_task_with(&app, json!({ "title": "Old" })).await;
+
+    let (status, _) = request(app.clone(), "DELETE", &format!("/tasks/{}", id), None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    assert!(list_titles(&app, "/tasks").await.is_empty());
+    let (_, body) = request(app.clone(), "GET", "/tasks/stats", None).await;
+    let stats: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(stats["total"], 0);
+
+    let events = history(&app, id).await;
+    assert_eq!(events[1]["kind"], "deleted");
+    assert_eq!(events[1]["changes"], json!({ "deleted": { "before": false, "after": true } }));
+
+    // Deleting twice is a 404
+    let (status, _) = request(app, "DELETE", &format!("/tasks/{}", id), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_restore_task_with_subtasks() {
+    let app = create_app().await;
+    let root = create_task_with(&app, json!({ "title": "Root" })).await;
+    let child = create_task_with(&app, json!({ "title": "Child", "parent_id": root })).await;
+
+    let _ = request(app.clone(), "DELETE", &format!("/tasks/{}", root), None).await;
+
+    // A subtask cannot come back before its parent
+    let (status, _) = request(app.clone(), "POST", &format!("/tasks/{}/restore", child), None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, body) = request(app.clone(), "POST", &format!("/tasks/{}/restore", root), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let task: Task = serde_json::from_str(&body).unwrap();
+    assert_eq!(task.title, "Root");
+
+    let (status, _) = request(app.clone(), "GET", &format!("/tasks/{}", child), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(history(&app, child).await.last().unwrap()["kind"], "restored");
+}
+
+#[tokio::test]
+async fn test_restore_keeps_subtasks_deleted_earlier() {
+    let app = create_app().await;
+    let root = create_task_with(&app, json!({ "title": "Root" })).await;
+    let child = create_task_with(&app, json!({ "title": "Child", "parent_id": root })).await;
+
+    // Both deletes usually fall in the same second of deleted_at
+    let _ = request(app.clone(), "DELETE", &format!("/tasks/{}", child), None).await;
+    let _ = request(app.clone(), "DELETE", &format!("/tasks/{}", root), None).await;
+    let _ = request(app.clone(), "POST", &format!("/tasks/{}/restore", root), None).await;
+
+    let (status, _) = request(app, "GET", &format!("/tasks/{}", child), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_restore_task_not_deleted() {
+    let app = create_app().await;
+    let id = create_task_with(&app, json!({ "title": "Alive" })).await;
+
+    let (status, _) = request(app.clone(), "POST", &format!("/tasks/{}/restore", id), None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = request(app, "POST", "/tasks/9999/restore", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_history_is_scoped_to_owner() {
+    let app = create_app().await;
+    let id = create_task_with(&app, json!({ "title": "Private" })).await;
+    let other = token_for(2, "other");
+
+    let (status, _) = request_as(app, "GET", &format!("/tasks/{}/history", id), None, Some(&other)).await;
 
-    let stats: serde_json::Value = serde_json::from_str(&body).unwrap();
-    assert!(stats.get("total").is_some());
-    assert!(stats.get("completed").is_some());
-    assert!(stats.get("pending").is_some());
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+// ============================================================
+// Conditional Request Tests
+// ============================================================
+
+/// Helper to get the ETag of a task
+async fn etag_of(app: &Router, id: i64) -> String {
+    let (status, headers, _) = request_with_headers(app.clone(), "GET", &format!("/tasks/{}", id), None, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+
+    headers["etag"].to_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn test_etag_follows_version() {
+    let app = create_app().await;
+
+    let (status, headers, body) =
+        request_with_headers(app.clone(), "POST", "/tasks", Some(json!({ "title": "Versioned" })), &[]).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let task: Task = serde_json::from_str(&body).unwrap();
+    assert_eq!(task.version, 1);
+    assert_eq!(headers["etag"], "\"1\"");
+    assert_eq!(etag_of(&app, task.id).await, "\"1\"");
+
+    let (_, headers, body) = request_with_headers(
+        app.clone(),
+        "PATCH",
+        &format!("/tasks/{}", task.id),
+        Some(json!({ "completed": true })),
+        &[],
+    )
+    .await;
+    let task: Task = serde_json::from_str(&body).unwrap();
+    assert_eq!(task.version, 2);
+    assert_eq!(headers["etag"], "\"2\"");
+
+    // Tag changes are changes too
+    let _ = request(app.clone(), "POST", &format!("/tasks/{}/tags", task.id), Some(json!({ "tags": ["x"] }))).await;
+    assert_eq!(etag_of(&app, task.id).await, "\"3\"");
+}
+
+#[tokio::test]
+async fn test_if_match_rejects_stale_writes() {
+    let app = create_app().await;
+    let id = create_task_with(&app, json!({ "title": "Contended" })).await;
+    let uri = format!("/tasks/{}", id);
+    let stale = etag_of(&app, id).await;
+
+    // First client wins
+    let (status, headers, _) = request_with_headers(
+        app.clone(),
+        "PATCH",
+        &uri,
+        Some(json!({ "title": "First" })),
+        &[("if-match", &stale)],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let current = headers["etag"].to_str().unwrap().to_string();
+
+    // Second client still holds the old version
+    for (method, body) in [
+        ("PATCH", Some(json!({ "title": "Second" }))),
+        ("PUT", Some(json!({ "title": "Second" }))),
+        ("DELETE", None),
+    ] {
+        let (status, _, body) =
+            request_with_headers(app.clone(), method, &uri, body, &[("if-match", &stale)]).await;
+        assert_eq!(status, StatusCode::PRECONDITION_FAILED, "{}", method);
+
+        let error: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(error["status"], 412);
+    }
+
+    let (_, body) = request(app.clone(), "GET", &uri, None).await;
+    let task: Task = serde_json::from_str(&body).unwrap();
+    assert_eq!(task.title, "First");
+
+    let (status, _, _) = request_with_headers(app, "DELETE", &uri, None, &[("if-match", &current)]).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn test_if_match_wildcard_and_lists() {
+    let app = create_app().await;
+    let id = create_task_with(&app, json!({ "title": "Any" })).await;
+    let uri = format!("/tasks/{}", id);
+
+    let (status, _, _) =
+        request_with_headers(app.clone(), "PATCH", &uri, Some(json!({ "title": "A" })), &[("if-match", "*")]).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _, _) = request_with_headers(
+        app,
+        "PATCH",
+        &uri,
+        Some(json!({ "title": "B" })),
+        &[("if-match", "\"7\", \"2\"")],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_if_none_match_returns_not_modified() {
+    let app = create_app().await;
+    let id = create_task_with(&app, json!({ "title": "Polled" })).await;
+    let uri = format!("/tasks/{}", id);
+    let etag = etag_of(&app, id).await;
+
+    let (status, headers, body) =
+        request_with_headers(app.clone(), "GET", &uri, None, &[("if-none-match", &etag)]).await;
+    assert_eq!(status, StatusCode::NOT_MODIFIED);
+    assert_eq!(headers["etag"], etag.as_str());
+    assert!(body.is_empty());
+
+    let _ = request(app.clone(), "PATCH", &uri, Some(json!({ "completed": true }))).await;
+
+    let (status, _, _) = request_with_headers(app, "GET", &uri, None, &[("if-none-match", &etag)]).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+// ============================================================
+// Search Tests
+// ============================================================
+
+#[tokio::test]
+async fn test_search_tasks_prefix_and_highlight() {
+    let app = create_app().await;
+
+    for (title, description) in [
+        ("Learn Rust", "Complete the bootcamp"),
+        ("Buy groceries", "Milk and bread"),
+        ("Write docs", "Document the Rust API"),
+    ] {
+        let _ = request(
+            app.clone(),
+            "POST",
+            "/tasks",
+            Some(json!({ "title": title, "description": description })),
+        )
+        .await;
+    }
+
+    let (status, body) = request(app, "GET", "/tasks/search?q=rus", None).await;
+
+    assert_eq!(status, StatusCode::OK);
+    let results: Vec<serde_json::Value> = serde_json::from_str(&body).unwrap();
+    assert_eq!(results.len(), 2);
+
+    // Title matches rank above description matches
+    assert_eq!(results[0]["title"], "Learn Rust");
+    assert_eq!(results[0]["title_highlight"], "Learn <mark>Rust</mark>");
+    assert!(results[1]["snippet"]
+        .as_str()
+        .unwrap()
+        .contains("<mark>Rust</mark>"));
+}
+
+#[tokio::test]
+async fn test_search_reflects_updates_and_deletes() {
+    let app = create_app().await;
+
+    let (_, body) = request(
+        app.clone(),
+        "POST",
+        "/tasks",
+        Some(json!({ "title": "Paint fence" })),
+    )
+    .await;
+    let task: Task = serde_json::from_str(&body).unwrap();
+
+    let _ = request(
+        app.clone(),
+        "PATCH",
+        &format!("/tasks/{}", task.id),
+        Some(json!({ "title": "Repair roof" })),
+    )
+    .await;
+
+    let (_, body) = request(app.clone(), "GET", "/tasks/search?q=fence", None).await;
+    let results: Vec<serde_json::Value> = serde_json::from_str(&body).unwrap();
+    assert!(results.is_empty());
+
+    let (_, body) = request(app.clone(), "GET", "/tasks/search?q=roof", None).await;
+    let results: Vec<serde_json::Value> = serde_json::from_str(&body).unwrap();
+    assert_eq!(results.len(), 1);
+
+    let _ = request(app.clone(), "DELETE", &format!("/tasks/{}", task.id), None).await;
+
+    let (_, body) = request(app, "GET", "/tasks/search?q=roof", None).await;
+    let results: Vec<serde_json::Value> = serde_json::from_str(&body).unwrap();
+    assert!(results.is_empty());
+}
+
+#[tokio::test]
+async fn test_search_ignores_fts_syntax() {
+    let app = create_app().await;
+
+    let (status, _body) = request(app, "GET", "/tasks/search?q=%22AND%20(", None).await;
+
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_search_empty_query() {
+    let app = create_app().await;
+
+    let (status, _body) = request(app, "GET", "/tasks/search?q=%20", None).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_search_rejects_invalid_limit() {
+    let app = create_app().await;
+
+    for limit in ["0", "-1", "101"] {
+        let uri = format!("/tasks/search?q=rust&limit={}", limit);
+        let (status, _body) = request(app.clone(), "GET", &uri, None).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST, "limit={}", limit);
+    }
+}
+
+// ============================================================
+// Authentication Tests
+// ============================================================
+
+#[tokio::test]
+async fn test_register_and_login() {
+    let app = create_app().await;
+    let credentials = json!({ "username": "ferris", "password": "crab-secret" });
+
+    let (status, body) = request_as(app.clone(), "POST", "/auth/register", Some(credentials.clone()), None).await;
+
+    assert_eq!(status, StatusCode::CREATED);
+    let user: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(user["username"], "ferris");
+    assert!(user.get("password_hash").is_none());
+
+    let (status, body) = request_as(app.clone(), "POST", "/auth/login", Some(credentials), None).await;
+
+    assert_eq!(status, StatusCode::OK);
+    let auth: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(auth["token_type"], "Bearer");
+    let token = auth["access_token"].as_str().unwrap();
+
+    // The token grants access to task routes
+    let (status, _body) = request_as(app, "GET", "/tasks", None, Some(token)).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_register_duplicate_username() {
+    let app = create_app().await;
+
+    let (status, _body) = request_as(
+        app,
+        "POST",
+        "/auth/register",
+        Some(json!({ "username": "TESTER", "password": "long-enough" })),
+        None,
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_register_same_username_concurrently() {
+    let app = create_app().await;
+    let credentials = json!({ "username": "ferris", "password": "crab-secret" });
+
+    let (first, second) = tokio::join!(
+        request_as(app.clone(), "POST", "/auth/register", Some(credentials.clone()), None),
+        request_as(app, "POST", "/auth/register", Some(credentials), None),
+    );
+
+    let mut statuses = [first.0, second.0];
+    statuses.sort();
+    assert_eq!(statuses, [StatusCode::CREATED, StatusCode::BAD_REQUEST]);
+}
+
+#[tokio::test]
+async fn test_register_short_password() {
+    let app = create_app().await;
+
+    let (status, _body) = request_as(
+        app,
+        "POST",
+        "/auth/register",
+        Some(json!({ "username": "ferris", "password": "short" })),
+        None,
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_login_wrong_password() {
+    let app = create_app().await;
+    let _ = request_as(
+        app.clone(),
+        "POST",
+        "/auth/register",
+        Some(json!({ "username": "ferris", "password": "crab-secret" })),
+        None,
+    )
+    .await;
+
+    let (status, _body) = request_as(
+        app,
+        "POST",
+        "/auth/login",
+        Some(json!({ "username": "ferris", "password": "wrong-secret" })),
+        None,
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_login_unknown_user() {
+    let app = create_app().await;
+
+    let (status, body) = request_as(
+        app,
+        "POST",
+        "/auth/login",
+        Some(json!({ "username": "nobody", "password": "crab-secret" })),
+        None,
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(body.contains("Invalid username or password"));
+
+    // Unknown users are checked against a real Argon2 hash
+    assert!(auth::dummy_hash().starts_with("$argon2"));
+    assert!(!auth::verify_password("crab-secret", auth::dummy_hash()));
+}
+
+#[tokio::test]
+async fn test_missing_token() {
+    let app = create_app().await;
+
+    let (status, body) = request_as(app, "GET", "/tasks", None, None).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let error: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(error["status"], 401);
+}
+
+#[tokio::test]
+async fn test_invalid_token() {
+    let app = create_app().await;
+    let forged = AuthConfig::new("other-secret").issue_token(1, "tester").unwrap();
+
+    let (status, _body) = request_as(app, "GET", "/tasks", None, Some(&forged)).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_tasks_are_scoped_to_owner() {
+    let app = create_app().await;
+
+    let (_, body) = request(
+        app.clone(),
+        "POST",
+        "/tasks",
+        Some(json!({ "title": "Private task" })),
+    )
+    .await;
+    let task: Task = serde_json::from_str(&body).unwrap();
+
+    let _ = request_as(
+        app.clone(),
+        "POST",
+        "/auth/register",
+        Some(json!({ "username": "intruder", "password": "crab-secret" })),
+        None,
+    )
+    .await;
+    let other = token_for(2, "intruder");
+    let uri = format!("/tasks/{}", task.id);
+
+    let (_, body) = request_as(app.clone(), "GET", "/tasks", None, Some(&other)).await;
+    let tasks: Vec<Task> = serde_json::from_str(&body).unwrap();
+    assert!(tasks.is_empty());
+
+    let (status, _) = request_as(app.clone(), "GET", &uri, None, Some(&other)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = request_as(
+        app.clone(),
+        "PATCH",
+        &uri,
+        Some(json!({ "title": "Hijacked" })),
+        Some(&other),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = request_as(app.clone(), "DELETE", &uri, None, Some(&other)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // Still intact for its owner
+    let (status, body) = request(app, "GET", &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let task: Task = serde_json::from_str(&body).unwrap();
+    assert_eq!(task.title, "Private task");
+}
+
+// ============================================================
+// Health Tests
+// ============================================================
+
+#[tokio::test]
+async fn test_health_live_needs_no_token() {
+    let app = create_app().await;
+
+    let (status, body) = request_as(app, "GET", "/health/live", None, None).await;
+
+    assert_eq!(status, StatusCode::OK);
+    let live: Liveness = serde_json::from_str(&body).unwrap();
+    assert_eq!(live.status, HealthStatus::Ok);
+    assert_eq!(live.build.name, "project-task-api");
+    assert_eq!(live.build.version, env!("CARGO_PKG_VERSION"));
+}
+
+#[tokio::test]
+async fn test_health_ready_reports_database_and_migrations() {
+    let app = create_app().await;
+
+    let (status, body) = request_as(app, "GET", "/health/ready", None, None).await;
+
+    assert_eq!(status, StatusCode::OK);
+    let ready: Readiness = serde_json::from_str(&body).unwrap();
+    assert_eq!(ready.status, HealthStatus::Ok);
+    assert_eq!(ready.database, HealthStatus::Ok);
+    let state = ready.migrations.unwrap();
+    assert_eq!(state.current, migrations::latest_version());
+    assert_eq!(state.pending, 0);
+}
+
+#[tokio::test]
+async fn test_health_ready_unavailable_with_pending_migrations() {
+    let pool = db::create_test_pool().await.unwrap();
+    migrations::migrate_down(&pool, 1).await.unwrap();
+    let app = app_with_pool(pool).await;
+
+    let (status, body) = request_as(app, "GET", "/health/ready", None, None).await;
+
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    let ready: Readiness = serde_json::from_str(&body).unwrap();
+    assert_eq!(ready.status, HealthStatus::Unavailable);
+    assert_eq!(ready.database, HealthStatus::Ok);
+    assert_eq!(ready.migrations.unwrap().pending, 1);
+}
+
+#[tokio::test]
+async fn test_health_ready_unavailable_without_database() {
+    let pool = db::create_test_pool().await.unwrap();
+    let app = app_with_pool(pool.clone()).await;
+    pool.close().await;
+
+    let (status, body) = request_as(app.clone(), "GET", "/health/ready", None, None).await;
+
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    let ready: Readiness = serde_json::from_str(&body).unwrap();
+    assert_eq!(ready.database, HealthStatus::Unavailable);
+    assert!(ready.migrations.is_none());
+
+    // Liveness does not depend on the database
+    let (status, _) = request_as(app, "GET", "/health/live", None, None).await;
+    assert_eq!(status, StatusCode::OK);
 }
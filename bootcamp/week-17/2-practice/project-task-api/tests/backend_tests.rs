@@ -0,0 +1,489 @@
+//! Core task routes against every `TaskRepository` backend
+//!
+//! Run with: `cargo test` (SQLite and in-memory). To include PostgreSQL:
+//! `TEST_DATABASE_URL=postgres://... cargo test --features postgres`
+
+mod common;
+
+use axum::http::{HeaderMap, StatusCode};
+use common::TestApp;
+use project_task_api::{
+    models::{Task, TaskPage},
+    repository::InMemoryTaskRepository,
+    routes::SQLITE_ONLY_PATHS,
+};
+use serde_json::{json, Value};
+
+/// Backend helpers; requests go as the app's user, who has no tasks yet
+impl TestApp {
+    /// Send a request as the app's user
+    async fn call(&self, method: &str, uri: &str, body: Option<Value>) -> (StatusCode, HeaderMap, Value) {
+        self.send_with_headers(self.user_id(), method, uri, body, &[]).await
+    }
+
+    /// Create a task and return it
+    async fn create(&self, body: Value) -> Task {
+        let (status, _, task) = self.call("POST", "/tasks", Some(body)).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", task);
+
+        serde_json::from_value(task).unwrap()
+    }
+
+    /// Titles returned by a listing
+    async fn titles(&self, uri: &str) -> Vec<String> {
+        let (status, _, body) = self.call("GET", uri, None).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+
+        let tasks: Vec<Task> = serde_json::from_value(body).unwrap();
+        tasks.into_iter().map(|t| t.title).collect()
+    }
+}
+
+/// A user id no other test uses, so backends with shared storage stay isolated
+#[cfg(feature = "postgres")]
+fn fresh_user_id() -> i64 {
+    use std::sync::atomic::{AtomicI64, Ordering};
+
+    static NEXT: AtomicI64 = AtomicI64::new(0);
+
+    let micros = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_micros() as i64;
+
+    micros * 1000 + NEXT.fetch_add(1, Ordering::Relaxed) % 1000
+}
+
+async fn sqlite_app() -> TestApp {
+    TestApp::with_users(&["tester"]).await
+}
+
+async fn memory_app() -> TestApp {
+    TestApp::from_repository(InMemoryTaskRepository::new(), &[(1, "tester")])
+}
+
+/// `None` when `TEST_DATABASE_URL` is unset, so the PostgreSQL cases skip
+#[cfg(feature = "postgres")]
+async fn postgres_app() -> Option<TestApp> {
+    use project_task_api::repository::PostgresTaskRepository;
+
+    static MIGRATED: tokio::sync::OnceCell<()> = tokio::sync::OnceCell::const_new();
+
+    let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+        eprintln!("Skipping PostgreSQL backend test: TEST_DATABASE_URL is not set");
+        return None;
+    };
+    let repository = PostgresTaskRepository::new(
+        sqlx::PgPool::connect(&url).await.expect("Error connecting to PostgreSQL"),
+    );
+    MIGRATED
+        .get_or_init(|| async { repository.migrate().await.expect("Error migrating") })
+        .await;
+
+    Some(TestApp::from_repository(repository, &[(fresh_user_id(), "tester")]))
+}
+
+/// Run every case against each backend
+macro_rules! backend_tests {
+    ($($case:ident),* $(,)?) => {
+        mod sqlite {
+            $(
+                #[tokio::test]
+                async fn $case() {
+                    super::$case(super::sqlite_app().await).await;
+                }
+            )*
+        }
+
+        mod memory {
+            $(
+                #[tokio::test]
+                async fn $case() {
+                    super::$case(super::memory_app().await).await;
+                }
+            )*
+        }
+
+        #[cfg(feature = "postgres")]
+        mod postgres {
+            $(
+                #[tokio::test]
+                async fn $case() {
+                    if let Some(app) = super::postgres_app().await {
+                        super::$case(app).await;
+                    }
+                }
+            )*
+        }
+    };
+}
+
+backend_tests!(
+    create_and_get,
+    create_validation,
+    tasks_are_scoped_to_owner,
+    list_filters_and_sort,
+    offset_pagination,
+    cursor_pagination,
+    patch_merges_fields,
+    put_replaces_task,
+    conditional_requests,
+    cascade_and_cycles,
+    delete_removes_subtree,
+    stats,
+);
+
+async fn create_and_get(app: TestApp) {
+    let (status, headers, body) = app
+        .call(
+            "POST",
+            "/tasks",
+            Some(json!({
+                "title": "Learn Rust",
+                "description": "Traits",
+                "due_at": "2030-05-01T10:00:00Z",
+                "priority": "high",
+                "tags": ["Rust", " study ", "rust"]
+            })),
+        )
+        .await;
+
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(headers["etag"], "\"1\"");
+    let created: Task = serde_json::from_value(body).unwrap();
+    assert_eq!(created.title, "Learn Rust");
+    assert!(!created.completed);
+    assert_eq!(created.version, 1);
+    assert_eq!(created.tags, ["rust", "study"]);
+
+    let (status, _, body) = app.call("GET", &format!("/tasks/{}", created.id), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let fetched: Task = serde_json::from_value(body).unwrap();
+    assert_eq!(fetched.description.as_deref(), Some("Traits"));
+    assert_eq!(fetched.due_at, created.due_at);
+    assert_eq!(fetched.created_at, created.created_at);
+
+    let (status, _, _) = app.call("GET", "/tasks/999999999", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+async fn create_validation(app: TestApp) {
+    for body in [
+        json!({ "title": "   " }),
+        json!({ "title": "x".repeat(201) }),
+        json!({ "title": "Tagged", "tags": [""] }),
+        json!({ "title": "Orphan", "parent_id": 999999999 }),
+    ] {
+        let (status, _, error) = app.call("POST", "/tasks", Some(body)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", error);
+    }
+
+    let (_, _, error) = app.call("POST", "/tasks", Some(json!({ "title": "" }))).await;
+    assert_eq!(error["detail"], "Title is required");
+
+    assert!(app.titles("/tasks").await.is_empty());
+}
+
+async fn tasks_are_scoped_to_owner(app: TestApp) {
+    let task = app.create(json!({ "title": "Private" })).await;
+    let other = app.user_id() + 1;
+    let uri = format!("/tasks/{}", task.id);
+
+    let (status, _, _) = app.send_with_headers(other, "GET", &uri, None, &[]).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _, _) = app
+        .send_with_headers(other, "PATCH", &uri, Some(json!({ "title": "Mine" })), &[])
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _, _) = app.send_with_headers(other, "DELETE", &uri, None, &[]).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _, body) = app
+        .send_with_headers(other, "POST", "/tasks", Some(json!({ "title": "Sub", "parent_id": task.id })), &[])
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+
+    assert_eq!(app.titles("/tasks").await, ["Private"]);
+}
+
+async fn list_filters_and_sort(app: TestApp) {
+    let late = app
+        .create(json!({ "title": "Late", "due_at": "2000-01-01T00:00:00Z", "tags": ["work"] }))
+        .await;
+    app.create(json!({ "title": "Urgent", "priority": "urgent", "tags": ["work", "home"] }))
+        .await;
+    let done = app.create(json!({ "title": "Done", "due_at": "2001-01-01T00:00:00Z" })).await;
+    app.call("PATCH", &format!("/tasks/{}", done.id), Some(json!({ "completed": true })))
+        .await;
+
+    assert_eq!(app.titles("/tasks").await, ["Done", "Urgent", "Late"]);
+    assert_eq!(app.titles("/tasks?completed=true").await, ["Done"]);
+    assert_eq!(app.titles("/tasks?completed=false").await, ["Urgent", "Late"]);
+    assert_eq!(app.titles("/tasks?tag=WORK").await, ["Urgent", "Late"]);
+    assert_eq!(app.titles("/tasks?tag=work&tag=home&tag_match=all").await, ["Urgent"]);
+    assert_eq!(app.titles("/tasks?tag=home&tag=nothing").await, ["Urgent"]);
+    assert_eq!(app.titles("/tasks?overdue=true").await, ["Late"]);
+    assert_eq!(app.titles("/tasks?sort=priority").await, ["Urgent", "Done", "Late"]);
+    assert_eq!(app.titles("/tasks?sort=due_at").await, ["Late", "Done", "Urgent"]);
+
+    let (status, _, _) = app.call("GET", "/tasks?tag=", None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    assert_eq!(late.tags, ["work"]);
+}
+
+async fn offset_pagination(app: TestApp) {
+    for title in ["A", "B", "C", "D", "E"] {
+        app.create(json!({ "title": title })).await;
+    }
+
+    assert_eq!(app.titles("/tasks?limit=2").await, ["E", "D"]);
+    assert_eq!(app.titles("/tasks?limit=2&offset=2").await, ["C", "B"]);
+    assert_eq!(app.titles("/tasks?limit=2&offset=4").await, ["A"]);
+    assert!(app.titles("/tasks?offset=10").await.is_empty());
+
+    // Old offset-mode values keep working: a negative limit means no limit
+    assert_eq!(app.titles("/tasks?limit=-1&offset=3").await, ["B", "A"]);
+    assert_eq!(app.titles("/tasks?limit=9223372036854775807").await.len(), 5);
+    assert_eq!(app.titles("/tasks?limit=1&offset=-1").await, ["E"]);
+    assert!(app.titles("/tasks?limit=0").await.is_empty());
+}
+
+async fn cursor_pagination(app: TestApp) {
+    for (title, due_at) in [
+        ("No date 1", None),
+        ("March", Some("2030-03-01T00:00:00Z")),
+        ("January", Some("2030-01-01T00:00:00Z")),
+        ("No date 2", None),
+        ("February", Some("2030-02-01T00:00:00Z")),
+    ] {
+        app.create(json!({ "title": title, "due_at": due_at })).await;
+    }
+
+    let mut titles = Vec::new();
+    let mut cursor = String::new();
+    loop {
+        let (status, _, body) = app
+            .call("GET", &format!("/tasks?sort=due_at&limit=2&cursor={}", cursor), None)
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+
+        let page: TaskPage = serde_json::from_value(body).unwrap();
+        titles.extend(page.items.into_iter().map(|t| t.title));
+
+        match page.next_cursor {
+            Some(next) => cursor = next,
+            None => {
+                assert!(!page.has_more);
+                break;
+            }
+        }
+    }
+
+    assert_eq!(titles, ["January", "February", "March", "No date 1", "No date 2"]);
+
+    let (status, _, _) = app.call("GET", "/tasks?cursor=garbage", None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+async fn patch_merges_fields(app: TestApp) {
+    let task = app
+        .create(json!({ "title": "Draft", "description": "Notes", "priority": "low", "tags": ["a"] }))
+        .await;
+    let uri = format!("/tasks/{}", task.id);
+
+    let (status, headers, body) = app
+        .call("PATCH", &uri, Some(json!({ "description": null, "tags": ["B", "a"] })))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(headers["etag"], "\"2\"");
+    let patched: Task = serde_json::from_value(body).unwrap();
+    assert_eq!(patched.title, "Draft");
+    assert_eq!(patched.description, None);
+    assert_eq!(patched.tags, ["a", "b"]);
+    assert_eq!(patched.version, 2);
+
+    let (status, _, _) = app.call("PATCH", &uri, Some(json!({ "title": null }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _, _) = app.call("PATCH", &uri, Some(json!({ "title": " " }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Failed patches change nothing
+    let (_, _, body) = app.call("GET", &uri, None).await;
+    assert_eq!(body["version"], 2);
+}
+
+async fn put_replaces_task(app: TestApp) {
+    let task = app
+        .create(json!({ "title": "Old", "description": "Gone", "priority": "high", "tags": ["x"] }))
+        .await;
+
+    let (status, _, body) = app
+        .call("PUT", &format!("/tasks/{}", task.id), Some(json!({ "title": "New", "completed": true })))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let replaced: Task = serde_json::from_value(body).unwrap();
+    assert_eq!(replaced.title, "New");
+    assert!(replaced.completed);
+    assert_eq!(replaced.description, None);
+    assert_eq!(replaced.priority, project_task_api::models::Priority::Medium);
+    assert!(replaced.tags.is_empty());
+}
+
+async fn conditional_requests(app: TestApp) {
+    let task = app.create(json!({ "title": "Versioned" })).await;
+    let uri = format!("/tasks/{}", task.id);
+    let user = app.user_id();
+
+    let (status, _, _) = app.send_with_headers(user, "GET", &uri, None, &[("if-none-match", "\"1\"")]).await;
+    assert_eq!(status, StatusCode::NOT_MODIFIED);
+
+    let (status, _, _) = app
+        .send_with_headers(user, "PATCH", &uri, Some(json!({ "title": "First" })), &[("if-match", "\"1\"")])
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _, _) = app
+        .send_with_headers(user, "PATCH", &uri, Some(json!({ "title": "Stale" })), &[("if-match", "\"1\"")])
+        .await;
+    assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+
+    let (status, _, _) = app.send_with_headers(user, "DELETE", &uri, None, &[("if-match", "\"1\"")]).await;
+    assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+
+    let (_, _, body) = app.call("GET", &uri, None).await;
+    assert_eq!(body["title"], "First");
+
+    let (status, _, _) = app.send_with_headers(user, "DELETE", &uri, None, &[("if-match", "*")]).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+}
+
+async fn cascade_and_cycles(app: TestApp) {
+    let root = app.create(json!({ "title": "Root" })).await;
+    let child = app.create(json!({ "title": "Child", "parent_id": root.id })).await;
+    let grandchild = app.create(json!({ "title": "Grandchild", "parent_id": child.id })).await;
+
+    let (status, _, _) = app
+        .call("PATCH", &format!("/tasks/{}?cascade=true", root.id), Some(json!({ "completed": true })))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    for id in [child.id, grandchild.id] {
+        let (_, _, body) = app.call("GET", &format!("/tasks/{}", id), None).await;
+        assert_eq!(body["completed"], true);
+        assert_eq!(body["version"], 2);
+    }
+
+    // Without cascade only the task itself changes
+    app.call("PATCH", &format!("/tasks/{}", root.id), Some(json!({ "completed": false })))
+        .await;
+    let (_, _, body) = app.call("GET", &format!("/tasks/{}", child.id), None).await;
+    assert_eq!(body["completed"], true);
+
+    for parent_id in [root.id, grandchild.id] {
+        let (status, _, body) = app
+            .call("PATCH", &format!("/tasks/{}", root.id), Some(json!({ "parent_id": parent_id })))
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    }
+}
+
+async fn delete_removes_subtree(app: TestApp) {
+    let root = app.create(json!({ "title": "Root" })).await;
+    let child = app.create(json!({ "title": "Child", "parent_id": root.id })).await;
+    app.create(json!({ "title": "Keep" })).await;
+
+    let (status, _, _) = app.call("DELETE", &format!("/tasks/{}", root.id), None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    for id in [root.id, child.id] {
+        let (status, _, _) = app.call("GET", &format!("/tasks/{}", id), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    let (status, _, _) = app.call("DELETE", &format!("/tasks/{}", root.id), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    assert_eq!(app.titles("/tasks").await, ["Keep"]);
+}
+
+async fn stats(app: TestApp) {
+    let root = app.create(json!({ "title": "Launch", "priority": "high" })).await;
+    let a = app.create(json!({ "title": "A", "parent_id": root.id })).await;
+    app.create(json!({ "title": "B", "parent_id": root.id, "due_at": "2000-01-01T00:00:00Z" }))
+        .await;
+    app.create(json!({ "title": "C", "parent_id": a.id, "priority": "urgent" })).await;
+    app.create(json!({ "title": "Alone", "priority": "low" })).await;
+    app.call("PATCH", &format!("/tasks/{}", a.id), Some(json!({ "completed": true })))
+        .await;
+
+    let (status, _, stats) = app.call("GET", "/tasks/stats", None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    assert_eq!(stats["total"], 5);
+    assert_eq!(stats["completed"], 1);
+    assert_eq!(stats["pending"], 4);
+    assert_eq!(stats["overdue"], 1);
+    assert_eq!(
+        stats["by_priority"],
+        json!({ "low": 1, "medium": 2, "high": 1, "urgent": 1 })
+    );
+    assert_eq!(
+        stats["roots"],
+        json!([{ "id": root.id, "title": "Launch", "subtasks": 3, "completed": 1, "percent": 33.3 }])
+    );
+}
+
+// =============================================================================
+// ===== SQLite-only routes =====
+// =============================================================================
+
+/// Detail of the 404 answered by backends without a route
+const UNSUPPORTED: &str = "not available with this storage backend";
+
+/// `path` with its parameters pointing at task `id`
+fn concrete(path: &str, id: i64) -> String {
+    path.replace("{id}", &id.to_string())
+        .replace("{pid}", "1")
+        .replace("{username}", "tester")
+}
+
+#[tokio::test]
+async fn memory_backend_answers_404_on_sqlite_only_routes() {
+    let app = memory_app().await;
+    let task = app.create(json!({ "title": "Exists" })).await;
+
+    for path in SQLITE_ONLY_PATHS {
+        let uri = concrete(path, task.id);
+
+        for method in ["GET", "POST", "DELETE"] {
+            let (status, _, body) = app.call(method, &uri, None).await;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{} {}: {}", method, uri, body);
+            assert!(body["detail"].as_str().unwrap().contains(UNSUPPORTED), "{}", body);
+        }
+    }
+
+    // The task itself is untouched
+    let (status, _, _) = app.call("GET", &format!("/tasks/{}", task.id), None).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn sqlite_backend_serves_sqlite_only_routes() {
+    let app = sqlite_app().await;
+    let task = app.create(json!({ "title": "Exists" })).await;
+
+    for path in SQLITE_ONLY_PATHS {
+        let uri = concrete(path, task.id);
+        let (_, _, body) = app.call("GET", &uri, None).await;
+
+        let unsupported = body["detail"]
+            .as_str()
+            .is_some_and(|detail| detail.contains(UNSUPPORTED));
+        assert!(!unsupported, "GET {}: {}", uri, body);
+    }
+}
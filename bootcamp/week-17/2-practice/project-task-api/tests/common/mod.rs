@@ -0,0 +1,203 @@
+//! Fixture shared by the integration tests
+//!
+//! Each test binary compiles this module on its own and uses only part of it.
+#![allow(dead_code)]
+
+use axum::{
+    body::{Body, Bytes},
+    http::{HeaderMap, Request, StatusCode},
+    response::Response,
+    Router,
+};
+use project_task_api::{
+    auth::AuthConfig,
+    db,
+    events::EventBus,
+    repository::{SqliteTaskRepository, TaskRepository},
+    routes,
+    state::AppState,
+};
+use serde_json::Value;
+use sqlx::SqlitePool;
+use tower::ServiceExt;
+
+pub const TEST_SECRET: &str = "test-secret";
+
+/// Users of the default application, by ID
+pub const USERS: [&str; 2] = ["tester", "other"];
+
+/// SQLite routes before the state is applied, for wrapping in middleware
+pub type Routes = Router<AppState<SqliteTaskRepository>>;
+
+/// Application under test with its users
+pub struct TestApp {
+    pub router: Router,
+    pub events: EventBus,
+    pool: Option<SqlitePool>,
+    users: Vec<(i64, &'static str)>,
+}
+
+impl TestApp {
+    /// Application with users tester (1) and other (2)
+    pub async fn new() -> Self {
+        Self::with_users(&USERS).await
+    }
+
+    /// Application with `users`, numbered from 1
+    pub async fn with_users(users: &[&'static str]) -> Self {
+        Self::from_pool(test_pool().await, users, |routes, _| routes).await
+    }
+
+    /// Default application with `layers` applied to its routes
+    pub async fn with_layers(layers: impl FnOnce(Routes, &SqlitePool) -> Routes) -> Self {
+        Self::from_pool(test_pool().await, &USERS, layers).await
+    }
+
+    /// Application on a migrated `pool`, creating `users` numbered from 1
+    pub async fn from_pool(
+        pool: SqlitePool,
+        users: &[&'static str],
+        layers: impl FnOnce(Routes, &SqlitePool) -> Routes,
+    ) -> Self {
+        for (id, username) in (1..).zip(users) {
+            sqlx::query("INSERT INTO users (id, username, password_hash) VALUES (?, ?, '')")
+                .bind(id)
+                .bind(username)
+                .execute(&pool)
+                .await
+                .expect("Error creating test users");
+        }
+
+        let state = AppState::new(
+            SqliteTaskRepository::new(pool.clone()),
+            AuthConfig::new(TEST_SECRET),
+        );
+        let events = state.events.clone();
+
+        let routes = Router::new().merge(routes::create_routes::<SqliteTaskRepository>());
+        let router = layers(routes, &pool).with_state(state);
+
+        Self {
+            router,
+            events,
+            pool: Some(pool),
+            users: (1..).zip(users.iter().copied()).collect(),
+        }
+    }
+
+    /// Application on any backend whose storage already has `users`
+    pub fn from_repository<R: TaskRepository>(
+        repository: R,
+        users: &[(i64, &'static str)],
+    ) -> Self {
+        let state = AppState::new(repository, AuthConfig::new(TEST_SECRET));
+        let events = state.events.clone();
+
+        let router = Router::new()
+            .merge(routes::create_routes::<R>())
+            .with_state(state);
+
+        Self {
+            router,
+            events,
+            pool: None,
+            users: users.to_vec(),
+        }
+    }
+
+    /// Database of a SQLite application
+    pub fn pool(&self) -> &SqlitePool {
+        self.pool.as_ref().expect("Not a SQLite application")
+    }
+
+    /// ID of the first user, who tests act as by default
+    pub fn user_id(&self) -> i64 {
+        self.users[0].0
+    }
+
+    /// `Authorization` header value for `user_id`
+    ///
+    /// Users the application does not know get a token for `nobody`.
+    pub fn bearer(&self, user_id: i64) -> String {
+        let username = self
+            .users
+            .iter()
+            .find(|(id, _)| *id == user_id)
+            .map_or("nobody", |(_, username)| username);
+        let token = AuthConfig::new(TEST_SECRET)
+            .issue_token(user_id, username)
+            .unwrap();
+
+        format!("Bearer {}", token)
+    }
+
+    /// Send a JSON request as `user_id`
+    pub async fn send(
+        &self,
+        user_id: i64,
+        method: &str,
+        uri: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let (status, _, body) = self
+            .send_with_headers(user_id, method, uri, body, &[])
+            .await;
+
+        (status, body)
+    }
+
+    /// Send a JSON request as `user_id` with extra headers, also returning
+    /// the response headers
+    pub async fn send_with_headers(
+        &self,
+        user_id: i64,
+        method: &str,
+        uri: &str,
+        body: Option<Value>,
+        headers: &[(&str, &str)],
+    ) -> (StatusCode, HeaderMap, Value) {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .header("authorization", self.bearer(user_id));
+
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+
+        let body = body.map_or_else(Body::empty, |json| Body::from(json.to_string()));
+        let response = self.request(request.body(body).unwrap()).await;
+        let status = response.status();
+        let headers = response.headers().clone();
+        let bytes = body_bytes(response).await;
+
+        (
+            status,
+            headers,
+            serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+        )
+    }
+
+    /// Send a prepared request
+    pub async fn request(&self, request: Request<Body>) -> Response {
+        self.router.clone().oneshot(request).await.unwrap()
+    }
+}
+
+/// Migrated in-memory database
+pub async fn test_pool() -> SqlitePool {
+    db::create_test_pool().await.expect("Error creating pool")
+}
+
+/// Body of a response
+pub async fn body_bytes(response: Response) -> Bytes {
+    axum::body::to_bytes(response.into_body(), 1024 * 1024)
+        .await
+        .unwrap()
+}
+
+/// Body of a response, which must be JSON
+pub async fn json_body(response: Response) -> Value {
+    serde_json::from_slice(&body_bytes(response).await).unwrap()
+}
@@ -0,0 +1,319 @@
+//! Configuration loading tests
+//!
+//! Run with: `cargo test --test config_tests`
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    routing::{get, post},
+    Router,
+};
+use project_task_api::{
+    config::{AppConfig, ConfigArgs, ConfigError, LogFormat},
+    routes,
+};
+use tower::ServiceExt;
+
+/// Config file removed when the test ends
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new(name: &str, contents: &str) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("task-api-{}-{}.toml", name, std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        Self(path)
+    }
+
+    fn args(&self) -> ConfigArgs {
+        ConfigArgs {
+            config: Some(self.0.clone()),
+            ..ConfigArgs::default()
+        }
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Environment lookup backed by a fixed set of variables
+fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+    let vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+
+    move |name| vars.get(name).cloned()
+}
+
+const FILE: &str = r#"
+[server]
+bind = "127.0.0.1:8080"
+body_limit = 1024
+
+[database]
+url = "sqlite:file.db"
+max_connections = 10
+
+[cors]
+allowed_origins = ["https://app.example.com"]
+
+[log]
+format = "pretty"
+level = "debug"
+"#;
+
+// ============================================================
+// ===== Loading Tests =====
+// ============================================================
+
+#[test]
+fn test_defaults_without_any_source() {
+    let args = ConfigArgs {
+        config: Some(PathBuf::from("/nonexistent/config.toml")),
+        ..ConfigArgs::default()
+    };
+    assert!(matches!(
+        AppConfig::load(&args, env(&[])),
+        Err(ConfigError::Read { .. })
+    ));
+
+    let config = AppConfig::load(&ConfigArgs::default(), env(&[])).unwrap();
+
+    assert_eq!(config.server.bind.to_string(), "0.0.0.0:3000");
+    assert_eq!(config.server.body_limit, 2 * 1024 * 1024);
+    assert_eq!(config.database.url, "sqlite:tasks.db?mode=rwc");
+    assert_eq!(config.database.max_connections, 5);
+    assert!(config.cors.allows_any());
+    assert_eq!(config.log.format, LogFormat::Compact);
+}
+
+#[test]
+fn test_file_overrides_defaults() {
+    let file = TempFile::new("file", FILE);
+
+    let config = AppConfig::load(&file.args(), env(&[])).unwrap();
+
+    assert_eq!(config.server.bind.to_string(), "127.0.0.1:8080");
+    assert_eq!(config.server.body_limit, 1024);
+    assert_eq!(config.server.request_timeout_secs, 30);
+    assert_eq!(config.database.url, "sqlite:file.db");
+    assert_eq!(config.database.max_connections, 10);
+    assert_eq!(config.cors.allowed_origins, ["https://app.example.com"]);
+    assert_eq!(config.log.format, LogFormat::Pretty);
+    assert_eq!(config.log.level, "debug");
+}
+
+#[test]
+fn test_env_overrides_file_and_flags_override_env() {
+    let file = TempFile::new("precedence", FILE);
+    let vars = env(&[
+        ("TASK_API_BIND", "127.0.0.1:9000"),
+        ("TASK_API_MAX_CONNECTIONS", "20"),
+        ("DATABASE_URL", "sqlite:env.db"),
+        ("TASK_API_METRICS_ENABLED", "false"),
+        ("TASK_API_IDEMPOTENCY_TTL_SECS", "60"),
+        (
+            "TASK_API_CORS_ORIGINS",
+            "https://a.example.com, https://b.example.com",
+        ),
+    ]);
+
+    let config = AppConfig::load(&file.args(), &vars).unwrap();
+    assert_eq!(config.server.bind.to_string(), "127.0.0.1:9000");
+    assert_eq!(config.database.max_connections, 20);
+    assert_eq!(config.database.url, "sqlite:env.db");
+    assert!(!config.metrics.enabled);
+    assert_eq!(config.idempotency.ttl_secs, 60);
+    assert_eq!(
+        config.cors.allowed_origins,
+        ["https://a.example.com", "https://b.example.com"]
+    );
+    // Untouched by the environment
+    assert_eq!(config.server.body_limit, 1024);
+
+    let args = ConfigArgs {
+        bind: Some("127.0.0.1:9100".parse().unwrap()),
+        max_connections: Some(2),
+        log_format: Some(LogFormat::Full),
+        metrics: Some(true),
+        idempotency_ttl: Some(3600),
+        ..file.args()
+    };
+
+    let config = AppConfig::load(&args, &vars).unwrap();
+    assert_eq!(config.server.bind.to_string(), "127.0.0.1:9100");
+    assert_eq!(config.database.max_connections, 2);
+    assert_eq!(config.database.url, "sqlite:env.db");
+    assert_eq!(config.log.format, LogFormat::Full);
+    assert!(config.metrics.enabled);
+    assert_eq!(config.idempotency.ttl_secs, 3600);
+}
+
+#[test]
+fn test_config_file_from_env() {
+    let file = TempFile::new("env-path", FILE);
+    let path = file.0.to_str().unwrap();
+
+    let config =
+        AppConfig::load(&ConfigArgs::default(), env(&[("TASK_API_CONFIG", path)])).unwrap();
+
+    assert_eq!(config.database.max_connections, 10);
+}
+
+// ============================================================
+// ===== Validation Tests =====
+// ============================================================
+
+#[test]
+fn test_unknown_file_key_is_rejected() {
+    let file = TempFile::new("unknown", "[server]\nport = 3000\n");
+
+    let error = AppConfig::load(&file.args(), env(&[])).unwrap_err();
+
+    assert!(matches!(error, ConfigError::Parse { .. }));
+    assert!(error.to_string().contains("port"), "{}", error);
+}
+
+#[test]
+fn test_invalid_env_value_names_the_variable() {
+    let error = AppConfig::load(
+        &ConfigArgs::default(),
+        env(&[("TASK_API_MAX_CONNECTIONS", "many")]),
+    )
+    .unwrap_err();
+
+    assert!(matches!(
+        error,
+        ConfigError::Env {
+            var: "TASK_API_MAX_CONNECTIONS",
+            ..
+        }
+    ));
+
+    let error = AppConfig::load(
+        &ConfigArgs::default(),
+        env(&[("TASK_API_LOG_FORMAT", "xml")]),
+    )
+    .unwrap_err();
+    assert!(error.to_string().contains("TASK_API_LOG_FORMAT"));
+}
+
+#[test]
+fn test_validation_reports_every_problem() {
+    let file = TempFile::new(
+        "invalid",
+        r#"
+        [server]
+        body_limit = 0
+        request_timeout_secs = 0
+
+        [database]
+        url = "postgres://localhost/tasks"
+        max_connections = 0
+
+        [cors]
+        allowed_origins = ["*", "example.com"]
+
+        [rate_limit]
+        bulk = { burst = 0, per_minute = 10 }
+
+        [idempotency]
+        ttl_secs = 0
+
+        [log]
+        level = "loud"
+        "#,
+    );
+
+    let ConfigError::Invalid(errors) = AppConfig::load(&file.args(), env(&[])).unwrap_err() else {
+        panic!("Expected validation errors");
+    };
+
+    let expected = [
+        "server.body_limit",
+        "server.request_timeout_secs",
+        "database.url",
+        "database.max_connections",
+        "cannot mix",
+        "'example.com' is not an origin",
+        "rate_limit.bulk",
+        "idempotency.ttl_secs",
+        "log.level",
+    ];
+    assert_eq!(errors.len(), expected.len(), "{:?}", errors);
+    for (error, expected) in errors.iter().zip(expected) {
+        assert!(
+            error.contains(expected),
+            "{} should mention {}",
+            error,
+            expected
+        );
+    }
+}
+
+// ============================================================
+// ===== Middleware Tests =====
+// ============================================================
+
+/// Small app with the configured middleware
+fn app(config: &AppConfig) -> Router {
+    let router = Router::new()
+        .route("/echo", post(|body: String| async move { body }))
+        .route("/ping", get(|| async { "pong" }));
+
+    routes::with_middleware(router, config)
+}
+
+#[tokio::test]
+async fn test_body_limit_is_applied() {
+    let mut config = AppConfig::default();
+    config.server.body_limit = 16;
+
+    let small = Request::post("/echo").body(Body::from("short")).unwrap();
+    let response = app(&config).oneshot(small).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let large = Request::post("/echo")
+        .body(Body::from("x".repeat(17)))
+        .unwrap();
+    let response = app(&config).oneshot(large).await.unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[tokio::test]
+async fn test_cors_only_allows_configured_origins() {
+    let mut config = AppConfig::default();
+    config.cors.allowed_origins = vec!["https://app.example.com".to_string()];
+
+    let request = |origin: &str| {
+        Request::get("/ping")
+            .header("origin", origin)
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let response = app(&config)
+        .oneshot(request("https://app.example.com"))
+        .await
+        .unwrap();
+    assert_eq!(
+        response.headers()["access-control-allow-origin"],
+        "https://app.example.com"
+    );
+
+    let response = app(&config)
+        .oneshot(request("https://evil.example.com"))
+        .await
+        .unwrap();
+    assert!(response
+        .headers()
+        .get("access-control-allow-origin")
+        .is_none());
+}
@@ -0,0 +1,342 @@
+//! Live change feed tests (SSE and WebSocket)
+//!
+//! Run with: `cargo test --test event_tests`
+
+mod common;
+
+use std::time::Duration;
+
+use axum::{
+    body::{Body, BodyDataStream},
+    http::{Request, StatusCode},
+    Router,
+};
+use common::TestApp;
+use futures_util::StreamExt;
+use project_task_api::events::EventBus;
+use project_task_api::models::{ChangeKind, Task};
+use serde_json::{json, Value};
+use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Message};
+
+/// How long to wait for an event before failing
+const EVENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Open `GET /tasks/events` as user 1
+async fn subscribe(app: &TestApp, query: &str) -> SseReader {
+    let request = Request::builder()
+        .uri(format!("/tasks/events{}", query))
+        .header("authorization", app.bearer(1))
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.request(request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "text/event-stream");
+
+    SseReader {
+        body: response.into_body().into_data_stream(),
+        buffer: String::new(),
+    }
+}
+
+/// Splits an SSE body into events
+struct SseReader {
+    body: BodyDataStream,
+    buffer: String,
+}
+
+impl SseReader {
+    /// Next event name and data, skipping keep-alive comments
+    async fn next(&mut self) -> (String, Value) {
+        loop {
+            if let Some(end) = self.buffer.find("\n\n") {
+                let block: String = self.buffer.drain(..end + 2).collect();
+                let field = |name: &str| {
+                    block
+                        .lines()
+                        .find_map(|line| line.strip_prefix(name))
+                        .map(str::to_string)
+                };
+
+                if let (Some(event), Some(data)) = (field("event: "), field("data: ")) {
+                    return (event, serde_json::from_str(&data).unwrap());
+                }
+                continue;
+            }
+
+            let chunk = tokio::time::timeout(EVENT_TIMEOUT, self.body.next())
+                .await
+                .expect("No event received")
+                .expect("Stream ended")
+                .unwrap();
+            self.buffer.push_str(std::str::from_utf8(&chunk).unwrap());
+        }
+    }
+}
+
+// ============================================================
+// ===== Server-Sent Events Tests =====
+// ============================================================
+
+#[tokio::test]
+async fn test_sse_streams_task_changes() {
+    let app = TestApp::new().await;
+    let mut events = subscribe(&app, "").await;
+
+    let (_, task) = app
+        .send(1, "POST", "/tasks", Some(json!({"title": "Live"})))
+        .await;
+    let id = task["id"].as_i64().unwrap();
+    let uri = format!("/tasks/{}", id);
+    app.send(1, "PATCH", &uri, Some(json!({"completed": true})))
+        .await;
+    app.send(1, "DELETE", &uri, None).await;
+
+    let (name, data) = events.next().await;
+    assert_eq!(name, "task.created");
+    assert_eq!(data["type"], "task.created");
+    assert_eq!(data["task"]["title"], "Live");
+
+    let (name, data) = events.next().await;
+    assert_eq!(name, "task.updated");
+    assert_eq!(data["task"]["completed"], true);
+    assert_eq!(data["task"]["version"], 2);
+
+    let (name, data) = events.next().await;
+    assert_eq!(name, "task.deleted");
+    assert_eq!(data["task"]["id"], id);
+}
+
+#[tokio::test]
+async fn test_sse_filters_by_id_and_completed() {
+    let app = TestApp::new().await;
+
+    let (_, first) = app
+        .send(1, "POST", "/tasks", Some(json!({"title": "First"})))
+        .await;
+    let (_, second) = app
+        .send(1, "POST", "/tasks", Some(json!({"title": "Second"})))
+        .await;
+    let first_uri = format!("/tasks/{}", first["id"]);
+    let second_uri = format!("/tasks/{}", second["id"]);
+
+    let mut by_id = subscribe(&app, &format!("?id={}", second["id"])).await;
+    let mut completed = subscribe(&app, "?completed=true").await;
+
+    app.send(
+        1,
+        "PATCH",
+        &first_uri,
+        Some(json!({"title": "First again"})),
+    )
+    .await;
+    app.send(1, "PATCH", &first_uri, Some(json!({"completed": true})))
+        .await;
+    app.send(
+        1,
+        "PATCH",
+        &second_uri,
+        Some(json!({"title": "Second again"})),
+    )
+    .await;
+
+    let (_, data) = by_id.next().await;
+    assert_eq!(data["task"]["title"], "Second again");
+
+    let (_, data) = completed.next().await;
+    assert_eq!(data["task"]["title"], "First again");
+    assert_eq!(data["task"]["completed"], true);
+}
+
+#[tokio::test]
+async fn test_sse_only_sends_own_tasks() {
+    let app = TestApp::new().await;
+    let mut events = subscribe(&app, "").await;
+
+    app.send(2, "POST", "/tasks", Some(json!({"title": "Not mine"})))
+        .await;
+    app.send(1, "POST", "/tasks", Some(json!({"title": "Mine"})))
+        .await;
+
+    let (_, data) = events.next().await;
+    assert_eq!(data["task"]["title"], "Mine");
+}
+
+#[tokio::test]
+async fn test_sse_skips_failed_changes() {
+    let app = TestApp::new().await;
+    let mut events = subscribe(&app, "").await;
+
+    let (status, _) = app
+        .send(1, "POST", "/tasks", Some(json!({"title": ""})))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = app.send(1, "DELETE", "/tasks/999", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    app.send(1, "POST", "/tasks", Some(json!({"title": "Valid"})))
+        .await;
+
+    let (name, data) = events.next().await;
+    assert_eq!(name, "task.created");
+    assert_eq!(data["task"]["title"], "Valid");
+}
+
+#[tokio::test]
+async fn test_sse_announces_bulk_changes_once_committed() {
+    let app = TestApp::new().await;
+    let mut events = subscribe(&app, "").await;
+
+    let rolled_back = json!({"operations": [
+        {"op": "create", "task": {"title": "Rolled back"}},
+        {"op": "delete", "id": 999}
+    ]});
+    let (status, _) = app.send(1, "POST", "/tasks/bulk", Some(rolled_back)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let committed = json!({"operations": [
+        {"op": "create", "task": {"title": "Bulk"}}
+    ]});
+    let (status, _) = app.send(1, "POST", "/tasks/bulk", Some(committed)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (name, data) = events.next().await;
+    assert_eq!(name, "task.created");
+    assert_eq!(data["task"]["title"], "Bulk");
+}
+
+#[tokio::test]
+async fn test_sse_requires_token() {
+    let app = TestApp::new().await;
+
+    let request = Request::builder()
+        .uri("/tasks/events")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.request(request).await;
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+// ============================================================
+// ===== WebSocket Tests =====
+// ============================================================
+
+/// Serve the app on an ephemeral port, returning its address
+async fn serve(app: Router) -> std::net::SocketAddr {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    address
+}
+
+#[tokio::test]
+async fn test_websocket_streams_task_changes() {
+    let app = TestApp::new().await;
+    let address = serve(app.router.clone()).await;
+
+    let mut request = format!("ws://{}/ws?completed=true", address)
+        .into_client_request()
+        .unwrap();
+    request
+        .headers_mut()
+        .insert("authorization", app.bearer(1).parse().unwrap());
+    let (mut socket, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+
+    let (_, task) = app
+        .send(1, "POST", "/tasks", Some(json!({"title": "Socket"})))
+        .await;
+    let uri = format!("/tasks/{}", task["id"]);
+    app.send(1, "PATCH", &uri, Some(json!({"completed": true})))
+        .await;
+
+    let message = tokio::time::timeout(EVENT_TIMEOUT, socket.next())
+        .await
+        .expect("No message received")
+        .unwrap()
+        .unwrap();
+    let Message::Text(text) = message else {
+        panic!("Expected a text message, got {:?}", message);
+    };
+    let data: Value = serde_json::from_str(&text).unwrap();
+
+    assert_eq!(data["type"], "task.updated");
+    assert_eq!(data["task"]["title"], "Socket");
+    assert_eq!(data["task"]["completed"], true);
+}
+
+#[tokio::test]
+async fn test_websocket_requires_token() {
+    let address = serve(TestApp::new().await.router).await;
+
+    let error = tokio_tungstenite::connect_async(format!("ws://{}/ws", address))
+        .await
+        .unwrap_err();
+
+    let tokio_tungstenite::tungstenite::Error::Http(response) = error else {
+        panic!("Expected an HTTP error, got {:?}", error);
+    };
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+// ============================================================
+// ===== Shutdown Tests =====
+// ============================================================
+
+#[tokio::test]
+async fn test_closing_the_bus_ends_open_feeds() {
+    let app = TestApp::new().await;
+    let address = serve(app.router.clone()).await;
+
+    let mut sse = subscribe(&app, "").await;
+    let mut request = format!("ws://{}/ws", address)
+        .into_client_request()
+        .unwrap();
+    request
+        .headers_mut()
+        .insert("authorization", app.bearer(1).parse().unwrap());
+    let (mut socket, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+
+    app.events.close();
+
+    let end = tokio::time::timeout(EVENT_TIMEOUT, sse.body.next())
+        .await
+        .expect("SSE feed still open");
+    assert!(end.is_none());
+
+    let message = tokio::time::timeout(EVENT_TIMEOUT, socket.next())
+        .await
+        .expect("WebSocket still open")
+        .unwrap()
+        .unwrap();
+    let Message::Close(Some(frame)) = message else {
+        panic!("Expected a close frame, got {:?}", message);
+    };
+    assert_eq!(u16::from(frame.code), 1001);
+}
+
+// ============================================================
+// ===== Queue Tests =====
+// ============================================================
+
+#[tokio::test]
+async fn test_publishing_does_not_wait_for_the_queue_consumer() {
+    let app = TestApp::new().await;
+    let (_, body) = app
+        .send(1, "POST", "/tasks", Some(json!({"title": "Queued"})))
+        .await;
+    let task: Task = serde_json::from_value(body).unwrap();
+
+    let events = EventBus::new();
+    let queue = events.queue_all();
+
+    // Far more changes than any channel buffer, with nobody reading yet
+    for _ in 0..5000 {
+        events.publish(1, ChangeKind::Updated, task.clone());
+    }
+
+    let received = tokio::time::timeout(EVENT_TIMEOUT, queue.take(5000).count())
+        .await
+        .expect("Queued changes missing");
+    assert_eq!(received, 5000);
+}
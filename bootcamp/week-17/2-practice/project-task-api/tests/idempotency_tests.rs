@@ -0,0 +1,322 @@
+//! Idempotency-Key tests
+//!
+//! Run with: `cargo test --test idempotency_tests`
+
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    response::Response,
+};
+use common::{json_body, TestApp};
+use project_task_api::{auth::AuthConfig, config::AppConfig, db::DbPool, idempotency, routes};
+use serde_json::{json, Value};
+
+/// Application with users 1 and 2 behind the idempotency layer
+async fn create_app(config: &AppConfig) -> TestApp {
+    TestApp::with_layers(|app, pool| {
+        let auth = AuthConfig::new(common::TEST_SECRET);
+        routes::with_idempotency(app, config, pool.clone(), auth)
+    })
+    .await
+}
+
+/// A JSON `POST` as `user_id`, with `key` if given
+fn post(app: &TestApp, uri: &str, user_id: i64, key: Option<&str>, body: Value) -> Request<Body> {
+    let mut request = Request::post(uri)
+        .header("authorization", app.bearer(user_id))
+        .header("content-type", "application/json");
+
+    if let Some(key) = key {
+        request = request.header("idempotency-key", key);
+    }
+
+    request.body(Body::from(body.to_string())).unwrap()
+}
+
+fn replayed(response: &Response) -> bool {
+    response
+        .headers()
+        .get("idempotent-replayed")
+        .is_some_and(|value| value == "true")
+}
+
+async fn task_count(pool: &DbPool) -> i64 {
+    sqlx::query_scalar("SELECT COUNT(*) FROM tasks")
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+// ============================================================
+// ===== Replay Tests =====
+// ============================================================
+
+#[tokio::test]
+async fn test_repeated_key_replays_response() {
+    let app = create_app(&AppConfig::default()).await;
+    let body = json!({"title": "Buy milk"});
+
+    let first = app
+        .request(post(&app, "/tasks", 1, Some("retry-1"), body.clone()))
+        .await;
+    assert_eq!(first.status(), StatusCode::CREATED);
+    assert!(!replayed(&first));
+    let etag = first.headers()["etag"].clone();
+    let created = json_body(first).await;
+
+    let second = app
+        .request(post(&app, "/tasks", 1, Some("retry-1"), body))
+        .await;
+    assert_eq!(second.status(), StatusCode::CREATED);
+    assert!(replayed(&second));
+    assert_eq!(second.headers()["etag"], etag);
+    assert_eq!(second.headers()["content-type"], "application/json");
+    assert_eq!(json_body(second).await, created);
+
+    assert_eq!(task_count(app.pool()).await, 1);
+}
+
+#[tokio::test]
+async fn test_bulk_request_is_replayed() {
+    let app = create_app(&AppConfig::default()).await;
+    let body = json!({"operations": [
+        {"op": "create", "task": {"title": "One"}},
+        {"op": "create", "task": {"title": "Two"}}
+    ]});
+
+    let first = app
+        .request(post(&app, "/tasks/bulk", 1, Some("bulk-1"), body.clone()))
+        .await;
+    assert_eq!(first.status(), StatusCode::OK);
+    let results = json_body(first).await;
+
+    let second = app
+        .request(post(&app, "/tasks/bulk", 1, Some("bulk-1"), body))
+        .await;
+    assert_eq!(second.status(), StatusCode::OK);
+    assert!(replayed(&second));
+    assert_eq!(json_body(second).await, results);
+
+    assert_eq!(task_count(app.pool()).await, 2);
+}
+
+#[tokio::test]
+async fn test_client_errors_are_replayed() {
+    let app = create_app(&AppConfig::default()).await;
+    let body = json!({"title": ""});
+
+    let first = app
+        .request(post(&app, "/tasks", 1, Some("bad-1"), body.clone()))
+        .await;
+    assert_eq!(first.status(), StatusCode::BAD_REQUEST);
+
+    let second = app
+        .request(post(&app, "/tasks", 1, Some("bad-1"), body))
+        .await;
+    assert_eq!(second.status(), StatusCode::BAD_REQUEST);
+    assert!(replayed(&second));
+    assert_eq!(second.headers()["content-type"], "application/problem+json");
+
+    assert_eq!(task_count(app.pool()).await, 0);
+}
+
+#[tokio::test]
+async fn test_keys_are_per_user() {
+    let app = create_app(&AppConfig::default()).await;
+    let body = json!({"title": "Same key"});
+
+    app.request(post(&app, "/tasks", 1, Some("shared"), body.clone()))
+        .await;
+    let response = app
+        .request(post(&app, "/tasks", 2, Some("shared"), body))
+        .await;
+
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert!(!replayed(&response));
+    assert_eq!(task_count(app.pool()).await, 2);
+}
+
+#[tokio::test]
+async fn test_requests_without_key_are_not_deduplicated() {
+    let app = create_app(&AppConfig::default()).await;
+
+    app.request(post(&app, "/tasks", 1, None, json!({"title": "Twice"})))
+        .await;
+    app.request(post(&app, "/tasks", 1, None, json!({"title": "Twice"})))
+        .await;
+
+    assert_eq!(task_count(app.pool()).await, 2);
+}
+
+#[tokio::test]
+async fn test_disabled_idempotency_ignores_key() {
+    let mut config = AppConfig::default();
+    config.idempotency.enabled = false;
+    let app = create_app(&config).await;
+    let body = json!({"title": "Twice"});
+
+    app.request(post(&app, "/tasks", 1, Some("retry-1"), body.clone()))
+        .await;
+    let response = app
+        .request(post(&app, "/tasks", 1, Some("retry-1"), body))
+        .await;
+
+    assert!(!replayed(&response));
+    assert_eq!(task_count(app.pool()).await, 2);
+}
+
+// ============================================================
+// ===== Rejection Tests =====
+// ============================================================
+
+#[tokio::test]
+async fn test_reused_key_with_different_body_gets_422() {
+    let app = create_app(&AppConfig::default()).await;
+
+    app.request(post(
+        &app,
+        "/tasks",
+        1,
+        Some("retry-1"),
+        json!({"title": "First"}),
+    ))
+    .await;
+    let response = app
+        .request(post(
+            &app,
+            "/tasks",
+            1,
+            Some("retry-1"),
+            json!({"title": "Second"}),
+        ))
+        .await;
+
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let problem = json_body(response).await;
+    assert_eq!(problem["status"], 422);
+    assert_eq!(problem["instance"], "/tasks");
+    assert!(problem["detail"]
+        .as_str()
+        .unwrap()
+        .contains("different request"));
+
+    assert_eq!(task_count(app.pool()).await, 1);
+}
+
+#[tokio::test]
+async fn test_key_still_in_progress_gets_409() {
+    let app = create_app(&AppConfig::default()).await;
+
+    sqlx::query(
+        "INSERT INTO idempotency_keys (user_id, key, fingerprint, expires_at) VALUES (1, 'running', '', ?)",
+    )
+    .bind(i64::MAX)
+    .execute(app.pool())
+    .await
+    .unwrap();
+
+    let response = app
+        .request(post(
+            &app,
+            "/tasks",
+            1,
+            Some("running"),
+            json!({"title": "Retry"}),
+        ))
+        .await;
+
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    assert_eq!(task_count(app.pool()).await, 0);
+}
+
+#[tokio::test]
+async fn test_oversized_key_is_rejected() {
+    let app = create_app(&AppConfig::default()).await;
+    let key = "k".repeat(256);
+
+    let response = app
+        .request(post(
+            &app,
+            "/tasks",
+            1,
+            Some(&key),
+            json!({"title": "Task"}),
+        ))
+        .await;
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(task_count(app.pool()).await, 0);
+}
+
+// ============================================================
+// ===== Expiry Tests =====
+// ============================================================
+
+#[tokio::test]
+async fn test_expired_key_can_be_reused() {
+    let app = create_app(&AppConfig::default()).await;
+
+    app.request(post(
+        &app,
+        "/tasks",
+        1,
+        Some("old"),
+        json!({"title": "First"}),
+    ))
+    .await;
+    sqlx::query("UPDATE idempotency_keys SET expires_at = 0")
+        .execute(app.pool())
+        .await
+        .unwrap();
+
+    // Not removed yet, but no longer binding
+    let response = app
+        .request(post(
+            &app,
+            "/tasks",
+            1,
+            Some("old"),
+            json!({"title": "Second"}),
+        ))
+        .await;
+
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert!(!replayed(&response));
+    assert_eq!(task_count(app.pool()).await, 2);
+}
+
+#[tokio::test]
+async fn test_cleanup_removes_only_expired_keys() {
+    let app = create_app(&AppConfig::default()).await;
+
+    app.request(post(
+        &app,
+        "/tasks",
+        1,
+        Some("old"),
+        json!({"title": "Old"}),
+    ))
+    .await;
+    app.request(post(
+        &app,
+        "/tasks",
+        1,
+        Some("new"),
+        json!({"title": "New"}),
+    ))
+    .await;
+    sqlx::query("UPDATE idempotency_keys SET expires_at = 0 WHERE key = 'old'")
+        .execute(app.pool())
+        .await
+        .unwrap();
+
+    assert_eq!(idempotency::remove_expired(app.pool()).await.unwrap(), 1);
+
+    let keys: Vec<String> = sqlx::query_scalar("SELECT key FROM idempotency_keys")
+        .fetch_all(app.pool())
+        .await
+        .unwrap();
+    assert_eq!(keys, ["new"]);
+}
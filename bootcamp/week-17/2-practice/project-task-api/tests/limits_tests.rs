@@ -0,0 +1,308 @@
+//! Rate limit and body size limit tests
+//!
+//! Run with: `cargo test --test limits_tests`
+
+mod common;
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use axum::{
+    body::Body,
+    extract::ConnectInfo,
+    http::{Request, StatusCode},
+    response::Response,
+};
+use common::{json_body, TestApp};
+use project_task_api::{
+    auth::AuthConfig,
+    config::{AppConfig, RateLimit, RateLimitConfig},
+    routes,
+};
+
+/// Limits small enough to run out of in a test
+fn tight_limits() -> RateLimitConfig {
+    RateLimitConfig {
+        auth: RateLimit::new(1, 1),
+        bulk: RateLimit::new(1, 1),
+        default: RateLimit::new(2, 60),
+        ..RateLimitConfig::default()
+    }
+}
+
+/// Application with users 1 and 2 behind the limiter
+async fn create_app(config: RateLimitConfig) -> TestApp {
+    TestApp::with_layers(|app, _| {
+        routes::with_rate_limit(app, &config, AuthConfig::new(common::TEST_SECRET))
+    })
+    .await
+}
+
+/// A request from `ip`, as `user_id` if given
+fn request(app: &TestApp, uri: &str, ip: [u8; 4], user_id: Option<i64>) -> Request<Body> {
+    let mut request = Request::get(uri);
+
+    if let Some(user_id) = user_id {
+        request = request.header("authorization", app.bearer(user_id));
+    }
+
+    let mut request = request.body(Body::empty()).unwrap();
+    request
+        .extensions_mut()
+        .insert(ConnectInfo(SocketAddr::from((ip, 40000))));
+
+    request
+}
+
+fn header(response: &Response, name: &str) -> Option<String> {
+    response
+        .headers()
+        .get(name)
+        .map(|value| value.to_str().unwrap().to_string())
+}
+
+const CLIENT: [u8; 4] = [10, 0, 0, 1];
+const OTHER_CLIENT: [u8; 4] = [10, 0, 0, 2];
+
+// ============================================================
+// ===== Rate Limit Tests =====
+// ============================================================
+
+#[tokio::test]
+async fn test_rate_limit_headers_on_allowed_requests() {
+    let app = create_app(tight_limits()).await;
+
+    let response = app.request(request(&app, "/tasks", CLIENT, Some(1))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(header(&response, "ratelimit-limit").unwrap(), "2");
+    assert_eq!(header(&response, "ratelimit-remaining").unwrap(), "1");
+    assert_eq!(header(&response, "ratelimit-reset").unwrap(), "1");
+    assert!(header(&response, "retry-after").is_none());
+
+    let response = app.request(request(&app, "/tasks", CLIENT, Some(1))).await;
+    assert_eq!(header(&response, "ratelimit-remaining").unwrap(), "0");
+    assert_eq!(header(&response, "ratelimit-reset").unwrap(), "2");
+}
+
+#[tokio::test]
+async fn test_over_limit_gets_429() {
+    let app = create_app(tight_limits()).await;
+
+    app.request(request(&app, "/tasks", CLIENT, Some(1))).await;
+    app.request(request(&app, "/tasks", CLIENT, Some(1))).await;
+    let response = app.request(request(&app, "/tasks", CLIENT, Some(1))).await;
+
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(header(&response, "retry-after").unwrap(), "1");
+    assert_eq!(header(&response, "ratelimit-limit").unwrap(), "2");
+    assert_eq!(header(&response, "ratelimit-remaining").unwrap(), "0");
+
+    let body = json_body(response).await;
+    assert_eq!(body["status"], 429);
+    assert_eq!(body["instance"], "/tasks");
+    assert!(body["detail"].as_str().unwrap().contains("Rate limit"));
+}
+
+#[tokio::test]
+async fn test_tokens_refill_over_time() {
+    let app = create_app(tight_limits()).await;
+    // Anonymous requests are refused before touching the database, which
+    // does not work with paused time
+    tokio::time::pause();
+
+    app.request(request(&app, "/tasks", CLIENT, None)).await;
+    app.request(request(&app, "/tasks", CLIENT, None)).await;
+    let response = app.request(request(&app, "/tasks", CLIENT, None)).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    // 60 per minute: one token per second
+    tokio::time::advance(Duration::from_secs(1)).await;
+
+    let response = app.request(request(&app, "/tasks", CLIENT, None)).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = app.request(request(&app, "/tasks", CLIENT, None)).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn test_limits_are_per_user_and_per_ip() {
+    let app = create_app(tight_limits()).await;
+
+    // User 1 spends their tokens from two different addresses
+    app.request(request(&app, "/tasks", CLIENT, Some(1))).await;
+    app.request(request(&app, "/tasks", OTHER_CLIENT, Some(1)))
+        .await;
+    let response = app
+        .request(request(&app, "/tasks", OTHER_CLIENT, Some(1)))
+        .await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    // User 2 on the same address is unaffected
+    let response = app.request(request(&app, "/tasks", CLIENT, Some(2))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Anonymous requests count against their address
+    app.request(request(&app, "/tasks", CLIENT, None)).await;
+    app.request(request(&app, "/tasks", CLIENT, None)).await;
+    let response = app.request(request(&app, "/tasks", CLIENT, None)).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let response = app
+        .request(request(&app, "/tasks", OTHER_CLIENT, None))
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_route_groups_have_separate_limits() {
+    let app = create_app(tight_limits()).await;
+
+    let response = app
+        .request(request(&app, "/tasks/export", CLIENT, Some(1)))
+        .await;
+    assert_eq!(header(&response, "ratelimit-limit").unwrap(), "1");
+    let response = app
+        .request(request(&app, "/tasks/export", CLIENT, Some(1)))
+        .await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    // The default group still has its own tokens
+    let response = app.request(request(&app, "/tasks", CLIENT, Some(1))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(header(&response, "ratelimit-limit").unwrap(), "2");
+}
+
+#[tokio::test]
+async fn test_auth_routes_limited_by_ip_even_with_token() {
+    let app = create_app(tight_limits()).await;
+
+    app.request(request(&app, "/auth/login", CLIENT, Some(1)))
+        .await;
+    let response = app
+        .request(request(&app, "/auth/login", CLIENT, Some(2)))
+        .await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    let response = app
+        .request(request(&app, "/auth/login", OTHER_CLIENT, Some(1)))
+        .await;
+    assert_ne!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn test_health_routes_are_not_limited() {
+    let app = create_app(tight_limits()).await;
+
+    for _ in 0..5 {
+        let response = app
+            .request(request(&app, "/health/live", CLIENT, None))
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(header(&response, "ratelimit-limit").is_none());
+    }
+}
+
+#[tokio::test]
+async fn test_forwarded_for_only_when_trusted() {
+    let forwarded = |app: &TestApp, ip: &str| {
+        let mut request = request(app, "/tasks", CLIENT, None);
+        request.headers_mut().insert(
+            "x-forwarded-for",
+            format!("{}, 10.0.0.1", ip).parse().unwrap(),
+        );
+        request
+    };
+
+    // Ignored by default: every request comes from CLIENT
+    let app = create_app(tight_limits()).await;
+    app.request(forwarded(&app, "203.0.113.1")).await;
+    app.request(forwarded(&app, "203.0.113.2")).await;
+    let response = app.request(forwarded(&app, "203.0.113.3")).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    let app = create_app(RateLimitConfig {
+        trust_forwarded_for: true,
+        ..tight_limits()
+    })
+    .await;
+    app.request(forwarded(&app, "203.0.113.1")).await;
+    app.request(forwarded(&app, "203.0.113.1")).await;
+    let response = app.request(forwarded(&app, "203.0.113.2")).await;
+    assert_ne!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn test_rate_limit_can_be_disabled() {
+    let app = create_app(RateLimitConfig {
+        enabled: false,
+        ..tight_limits()
+    })
+    .await;
+
+    for _ in 0..5 {
+        let response = app.request(request(&app, "/tasks", CLIENT, Some(1))).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(header(&response, "ratelimit-limit").is_none());
+    }
+}
+
+// ============================================================
+// ===== Body Limit Tests =====
+// ============================================================
+
+/// Task routes with a 64-byte body limit
+async fn create_limited_app() -> TestApp {
+    let mut config = AppConfig::default();
+    config.server.body_limit = 64;
+
+    TestApp::with_layers(|app, _| routes::with_middleware(app, &config)).await
+}
+
+fn post_task(app: &TestApp, body: Body) -> Request<Body> {
+    Request::post("/tasks")
+        .header("content-type", "application/json")
+        .header("authorization", app.bearer(1))
+        .body(body)
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_oversized_body_rejected_as_json() {
+    let app = create_limited_app().await;
+    let title = "x".repeat(100);
+
+    let response = app
+        .request(post_task(
+            &app,
+            Body::from(format!(r#"{{"title":"{}"}}"#, title)),
+        ))
+        .await;
+
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(
+        header(&response, "content-type").unwrap(),
+        "application/problem+json"
+    );
+    let body = json_body(response).await;
+    assert_eq!(body["status"], 413);
+    assert_eq!(body["instance"], "/tasks");
+    assert_eq!(body["detail"], "Request body exceeds the limit of 64 bytes");
+}
+
+#[tokio::test]
+async fn test_oversized_body_without_length_rejected_as_json() {
+    let app = create_limited_app().await;
+    let chunks = ["{\"title\":\"", &"x".repeat(100), "\"}"]
+        .map(|chunk| Ok::<_, std::io::Error>(chunk.to_string()));
+
+    let response = app
+        .request(post_task(
+            &app,
+            Body::from_stream(futures_util::stream::iter(chunks)),
+        ))
+        .await;
+
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    let body = json_body(response).await;
+    assert_eq!(body["status"], 413);
+    assert_eq!(body["detail"], "Request body exceeds the limit of 64 bytes");
+}
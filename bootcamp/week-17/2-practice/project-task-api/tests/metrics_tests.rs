@@ -0,0 +1,253 @@
+//! Prometheus metrics tests
+//!
+//! Run with: `cargo test --test metrics_tests`
+
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use common::{body_bytes, TestApp};
+use project_task_api::{config::MetricsConfig, routes};
+use serde_json::{json, Value};
+
+/// Application with users 1 and 2
+async fn create_app(enabled: bool) -> TestApp {
+    TestApp::with_layers(|app, pool| {
+        routes::with_metrics(app, &MetricsConfig { enabled }, pool.clone())
+    })
+    .await
+}
+
+/// Helper to send a JSON request, as `user_id` if given
+async fn send(
+    app: &TestApp,
+    user_id: Option<i64>,
+    method: &str,
+    uri: &str,
+    body: Option<Value>,
+) -> (StatusCode, String) {
+    let mut request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("content-type", "application/json");
+
+    if let Some(user_id) = user_id {
+        request = request.header("authorization", app.bearer(user_id));
+    }
+
+    let body = body.map_or_else(Body::empty, |json| Body::from(json.to_string()));
+    let response = app.request(request.body(body).unwrap()).await;
+    let status = response.status();
+    let bytes = body_bytes(response).await;
+
+    (status, String::from_utf8(bytes.to_vec()).unwrap())
+}
+
+/// Scrape `/metrics`
+async fn scrape(app: &TestApp) -> String {
+    let (status, body) = send(app, None, "GET", "/metrics", None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    body
+}
+
+/// Value of the sample `series` (name with labels), if present
+fn sample(metrics: &str, series: &str) -> Option<f64> {
+    metrics.lines().find_map(|line| {
+        let value = line.strip_prefix(series)?.strip_prefix(' ')?;
+        Some(value.parse().unwrap())
+    })
+}
+
+// ============================================================
+// ===== Request Metrics Tests =====
+// ============================================================
+
+#[tokio::test]
+async fn test_requests_counted_per_route_and_status() {
+    let app = create_app(true).await;
+
+    send(&app, Some(1), "GET", "/tasks", None).await;
+    send(&app, Some(1), "GET", "/tasks", None).await;
+    send(&app, None, "GET", "/tasks", None).await;
+    send(&app, Some(1), "GET", "/tasks/42", None).await;
+    send(&app, Some(1), "GET", "/tasks/43", None).await;
+    send(&app, None, "GET", "/no/such/route", None).await;
+
+    let metrics = scrape(&app).await;
+    let requests = |labels: &str| {
+        sample(
+            &metrics,
+            &format!("task_api_http_requests_total{{{}}}", labels),
+        )
+    };
+
+    assert_eq!(
+        requests(r#"method="GET",route="/tasks",status="200""#),
+        Some(2.0)
+    );
+    assert_eq!(
+        requests(r#"method="GET",route="/tasks",status="401""#),
+        Some(1.0)
+    );
+    // Labelled by route pattern, not by path
+    assert_eq!(
+        requests(r#"method="GET",route="/tasks/{id}",status="404""#),
+        Some(2.0)
+    );
+    assert_eq!(
+        requests(r#"method="GET",route="unmatched",status="404""#),
+        Some(1.0)
+    );
+    assert!(!metrics.contains("/tasks/42"));
+}
+
+#[tokio::test]
+async fn test_latency_histogram_by_status() {
+    let app = create_app(true).await;
+
+    send(
+        &app,
+        Some(1),
+        "POST",
+        "/tasks",
+        Some(json!({"title": "Timed"})),
+    )
+    .await;
+    send(&app, Some(1), "POST", "/tasks", Some(json!({"title": ""}))).await;
+
+    let metrics = scrape(&app).await;
+    let count = |status: &str| {
+        sample(
+            &metrics,
+            &format!(
+                r#"task_api_http_request_duration_seconds_count{{method="POST",route="/tasks",status="{}"}}"#,
+                status
+            ),
+        )
+    };
+
+    assert_eq!(count("201"), Some(1.0));
+    assert_eq!(count("400"), Some(1.0));
+    assert_eq!(
+        sample(
+            &metrics,
+            r#"task_api_http_request_duration_seconds_bucket{method="POST",route="/tasks",status="201",le="+Inf"}"#
+        ),
+        Some(1.0)
+    );
+}
+
+// ============================================================
+// ===== Gauge Tests =====
+// ============================================================
+
+#[tokio::test]
+async fn test_task_counts_cover_every_user() {
+    let app = create_app(true).await;
+
+    let (_, body) = send(
+        &app,
+        Some(1),
+        "POST",
+        "/tasks",
+        Some(json!({"title": "Done", "priority": "high"})),
+    )
+    .await;
+    let done: Value = serde_json::from_str(&body).unwrap();
+    send(
+        &app,
+        Some(1),
+        "PATCH",
+        &format!("/tasks/{}", done["id"]),
+        Some(json!({"completed": true})),
+    )
+    .await;
+    send(
+        &app,
+        Some(2),
+        "POST",
+        "/tasks",
+        Some(json!({"title": "Late", "due_at": "2020-01-01T00:00:00Z"})),
+    )
+    .await;
+    let (_, body) = send(
+        &app,
+        Some(2),
+        "POST",
+        "/tasks",
+        Some(json!({"title": "Gone"})),
+    )
+    .await;
+    let gone: Value = serde_json::from_str(&body).unwrap();
+    send(
+        &app,
+        Some(2),
+        "DELETE",
+        &format!("/tasks/{}", gone["id"]),
+        None,
+    )
+    .await;
+
+    let metrics = scrape(&app).await;
+    let tasks = |state: &str| sample(&metrics, &format!(r#"task_api_tasks{{state="{}"}}"#, state));
+
+    assert_eq!(tasks("total"), Some(2.0));
+    assert_eq!(tasks("completed"), Some(1.0));
+    assert_eq!(tasks("pending"), Some(1.0));
+    assert_eq!(tasks("overdue"), Some(1.0));
+    assert_eq!(
+        sample(&metrics, r#"task_api_tasks_by_priority{priority="high"}"#),
+        Some(1.0)
+    );
+    assert_eq!(
+        sample(&metrics, r#"task_api_tasks_by_priority{priority="medium"}"#),
+        Some(1.0)
+    );
+}
+
+#[tokio::test]
+async fn test_pool_usage_reported() {
+    let app = create_app(true).await;
+
+    let metrics = scrape(&app).await;
+
+    // The in-memory database keeps a single connection
+    assert_eq!(
+        sample(&metrics, "task_api_db_pool_max_connections"),
+        Some(1.0)
+    );
+    let idle = sample(&metrics, r#"task_api_db_pool_connections{state="idle"}"#).unwrap();
+    let in_use = sample(&metrics, r#"task_api_db_pool_connections{state="in_use"}"#).unwrap();
+    assert_eq!(idle + in_use, 1.0);
+}
+
+// ============================================================
+// ===== Configuration Tests =====
+// ============================================================
+
+#[tokio::test]
+async fn test_metrics_use_prometheus_text_format() {
+    let app = create_app(true).await;
+
+    let request = Request::get("/metrics").body(Body::empty()).unwrap();
+    let response = app.request(request).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers()["content-type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/plain; version=0.0.4"));
+}
+
+#[tokio::test]
+async fn test_metrics_can_be_disabled() {
+    let app = create_app(false).await;
+
+    send(&app, Some(1), "GET", "/tasks", None).await;
+    let (status, _) = send(&app, None, "GET", "/metrics", None).await;
+
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,145 @@
+//! Schema migration tests
+//!
+//! Run with: `cargo test --test migration_tests`
+
+use project_task_api::{db, migrations};
+use sqlx::SqlitePool;
+
+/// Names of the user tables and indexes in the schema
+async fn schema_objects(pool: &SqlitePool, kind: &str) -> Vec<String> {
+    let rows: Vec<(String,)> = sqlx::query_as(
+        "SELECT name FROM sqlite_master WHERE type = ? AND name NOT LIKE 'sqlite_%' ORDER BY name",
+    )
+    .bind(kind)
+    .fetch_all(pool)
+    .await
+    .unwrap();
+
+    rows.into_iter().map(|(name,)| name).collect()
+}
+
+/// Column names of a table
+async fn columns(pool: &SqlitePool, table: &str) -> Vec<String> {
+    let rows: Vec<(String,)> = sqlx::query_as("SELECT name FROM pragma_table_info(?) ORDER BY cid")
+        .bind(table)
+        .fetch_all(pool)
+        .await
+        .unwrap();
+
+    rows.into_iter().map(|(name,)| name).collect()
+}
+
+#[tokio::test]
+async fn test_migrate_up_creates_schema() {
+    let pool = db::connect("sqlite::memory:").await.unwrap();
+
+    let applied = migrations::migrate_up(&pool).await.unwrap();
+
+    assert_eq!(applied.len(), migrations::MIGRATIONS.len());
+    assert_eq!(
+        migrations::current_version(&pool).await.unwrap(),
+        migrations::latest_version()
+    );
+
+    let tables = schema_objects(&pool, "table").await;
+    assert!(tables.contains(&"tasks".to_string()));
+    assert!(tables.contains(&"schema_migrations".to_string()));
+    assert!(tables.contains(&"users".to_string()));
+    assert!(tables.contains(&"tags".to_string()));
+    assert!(tables.contains(&"task_tags".to_string()));
+    assert!(tables.contains(&"task_events".to_string()));
+    assert!(tables.contains(&"webhooks".to_string()));
+    assert!(tables.contains(&"webhook_deliveries".to_string()));
+    assert!(tables.contains(&"webhook_dead_letters".to_string()));
+    assert!(tables.contains(&"projects".to_string()));
+    assert!(tables.contains(&"project_members".to_string()));
+
+    let indexes = schema_objects(&pool, "index").await;
+    assert!(indexes.contains(&"idx_tasks_completed".to_string()));
+
+    assert_eq!(
+        columns(&pool, "tasks").await,
+        [
+            "id",
+            "title",
+            "description",
+            "completed",
+            "created_at",
+            "updated_at",
+            "owner_id",
+            "due_at",
+            "priority",
+            "parent_id",
+            "deleted_at",
+            "version",
+            "project_id",
+            "deleted_with"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_migrate_up_is_idempotent() {
+    let pool = db::create_test_pool().await.unwrap();
+
+    let applied = migrations::migrate_up(&pool).await.unwrap();
+
+    assert!(applied.is_empty());
+}
+
+#[tokio::test]
+async fn test_migrate_down_and_up_again() {
+    let pool = db::create_test_pool().await.unwrap();
+
+    let reverted = migrations::migrate_down(&pool, migrations::MIGRATIONS.len())
+        .await
+        .unwrap();
+
+    assert_eq!(reverted.len(), migrations::MIGRATIONS.len());
+    assert_eq!(migrations::current_version(&pool).await.unwrap(), 0);
+    assert_eq!(schema_objects(&pool, "table").await, ["schema_migrations"]);
+
+    migrations::migrate_up(&pool).await.unwrap();
+
+    assert_eq!(
+        migrations::current_version(&pool).await.unwrap(),
+        migrations::latest_version()
+    );
+}
+
+#[tokio::test]
+async fn test_status_reports_pending_migrations() {
+    let pool = db::connect("sqlite::memory:").await.unwrap();
+
+    let status = migrations::status(&pool).await.unwrap();
+    assert!(status.iter().all(|m| m.applied_at.is_none()));
+
+    migrations::migrate_up(&pool).await.unwrap();
+
+    let status = migrations::status(&pool).await.unwrap();
+    assert_eq!(status.len(), migrations::MIGRATIONS.len());
+    assert!(status.iter().all(|m| m.applied_at.is_some()));
+}
+
+#[tokio::test]
+async fn test_legacy_database_is_adopted() {
+    let pool = db::connect("sqlite::memory:").await.unwrap();
+
+    // Schema created by the old inline CREATE TABLE
+    sqlx::raw_sql(migrations::MIGRATIONS[0].up)
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO tasks (title) VALUES ('Existing task')")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    migrations::migrate_up(&pool).await.unwrap();
+
+    let count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM tasks")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(count.0, 1);
+}
@@ -0,0 +1,780 @@
+//! Project tests
+//!
+//! Project and member management, role checks on project tasks and per
+//! project statistics.
+//!
+//! Run with: `cargo test --test project_tests`
+
+mod common;
+
+use std::time::Duration;
+
+use axum::http::StatusCode;
+use chrono::Utc;
+use common::TestApp;
+use futures_util::StreamExt;
+use project_task_api::{
+    db, migrations,
+    models::{ChangeKind, EventFilters},
+    recurrence,
+};
+use serde_json::{json, Value};
+
+/// Users of the tests, by ID
+const USERS: [&str; 3] = ["alice", "bob", "carol"];
+
+/// Application with users alice (1), bob (2) and carol (3)
+async fn project_app() -> TestApp {
+    TestApp::with_users(&USERS).await
+}
+
+/// `project_app` on a database file, whose connections can run side by side
+///
+/// Returns the file too, to remove once done.
+async fn file_project_app(name: &str) -> (TestApp, std::path::PathBuf) {
+    let path = std::env::temp_dir().join(format!(
+        "task-api-projects-{}-{}.db",
+        name,
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+
+    let pool = db::connect(&format!("sqlite:{}?mode=rwc", path.display()))
+        .await
+        .expect("Error creating pool");
+    migrations::migrate_up(&pool).await.expect("Error migrating");
+
+    (TestApp::from_pool(pool, &USERS, |routes, _| routes).await, path)
+}
+
+/// Project helpers
+impl TestApp {
+    /// Project owned by alice, with bob as editor and carol as viewer
+    async fn project(&self) -> i64 {
+        let (status, body) = self
+            .send(1, "POST", "/projects", Some(json!({"name": "Launch"})))
+            .await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+        let pid = body["id"].as_i64().unwrap();
+
+        self.set_role(pid, "bob", "editor").await;
+        self.set_role(pid, "carol", "viewer").await;
+
+        pid
+    }
+
+    /// Give `username` a role in `pid`, as alice
+    async fn set_role(&self, pid: i64, username: &str, role: &str) {
+        let (status, body) = self
+            .send(
+                1,
+                "PUT",
+                &format!("/projects/{}/members/{}", pid, username),
+                Some(json!({"role": role})),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+    }
+
+    /// Create a task in `pid` as `user_id`, returning its ID
+    async fn project_task(&self, user_id: i64, pid: i64, task: Value) -> i64 {
+        let (status, body) = self
+            .send(
+                user_id,
+                "POST",
+                &format!("/projects/{}/tasks", pid),
+                Some(task),
+            )
+            .await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+
+        body["id"].as_i64().unwrap()
+    }
+}
+
+// =============================================================================
+// ===== Project Tests =====
+// =============================================================================
+
+#[tokio::test]
+async fn test_create_project_makes_caller_owner() {
+    let app = project_app().await;
+
+    let (status, body) = app
+        .send(
+            1,
+            "POST",
+            "/projects",
+            Some(json!({"name": "  Launch  ", "description": "Q3 release"})),
+        )
+        .await;
+
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert_eq!(body["name"], "Launch");
+    assert_eq!(body["description"], "Q3 release");
+    assert_eq!(body["role"], "owner");
+
+    let (_, members) = app
+        .send(1, "GET", &format!("/projects/{}/members", body["id"]), None)
+        .await;
+    assert_eq!(members.as_array().unwrap().len(), 1);
+    assert_eq!(members[0]["username"], "alice");
+    assert_eq!(members[0]["role"], "owner");
+}
+
+#[tokio::test]
+async fn test_create_project_validates_name() {
+    let app = project_app().await;
+
+    let (status, body) = app
+        .send(1, "POST", "/projects", Some(json!({"name": "   "})))
+        .await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["errors"][0]["field"], "name");
+}
+
+#[tokio::test]
+async fn test_list_projects_shows_only_memberships_with_role() {
+    let app = project_app().await;
+    let pid = app.project().await;
+    app.send(1, "POST", "/projects", Some(json!({"name": "Private"})))
+        .await;
+
+    let (_, alice) = app.send(1, "GET", "/projects", None).await;
+    let (_, carol) = app.send(3, "GET", "/projects", None).await;
+
+    assert_eq!(alice.as_array().unwrap().len(), 2);
+    assert_eq!(carol.as_array().unwrap().len(), 1);
+    assert_eq!(carol[0]["id"], pid);
+    assert_eq!(carol[0]["role"], "viewer");
+}
+
+#[tokio::test]
+async fn test_non_member_gets_not_found() {
+    let app = project_app().await;
+    let (_, body) = app
+        .send(1, "POST", "/projects", Some(json!({"name": "Private"})))
+        .await;
+    let pid = body["id"].as_i64().unwrap();
+
+    for uri in [
+        format!("/projects/{}", pid),
+        format!("/projects/{}/tasks", pid),
+        format!("/projects/{}/tasks/stats", pid),
+        "/projects/999".to_string(),
+    ] {
+        let (status, body) = app.send(2, "GET", &uri, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{}: {}", uri, body);
+    }
+}
+
+// =============================================================================
+// ===== Role Tests =====
+// =============================================================================
+
+#[tokio::test]
+async fn test_viewer_reads_but_cannot_write() {
+    let app = project_app().await;
+    let pid = app.project().await;
+    let id = app
+        .project_task(2, pid, json!({"title": "Write copy"}))
+        .await;
+
+    let (status, body) = app
+        .send(3, "GET", &format!("/projects/{}/tasks/{}", pid, id), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["title"], "Write copy");
+
+    let (status, body) = app
+        .send(
+            3,
+            "POST",
+            &format!("/projects/{}/tasks", pid),
+            Some(json!({"title": "Sneaky"})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["status"], 403);
+
+    let (status, _) = app
+        .send(
+            3,
+            "PATCH",
+            &format!("/projects/{}/tasks/{}", pid, id),
+            Some(json!({"completed": true})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = app
+        .send(
+            3,
+            "DELETE",
+            &format!("/projects/{}/tasks/{}", pid, id),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_editor_manages_tasks_but_not_members() {
+    let app = project_app().await;
+    let pid = app.project().await;
+    let id = app
+        .project_task(2, pid, json!({"title": "Write copy"}))
+        .await;
+
+    let (status, body) = app
+        .send(
+            2,
+            "PATCH",
+            &format!("/projects/{}/tasks/{}", pid, id),
+            Some(json!({"completed": true})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["completed"], true);
+
+    let (status, _) = app
+        .send(
+            2,
+            "PUT",
+            &format!("/projects/{}/members/carol", pid),
+            Some(json!({"role": "editor"})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = app
+        .send(2, "DELETE", &format!("/projects/{}", pid), None)
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = app
+        .send(
+            2,
+            "DELETE",
+            &format!("/projects/{}/tasks/{}", pid, id),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn test_role_change_takes_effect() {
+    let app = project_app().await;
+    let pid = app.project().await;
+
+    app.set_role(pid, "carol", "editor").await;
+
+    app.project_task(3, pid, json!({"title": "Promoted"})).await;
+}
+
+#[tokio::test]
+async fn test_downgrade_during_a_write_is_enforced() {
+    let (app, path) = file_project_app("downgrade").await;
+    let pid = app.project().await;
+    let id = app.project_task(2, pid, json!({"title": "Draft"})).await;
+
+    // Hold the write lock, so bob's update is let in by his role as editor
+    // and then waits for the lock while he becomes a viewer
+    let mut lock = app.pool().begin_with("BEGIN IMMEDIATE").await.unwrap();
+    let update = tokio::spawn({
+        let router = app.router.clone();
+        let bearer = app.bearer(2);
+        async move {
+            let request = axum::http::Request::builder()
+                .method("PATCH")
+                .uri(format!("/projects/{}/tasks/{}", pid, id))
+                .header("Authorization", bearer)
+                .header("Content-Type", "application/json")
+                .body(axum::body::Body::from(r#"{"completed": true}"#))
+                .unwrap();
+            tower::ServiceExt::oneshot(router, request).await.unwrap().status()
+        }
+    });
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    sqlx::query("UPDATE project_members SET role = 'viewer' WHERE project_id = ? AND user_id = 2")
+        .bind(pid)
+        .execute(&mut *lock)
+        .await
+        .unwrap();
+    lock.commit().await.unwrap();
+
+    assert_eq!(update.await.unwrap(), StatusCode::FORBIDDEN);
+
+    let (_, task) = app
+        .send(1, "GET", &format!("/projects/{}/tasks/{}", pid, id), None)
+        .await;
+    assert_eq!(task["completed"], false);
+
+    app.pool().close().await;
+    let _ = std::fs::remove_file(path);
+}
+
+// =============================================================================
+// ===== Member Tests =====
+// =============================================================================
+
+#[tokio::test]
+async fn test_set_member_unknown_user() {
+    let app = project_app().await;
+    let pid = app.project().await;
+
+    let (status, _) = app
+        .send(
+            1,
+            "PUT",
+            &format!("/projects/{}/members/nobody", pid),
+            Some(json!({"role": "viewer"})),
+        )
+        .await;
+
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_last_owner_cannot_step_down_or_leave() {
+    let app = project_app().await;
+    let pid = app.project().await;
+
+    let (status, _) = app
+        .send(
+            1,
+            "PUT",
+            &format!("/projects/{}/members/alice", pid),
+            Some(json!({"role": "editor"})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, _) = app
+        .send(
+            1,
+            "DELETE",
+            &format!("/projects/{}/members/alice", pid),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    // With a second owner, alice can step down
+    app.set_role(pid, "bob", "owner").await;
+    app.set_role(pid, "alice", "viewer").await;
+
+    let (_, body) = app
+        .send(1, "GET", &format!("/projects/{}", pid), None)
+        .await;
+    assert_eq!(body["role"], "viewer");
+}
+
+#[tokio::test]
+async fn test_member_can_leave_but_not_remove_others() {
+    let app = project_app().await;
+    let pid = app.project().await;
+
+    let (status, _) = app
+        .send(3, "DELETE", &format!("/projects/{}/members/bob", pid), None)
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // Usernames are case-insensitive, so this is still carol leaving
+    let (status, _) = app
+        .send(
+            3,
+            "DELETE",
+            &format!("/projects/{}/members/Carol", pid),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let (status, _) = app
+        .send(3, "GET", &format!("/projects/{}", pid), None)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+// =============================================================================
+// ===== Project Task Tests =====
+// =============================================================================
+
+#[tokio::test]
+async fn test_project_tasks_are_separate_from_personal_tasks() {
+    let app = project_app().await;
+    let pid = app.project().await;
+    let id = app.project_task(1, pid, json!({"title": "Shared"})).await;
+    app.send(1, "POST", "/tasks", Some(json!({"title": "Mine"})))
+        .await;
+
+    let (_, personal) = app.send(1, "GET", "/tasks", None).await;
+    assert_eq!(personal.as_array().unwrap().len(), 1);
+    assert_eq!(personal[0]["title"], "Mine");
+
+    let (status, _) = app.send(1, "GET", &format!("/tasks/{}", id), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (_, shared) = app
+        .send(3, "GET", &format!("/projects/{}/tasks", pid), None)
+        .await;
+    assert_eq!(shared.as_array().unwrap().len(), 1);
+    assert_eq!(shared[0]["title"], "Shared");
+}
+
+#[tokio::test]
+async fn test_project_task_from_another_project_not_found() {
+    let app = project_app().await;
+    let pid = app.project().await;
+    let (_, other) = app
+        .send(1, "POST", "/projects", Some(json!({"name": "Other"})))
+        .await;
+    let other = other["id"].as_i64().unwrap();
+    let id = app
+        .project_task(1, other, json!({"title": "Elsewhere"}))
+        .await;
+
+    let (status, _) = app
+        .send(1, "GET", &format!("/projects/{}/tasks/{}", pid, id), None)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // A parent must belong to the same project
+    let (status, _) = app
+        .send(
+            1,
+            "POST",
+            &format!("/projects/{}/tasks", pid),
+            Some(json!({"title": "Child", "parent_id": id})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_members_work_on_project_subtasks_tags_and_history() {
+    let app = project_app().await;
+    let pid = app.project().await;
+    let parent = app
+        .project_task(1, pid, json!({"title": "Release"}))
+        .await;
+    let child = app
+        .project_task(1, pid, json!({"title": "Notes", "parent_id": parent}))
+        .await;
+
+    let (status, body) = app
+        .send(
+            2,
+            "POST",
+            &format!("/projects/{}/tasks/{}/tags", pid, child),
+            Some(json!({"tags": ["docs"]})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["tags"], json!(["docs"]));
+
+    let (status, tree) = app
+        .send(
+            3,
+            "GET",
+            &format!("/projects/{}/tasks/{}/tree", pid, parent),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", tree);
+    assert_eq!(tree["children"][0]["id"], child);
+
+    let (_, subtasks) = app
+        .send(
+            3,
+            "GET",
+            &format!("/projects/{}/tasks/{}/subtasks", pid, parent),
+            None,
+        )
+        .await;
+    assert_eq!(subtasks.as_array().unwrap().len(), 1);
+
+    app.send(
+        1,
+        "DELETE",
+        &format!("/projects/{}/tasks/{}", pid, parent),
+        None,
+    )
+    .await;
+    let (status, body) = app
+        .send(
+            2,
+            "POST",
+            &format!("/projects/{}/tasks/{}/restore", pid, parent),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let (status, history) = app
+        .send(
+            3,
+            "GET",
+            &format!("/projects/{}/tasks/{}/history", pid, child),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", history);
+    let kinds: Vec<&str> = history
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|event| event["kind"].as_str().unwrap())
+        .collect();
+    assert_eq!(kinds, ["created", "updated", "deleted", "restored"]);
+
+    // Project sub-resources are not reachable through the personal routes
+    let (status, _) = app
+        .send(1, "GET", &format!("/tasks/{}/tree", parent), None)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_viewer_cannot_tag_restore_or_schedule_project_tasks() {
+    let app = project_app().await;
+    let pid = app.project().await;
+    let id = app
+        .project_task(
+            1,
+            pid,
+            json!({"title": "Standup", "due_at": "2030-01-01T09:00:00Z"}),
+        )
+        .await;
+
+    let (status, _) = app
+        .send(
+            3,
+            "POST",
+            &format!("/projects/{}/tasks/{}/tags", pid, id),
+            Some(json!({"tags": ["daily"]})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = app
+        .send(
+            3,
+            "PUT",
+            &format!("/projects/{}/tasks/{}/recurrence", pid, id),
+            Some(json!({"frequency": "daily"})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, body) = app
+        .send(
+            2,
+            "PUT",
+            &format!("/projects/{}/tasks/{}/recurrence", pid, id),
+            Some(json!({"frequency": "daily"})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let (status, occurrences) = app
+        .send(
+            3,
+            "GET",
+            &format!("/projects/{}/tasks/{}/occurrences?limit=3", pid, id),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", occurrences);
+    assert_eq!(occurrences.as_array().unwrap().len(), 3);
+
+    let (status, _) = app
+        .send(
+            3,
+            "POST",
+            &format!("/projects/{}/tasks/{}/restore", pid, id),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_completed_recurring_project_task_gets_next_occurrence() {
+    let app = project_app().await;
+    let pid = app.project().await;
+    let id = app
+        .project_task(
+            1,
+            pid,
+            json!({"title": "Standup", "due_at": "2030-01-01T09:00:00Z"}),
+        )
+        .await;
+    let (status, body) = app
+        .send(
+            1,
+            "PUT",
+            &format!("/projects/{}/tasks/{}/recurrence", pid, id),
+            Some(json!({"frequency": "daily"})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let (status, _) = app
+        .send(
+            2,
+            "PATCH",
+            &format!("/projects/{}/tasks/{}", pid, id),
+            Some(json!({"completed": true})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let mut changes = std::pin::pin!(app.events.subscribe(3, EventFilters::default()));
+
+    let created = recurrence::materialize_due(app.pool(), &app.events, Utc::now())
+        .await
+        .unwrap();
+
+    assert_eq!(created.len(), 1);
+    let next = created[0].id;
+    let (status, task) = app
+        .send(3, "GET", &format!("/projects/{}/tasks/{}", pid, next), None)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", task);
+    assert_eq!(task["title"], "Standup");
+    assert_eq!(task["due_at"], "2030-01-02T09:00:00Z");
+    assert_eq!(task["completed"], false);
+
+    // The rule moved with it, and it was logged as done by bob
+    let (status, _) = app
+        .send(
+            1,
+            "GET",
+            &format!("/projects/{}/tasks/{}/recurrence", pid, next),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, history) = app
+        .send(
+            1,
+            "GET",
+            &format!("/projects/{}/tasks/{}/history", pid, next),
+            None,
+        )
+        .await;
+    assert_eq!(history[0]["actor_id"], 2);
+
+    // Every member hears about it
+    let event = tokio::time::timeout(Duration::from_secs(5), changes.next())
+        .await
+        .expect("No event received")
+        .unwrap();
+    assert_eq!(event.kind, ChangeKind::Created);
+    assert_eq!(event.task.id, next);
+
+    // Nobody's personal tasks got it
+    let (_, personal) = app.send(1, "GET", "/tasks", None).await;
+    assert_eq!(personal.as_array().unwrap().len(), 0);
+}
+
+#[tokio::test]
+async fn test_project_task_changes_reach_every_member() {
+    let app = project_app().await;
+    let pid = app.project().await;
+    let mut changes = std::pin::pin!(app.events.subscribe(3, EventFilters::default()));
+
+    let id = app.project_task(2, pid, json!({"title": "Shared"})).await;
+    app.send(
+        1,
+        "DELETE",
+        &format!("/projects/{}/tasks/{}", pid, id),
+        None,
+    )
+    .await;
+
+    for kind in [ChangeKind::Created, ChangeKind::Deleted] {
+        let event = tokio::time::timeout(Duration::from_secs(5), changes.next())
+            .await
+            .expect("No event received")
+            .unwrap();
+        assert_eq!(event.kind, kind);
+        assert_eq!(event.task.id, id);
+    }
+}
+
+#[tokio::test]
+async fn test_project_stats() {
+    let app = project_app().await;
+    let pid = app.project().await;
+    let parent = app
+        .project_task(1, pid, json!({"title": "Release", "priority": "high"}))
+        .await;
+    let child = app
+        .project_task(2, pid, json!({"title": "Notes", "parent_id": parent}))
+        .await;
+    app.send(
+        2,
+        "PATCH",
+        &format!("/projects/{}/tasks/{}", pid, child),
+        Some(json!({"completed": true})),
+    )
+    .await;
+    app.send(1, "POST", "/tasks", Some(json!({"title": "Mine"})))
+        .await;
+
+    let (status, stats) = app
+        .send(3, "GET", &format!("/projects/{}/tasks/stats", pid), None)
+        .await;
+
+    assert_eq!(status, StatusCode::OK, "{}", stats);
+    assert_eq!(stats["total"], 2);
+    assert_eq!(stats["completed"], 1);
+    assert_eq!(stats["by_priority"]["high"], 1);
+    assert_eq!(stats["roots"][0]["id"], parent);
+    assert_eq!(stats["roots"][0]["subtasks"], 1);
+
+    let (_, personal) = app.send(1, "GET", "/tasks/stats", None).await;
+    assert_eq!(personal["total"], 1);
+}
+
+#[tokio::test]
+async fn test_delete_project_requires_no_live_tasks() {
+    let app = project_app().await;
+    let pid = app.project().await;
+    let id = app.project_task(1, pid, json!({"title": "Shared"})).await;
+
+    let (status, body) = app
+        .send(1, "DELETE", &format!("/projects/{}", pid), None)
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT, "{}", body);
+
+    let (status, _) = app
+        .send(1, "GET", &format!("/projects/{}/tasks/{}", pid, id), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = app
+        .send(1, "DELETE", &format!("/projects/{}/tasks/{}", pid, id), None)
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let (status, _) = app
+        .send(1, "DELETE", &format!("/projects/{}", pid), None)
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let (status, _) = app
+        .send(1, "GET", &format!("/projects/{}", pid), None)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (tasks,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM tasks")
+        .fetch_one(app.pool())
+        .await
+        .unwrap();
+    assert_eq!(tasks, 0);
+}
@@ -0,0 +1,641 @@
+//! Recurring task tests
+//!
+//! Schedule arithmetic, the recurrence routes and the scheduler that creates
+//! the next occurrences.
+//!
+//! Run with: `cargo test --test recurrence_tests`
+
+mod common;
+
+use std::time::Duration;
+
+use axum::http::StatusCode;
+use chrono::{DateTime, TimeZone, Utc};
+use common::TestApp;
+use futures_util::StreamExt;
+use project_task_api::{
+    models::{ChangeKind, EventFilters, Frequency, RecurrenceRule},
+    recurrence::{self, Schedule, SchedulerConfig},
+};
+use serde_json::{json, Value};
+
+/// How long to wait for the scheduler before failing
+const SCHEDULER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Recurrence helpers; the scheduler is started by the tests
+impl TestApp {
+    /// Create a task for user 1, returning its ID
+    async fn create_task(&self, task: Value) -> i64 {
+        let (status, body) = self.send(1, "POST", "/tasks", Some(task)).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+
+        body["id"].as_i64().unwrap()
+    }
+
+    /// Create a task due at `due_at` that recurs by `rule`
+    async fn recurring_task(&self, due_at: DateTime<Utc>, rule: Value) -> i64 {
+        let id = self
+            .create_task(json!({
+                "title": "Water the plants",
+                "priority": "high",
+                "tags": ["home"],
+                "due_at": due_at,
+            }))
+            .await;
+
+        let (status, body) = self
+            .send(1, "PUT", &format!("/tasks/{}/recurrence", id), Some(rule))
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+
+        id
+    }
+
+    /// All of user 1's tasks, oldest first
+    async fn tasks(&self) -> Vec<Value> {
+        let (_, body) = self.send(1, "GET", "/tasks?sort=created", None).await;
+        let mut tasks = body.as_array().unwrap().clone();
+        tasks.reverse();
+        tasks
+    }
+
+    /// Tasks once there are `count` of them
+    async fn tasks_when(&self, count: usize) -> Vec<Value> {
+        tokio::time::timeout(SCHEDULER_TIMEOUT, async {
+            loop {
+                let tasks = self.tasks().await;
+                if tasks.len() >= count {
+                    return tasks;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("The scheduler did not create the occurrence")
+    }
+}
+
+fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+}
+
+fn rule(frequency: Frequency, interval: Option<u32>) -> RecurrenceRule {
+    RecurrenceRule {
+        frequency,
+        interval,
+        cron: None,
+        until: None,
+    }
+}
+
+fn cron(expression: &str) -> RecurrenceRule {
+    RecurrenceRule {
+        cron: Some(expression.to_string()),
+        ..rule(Frequency::Cron, None)
+    }
+}
+
+/// The first `n` due dates after `after`
+fn upcoming(schedule: &Schedule, after: DateTime<Utc>, n: usize) -> Vec<DateTime<Utc>> {
+    schedule.occurrences(after).take(n).collect()
+}
+
+/// Field names of a validation problem
+fn error_fields(body: &Value) -> Vec<&str> {
+    body["errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|error| error["field"].as_str().unwrap())
+        .collect()
+}
+
+// ============================================================
+// ===== Schedule Tests =====
+// ============================================================
+
+#[test]
+fn test_daily_schedule() {
+    let start = at(2025, 1, 6, 9, 0);
+    let schedule = Schedule::new(rule(Frequency::Daily, Some(2)), start).unwrap();
+
+    assert_eq!(
+        upcoming(&schedule, start, 3),
+        [at(2025, 1, 8, 9, 0), at(2025, 1, 10, 9, 0), at(2025, 1, 12, 9, 0)]
+    );
+    // Dates stay on the grid of the first occurrence
+    assert_eq!(
+        schedule.next_after(at(2025, 1, 9, 12, 0)),
+        Some(at(2025, 1, 10, 9, 0))
+    );
+    assert_eq!(schedule.next_after(at(2024, 12, 1, 0, 0)), Some(start));
+}
+
+#[test]
+fn test_weekly_schedule() {
+    let start = at(2025, 1, 6, 9, 0);
+    let schedule = Schedule::new(rule(Frequency::Weekly, None), start).unwrap();
+
+    assert_eq!(schedule.rule().interval, Some(1));
+    assert_eq!(
+        upcoming(&schedule, start, 2),
+        [at(2025, 1, 13, 9, 0), at(2025, 1, 20, 9, 0)]
+    );
+    assert_eq!(
+        schedule.next_after(at(2025, 1, 13, 9, 0)),
+        Some(at(2025, 1, 20, 9, 0))
+    );
+}
+
+#[test]
+fn test_monthly_schedule_keeps_day() {
+    let start = at(2025, 1, 31, 18, 0);
+    let schedule = Schedule::new(rule(Frequency::Monthly, None), start).unwrap();
+
+    // Shorter months get their last day; the day comes back afterwards
+    assert_eq!(
+        upcoming(&schedule, start, 3),
+        [at(2025, 2, 28, 18, 0), at(2025, 3, 31, 18, 0), at(2025, 4, 30, 18, 0)]
+    );
+
+    let quarterly = Schedule::new(rule(Frequency::Monthly, Some(3)), start).unwrap();
+    assert_eq!(
+        quarterly.next_after(at(2025, 6, 1, 0, 0)),
+        Some(at(2025, 7, 31, 18, 0))
+    );
+}
+
+#[test]
+fn test_cron_schedule() {
+    // Friday
+    let start = at(2025, 1, 3, 9, 0);
+    let weekdays = Schedule::new(cron(" 0 9 * * 1-5 "), start).unwrap();
+
+    assert_eq!(weekdays.rule().cron.as_deref(), Some("0 9 * * 1-5"));
+    assert_eq!(
+        upcoming(&weekdays, start, 2),
+        [at(2025, 1, 6, 9, 0), at(2025, 1, 7, 9, 0)]
+    );
+
+    let quarter_hours = Schedule::new(cron("*/15 * * * *"), start).unwrap();
+    assert_eq!(
+        quarter_hours.next_after(at(2025, 1, 3, 10, 7)),
+        Some(at(2025, 1, 3, 10, 15))
+    );
+
+    // Day or weekday, as in cron: the 1st and 15th, and every Sunday
+    let either = Schedule::new(cron("0 0 1,15 * 0"), start).unwrap();
+    assert_eq!(
+        upcoming(&either, start, 3),
+        [at(2025, 1, 5, 0, 0), at(2025, 1, 12, 0, 0), at(2025, 1, 15, 0, 0)]
+    );
+
+    let leap_day = Schedule::new(cron("0 12 29 2 *"), start).unwrap();
+    assert_eq!(leap_day.next_after(start), Some(at(2028, 2, 29, 12, 0)));
+}
+
+#[test]
+fn test_schedule_until() {
+    let start = at(2025, 1, 6, 9, 0);
+    let schedule = Schedule::new(
+        RecurrenceRule {
+            until: Some(at(2025, 1, 8, 9, 0)),
+            ..rule(Frequency::Daily, None)
+        },
+        start,
+    )
+    .unwrap();
+
+    assert_eq!(
+        upcoming(&schedule, start, 10),
+        [at(2025, 1, 7, 9, 0), at(2025, 1, 8, 9, 0)]
+    );
+    assert_eq!(schedule.next_after(at(2025, 1, 8, 9, 0)), None);
+}
+
+#[test]
+fn test_schedule_validation() {
+    let start = at(2025, 1, 6, 9, 0);
+    let invalid = [
+        rule(Frequency::Daily, Some(0)),
+        rule(Frequency::Weekly, Some(recurrence::MAX_INTERVAL + 1)),
+        RecurrenceRule {
+            cron: Some("* * * * *".into()),
+            ..rule(Frequency::Daily, None)
+        },
+        rule(Frequency::Cron, None),
+        cron("0 9 * *"),
+        cron("60 * * * *"),
+        cron("0 9 */0 * *"),
+        cron("0 9 5-1 * *"),
+        cron("0 0 31 2 *"),
+        RecurrenceRule {
+            until: Some(at(2025, 1, 1, 0, 0)),
+            ..rule(Frequency::Daily, None)
+        },
+    ];
+
+    for rule in invalid {
+        assert!(Schedule::new(rule.clone(), start).is_err(), "{:?}", rule);
+    }
+}
+
+// ============================================================
+// ===== API Tests =====
+// ============================================================
+
+#[tokio::test]
+async fn test_set_and_get_recurrence() {
+    let app = TestApp::new().await;
+    let due = at(2030, 1, 6, 9, 0);
+    let id = app
+        .recurring_task(due, json!({"frequency": "weekly", "interval": 2}))
+        .await;
+
+    let (status, body) = app
+        .send(1, "GET", &format!("/tasks/{}/recurrence", id), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["task_id"], id);
+    assert_eq!(body["frequency"], "weekly");
+    assert_eq!(body["interval"], 2);
+    assert_eq!(body["starts_at"], json!(due));
+    assert!(body.get("cron").is_none());
+
+    // Replacing the rule
+    let (status, body) = app
+        .send(
+            1,
+            "PUT",
+            &format!("/tasks/{}/recurrence", id),
+            Some(json!({"frequency": "cron", "cron": "0 9 * * 1"})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["cron"], "0 9 * * 1");
+    assert!(body.get("interval").is_none());
+
+    let (status, _) = app
+        .send(1, "DELETE", &format!("/tasks/{}/recurrence", id), None)
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let (status, _) = app
+        .send(1, "GET", &format!("/tasks/{}/recurrence", id), None)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = app
+        .send(1, "DELETE", &format!("/tasks/{}/recurrence", id), None)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_set_recurrence_validation() {
+    let app = TestApp::new().await;
+
+    let undated = app.create_task(json!({"title": "Someday"})).await;
+    let (status, body) = app
+        .send(
+            1,
+            "PUT",
+            &format!("/tasks/{}/recurrence", undated),
+            Some(json!({"frequency": "daily"})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["detail"].as_str().unwrap().contains("due date"));
+
+    let dated = app
+        .create_task(json!({"title": "Report", "due_at": at(2030, 1, 6, 9, 0)}))
+        .await;
+    let (status, body) = app
+        .send(
+            1,
+            "PUT",
+            &format!("/tasks/{}/recurrence", dated),
+            Some(json!({"frequency": "daily", "interval": 0, "until": at(2029, 1, 1, 0, 0)})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error_fields(&body), ["interval", "until"]);
+
+    let (status, body) = app
+        .send(
+            1,
+            "PUT",
+            &format!("/tasks/{}/recurrence", dated),
+            Some(json!({"frequency": "cron", "cron": "0 25 * * *"})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error_fields(&body), ["cron"]);
+    assert!(body["errors"][0]["message"]
+        .as_str()
+        .unwrap()
+        .contains("hour"));
+}
+
+#[tokio::test]
+async fn test_recurrence_is_private() {
+    let app = TestApp::new().await;
+    let id = app
+        .recurring_task(at(2030, 1, 6, 9, 0), json!({"frequency": "daily"}))
+        .await;
+
+    for (method, uri) in [
+        ("GET", format!("/tasks/{}/recurrence", id)),
+        ("DELETE", format!("/tasks/{}/recurrence", id)),
+        ("GET", format!("/tasks/{}/occurrences", id)),
+    ] {
+        let (status, _) = app.send(2, method, &uri, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{} {}", method, uri);
+    }
+
+    let (status, _) = app
+        .send(
+            2,
+            "PUT",
+            &format!("/tasks/{}/recurrence", id),
+            Some(json!({"frequency": "daily"})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_list_occurrences() {
+    let app = TestApp::new().await;
+    let id = app
+        .recurring_task(
+            at(2030, 1, 31, 9, 0),
+            json!({"frequency": "monthly", "until": at(2030, 12, 31, 0, 0)}),
+        )
+        .await;
+
+    let (status, body) = app
+        .send(1, "GET", &format!("/tasks/{}/occurrences?limit=3", id), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body,
+        json!([
+            {"due_at": at(2030, 2, 28, 9, 0)},
+            {"due_at": at(2030, 3, 31, 9, 0)},
+            {"due_at": at(2030, 4, 30, 9, 0)},
+        ])
+    );
+
+    // Stops at `until`
+    let (_, body) = app
+        .send(1, "GET", &format!("/tasks/{}/occurrences?limit=100", id), None)
+        .await;
+    assert_eq!(body.as_array().unwrap().len(), 10);
+    assert_eq!(body[9]["due_at"], json!(at(2030, 11, 30, 9, 0)));
+
+    let plain = app.create_task(json!({"title": "Once"})).await;
+    let (status, _) = app
+        .send(1, "GET", &format!("/tasks/{}/occurrences", plain), None)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_occurrences_reject_invalid_limit() {
+    let app = TestApp::new().await;
+    let id = app
+        .recurring_task(at(2030, 1, 6, 9, 0), json!({"frequency": "daily"}))
+        .await;
+
+    for limit in ["0", "-1", "101"] {
+        let uri = format!("/tasks/{}/occurrences?limit={}", id, limit);
+        let (status, body) = app.send(1, "GET", &uri, None).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST, "limit={}: {}", limit, body);
+    }
+}
+
+// ============================================================
+// ===== Scheduler Tests =====
+// ============================================================
+
+#[tokio::test]
+async fn test_materialize_completed_occurrence() {
+    let app = TestApp::new().await;
+    let due = Utc::now() + chrono::TimeDelta::days(1);
+    let id = app
+        .recurring_task(due, json!({"frequency": "weekly"}))
+        .await;
+    let mut changes = std::pin::pin!(app.events.subscribe(1, EventFilters::default()));
+
+    // Nothing to do until the task is completed
+    let created = recurrence::materialize_due(app.pool(), &app.events, Utc::now())
+        .await
+        .unwrap();
+    assert!(created.is_empty());
+
+    app.send(
+        1,
+        "PATCH",
+        &format!("/tasks/{}", id),
+        Some(json!({"completed": true})),
+    )
+    .await;
+
+    let created = recurrence::materialize_due(app.pool(), &app.events, Utc::now())
+        .await
+        .unwrap();
+    assert_eq!(created.len(), 1);
+    let next = &created[0];
+    assert_eq!(next.title, "Water the plants");
+    assert_eq!(next.tags, ["home"]);
+    assert!(!next.completed);
+    assert_eq!(next.due_at, Some(due + chrono::TimeDelta::weeks(1)));
+
+    // The rule moved to the new occurrence
+    let (status, _) = app
+        .send(1, "GET", &format!("/tasks/{}/recurrence", id), None)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, body) = app
+        .send(1, "GET", &format!("/tasks/{}/recurrence", next.id), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["starts_at"], json!(due));
+
+    // Running again creates nothing
+    let created = recurrence::materialize_due(app.pool(), &app.events, Utc::now())
+        .await
+        .unwrap();
+    assert!(created.is_empty());
+    assert_eq!(app.tasks().await.len(), 2);
+
+    let kinds: Vec<ChangeKind> = tokio::time::timeout(
+        Duration::from_secs(1),
+        changes.by_ref().take(2).collect::<Vec<_>>(),
+    )
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|event| event.kind)
+    .collect();
+    assert_eq!(kinds, [ChangeKind::Updated, ChangeKind::Created]);
+}
+
+#[tokio::test]
+async fn test_materialize_due_occurrence() {
+    let app = TestApp::new().await;
+    let id = app
+        .recurring_task(at(2030, 1, 6, 9, 0), json!({"frequency": "daily"}))
+        .await;
+
+    // Not due yet
+    let created = recurrence::materialize_due(app.pool(), &app.events, at(2030, 1, 6, 8, 59))
+        .await
+        .unwrap();
+    assert!(created.is_empty());
+
+    let created = recurrence::materialize_due(app.pool(), &app.events, at(2030, 1, 6, 9, 0))
+        .await
+        .unwrap();
+    assert_eq!(created.len(), 1);
+    assert_eq!(created[0].due_at, Some(at(2030, 1, 7, 9, 0)));
+
+    // The overdue occurrence is kept as it was
+    let (_, body) = app.send(1, "GET", &format!("/tasks/{}", id), None).await;
+    assert_eq!(body["completed"], false);
+}
+
+#[tokio::test]
+async fn test_materialize_skips_missed_occurrences() {
+    let app = TestApp::new().await;
+    app.recurring_task(at(2030, 1, 6, 9, 0), json!({"frequency": "daily"}))
+        .await;
+
+    // Ten days later, e.g. after the server was down: one task, the next one
+    let created = recurrence::materialize_due(app.pool(), &app.events, at(2030, 1, 16, 12, 0))
+        .await
+        .unwrap();
+    assert_eq!(created.len(), 1);
+    assert_eq!(created[0].due_at, Some(at(2030, 1, 17, 9, 0)));
+}
+
+#[tokio::test]
+async fn test_series_ends_at_until() {
+    let app = TestApp::new().await;
+    let id = app
+        .recurring_task(
+            at(2030, 1, 6, 9, 0),
+            json!({"frequency": "daily", "until": at(2030, 1, 7, 9, 0)}),
+        )
+        .await;
+
+    let created = recurrence::materialize_due(app.pool(), &app.events, at(2030, 1, 6, 9, 0))
+        .await
+        .unwrap();
+    assert_eq!(created.len(), 1);
+    let last = created[0].id;
+
+    let created = recurrence::materialize_due(app.pool(), &app.events, at(2030, 1, 7, 9, 0))
+        .await
+        .unwrap();
+    assert!(created.is_empty());
+
+    for task in [id, last] {
+        let (status, _) = app
+            .send(1, "GET", &format!("/tasks/{}/recurrence", task), None)
+            .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
+
+#[tokio::test]
+async fn test_deleted_task_stops_series() {
+    let app = TestApp::new().await;
+    let id = app
+        .recurring_task(at(2030, 1, 6, 9, 0), json!({"frequency": "daily"}))
+        .await;
+
+    let (status, _) = app.send(1, "DELETE", &format!("/tasks/{}", id), None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let created = recurrence::materialize_due(app.pool(), &app.events, at(2030, 2, 1, 0, 0))
+        .await
+        .unwrap();
+    assert!(created.is_empty());
+}
+
+#[tokio::test]
+async fn test_scheduler_wakes_on_completion() {
+    let app = TestApp::new().await;
+    let id = app
+        .recurring_task(
+            Utc::now() + chrono::TimeDelta::days(1),
+            json!({"frequency": "daily"}),
+        )
+        .await;
+
+    // A poll interval longer than the test: only the completion can wake it
+    recurrence::spawn_scheduler(
+        app.pool().clone(),
+        &app.events,
+        SchedulerConfig {
+            poll_interval: Duration::from_secs(3600),
+        },
+    );
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(app.tasks().await.len(), 1);
+
+    app.send(
+        1,
+        "PATCH",
+        &format!("/tasks/{}", id),
+        Some(json!({"completed": true})),
+    )
+    .await;
+
+    let tasks = app.tasks_when(2).await;
+    assert_eq!(tasks.len(), 2);
+    assert_eq!(tasks[1]["completed"], false);
+}
+
+#[tokio::test]
+async fn test_scheduler_catches_up_on_start() {
+    let app = TestApp::new().await;
+    let id = app
+        .recurring_task(
+            Utc::now() + chrono::TimeDelta::days(1),
+            json!({"frequency": "daily"}),
+        )
+        .await;
+
+    // Completed while no scheduler was running, e.g. before a restart
+    app.send(
+        1,
+        "PATCH",
+        &format!("/tasks/{}", id),
+        Some(json!({"completed": true})),
+    )
+    .await;
+    sqlx::query("UPDATE tasks SET due_at = ? WHERE id = ?")
+        .bind(Utc::now() - chrono::TimeDelta::days(3))
+        .bind(id)
+        .execute(app.pool())
+        .await
+        .unwrap();
+
+    recurrence::spawn_scheduler(
+        app.pool().clone(),
+        &app.events,
+        SchedulerConfig {
+            poll_interval: Duration::from_secs(3600),
+        },
+    );
+
+    let tasks = app.tasks_when(2).await;
+    assert_eq!(tasks.len(), 2);
+    let due: DateTime<Utc> = serde_json::from_value(tasks[1]["due_at"].clone()).unwrap();
+    assert!(due > Utc::now());
+    assert!(due < Utc::now() + chrono::TimeDelta::days(1));
+}
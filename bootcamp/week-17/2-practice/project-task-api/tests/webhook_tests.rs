@@ -0,0 +1,448 @@
+//! Webhook registration and delivery tests
+//!
+//! A local axum server stands in for the webhook endpoint.
+//!
+//! Run with: `cargo test --test webhook_tests`
+
+mod common;
+
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    routing::post,
+    Router,
+};
+use common::TestApp;
+use hmac::{Hmac, Mac};
+use project_task_api::webhooks::{self, WebhookConfig};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use tokio::sync::mpsc;
+
+/// How long to wait for a delivery before failing
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Worker settings with short delays
+fn fast_config() -> WebhookConfig {
+    WebhookConfig {
+        max_attempts: 3,
+        base_delay: Duration::from_millis(20),
+        max_delay: Duration::from_secs(1),
+        poll_interval: Duration::from_millis(50),
+        timeout: Duration::from_secs(2),
+    }
+}
+
+/// Application with users 1 and 2 and a running webhook worker
+async fn webhook_app() -> TestApp {
+    let app = TestApp::new().await;
+    webhooks::spawn_worker(app.pool().clone(), &app.events, fast_config());
+
+    app
+}
+
+/// Webhook helpers
+impl TestApp {
+    /// Register a webhook for user 1, returning its ID and secret
+    async fn register(&self, url: &str, events: Value) -> (i64, String) {
+        let (status, body) = self
+            .send(
+                1,
+                "POST",
+                "/webhooks",
+                Some(json!({"url": url, "events": events})),
+            )
+            .await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+
+        (
+            body["id"].as_i64().unwrap(),
+            body["secret"].as_str().unwrap().to_string(),
+        )
+    }
+
+    /// Deliveries of a webhook once `done` holds for them
+    async fn deliveries_when(
+        &self,
+        webhook_id: i64,
+        done: impl Fn(&[Value]) -> bool,
+    ) -> Vec<Value> {
+        let uri = format!("/webhooks/{}/deliveries", webhook_id);
+
+        tokio::time::timeout(DELIVERY_TIMEOUT, async {
+            loop {
+                let (_, body) = self.send(1, "GET", &uri, None).await;
+                let deliveries = body.as_array().unwrap().clone();
+                if done(&deliveries) {
+                    return deliveries;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("Deliveries did not settle")
+    }
+}
+
+/// Request received by the stand-in endpoint
+struct Received {
+    headers: HeaderMap,
+    body: String,
+}
+
+/// Stand-in endpoint state: failures left and where to report requests
+#[derive(Clone)]
+struct Receiver {
+    failures: Arc<AtomicUsize>,
+    requests: mpsc::UnboundedSender<Received>,
+}
+
+/// Start an endpoint that answers 500 to its first `failures` requests
+async fn receiver(failures: usize) -> (String, mpsc::UnboundedReceiver<Received>) {
+    let (requests, received) = mpsc::unbounded_channel();
+    let state = Receiver {
+        failures: Arc::new(AtomicUsize::new(failures)),
+        requests,
+    };
+
+    async fn hook(State(state): State<Receiver>, headers: HeaderMap, body: String) -> StatusCode {
+        let _ = state.requests.send(Received { headers, body });
+
+        let failing = state
+            .failures
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok();
+
+        if failing {
+            StatusCode::INTERNAL_SERVER_ERROR
+        } else {
+            StatusCode::NO_CONTENT
+        }
+    }
+
+    let app = Router::new().route("/hook", post(hook)).with_state(state);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address: SocketAddr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    (format!("http://{}/hook", address), received)
+}
+
+async fn next_request(requests: &mut mpsc::UnboundedReceiver<Received>) -> Received {
+    tokio::time::timeout(DELIVERY_TIMEOUT, requests.recv())
+        .await
+        .expect("No webhook request received")
+        .unwrap()
+}
+
+fn header<'a>(request: &'a Received, name: &str) -> &'a str {
+    request.headers[name].to_str().unwrap()
+}
+
+// ============================================================
+// ===== Registration Tests =====
+// ============================================================
+
+#[tokio::test]
+async fn test_register_webhook() {
+    let app = webhook_app().await;
+
+    let (status, body) = app
+        .send(
+            1,
+            "POST",
+            "/webhooks",
+            Some(json!({
+                "url": "https://example.com/hook",
+                "events": ["task.updated", "task.created", "task.updated"]
+            })),
+        )
+        .await;
+
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(body["url"], "https://example.com/hook");
+    assert_eq!(body["events"], json!(["task.created", "task.updated"]));
+    assert_eq!(body["secret"].as_str().unwrap().len(), 64);
+
+    let (status, body) = app.send(1, "GET", "/webhooks", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    assert!(body[0].get("secret").is_none());
+}
+
+#[tokio::test]
+async fn test_register_webhook_validation() {
+    let app = webhook_app().await;
+
+    for url in ["not a url", "ftp://example.com/hook", "/relative"] {
+        let (status, body) = app
+            .send(
+                1,
+                "POST",
+                "/webhooks",
+                Some(json!({"url": url, "events": ["task.created"]})),
+            )
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", url);
+        assert!(body["detail"].as_str().unwrap().contains("URL"));
+    }
+
+    let (status, _) = app
+        .send(
+            1,
+            "POST",
+            "/webhooks",
+            Some(json!({"url": "http://example.com", "events": []})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = app
+        .send(
+            1,
+            "POST",
+            "/webhooks",
+            Some(json!({"url": "http://example.com", "events": ["task.read"]})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn test_webhooks_are_private() {
+    let app = webhook_app().await;
+    let (id, _) = app
+        .register("http://example.com/hook", json!(["task.created"]))
+        .await;
+
+    let (status, _) = app
+        .send(2, "GET", &format!("/webhooks/{}/deliveries", id), None)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = app
+        .send(2, "DELETE", &format!("/webhooks/{}", id), None)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (_, body) = app.send(2, "GET", "/webhooks", None).await;
+    assert_eq!(body, json!([]));
+
+    let (status, _) = app
+        .send(1, "DELETE", &format!("/webhooks/{}", id), None)
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+}
+
+// ============================================================
+// ===== Delivery Tests =====
+// ============================================================
+
+#[tokio::test]
+async fn test_delivers_signed_event() {
+    let app = webhook_app().await;
+    let (url, mut requests) = receiver(0).await;
+    let (id, secret) = app.register(&url, json!(["task.created"])).await;
+
+    app.send(1, "POST", "/tasks", Some(json!({"title": "Hooked"})))
+        .await;
+
+    let request = next_request(&mut requests).await;
+    let body: Value = serde_json::from_str(&request.body).unwrap();
+    assert_eq!(body["type"], "task.created");
+    assert_eq!(body["task"]["title"], "Hooked");
+    assert_eq!(header(&request, "x-webhook-event"), "task.created");
+    assert_eq!(header(&request, "x-webhook-id"), id.to_string());
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(
+        format!(
+            "{}.{}",
+            header(&request, "x-webhook-timestamp"),
+            request.body
+        )
+        .as_bytes(),
+    );
+    let expected = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+    assert_eq!(header(&request, "x-webhook-signature"), expected);
+
+    let deliveries = app
+        .deliveries_when(id, |d| d.len() == 1 && d[0]["status"] == "delivered")
+        .await;
+    assert_eq!(deliveries[0]["attempts"], 1);
+    assert_eq!(deliveries[0]["last_status"], 204);
+    assert_eq!(deliveries[0]["payload"], body);
+}
+
+#[tokio::test]
+async fn test_only_subscribed_events_are_delivered() {
+    let app = webhook_app().await;
+    let (url, mut requests) = receiver(0).await;
+    app.register(&url, json!(["task.deleted"])).await;
+
+    // Changes of other users never reach this webhook
+    let (_, other) = app
+        .send(2, "POST", "/tasks", Some(json!({"title": "Other"})))
+        .await;
+    app.send(2, "DELETE", &format!("/tasks/{}", other["id"]), None)
+        .await;
+
+    let (_, task) = app
+        .send(1, "POST", "/tasks", Some(json!({"title": "Short-lived"})))
+        .await;
+    app.send(
+        1,
+        "PATCH",
+        &format!("/tasks/{}", task["id"]),
+        Some(json!({"completed": true})),
+    )
+    .await;
+    app.send(1, "DELETE", &format!("/tasks/{}", task["id"]), None)
+        .await;
+
+    let request = next_request(&mut requests).await;
+    let body: Value = serde_json::from_str(&request.body).unwrap();
+    assert_eq!(body["type"], "task.deleted");
+    assert_eq!(body["task"]["id"], task["id"]);
+    assert!(requests.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn test_bulk_create_queues_a_delivery_per_task() {
+    let app = webhook_app().await;
+    let (url, _requests) = receiver(0).await;
+    let (id, _) = app.register(&url, json!(["task.created"])).await;
+
+    // More changes than a live feed subscriber can fall behind by
+    let operations: Vec<Value> = (0..600)
+        .map(|i| json!({"op": "create", "task": {"title": format!("Bulk {}", i)}}))
+        .collect();
+    let (status, body) = app
+        .send(
+            1,
+            "POST",
+            "/tasks/bulk",
+            Some(json!({"operations": operations})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["succeeded"], 600);
+
+    let queued = tokio::time::timeout(DELIVERY_TIMEOUT, async {
+        loop {
+            let (queued,): (i64,) =
+                sqlx::query_as("SELECT COUNT(*) FROM webhook_deliveries WHERE webhook_id = ?")
+                    .bind(id)
+                    .fetch_one(app.pool())
+                    .await
+                    .unwrap();
+            if queued >= 600 {
+                return queued;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("Deliveries were not queued");
+    assert_eq!(queued, 600);
+}
+
+#[tokio::test]
+async fn test_failed_delivery_is_retried() {
+    let app = webhook_app().await;
+    let (url, mut requests) = receiver(2).await;
+    let (id, _) = app.register(&url, json!(["task.created"])).await;
+
+    app.send(1, "POST", "/tasks", Some(json!({"title": "Flaky"})))
+        .await;
+
+    let deliveries = app
+        .deliveries_when(id, |d| d.len() == 1 && d[0]["status"] == "delivered")
+        .await;
+    assert_eq!(deliveries[0]["attempts"], 3);
+    assert_eq!(deliveries[0]["last_error"], Value::Null);
+
+    // Every attempt is the same delivery
+    let delivery_id = deliveries[0]["id"].to_string();
+    for _ in 0..3 {
+        let request = next_request(&mut requests).await;
+        assert_eq!(header(&request, "x-webhook-delivery"), delivery_id);
+    }
+}
+
+#[tokio::test]
+async fn test_exhausted_delivery_is_dead_lettered() {
+    let app = webhook_app().await;
+    let (url, _requests) = receiver(usize::MAX).await;
+    let (id, _) = app.register(&url, json!(["task.created"])).await;
+
+    app.send(1, "POST", "/tasks", Some(json!({"title": "Doomed"})))
+        .await;
+
+    let deliveries = app
+        .deliveries_when(id, |d| d.len() == 1 && d[0]["status"] == "dead")
+        .await;
+    assert_eq!(deliveries[0]["attempts"], 3);
+    assert_eq!(deliveries[0]["last_status"], 500);
+    assert_eq!(deliveries[0]["next_attempt_at"], Value::Null);
+
+    let (_, dead) = app
+        .send(
+            1,
+            "GET",
+            &format!("/webhooks/{}/deliveries?status=dead", id),
+            None,
+        )
+        .await;
+    assert_eq!(dead.as_array().unwrap().len(), 1);
+
+    let dead_letters: (i64, i64) = sqlx::query_as(
+        "SELECT delivery_id, attempts FROM webhook_dead_letters WHERE webhook_id = ?",
+    )
+    .bind(id)
+    .fetch_one(app.pool())
+    .await
+    .unwrap();
+    assert_eq!(dead_letters, (deliveries[0]["id"].as_i64().unwrap(), 3));
+}
+
+#[tokio::test]
+async fn test_unreachable_endpoint_is_retried() {
+    let app = webhook_app().await;
+
+    // Nothing listens on this port once the listener is dropped
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    drop(listener);
+
+    let (id, _) = app.register(&url, json!(["task.created"])).await;
+    app.send(1, "POST", "/tasks", Some(json!({"title": "Nobody home"})))
+        .await;
+
+    let deliveries = app
+        .deliveries_when(id, |d| d.len() == 1 && d[0]["status"] == "dead")
+        .await;
+    assert_eq!(deliveries[0]["attempts"], 3);
+    assert_eq!(deliveries[0]["last_status"], Value::Null);
+    assert!(deliveries[0]["last_error"].is_string());
+}
+
+#[test]
+fn test_backoff_doubles_up_to_the_limit() {
+    let config = WebhookConfig {
+        base_delay: Duration::from_secs(2),
+        max_delay: Duration::from_secs(10),
+        ..WebhookConfig::default()
+    };
+
+    assert_eq!(config.backoff(1), Duration::from_secs(2));
+    assert_eq!(config.backoff(2), Duration::from_secs(4));
+    assert_eq!(config.backoff(3), Duration::from_secs(8));
+    assert_eq!(config.backoff(4), Duration::from_secs(10));
+    assert_eq!(config.backoff(40), Duration::from_secs(10));
+}
@@ -0,0 +1,622 @@
+//! HTTP Client
+
+use futures_util::{stream, Stream, TryStreamExt};
+use reqwest::{header, Method, RequestBuilder, Response, StatusCode, Url};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::error::{ClientError, Result};
+use crate::events;
+use crate::models::{
+    AuthToken, BulkRequest, BulkResponse, ChangeEvent, CreateProject, CreateTask, CreateWebhook,
+    Credentials, DeliveryQuery, EventFilters, ExportFormat, ImportReport, Liveness, NewWebhook,
+    Occurrence, OccurrenceQuery, Project, ProjectMember, ProjectRole, Readiness, Recurrence,
+    RecurrenceRule, ReplaceTask, SearchQuery, SearchResult, SetMemberRole, TagCount, TagsInput,
+    Task, TaskEvent, TaskFilters, TaskList, TaskNode, TaskPage, TaskStats, UpdateOptions,
+    UpdateTask, User, Webhook, WebhookDelivery,
+};
+use crate::retry::RetryPolicy;
+
+/// Header that makes retrying a `POST` safe
+const IDEMPOTENCY_KEY: &str = "idempotency-key";
+
+/// Media type of error responses
+const PROBLEM_JSON: &str = "application/problem+json";
+
+/// Client of one task API server
+///
+/// Cheap to clone: clones share the connection pool.
+#[derive(Debug, Clone)]
+pub struct TaskClient {
+    http: reqwest::Client,
+    base_url: Url,
+    token: Option<String>,
+    retry: RetryPolicy,
+}
+
+impl TaskClient {
+    /// Client for the server at `base_url` (e.g. `http://localhost:3000`),
+    /// without a token and with the default retry policy
+    pub fn new(base_url: &str) -> Result<Self> {
+        let mut url = Url::parse(base_url)
+            .map_err(|e| ClientError::InvalidUrl(format!("{}: {}", base_url, e)))?;
+
+        // Routes are joined to the base path, which must end with a slash
+        if !url.path().ends_with('/') {
+            url.set_path(&format!("{}/", url.path()));
+        }
+
+        Ok(Self {
+            http: reqwest::Client::new(),
+            base_url: url,
+            token: None,
+            retry: RetryPolicy::default(),
+        })
+    }
+
+    /// Send `token` as `Authorization: Bearer <token>`
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Retry failed requests according to `retry`
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Send requests through `http` (e.g. one with custom timeouts)
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Replace or remove the token
+    pub fn set_token(&mut self, token: Option<String>) {
+        self.token = token;
+    }
+
+    /// Token sent with each request
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
+    fn request(&self, method: Method, path: &str) -> Result<RequestBuilder> {
+        let url = self
+            .base_url
+            .join(path)
+            .map_err(|e| ClientError::InvalidUrl(format!("{}: {}", path, e)))?;
+
+        let mut request = self.http.request(method.clone(), url);
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        // Same key on every attempt, so the server runs the request once
+        if method == Method::POST {
+            request = request.header(IDEMPOTENCY_KEY, format!("{:032x}", rand::random::<u128>()));
+        }
+
+        Ok(request)
+    }
+
+    /// Send `request`, retrying as the policy allows
+    ///
+    /// Error statuses for which `accept` holds are returned like successes,
+    /// unless their body is problem details: some routes answer failures with
+    /// their usual body.
+    async fn send(&self, request: RequestBuilder, accept: Accept) -> Result<Response> {
+        let mut retry = 0;
+
+        loop {
+            // Bodies are always buffered, so the request can be cloned
+            let attempt = request.try_clone().expect("request body is buffered");
+
+            let error = match attempt.send().await {
+                Ok(response) if is_accepted(&response, accept) => return Ok(response),
+                Ok(response) => ClientError::from_response(response).await,
+                Err(e) => ClientError::Http(e),
+            };
+
+            match self.retry.delay(retry, &error) {
+                Some(delay) => {
+                    tokio::time::sleep(delay).await;
+                    retry += 1;
+                }
+                None => return Err(error),
+            }
+        }
+    }
+
+    async fn json<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T> {
+        self.json_accepting(request, |_| false).await
+    }
+
+    async fn json_accepting<T: DeserializeOwned>(
+        &self,
+        request: RequestBuilder,
+        accept: Accept,
+    ) -> Result<T> {
+        let body = self.send(request, accept).await?.bytes().await?;
+
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        self.json(self.request(Method::GET, path)?).await
+    }
+
+    async fn post<B: Serialize, T: DeserializeOwned>(&self, path: &str, body: &B) -> Result<T> {
+        self.json(self.request(Method::POST, path)?.json(body))
+            .await
+    }
+
+    /// `GET /health/live`
+    pub async fn health_live(&self) -> Result<Liveness> {
+        self.get("health/live").await
+    }
+
+    /// `GET /health/ready`; an unready server is reported in the result, not
+    /// as an error
+    pub async fn health_ready(&self) -> Result<Readiness> {
+        let request = self.request(Method::GET, "health/ready")?;
+
+        self.json_accepting(request, |status| status == StatusCode::SERVICE_UNAVAILABLE)
+            .await
+    }
+
+    /// `POST /auth/register`
+    pub async fn register(&self, credentials: &Credentials) -> Result<User> {
+        self.post("auth/register", credentials).await
+    }
+
+    /// `POST /auth/login`; pass the token to `with_token` to use it
+    pub async fn login(&self, credentials: &Credentials) -> Result<AuthToken> {
+        self.post("auth/login", credentials).await
+    }
+
+    /// `GET /tasks`: a page when `filters.cursor` is set, a plain list otherwise
+    pub async fn list_tasks(&self, filters: &TaskFilters) -> Result<TaskList> {
+        let request = self
+            .request(Method::GET, "tasks")?
+            .query(&filter_query(filters));
+
+        self.json(request).await
+    }
+
+    /// Every page of tasks matching `filters`, fetched as the stream is read
+    ///
+    /// `filters.limit` is the page size (server default: 100); pagination
+    /// starts at `filters.cursor`, or at the first page.
+    pub fn pages(&self, filters: TaskFilters) -> impl Stream<Item = Result<TaskPage>> + use<> {
+        let client = self.clone();
+        let first = TaskFilters {
+            cursor: Some(filters.cursor.clone().unwrap_or_default()),
+            ..filters
+        };
+
+        stream::try_unfold(Some(first), move |filters| {
+            let client = client.clone();
+
+            async move {
+                let Some(mut filters) = filters else {
+                    return Ok(None);
+                };
+
+                let page = match client.list_tasks(&filters).await? {
+                    TaskList::Page(page) => page,
+                    TaskList::Items(items) => TaskPage {
+                        items,
+                        next_cursor: None,
+                        has_more: false,
+                    },
+                };
+
+                let next = match &page.next_cursor {
+                    Some(cursor) if page.has_more => {
+                        filters.cursor = Some(cursor.clone());
+                        Some(filters)
+                    }
+                    _ => None,
+                };
+
+                Ok(Some((page, next)))
+            }
+        })
+    }
+
+    /// Every task matching `filters`, across all pages (see `pages`)
+    pub fn tasks(&self, filters: TaskFilters) -> impl Stream<Item = Result<Task>> + use<> {
+        self.pages(filters)
+            .map_ok(|page| stream::iter(page.items.into_iter().map(Ok)))
+            .try_flatten()
+    }
+
+    /// `GET /tasks/{id}`
+    pub async fn get_task(&self, id: i64) -> Result<Task> {
+        self.get(&format!("tasks/{}", id)).await
+    }
+
+    /// `POST /tasks`
+    pub async fn create_task(&self, task: &CreateTask) -> Result<Task> {
+        self.post("tasks", task).await
+    }
+
+    /// `PUT /tasks/{id}`
+    pub async fn replace_task(&self, id: i64, task: &ReplaceTask) -> Result<Task> {
+        self.replace_task_with(id, task, &UpdateOptions::default())
+            .await
+    }
+
+    /// `PUT /tasks/{id}` with options (e.g. `cascade`)
+    pub async fn replace_task_with(
+        &self,
+        id: i64,
+        task: &ReplaceTask,
+        options: &UpdateOptions,
+    ) -> Result<Task> {
+        let request = self
+            .request(Method::PUT, &format!("tasks/{}", id))?
+            .query(options)
+            .json(task);
+
+        self.json(request).await
+    }
+
+    /// `PATCH /tasks/{id}` (JSON Merge Patch)
+    pub async fn update_task(&self, id: i64, patch: &UpdateTask) -> Result<Task> {
+        self.update_task_with(id, patch, &UpdateOptions::default())
+            .await
+    }
+
+    /// `PATCH /tasks/{id}` with options (e.g. `cascade`)
+    pub async fn update_task_with(
+        &self,
+        id: i64,
+        patch: &UpdateTask,
+        options: &UpdateOptions,
+    ) -> Result<Task> {
+        let request = self
+            .request(Method::PATCH, &format!("tasks/{}", id))?
+            .query(options)
+            .json(patch);
+
+        self.json(request).await
+    }
+
+    /// `DELETE /tasks/{id}` (soft delete, undone by `restore_task`)
+    pub async fn delete_task(&self, id: i64) -> Result<()> {
+        let request = self.request(Method::DELETE, &format!("tasks/{}", id))?;
+        self.send(request, |_| false).await?;
+
+        Ok(())
+    }
+
+    /// `POST /tasks/{id}/restore`
+    pub async fn restore_task(&self, id: i64) -> Result<Task> {
+        let request = self.request(Method::POST, &format!("tasks/{}/restore", id))?;
+
+        self.json(request).await
+    }
+
+    /// `POST /tasks/bulk`
+    ///
+    /// A failed atomic request is not an error: the response tells which
+    /// operation failed, with `committed: false`.
+    pub async fn bulk(&self, request: &BulkRequest) -> Result<BulkResponse> {
+        let request = self.request(Method::POST, "tasks/bulk")?.json(request);
+
+        self.json_accepting(request, |status| status.is_client_error())
+            .await
+    }
+
+    /// `GET /tasks/export`: the tasks matching `filters` as one file
+    pub async fn export_tasks(
+        &self,
+        filters: &TaskFilters,
+        format: ExportFormat,
+    ) -> Result<String> {
+        let mut query = filter_query(filters);
+        query.push(("format", query_value(&format)));
+        let request = self.request(Method::GET, "tasks/export")?.query(&query);
+
+        Ok(self.send(request, |_| false).await?.text().await?)
+    }
+
+    /// `POST /tasks/import`
+    ///
+    /// When a row is invalid nothing is imported, and the report lists every
+    /// invalid row.
+    pub async fn import_tasks(
+        &self,
+        file: impl Into<String>,
+        format: ExportFormat,
+    ) -> Result<ImportReport> {
+        let request = self
+            .request(Method::POST, "tasks/import")?
+            .query(&[("format", query_value(&format))])
+            .body(file.into());
+
+        self.json_accepting(request, |status| status == StatusCode::BAD_REQUEST)
+            .await
+    }
+
+    /// `GET /tasks/search`
+    pub async fn search_tasks(&self, query: &SearchQuery) -> Result<Vec<SearchResult>> {
+        let request = self.request(Method::GET, "tasks/search")?.query(query);
+
+        self.json(request).await
+    }
+
+    /// `GET /tasks/stats`
+    pub async fn stats(&self) -> Result<TaskStats> {
+        self.get("tasks/stats").await
+    }
+
+    /// `GET /tasks/{id}/subtasks`
+    pub async fn subtasks(&self, id: i64) -> Result<Vec<Task>> {
+        self.get(&format!("tasks/{}/subtasks", id)).await
+    }
+
+    /// `GET /tasks/{id}/tree`
+    pub async fn task_tree(&self, id: i64) -> Result<TaskNode> {
+        self.get(&format!("tasks/{}/tree", id)).await
+    }
+
+    /// `GET /tasks/{id}/history`
+    pub async fn task_history(&self, id: i64) -> Result<Vec<TaskEvent>> {
+        self.get(&format!("tasks/{}/history", id)).await
+    }
+
+    /// `GET /tasks/events`: live changes, until the stream is dropped
+    ///
+    /// Errors opening the stream are returned here; the stream itself only
+    /// fails if the connection breaks. It is not retried.
+    pub async fn events(
+        &self,
+        filters: &EventFilters,
+    ) -> Result<impl Stream<Item = Result<ChangeEvent>> + use<>> {
+        let request = self.request(Method::GET, "tasks/events")?.query(filters);
+        let response = self.send(request, |_| false).await?;
+
+        Ok(events::change_events(response))
+    }
+
+    /// `POST /tasks/{id}/tags`
+    pub async fn add_tags(&self, id: i64, tags: &[&str]) -> Result<Task> {
+        self.post(&format!("tasks/{}/tags", id), &tags_input(tags))
+            .await
+    }
+
+    /// `DELETE /tasks/{id}/tags`
+    pub async fn remove_tags(&self, id: i64, tags: &[&str]) -> Result<Task> {
+        let request = self
+            .request(Method::DELETE, &format!("tasks/{}/tags", id))?
+            .json(&tags_input(tags));
+
+        self.json(request).await
+    }
+
+    /// `GET /tags`
+    pub async fn list_tags(&self) -> Result<Vec<TagCount>> {
+        self.get("tags").await
+    }
+
+    /// `PUT /tasks/{id}/recurrence`; the task needs a due date
+    pub async fn set_recurrence(&self, id: i64, rule: &RecurrenceRule) -> Result<Recurrence> {
+        let request = self
+            .request(Method::PUT, &format!("tasks/{}/recurrence", id))?
+            .json(rule);
+
+        self.json(request).await
+    }
+
+    /// `GET /tasks/{id}/recurrence`
+    pub async fn recurrence(&self, id: i64) -> Result<Recurrence> {
+        self.get(&format!("tasks/{}/recurrence", id)).await
+    }
+
+    /// `DELETE /tasks/{id}/recurrence`
+    pub async fn delete_recurrence(&self, id: i64) -> Result<()> {
+        let request = self.request(Method::DELETE, &format!("tasks/{}/recurrence", id))?;
+        self.send(request, |_| false).await?;
+
+        Ok(())
+    }
+
+    /// `GET /tasks/{id}/occurrences`
+    pub async fn occurrences(&self, id: i64, query: &OccurrenceQuery) -> Result<Vec<Occurrence>> {
+        let request = self
+            .request(Method::GET, &format!("tasks/{}/occurrences", id))?
+            .query(query);
+
+        self.json(request).await
+    }
+
+    /// `POST /webhooks`; the secret is only returned here
+    pub async fn create_webhook(&self, webhook: &CreateWebhook) -> Result<NewWebhook> {
+        self.post("webhooks", webhook).await
+    }
+
+    /// `GET /webhooks`
+    pub async fn list_webhooks(&self) -> Result<Vec<Webhook>> {
+        self.get("webhooks").await
+    }
+
+    /// `DELETE /webhooks/{id}`
+    pub async fn delete_webhook(&self, id: i64) -> Result<()> {
+        let request = self.request(Method::DELETE, &format!("webhooks/{}", id))?;
+        self.send(request, |_| false).await?;
+
+        Ok(())
+    }
+
+    /// `GET /webhooks/{id}/deliveries`
+    pub async fn webhook_deliveries(
+        &self,
+        id: i64,
+        query: &DeliveryQuery,
+    ) -> Result<Vec<WebhookDelivery>> {
+        let request = self
+            .request(Method::GET, &format!("webhooks/{}/deliveries", id))?
+            .query(query);
+
+        self.json(request).await
+    }
+
+    /// `POST /projects`; the caller becomes its owner
+    pub async fn create_project(&self, project: &CreateProject) -> Result<Project> {
+        self.post("projects", project).await
+    }
+
+    /// `GET /projects`: projects the caller is a member of
+    pub async fn list_projects(&self) -> Result<Vec<Project>> {
+        self.get("projects").await
+    }
+
+    /// `GET /projects/{pid}`
+    pub async fn get_project(&self, pid: i64) -> Result<Project> {
+        self.get(&format!("projects/{}", pid)).await
+    }
+
+    /// `DELETE /projects/{pid}`; the project must have no tasks left
+    pub async fn delete_project(&self, pid: i64) -> Result<()> {
+        let request = self.request(Method::DELETE, &format!("projects/{}", pid))?;
+        self.send(request, |_| false).await?;
+
+        Ok(())
+    }
+
+    /// `GET /projects/{pid}/members`
+    pub async fn project_members(&self, pid: i64) -> Result<Vec<ProjectMember>> {
+        self.get(&format!("projects/{}/members", pid)).await
+    }
+
+    /// `PUT /projects/{pid}/members/{username}`: add a member or change their role
+    pub async fn set_project_member(
+        &self,
+        pid: i64,
+        username: &str,
+        role: ProjectRole,
+    ) -> Result<ProjectMember> {
+        let request = self
+            .request(Method::PUT, &format!("projects/{}/members/{}", pid, username))?
+            .json(&SetMemberRole { role });
+
+        self.json(request).await
+    }
+
+    /// `DELETE /projects/{pid}/members/{username}`
+    pub async fn remove_project_member(&self, pid: i64, username: &str) -> Result<()> {
+        let request = self.request(
+            Method::DELETE,
+            &format!("projects/{}/members/{}", pid, username),
+        )?;
+        self.send(request, |_| false).await?;
+
+        Ok(())
+    }
+
+    /// `GET /projects/{pid}/tasks`, with the same filters as `list_tasks`
+    pub async fn list_project_tasks(&self, pid: i64, filters: &TaskFilters) -> Result<TaskList> {
+        let request = self
+            .request(Method::GET, &format!("projects/{}/tasks", pid))?
+            .query(&filter_query(filters));
+
+        self.json(request).await
+    }
+
+    /// `GET /projects/{pid}/tasks/{id}`
+    pub async fn get_project_task(&self, pid: i64, id: i64) -> Result<Task> {
+        self.get(&format!("projects/{}/tasks/{}", pid, id)).await
+    }
+
+    /// `POST /projects/{pid}/tasks`; needs the editor role
+    pub async fn create_project_task(&self, pid: i64, task: &CreateTask) -> Result<Task> {
+        self.post(&format!("projects/{}/tasks", pid), task).await
+    }
+
+    /// `PATCH /projects/{pid}/tasks/{id}` (JSON Merge Patch); needs the editor role
+    pub async fn update_project_task(&self, pid: i64, id: i64, patch: &UpdateTask) -> Result<Task> {
+        let request = self
+            .request(Method::PATCH, &format!("projects/{}/tasks/{}", pid, id))?
+            .json(patch);
+
+        self.json(request).await
+    }
+
+    /// `DELETE /projects/{pid}/tasks/{id}`; needs the editor role
+    pub async fn delete_project_task(&self, pid: i64, id: i64) -> Result<()> {
+        let request = self.request(Method::DELETE, &format!("projects/{}/tasks/{}", pid, id))?;
+        self.send(request, |_| false).await?;
+
+        Ok(())
+    }
+
+    /// `GET /projects/{pid}/tasks/stats`
+    pub async fn project_stats(&self, pid: i64) -> Result<TaskStats> {
+        self.get(&format!("projects/{}/tasks/stats", pid)).await
+    }
+}
+
+/// Which error statuses a route answers with its usual body
+type Accept = fn(StatusCode) -> bool;
+
+fn is_accepted(response: &Response, accept: Accept) -> bool {
+    let status = response.status();
+    if status.is_success() {
+        return true;
+    }
+
+    let is_problem = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(PROBLEM_JSON.as_bytes()));
+
+    accept(status) && !is_problem
+}
+
+fn tags_input(tags: &[&str]) -> TagsInput {
+    TagsInput {
+        tags: tags.iter().map(|tag| tag.to_string()).collect(),
+    }
+}
+
+/// Query string of `filters`, with `tag` repeated once per tag
+fn filter_query(filters: &TaskFilters) -> Vec<(&'static str, String)> {
+    let mut query = Vec::new();
+
+    if let Some(completed) = filters.completed {
+        query.push(("completed", completed.to_string()));
+    }
+    if let Some(limit) = filters.limit {
+        query.push(("limit", limit.to_string()));
+    }
+    if let Some(offset) = filters.offset {
+        query.push(("offset", offset.to_string()));
+    }
+    for tag in &filters.tag {
+        query.push(("tag", tag.clone()));
+    }
+    if let Some(tag_match) = &filters.tag_match {
+        query.push(("tag_match", query_value(tag_match)));
+    }
+    if let Some(overdue) = filters.overdue {
+        query.push(("overdue", overdue.to_string()));
+    }
+    if let Some(sort) = &filters.sort {
+        query.push(("sort", query_value(sort)));
+    }
+    if let Some(cursor) = &filters.cursor {
+        query.push(("cursor", cursor.clone()));
+    }
+
+    query
+}
+
+/// Name of an enum value, as the server spells it
+fn query_value<T: Serialize>(value: &T) -> String {
+    serde_json::to_value(value)
+        .ok()
+        .and_then(|value| value.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
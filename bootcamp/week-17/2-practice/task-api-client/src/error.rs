@@ -0,0 +1,179 @@
+//! Client Errors
+//!
+//! Error responses of the API are problem details (`ErrorResponse`); each
+//! status the server uses has its own variant, named like the server's
+//! `ApiError` variant that produced it.
+
+use std::time::Duration;
+
+use reqwest::{header, Response, StatusCode};
+use thiserror::Error;
+
+use crate::models::{ErrorResponse, FieldError};
+
+/// Why a request failed
+#[derive(Error, Debug)]
+pub enum ClientError {
+    /// 400: invalid request, with every invalid field in `errors`
+    #[error("Validation error: {}", .0.detail)]
+    Validation(Box<ErrorResponse>),
+
+    /// 401: missing, invalid or expired token
+    #[error("Unauthorized: {}", .0.detail)]
+    Unauthorized(Box<ErrorResponse>),
+
+    /// 403: the caller's project role does not allow the action
+    #[error("Forbidden: {}", .0.detail)]
+    Forbidden(Box<ErrorResponse>),
+
+    #[error("Not found: {}", .0.detail)]
+    NotFound(Box<ErrorResponse>),
+
+    /// 412: the task changed since the `ETag` was read
+    #[error("Precondition failed: {}", .0.detail)]
+    PreconditionFailed(Box<ErrorResponse>),
+
+    /// 409: a request with the same `Idempotency-Key` is still running
+    #[error("Conflict: {}", .0.detail)]
+    Conflict(Box<ErrorResponse>),
+
+    /// 422: `Idempotency-Key` reused for a different request
+    #[error("Unprocessable: {}", .0.detail)]
+    Unprocessable(Box<ErrorResponse>),
+
+    #[error("Payload too large: {}", .0.detail)]
+    PayloadTooLarge(Box<ErrorResponse>),
+
+    /// 429, with the wait the server asked for in `Retry-After`
+    #[error("Too many requests: {}", problem.detail)]
+    TooManyRequests {
+        problem: Box<ErrorResponse>,
+        retry_after: Option<Duration>,
+    },
+
+    /// 5xx
+    #[error("Server error ({}): {}", .0.status, .0.detail)]
+    Server(Box<ErrorResponse>),
+
+    /// Any other error status with problem details
+    #[error("HTTP {}: {}", .0.status, .0.detail)]
+    Api(Box<ErrorResponse>),
+
+    /// Error status without problem details (e.g. from a proxy or a timeout)
+    #[error("HTTP {status}: {body}")]
+    Status { status: StatusCode, body: String },
+
+    /// The request could not be sent or the response could not be read
+    #[error("Request failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    /// A response body that is not what the route returns
+    #[error("Invalid response: {0}")]
+    Decode(#[from] serde_json::Error),
+
+    #[error("Invalid URL: {0}")]
+    InvalidUrl(String),
+}
+
+impl ClientError {
+    /// Error for a response with an error status
+    pub(crate) async fn from_response(response: Response) -> Self {
+        let status = response.status();
+        let retry_after = response
+            .headers()
+            .get(header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok())
+            .map(Duration::from_secs);
+
+        let body = match response.bytes().await {
+            Ok(body) => body,
+            Err(e) => return Self::Http(e),
+        };
+        let Ok(problem) = serde_json::from_slice::<ErrorResponse>(&body) else {
+            return Self::Status {
+                status,
+                body: String::from_utf8_lossy(&body).into_owned(),
+            };
+        };
+        let problem = Box::new(problem);
+
+        match status {
+            StatusCode::BAD_REQUEST => Self::Validation(problem),
+            StatusCode::UNAUTHORIZED => Self::Unauthorized(problem),
+            StatusCode::FORBIDDEN => Self::Forbidden(problem),
+            StatusCode::NOT_FOUND => Self::NotFound(problem),
+            StatusCode::PRECONDITION_FAILED => Self::PreconditionFailed(problem),
+            StatusCode::CONFLICT => Self::Conflict(problem),
+            StatusCode::UNPROCESSABLE_ENTITY => Self::Unprocessable(problem),
+            StatusCode::PAYLOAD_TOO_LARGE => Self::PayloadTooLarge(problem),
+            StatusCode::TOO_MANY_REQUESTS => Self::TooManyRequests {
+                problem,
+                retry_after,
+            },
+            status if status.is_server_error() => Self::Server(problem),
+            _ => Self::Api(problem),
+        }
+    }
+
+    /// Problem details sent by the server, if any
+    pub fn problem(&self) -> Option<&ErrorResponse> {
+        match self {
+            Self::Validation(problem)
+            | Self::Unauthorized(problem)
+            | Self::Forbidden(problem)
+            | Self::NotFound(problem)
+            | Self::PreconditionFailed(problem)
+            | Self::Conflict(problem)
+            | Self::Unprocessable(problem)
+            | Self::PayloadTooLarge(problem)
+            | Self::TooManyRequests { problem, .. }
+            | Self::Server(problem)
+            | Self::Api(problem) => Some(problem),
+            _ => None,
+        }
+    }
+
+    /// HTTP status of the response, if one was received
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Self::Status { status, .. } => Some(*status),
+            Self::Http(e) => e.status(),
+            _ => self
+                .problem()
+                .and_then(|problem| StatusCode::from_u16(problem.status).ok()),
+        }
+    }
+
+    /// Invalid fields of a validation error (empty for other errors)
+    pub fn field_errors(&self) -> &[FieldError] {
+        self.problem().map_or(&[], |problem| &problem.errors)
+    }
+
+    /// Whether sending the same request again may succeed
+    pub fn is_retryable(&self) -> bool {
+        if let Self::Http(e) = self
+            && (e.is_connect() || e.is_timeout())
+        {
+            return true;
+        }
+
+        self.status().is_some_and(|status| {
+            matches!(
+                status,
+                StatusCode::REQUEST_TIMEOUT | StatusCode::TOO_MANY_REQUESTS
+            ) || (status.is_server_error() && status != StatusCode::NOT_IMPLEMENTED)
+        })
+    }
+
+    /// Wait asked for by the server with `Retry-After`
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::TooManyRequests { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+}
+
+/// Result type of client calls
+pub type Result<T> = std::result::Result<T, ClientError>;
@@ -0,0 +1,51 @@
+//! Server-Sent Events
+//!
+//! `GET /tasks/events` sends one `ChangeEvent` per event, as JSON in its
+//! `data` lines; comments (keep-alives) carry no data and are skipped.
+
+use futures_util::{stream, Stream};
+use reqwest::Response;
+
+use crate::error::Result;
+use crate::models::ChangeEvent;
+
+/// Change events read from an open event stream
+pub(crate) fn change_events(response: Response) -> impl Stream<Item = Result<ChangeEvent>> {
+    stream::try_unfold(
+        (response, Vec::new()),
+        |(mut response, mut buffer)| async move {
+            loop {
+                // Events end with a blank line
+                if let Some(end) = buffer.windows(2).position(|bytes| bytes == b"\n\n") {
+                    let event: Vec<u8> = buffer.drain(..end + 2).collect();
+
+                    if let Some(change) = parse_event(&event)? {
+                        return Ok(Some((change, (response, buffer))));
+                    }
+                    continue;
+                }
+
+                match response.chunk().await? {
+                    Some(chunk) => buffer.extend_from_slice(&chunk),
+                    None => return Ok(None),
+                }
+            }
+        },
+    )
+}
+
+/// The change in one event, `None` for an event without data
+fn parse_event(event: &[u8]) -> Result<Option<ChangeEvent>> {
+    let event = String::from_utf8_lossy(event);
+    let data: Vec<&str> = event
+        .lines()
+        .filter_map(|line| line.strip_prefix("data:"))
+        .map(|data| data.strip_prefix(' ').unwrap_or(data))
+        .collect();
+
+    if data.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(serde_json::from_str(&data.join("\n"))?))
+}
@@ -0,0 +1,49 @@
+//! Task API Client
+//!
+//! Typed async client for `project-task-api`, sharing its request and
+//! response types (re-exported as `models`).
+//!
+//! - One method per route, returning the server's own types
+//! - `tasks` / `pages` walk every page of a task list with cursor pagination
+//! - Connection failures, timeouts, 429 and 5xx are retried with exponential
+//!   backoff (see `RetryPolicy`); `POST` requests carry an `Idempotency-Key`,
+//!   so a retry never creates a task twice
+//! - Error responses come back as `ClientError` variants holding the
+//!   `ErrorResponse` problem details
+//!
+//! ```no_run
+//! use futures_util::TryStreamExt;
+//! use task_api_client::models::{CreateTask, Credentials};
+//! use task_api_client::TaskClient;
+//!
+//! # async fn run() -> task_api_client::Result<()> {
+//! let client = TaskClient::new("http://localhost:3000")?;
+//! let credentials = Credentials {
+//!     username: "ferris".into(),
+//!     password: "crab-secret".into(),
+//! };
+//! let token = client.login(&credentials).await?;
+//! let client = client.with_token(token.access_token);
+//!
+//! let task = CreateTask {
+//!     title: "Learn Rust".into(),
+//!     ..Default::default()
+//! };
+//! client.create_task(&task).await?;
+//!
+//! let tasks: Vec<_> = client.tasks(Default::default()).try_collect().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+mod client;
+mod error;
+mod events;
+mod retry;
+
+pub use client::TaskClient;
+pub use error::{ClientError, Result};
+pub use retry::RetryPolicy;
+
+/// Request and response types of the API
+pub use project_task_api::models;
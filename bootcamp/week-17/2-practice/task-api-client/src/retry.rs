@@ -0,0 +1,61 @@
+//! Retry Policy
+
+use std::time::Duration;
+
+use crate::error::ClientError;
+
+/// When and how long to wait before sending a failed request again
+///
+/// Only errors for which `ClientError::is_retryable` holds are retried. The
+/// wait doubles after every attempt, up to `max_backoff`, with random jitter
+/// so clients that failed together do not retry together. A `Retry-After`
+/// sent with 429 takes precedence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts after the first one (0 disables retrying)
+    pub max_retries: u32,
+    /// Wait before the first retry
+    pub initial_backoff: Duration,
+    /// Longest wait between two attempts
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Never retry
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            ..Self::default()
+        }
+    }
+
+    /// Wait before retry number `retry` (starting at 0) after `error`, or
+    /// `None` to give up
+    pub fn delay(&self, retry: u32, error: &ClientError) -> Option<Duration> {
+        if retry >= self.max_retries || !error.is_retryable() {
+            return None;
+        }
+
+        if let Some(retry_after) = error.retry_after() {
+            return Some(retry_after);
+        }
+
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_backoff);
+
+        // Between half and all of the backoff
+        Some(backoff.mul_f64(0.5 + rand::random::<f64>() / 2.0))
+    }
+}
@@ -0,0 +1,596 @@
+//! Client tests against the real router on an ephemeral port
+//!
+//! Run with: `cargo test -p task-api-client`
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::{
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    routing::{get, post},
+    Router,
+};
+use futures_util::{StreamExt, TryStreamExt};
+use project_task_api::{
+    auth::AuthConfig,
+    config::AppConfig,
+    db,
+    models::{ErrorResponse, Liveness},
+    repository::SqliteTaskRepository,
+    routes,
+    state::AppState,
+};
+use task_api_client::models::{
+    BulkMode, BulkOperation, BulkRequest, ChangeKind, CreateProject, CreateTask, CreateWebhook,
+    Credentials, EventFilters, ExportFormat, Frequency, OccurrenceQuery, Priority, ProjectRole,
+    RecurrenceRule, ReplaceTask, SearchQuery, TaskFilters, TaskSort, UpdateTask,
+};
+use task_api_client::{ClientError, RetryPolicy, TaskClient};
+use tokio::net::TcpListener;
+
+/// Serve `app` on an ephemeral port and return its base URL
+async fn serve(app: Router) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    format!("http://{}", address)
+}
+
+/// The task API with an in-memory database, as `main` builds it
+async fn spawn_server() -> String {
+    let pool = db::create_test_pool().await.expect("Error creating pool");
+    let config = AppConfig::default();
+    let auth = AuthConfig::new("test-secret");
+
+    let app = routes::create_routes::<SqliteTaskRepository>();
+    let app = routes::with_idempotency(app, &config, pool.clone(), auth.clone());
+    let app = routes::with_middleware(app, &config)
+        .with_state(AppState::new(SqliteTaskRepository::new(pool), auth));
+
+    serve(app).await
+}
+
+/// Client logged in as a new user of `base_url`
+async fn login(base_url: &str, username: &str) -> TaskClient {
+    let client = TaskClient::new(base_url).unwrap();
+    let credentials = Credentials {
+        username: username.to_string(),
+        password: "crab-secret".to_string(),
+    };
+
+    client.register(&credentials).await.unwrap();
+    let token = client.login(&credentials).await.unwrap();
+
+    client.with_token(token.access_token)
+}
+
+fn new_task(title: &str) -> CreateTask {
+    CreateTask {
+        title: title.to_string(),
+        ..CreateTask::default()
+    }
+}
+
+/// Retry quickly, so tests do not wait
+fn fast_retry(max_retries: u32) -> RetryPolicy {
+    RetryPolicy {
+        max_retries,
+        initial_backoff: Duration::from_millis(1),
+        max_backoff: Duration::from_millis(5),
+    }
+}
+
+// ============================================================
+// ===== Route Tests =====
+// ============================================================
+
+#[tokio::test]
+async fn test_task_lifecycle() {
+    let client = login(&spawn_server().await, "ferris").await;
+
+    let task = client
+        .create_task(&CreateTask {
+            description: Some("Chapter 1".to_string()),
+            priority: Some(Priority::High),
+            ..new_task("Read the book")
+        })
+        .await
+        .unwrap();
+    assert_eq!(task.title, "Read the book");
+    assert_eq!(task.priority, Priority::High);
+
+    let patch = UpdateTask {
+        description: Some(None),
+        completed: Some(Some(true)),
+        ..UpdateTask::default()
+    };
+    let updated = client.update_task(task.id, &patch).await.unwrap();
+    assert!(updated.completed);
+    assert_eq!(updated.description, None);
+    assert_eq!(updated.version, task.version + 1);
+
+    let replaced = client
+        .replace_task(
+            task.id,
+            &ReplaceTask {
+                title: "Reread the book".to_string(),
+                description: None,
+                completed: false,
+                due_at: None,
+                priority: None,
+                parent_id: None,
+                tags: vec!["books".to_string()],
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(replaced.title, "Reread the book");
+    assert_eq!(replaced.tags, ["books"]);
+
+    client.delete_task(task.id).await.unwrap();
+    let error = client.get_task(task.id).await.unwrap_err();
+    assert!(matches!(error, ClientError::NotFound(_)), "{:?}", error);
+
+    let restored = client.restore_task(task.id).await.unwrap();
+    assert_eq!(client.get_task(task.id).await.unwrap().id, restored.id);
+
+    let history = client.task_history(task.id).await.unwrap();
+    assert_eq!(history.len(), 5);
+}
+
+#[tokio::test]
+async fn test_subtasks_tags_search_and_stats() {
+    let client = login(&spawn_server().await, "ferris").await;
+
+    let root = client.create_task(&new_task("Launch")).await.unwrap();
+    let child = client
+        .create_task(&CreateTask {
+            parent_id: Some(root.id),
+            ..new_task("Write the rustacean docs")
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(client.subtasks(root.id).await.unwrap()[0].id, child.id);
+    assert_eq!(client.task_tree(root.id).await.unwrap().children.len(), 1);
+
+    let tagged = client
+        .add_tags(child.id, &["docs", "urgent"])
+        .await
+        .unwrap();
+    assert_eq!(tagged.tags, ["docs", "urgent"]);
+    let untagged = client.remove_tags(child.id, &["urgent"]).await.unwrap();
+    assert_eq!(untagged.tags, ["docs"]);
+    assert_eq!(client.list_tags().await.unwrap()[0].name, "docs");
+
+    let results = client
+        .search_tasks(&SearchQuery {
+            q: "rustac".to_string(),
+            completed: None,
+            limit: None,
+        })
+        .await
+        .unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].task.id, child.id);
+
+    let stats = client.stats().await.unwrap();
+    assert_eq!(stats.total, 2);
+    assert_eq!(stats.roots[0].subtasks, 1);
+}
+
+#[tokio::test]
+async fn test_bulk_export_and_import() {
+    let client = login(&spawn_server().await, "ferris").await;
+
+    let response = client
+        .bulk(&BulkRequest {
+            mode: BulkMode::Atomic,
+            operations: vec![
+                BulkOperation::Create {
+                    task: new_task("One"),
+                },
+                BulkOperation::Delete { id: 999 },
+            ],
+        })
+        .await
+        .unwrap();
+    assert!(!response.committed);
+    assert_eq!(response.results[1].status, 404);
+
+    let response = client
+        .bulk(&BulkRequest {
+            mode: BulkMode::BestEffort,
+            operations: vec![BulkOperation::Create {
+                task: new_task("One"),
+            }],
+        })
+        .await
+        .unwrap();
+    assert!(response.committed);
+
+    let csv = client
+        .export_tasks(&TaskFilters::default(), ExportFormat::Csv)
+        .await
+        .unwrap();
+    assert!(csv.contains("One"));
+
+    let report = client
+        .import_tasks(
+            "[{\"title\": \"Two\"}, {\"title\": \"\"}]",
+            ExportFormat::Json,
+        )
+        .await
+        .unwrap();
+    assert_eq!(report.imported, 0);
+    assert_eq!(report.errors[0].row, 2);
+
+    let report = client
+        .import_tasks("{\"title\": \"Two\"}\n", ExportFormat::Ndjson)
+        .await
+        .unwrap();
+    assert_eq!(report.imported, 1);
+}
+
+#[tokio::test]
+async fn test_webhooks_and_health() {
+    let base_url = spawn_server().await;
+    let client = login(&base_url, "ferris").await;
+
+    let webhook = client
+        .create_webhook(&CreateWebhook {
+            url: "http://127.0.0.1:9/hooks".to_string(),
+            events: vec![ChangeKind::Created],
+        })
+        .await
+        .unwrap();
+    assert!(!webhook.secret.is_empty());
+    assert_eq!(client.list_webhooks().await.unwrap().len(), 1);
+    assert!(client
+        .webhook_deliveries(webhook.webhook.id, &Default::default())
+        .await
+        .unwrap()
+        .is_empty());
+    client.delete_webhook(webhook.webhook.id).await.unwrap();
+    assert!(client.list_webhooks().await.unwrap().is_empty());
+
+    let anonymous = TaskClient::new(&base_url).unwrap();
+    assert_eq!(
+        anonymous.health_live().await.unwrap().build.name,
+        "project-task-api"
+    );
+    let ready = anonymous.health_ready().await.unwrap();
+    assert_eq!(ready.migrations.unwrap().pending, 0);
+}
+
+#[tokio::test]
+async fn test_recurrence() {
+    let client = login(&spawn_server().await, "ferris").await;
+    let task = client
+        .create_task(&CreateTask {
+            due_at: Some(serde_json::from_value("2030-01-06T09:00:00Z".into()).unwrap()),
+            ..new_task("Weekly review")
+        })
+        .await
+        .unwrap();
+
+    let rule = RecurrenceRule {
+        frequency: Frequency::Weekly,
+        interval: Some(2),
+        cron: None,
+        until: None,
+    };
+    let recurrence = client.set_recurrence(task.id, &rule).await.unwrap();
+    assert_eq!(recurrence.rule, rule);
+    assert_eq!(client.recurrence(task.id).await.unwrap().task_id, task.id);
+
+    let occurrences = client
+        .occurrences(task.id, &OccurrenceQuery { limit: Some(2) })
+        .await
+        .unwrap();
+    let dates: Vec<String> = occurrences
+        .iter()
+        .map(|occurrence| occurrence.due_at.to_rfc3339())
+        .collect();
+    assert_eq!(dates, ["2030-01-20T09:00:00+00:00", "2030-02-03T09:00:00+00:00"]);
+
+    client.delete_recurrence(task.id).await.unwrap();
+    let error = client.recurrence(task.id).await.unwrap_err();
+    assert_eq!(error.status(), Some(StatusCode::NOT_FOUND));
+}
+
+#[tokio::test]
+async fn test_projects() {
+    let server = spawn_server().await;
+    let owner = login(&server, "ferris").await;
+    let viewer = login(&server, "corro").await;
+
+    let project = owner
+        .create_project(&CreateProject {
+            name: "Launch".to_string(),
+            description: None,
+        })
+        .await
+        .unwrap();
+    assert_eq!(project.role, ProjectRole::Owner);
+
+    let member = owner
+        .set_project_member(project.id, "corro", ProjectRole::Viewer)
+        .await
+        .unwrap();
+    assert_eq!(member.role, ProjectRole::Viewer);
+
+    let task = owner
+        .create_project_task(project.id, &new_task("Shared"))
+        .await
+        .unwrap();
+    assert_eq!(viewer.get_project_task(project.id, task.id).await.unwrap().title, "Shared");
+    assert_eq!(viewer.project_stats(project.id).await.unwrap().total, 1);
+
+    let error = viewer
+        .create_project_task(project.id, &new_task("Not allowed"))
+        .await
+        .unwrap_err();
+    assert!(matches!(error, ClientError::Forbidden(_)), "{:?}", error);
+
+    let error = owner.delete_project(project.id).await.unwrap_err();
+    assert!(matches!(error, ClientError::Conflict(_)), "{:?}", error);
+
+    owner.delete_project_task(project.id, task.id).await.unwrap();
+    owner.delete_project(project.id).await.unwrap();
+    let error = viewer.get_project(project.id).await.unwrap_err();
+    assert_eq!(error.status(), Some(StatusCode::NOT_FOUND));
+}
+
+#[tokio::test]
+async fn test_event_stream() {
+    let client = login(&spawn_server().await, "ferris").await;
+
+    let events = client.events(&EventFilters::default()).await.unwrap();
+    let mut events = Box::pin(events);
+    let task = client.create_task(&new_task("Watched")).await.unwrap();
+
+    let event = tokio::time::timeout(Duration::from_secs(5), events.next())
+        .await
+        .expect("No event received")
+        .unwrap()
+        .unwrap();
+    assert_eq!(event.kind, ChangeKind::Created);
+    assert_eq!(event.task.id, task.id);
+}
+
+// ============================================================
+// ===== Pagination Tests =====
+// ============================================================
+
+#[tokio::test]
+async fn test_tasks_stream_walks_every_page() {
+    let client = login(&spawn_server().await, "ferris").await;
+    for n in 1..=7 {
+        client
+            .create_task(&new_task(&format!("Task {}", n)))
+            .await
+            .unwrap();
+    }
+
+    let filters = TaskFilters {
+        limit: Some(3),
+        ..TaskFilters::default()
+    };
+
+    let pages: Vec<_> = client.pages(filters.clone()).try_collect().await.unwrap();
+    let sizes: Vec<usize> = pages.iter().map(|page| page.items.len()).collect();
+    assert_eq!(sizes, [3, 3, 1]);
+    assert!(!pages[2].has_more);
+
+    let titles: Vec<String> = client
+        .tasks(filters)
+        .map_ok(|task| task.title)
+        .try_collect()
+        .await
+        .unwrap();
+    let expected: Vec<String> = (1..=7).rev().map(|n| format!("Task {}", n)).collect();
+    assert_eq!(titles, expected);
+}
+
+#[tokio::test]
+async fn test_tasks_stream_keeps_filters() {
+    let client = login(&spawn_server().await, "ferris").await;
+    for (title, priority) in [
+        ("a", Priority::Low),
+        ("b", Priority::Urgent),
+        ("c", Priority::High),
+    ] {
+        client
+            .create_task(&CreateTask {
+                priority: Some(priority),
+                tags: vec!["work".to_string()],
+                ..new_task(title)
+            })
+            .await
+            .unwrap();
+    }
+    client.create_task(&new_task("untagged")).await.unwrap();
+
+    let titles: Vec<String> = client
+        .tasks(TaskFilters {
+            limit: Some(1),
+            tag: vec!["work".to_string()],
+            sort: Some(TaskSort::Priority),
+            ..TaskFilters::default()
+        })
+        .map_ok(|task| task.title)
+        .try_collect()
+        .await
+        .unwrap();
+
+    assert_eq!(titles, ["b", "c", "a"]);
+}
+
+// ============================================================
+// ===== Error Tests =====
+// ============================================================
+
+#[tokio::test]
+async fn test_validation_error_lists_fields() {
+    let client = login(&spawn_server().await, "ferris").await;
+
+    let error = client.create_task(&new_task("")).await.unwrap_err();
+
+    let ClientError::Validation(problem) = &error else {
+        panic!("Expected a validation error, got {:?}", error);
+    };
+    assert_eq!(problem.status, 400);
+    assert_eq!(problem.instance.as_deref(), Some("/tasks"));
+    assert_eq!(error.field_errors()[0].field, "title");
+    assert_eq!(error.status(), Some(reqwest::StatusCode::BAD_REQUEST));
+}
+
+#[tokio::test]
+async fn test_missing_token_is_unauthorized() {
+    let client = TaskClient::new(&spawn_server().await).unwrap();
+
+    let error = client
+        .list_tasks(&TaskFilters::default())
+        .await
+        .unwrap_err();
+
+    assert!(matches!(error, ClientError::Unauthorized(_)), "{:?}", error);
+    assert!(!error.is_retryable());
+}
+
+// ============================================================
+// ===== Retry Tests =====
+// ============================================================
+
+/// Server answering 503 to the first `failures` requests
+async fn flaky_server(failures: usize) -> (String, Arc<AtomicUsize>) {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+
+    let app = Router::new().route(
+        "/health/live",
+        get(move || {
+            let call = counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if call < failures {
+                    return unavailable();
+                }
+                project_task_api::handlers::health_live()
+                    .await
+                    .into_response()
+            }
+        }),
+    );
+
+    (serve(app).await, calls)
+}
+
+fn problem(status: StatusCode) -> ErrorResponse {
+    ErrorResponse {
+        problem_type: "about:blank".to_string(),
+        title: status.canonical_reason().unwrap().to_string(),
+        status: status.as_u16(),
+        detail: "Try again".to_string(),
+        instance: None,
+        errors: Vec::new(),
+    }
+}
+
+fn unavailable() -> axum::response::Response {
+    let status = StatusCode::SERVICE_UNAVAILABLE;
+
+    (status, axum::Json(problem(status))).into_response()
+}
+
+#[tokio::test]
+async fn test_server_errors_are_retried() {
+    let (base_url, calls) = flaky_server(2).await;
+    let client = TaskClient::new(&base_url)
+        .unwrap()
+        .with_retry(fast_retry(3));
+
+    let live: Liveness = client.health_live().await.unwrap();
+
+    assert_eq!(live.build.name, "project-task-api");
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_retries_give_up_after_the_limit() {
+    let (base_url, calls) = flaky_server(10).await;
+    let client = TaskClient::new(&base_url)
+        .unwrap()
+        .with_retry(fast_retry(2));
+
+    let error = client.health_live().await.unwrap_err();
+
+    assert!(matches!(error, ClientError::Server(_)), "{:?}", error);
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+    let client = client.with_retry(RetryPolicy::none());
+    client.health_live().await.unwrap_err();
+    assert_eq!(calls.load(Ordering::SeqCst), 4);
+}
+
+#[tokio::test]
+async fn test_post_retries_reuse_the_idempotency_key() {
+    let keys = Arc::new(Mutex::new(Vec::new()));
+    let seen = keys.clone();
+
+    let app = Router::new().route(
+        "/tasks",
+        post(move |headers: HeaderMap| {
+            let key = headers["idempotency-key"].to_str().unwrap().to_string();
+            seen.lock().unwrap().push(key);
+            async { unavailable() }
+        }),
+    );
+    let client = TaskClient::new(&serve(app).await)
+        .unwrap()
+        .with_retry(fast_retry(2));
+
+    client.create_task(&new_task("Once")).await.unwrap_err();
+    client.create_task(&new_task("Twice")).await.unwrap_err();
+
+    // Three attempts per call, one key per call
+    let keys = keys.lock().unwrap();
+    assert_eq!(keys.len(), 6);
+    assert!(keys[..3].iter().all(|key| *key == keys[0]));
+    assert!(keys[3..].iter().all(|key| *key == keys[3]));
+    assert_ne!(keys[0], keys[3]);
+}
+
+#[tokio::test]
+async fn test_rate_limited_requests_are_retried() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+
+    let app = Router::new().route(
+        "/health/live",
+        get(move || {
+            let call = counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if call == 0 {
+                    let status = StatusCode::TOO_MANY_REQUESTS;
+                    return (status, [("retry-after", "0")], axum::Json(problem(status)))
+                        .into_response();
+                }
+                project_task_api::handlers::health_live()
+                    .await
+                    .into_response()
+            }
+        }),
+    );
+    let client = TaskClient::new(&serve(app).await)
+        .unwrap()
+        .with_retry(fast_retry(1));
+
+    client.health_live().await.unwrap();
+
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
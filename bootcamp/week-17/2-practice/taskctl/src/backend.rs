@@ -0,0 +1,176 @@
+//! Backends
+//!
+//! Every command runs either against a server, through `TaskClient`, or
+//! directly against its SQLite file, through `SqliteTaskRepository` (embedded
+//! mode). Both return the same models, so the output does not depend on the
+//! mode.
+
+use futures_util::{StreamExt, TryStreamExt};
+use project_task_api::auth::CurrentUser;
+use project_task_api::config::DatabaseConfig;
+use project_task_api::db;
+use project_task_api::models::{
+    CreateTask, SearchQuery, SearchResult, Task, TaskFilters, TaskList, TaskStats, UpdateTask,
+};
+use project_task_api::repository::{SqliteTaskRepository, TaskChange, TaskRepository};
+use task_api_client::TaskClient;
+
+use crate::config::Target;
+use crate::error::{CliError, Result};
+
+/// Page size used to walk every task
+const PAGE_SIZE: usize = 100;
+
+/// Where the commands are sent
+#[derive(Debug, Clone)]
+pub enum Backend {
+    Http(TaskClient),
+    Embedded {
+        repository: SqliteTaskRepository,
+        user: CurrentUser,
+    },
+}
+
+impl Backend {
+    /// Connect to the target; embedded mode migrates the database first
+    pub async fn connect(target: Target) -> Result<Self> {
+        match target {
+            Target::Http { url, token } => {
+                let client = TaskClient::new(&url)?;
+                Ok(Self::Http(match token {
+                    Some(token) => client.with_token(token),
+                    None => client,
+                }))
+            }
+            Target::Embedded { database, user } => {
+                let pool =
+                    db::create_pool(&DatabaseConfig::with_url(database_url(&database))).await?;
+                let user = current_user(&pool, &user).await?;
+
+                Ok(Self::embedded(SqliteTaskRepository::new(pool), user))
+            }
+        }
+    }
+
+    /// Embedded backend over an existing repository
+    pub fn embedded(repository: SqliteTaskRepository, user: CurrentUser) -> Self {
+        Self::Embedded { repository, user }
+    }
+
+    /// Tasks matching `filters`, walking pages until `limit` tasks (or all)
+    pub async fn list(&self, filters: TaskFilters, limit: Option<usize>) -> Result<Vec<Task>> {
+        let page_size = limit.unwrap_or(PAGE_SIZE).clamp(1, PAGE_SIZE);
+        let filters = TaskFilters {
+            limit: Some(page_size as i64),
+            ..filters
+        };
+
+        match self {
+            Self::Http(client) => {
+                let tasks = client.tasks(filters);
+                let tasks: Vec<Task> = match limit {
+                    Some(limit) => tasks.take(limit).try_collect().await?,
+                    None => tasks.try_collect().await?,
+                };
+                Ok(tasks)
+            }
+            Self::Embedded { repository, user } => {
+                let mut filters = TaskFilters {
+                    cursor: Some(String::new()),
+                    ..filters
+                };
+                let mut tasks = Vec::new();
+
+                loop {
+                    let page = match repository.list(user.id, filters.clone()).await? {
+                        TaskList::Page(page) => page,
+                        TaskList::Items(items) => {
+                            tasks.extend(items);
+                            break;
+                        }
+                    };
+                    tasks.extend(page.items);
+
+                    match page.next_cursor {
+                        Some(cursor) if page.has_more && limit.is_none_or(|n| tasks.len() < n) => {
+                            filters.cursor = Some(cursor);
+                        }
+                        _ => break,
+                    }
+                }
+
+                if let Some(limit) = limit {
+                    tasks.truncate(limit);
+                }
+                Ok(tasks)
+            }
+        }
+    }
+
+    pub async fn create(&self, task: CreateTask) -> Result<Task> {
+        match self {
+            Self::Http(client) => Ok(client.create_task(&task).await?),
+            Self::Embedded { repository, user } => Ok(repository.create(user, task).await?),
+        }
+    }
+
+    /// Apply a JSON Merge Patch
+    pub async fn update(&self, id: i64, patch: UpdateTask) -> Result<Task> {
+        match self {
+            Self::Http(client) => Ok(client.update_task(id, &patch).await?),
+            Self::Embedded { repository, user } => Ok(repository
+                .update(user, id, TaskChange::Patch(patch), false, &|_| Ok(()))
+                .await?),
+        }
+    }
+
+    /// Delete a task and its subtasks
+    pub async fn delete(&self, id: i64) -> Result<()> {
+        match self {
+            Self::Http(client) => client.delete_task(id).await?,
+            Self::Embedded { repository, user } => {
+                repository.delete(user, id, &|_| Ok(())).await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn stats(&self) -> Result<TaskStats> {
+        match self {
+            Self::Http(client) => Ok(client.stats().await?),
+            Self::Embedded { repository, user } => Ok(repository.stats(user.id).await?),
+        }
+    }
+
+    pub async fn search(&self, query: SearchQuery) -> Result<Vec<SearchResult>> {
+        match self {
+            Self::Http(client) => Ok(client.search_tasks(&query).await?),
+            Self::Embedded { repository, user } => Ok(repository.search(user.id, &query).await?),
+        }
+    }
+}
+
+/// SQLite URL for a `--database` value, which may be a plain path
+fn database_url(database: &str) -> String {
+    if database.starts_with("sqlite:") {
+        database.to_string()
+    } else {
+        // Unlike the server, never create the file: a typo should be an error
+        format!("sqlite:{}?mode=rw", database)
+    }
+}
+
+/// The registered user that owns the tasks in embedded mode
+async fn current_user(pool: &db::DbPool, username: &str) -> Result<CurrentUser> {
+    let id: Option<i64> = sqlx::query_scalar("SELECT id FROM users WHERE username = ?")
+        .bind(username)
+        .fetch_optional(pool)
+        .await?;
+
+    let id = id.ok_or_else(|| CliError::UnknownUser(username.to_string()))?;
+
+    Ok(CurrentUser {
+        id,
+        username: username.to_string(),
+    })
+}
@@ -0,0 +1,282 @@
+//! Command-Line Arguments
+//!
+//! Global flags pick the server (or database) and the output format; each
+//! subcommand maps to one task operation.
+
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use project_task_api::models::{Priority, TagMatch, TaskSort};
+
+/// Manage tasks of a Task API server from the terminal
+#[derive(Debug, Parser)]
+#[command(name = "taskctl", version)]
+pub struct Cli {
+    #[command(flatten)]
+    pub target: TargetArgs,
+
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t, global = true)]
+    pub output: OutputFormat,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+/// Where the tasks live: a profile, a server or a database file
+#[derive(Debug, Clone, Default, Args)]
+pub struct TargetArgs {
+    /// Profiles file (default: $XDG_CONFIG_HOME/taskctl/config.toml)
+    #[arg(long, env = "TASKCTL_CONFIG", value_name = "PATH", global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Profile to use instead of `default_profile`
+    #[arg(
+        short,
+        long,
+        env = "TASKCTL_PROFILE",
+        value_name = "NAME",
+        global = true
+    )]
+    pub profile: Option<String>,
+
+    /// Base URL of the server, overriding the profile
+    #[arg(long, env = "TASKCTL_URL", value_name = "URL", global = true)]
+    pub url: Option<String>,
+
+    /// Bearer token for the server, overriding the profile
+    #[arg(
+        long,
+        env = "TASKCTL_TOKEN",
+        value_name = "TOKEN",
+        global = true,
+        hide_env_values = true
+    )]
+    pub token: Option<String>,
+
+    /// Open this SQLite database directly instead of talking to a server
+    #[arg(
+        long,
+        env = "TASKCTL_DATABASE",
+        value_name = "PATH",
+        global = true,
+        conflicts_with = "url"
+    )]
+    pub database: Option<String>,
+
+    /// Owner of the tasks in embedded mode
+    #[arg(long, env = "TASKCTL_USER", value_name = "USERNAME", global = true)]
+    pub user: Option<String>,
+}
+
+/// How results are printed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Aligned columns
+    #[default]
+    Table,
+    /// Pretty-printed JSON, as the API returns it
+    Json,
+    /// Comma-separated values with a header row
+    Csv,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// List tasks
+    #[command(visible_alias = "ls")]
+    List(ListArgs),
+
+    /// Create a task
+    Add(AddArgs),
+
+    /// Mark tasks as completed
+    Done {
+        /// Task IDs
+        #[arg(required = true)]
+        ids: Vec<i64>,
+    },
+
+    /// Change fields of a task
+    Edit(EditArgs),
+
+    /// Delete tasks and their subtasks
+    Rm {
+        /// Task IDs
+        #[arg(required = true)]
+        ids: Vec<i64>,
+    },
+
+    /// Show task counts
+    Stats,
+
+    /// Full-text search in titles and descriptions
+    Search(SearchArgs),
+
+    /// Print a shell completion script
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Debug, Default, Args)]
+pub struct ListArgs {
+    /// Only completed tasks
+    #[arg(long, conflicts_with = "pending")]
+    pub done: bool,
+
+    /// Only pending tasks
+    #[arg(long)]
+    pub pending: bool,
+
+    /// Only tasks past their deadline
+    #[arg(long)]
+    pub overdue: bool,
+
+    /// Only tasks with this tag (repeatable)
+    #[arg(short, long = "tag", value_name = "TAG")]
+    pub tags: Vec<String>,
+
+    /// Require every tag instead of any
+    #[arg(long, requires = "tags")]
+    pub all_tags: bool,
+
+    /// Sort order (created, due_at, priority)
+    #[arg(long, value_parser = parse_sort)]
+    pub sort: Option<TaskSort>,
+
+    /// Maximum number of tasks (default: all)
+    #[arg(short = 'n', long)]
+    pub limit: Option<usize>,
+}
+
+impl ListArgs {
+    pub fn completed(&self) -> Option<bool> {
+        match (self.done, self.pending) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        }
+    }
+
+    pub fn tag_match(&self) -> Option<TagMatch> {
+        self.all_tags.then_some(TagMatch::All)
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct AddArgs {
+    /// Task title
+    pub title: String,
+
+    #[arg(short, long)]
+    pub description: Option<String>,
+
+    /// Deadline (RFC 3339, e.g. 2025-02-01T18:00:00Z)
+    #[arg(long, value_name = "DATE")]
+    pub due: Option<DateTime<Utc>>,
+
+    #[arg(short = 'P', long, value_parser = parse_priority)]
+    pub priority: Option<Priority>,
+
+    /// Parent task, making this a subtask
+    #[arg(long, value_name = "ID")]
+    pub parent: Option<i64>,
+
+    /// Tag (repeatable)
+    #[arg(short, long = "tag", value_name = "TAG")]
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct EditArgs {
+    /// Task ID
+    pub id: i64,
+
+    #[arg(long)]
+    pub title: Option<String>,
+
+    #[arg(short, long, conflicts_with = "clear_description")]
+    pub description: Option<String>,
+
+    /// Remove the description
+    #[arg(long)]
+    pub clear_description: bool,
+
+    /// Deadline (RFC 3339)
+    #[arg(long, value_name = "DATE", conflicts_with = "clear_due")]
+    pub due: Option<DateTime<Utc>>,
+
+    /// Remove the deadline
+    #[arg(long)]
+    pub clear_due: bool,
+
+    #[arg(short = 'P', long, value_parser = parse_priority)]
+    pub priority: Option<Priority>,
+
+    /// Mark as completed (`true`) or pending (`false`)
+    #[arg(long, value_name = "BOOL")]
+    pub completed: Option<bool>,
+
+    /// Move under another task
+    #[arg(long, value_name = "ID", conflicts_with = "root")]
+    pub parent: Option<i64>,
+
+    /// Make it a root task
+    #[arg(long)]
+    pub root: bool,
+
+    /// Replace the tags (repeatable)
+    #[arg(short, long = "tag", value_name = "TAG", conflicts_with = "clear_tags")]
+    pub tags: Vec<String>,
+
+    /// Remove every tag
+    #[arg(long)]
+    pub clear_tags: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct SearchArgs {
+    /// Words to search (prefix matching)
+    #[arg(required = true)]
+    pub words: Vec<String>,
+
+    /// Only completed tasks
+    #[arg(long, conflicts_with = "pending")]
+    pub done: bool,
+
+    /// Only pending tasks
+    #[arg(long)]
+    pub pending: bool,
+
+    /// Maximum number of results (default: 20)
+    #[arg(short = 'n', long)]
+    pub limit: Option<i64>,
+}
+
+impl SearchArgs {
+    pub fn completed(&self) -> Option<bool> {
+        match (self.done, self.pending) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        }
+    }
+}
+
+/// Parse a value with the names the API uses (`high`, `due_at`...)
+fn parse_api_name<T: serde::de::DeserializeOwned>(value: &str) -> Result<T, String> {
+    serde_json::from_value(serde_json::Value::String(value.to_string()))
+        .map_err(|_| format!("unknown value '{}'", value))
+}
+
+fn parse_priority(value: &str) -> Result<Priority, String> {
+    parse_api_name(value).map_err(|e| format!("{} (low, medium, high, urgent)", e))
+}
+
+fn parse_sort(value: &str) -> Result<TaskSort, String> {
+    parse_api_name(value).map_err(|e| format!("{} (created, due_at, priority)", e))
+}
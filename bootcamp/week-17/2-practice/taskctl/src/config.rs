@@ -0,0 +1,150 @@
+//! Profiles
+//!
+//! A TOML file lists the servers (or database files) `taskctl` can talk to;
+//! `--profile` picks one, `default_profile` otherwise. Flags and `TASKCTL_*`
+//! variables override the profile's values.
+//!
+//! ```toml
+//! default_profile = "local"
+//!
+//! [profiles.local]
+//! url = "http://localhost:3000"
+//! token = "eyJhbGciOi..."
+//!
+//! [profiles.prod]
+//! url = "https://tasks.example.com"
+//!
+//! [profiles.offline]
+//! database = "tasks.db"       # embedded mode, no server needed
+//! user = "ferris"
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::cli::TargetArgs;
+use crate::error::{CliError, Result};
+
+/// Server used when there is no profile and no `--url`
+pub const DEFAULT_URL: &str = "http://localhost:3000";
+
+/// Contents of the profiles file
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+    /// Profile used when `--profile` is not given
+    pub default_profile: Option<String>,
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+/// One server or database file
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    /// Base URL of the server
+    pub url: Option<String>,
+    /// Bearer token sent to the server
+    pub token: Option<String>,
+    /// SQLite file opened directly (embedded mode)
+    pub database: Option<String>,
+    /// Owner of the tasks in embedded mode
+    pub user: Option<String>,
+}
+
+/// What the commands run against
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    Http { url: String, token: Option<String> },
+    Embedded { database: String, user: String },
+}
+
+impl ConfigFile {
+    /// Parse a profiles file; a missing optional file has no profiles
+    pub fn from_file(path: &Path, required: bool) -> Result<Self> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if !required && e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Self::default());
+            }
+            Err(source) => {
+                return Err(CliError::ReadConfig {
+                    path: path.to_path_buf(),
+                    source,
+                });
+            }
+        };
+
+        toml::from_str(&text).map_err(|e| CliError::ParseConfig {
+            path: path.to_path_buf(),
+            message: e.message().to_string(),
+        })
+    }
+
+    /// The profile selected by `--profile` or `default_profile`
+    ///
+    /// Naming a profile that does not exist is an error; with no name at all
+    /// the result is an empty profile.
+    pub fn profile(&self, name: Option<&str>) -> Result<Profile> {
+        match name.or(self.default_profile.as_deref()) {
+            Some(name) => self
+                .profiles
+                .get(name)
+                .cloned()
+                .ok_or_else(|| CliError::UnknownProfile(name.to_string())),
+            None => Ok(Profile::default()),
+        }
+    }
+}
+
+impl Target {
+    /// Resolve the target from the flags and the profiles file
+    pub fn resolve(args: &TargetArgs) -> Result<Self> {
+        let (path, required) = match &args.config {
+            Some(path) => (path.clone(), true),
+            None => (default_config_file(), false),
+        };
+        let profile = ConfigFile::from_file(&path, required)?.profile(args.profile.as_deref())?;
+
+        Self::from_profile(args, profile)
+    }
+
+    /// Combine a profile with the flags, which take precedence
+    pub fn from_profile(args: &TargetArgs, profile: Profile) -> Result<Self> {
+        // An explicit URL switches a database profile to HTTP, and vice versa
+        let database = match &args.url {
+            Some(_) => args.database.clone(),
+            None => args.database.clone().or(profile.database),
+        };
+
+        if let Some(database) = database {
+            let user = args
+                .user
+                .clone()
+                .or(profile.user)
+                .ok_or(CliError::MissingUser)?;
+            return Ok(Self::Embedded { database, user });
+        }
+
+        Ok(Self::Http {
+            url: args
+                .url
+                .clone()
+                .or(profile.url)
+                .unwrap_or_else(|| DEFAULT_URL.to_string()),
+            token: args.token.clone().or(profile.token),
+        })
+    }
+}
+
+/// `$XDG_CONFIG_HOME/taskctl/config.toml`, or `~/.config/taskctl/config.toml`
+pub fn default_config_file() -> PathBuf {
+    let env = |var| std::env::var_os(var).filter(|value| !value.is_empty());
+    let config_dir = env("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .unwrap_or_default();
+
+    config_dir.join("taskctl").join("config.toml")
+}
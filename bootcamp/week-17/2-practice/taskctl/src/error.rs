@@ -0,0 +1,50 @@
+//! CLI Errors
+
+use std::path::PathBuf;
+
+use project_task_api::error::ApiError;
+use task_api_client::ClientError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum CliError {
+    #[error("cannot read {}: {source}", path.display())]
+    ReadConfig {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("invalid config file {}: {message}", path.display())]
+    ParseConfig { path: PathBuf, message: String },
+
+    #[error("unknown profile '{0}'")]
+    UnknownProfile(String),
+
+    #[error("embedded mode needs a user: pass --user or set it in the profile")]
+    MissingUser,
+
+    #[error("no user '{0}' in the database")]
+    UnknownUser(String),
+
+    #[error("cannot open database: {0}")]
+    Database(#[from] sqlx::Error),
+
+    /// Error from the server (HTTP mode)
+    #[error(transparent)]
+    Client(#[from] ClientError),
+
+    /// Error from the repository (embedded mode)
+    #[error(transparent)]
+    Api(#[from] ApiError),
+
+    #[error("cannot write output: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("cannot write output: {0}")]
+    Csv(#[from] csv::Error),
+
+    #[error("cannot write output: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+pub type Result<T> = std::result::Result<T, CliError>;
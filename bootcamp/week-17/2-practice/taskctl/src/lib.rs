@@ -0,0 +1,131 @@
+//! taskctl - Task API from the terminal
+//!
+//! Lists, creates, edits and deletes tasks of a Task API server, or of its
+//! SQLite file directly (embedded mode), printing them as a table, JSON or
+//! CSV.
+
+pub mod backend;
+pub mod cli;
+pub mod config;
+pub mod error;
+pub mod output;
+
+use std::io::Write;
+
+use clap::CommandFactory;
+use clap_complete::Shell;
+use project_task_api::models::{CreateTask, SearchQuery, TaskFilters, UpdateTask};
+
+use crate::backend::Backend;
+use crate::cli::{Cli, Command, EditArgs};
+use crate::config::Target;
+use crate::error::Result;
+
+/// Run a command line, writing its output to `out`
+pub async fn run(cli: Cli, out: &mut dyn Write) -> Result<()> {
+    // Completions need no server
+    if let Command::Completions { shell } = cli.command {
+        completions(shell, out);
+        return Ok(());
+    }
+
+    let backend = Backend::connect(Target::resolve(&cli.target)?).await?;
+    execute(&backend, cli, out).await
+}
+
+/// Run a command against a backend
+pub async fn execute(backend: &Backend, cli: Cli, out: &mut dyn Write) -> Result<()> {
+    let format = cli.output;
+
+    match cli.command {
+        Command::List(args) => {
+            let filters = TaskFilters {
+                completed: args.completed(),
+                tag_match: args.tag_match(),
+                overdue: args.overdue.then_some(true),
+                sort: args.sort,
+                tag: args.tags,
+                ..Default::default()
+            };
+            let tasks = backend.list(filters, args.limit).await?;
+            output::tasks(out, format, &tasks)
+        }
+        Command::Add(args) => {
+            let task = backend
+                .create(CreateTask {
+                    title: args.title,
+                    description: args.description,
+                    due_at: args.due,
+                    priority: args.priority,
+                    parent_id: args.parent,
+                    tags: args.tags,
+                })
+                .await?;
+            output::task(out, format, &task)
+        }
+        Command::Done { ids } => {
+            let mut tasks = Vec::with_capacity(ids.len());
+            for id in ids {
+                let patch = UpdateTask {
+                    completed: Some(Some(true)),
+                    ..Default::default()
+                };
+                tasks.push(backend.update(id, patch).await?);
+            }
+            output::tasks(out, format, &tasks)
+        }
+        Command::Edit(args) => {
+            let id = args.id;
+            let task = backend.update(id, edit_patch(args)).await?;
+            output::task(out, format, &task)
+        }
+        Command::Rm { ids } => {
+            for id in ids {
+                backend.delete(id).await?;
+            }
+            Ok(())
+        }
+        Command::Stats => output::stats(out, format, &backend.stats().await?),
+        Command::Search(args) => {
+            let query = SearchQuery {
+                completed: args.completed(),
+                limit: args.limit,
+                q: args.words.join(" "),
+            };
+            output::search_results(out, format, &backend.search(query).await?)
+        }
+        Command::Completions { shell } => {
+            completions(shell, out);
+            Ok(())
+        }
+    }
+}
+
+/// Print the completion script for `shell`
+pub fn completions(shell: Shell, out: &mut dyn Write) {
+    clap_complete::generate(shell, &mut Cli::command(), "taskctl", out);
+}
+
+/// Merge patch for `edit`: only the given flags change, `--clear-*` send null
+fn edit_patch(args: EditArgs) -> UpdateTask {
+    fn nullable<T>(value: Option<T>, clear: bool) -> Option<Option<T>> {
+        if clear {
+            Some(None)
+        } else {
+            value.map(Some)
+        }
+    }
+
+    UpdateTask {
+        title: args.title.map(Some),
+        description: nullable(args.description, args.clear_description),
+        completed: args.completed.map(Some),
+        due_at: nullable(args.due, args.clear_due),
+        priority: args.priority.map(Some),
+        parent_id: nullable(args.parent, args.root),
+        tags: nullable(
+            (!args.tags.is_empty()).then_some(args.tags),
+            args.clear_tags,
+        ),
+    }
+}
@@ -0,0 +1,38 @@
+//! taskctl
+//!
+//! ```bash
+//! taskctl add "Learn Rust" --priority high --tag bootcamp
+//! taskctl list --pending -o json
+//! taskctl --profile offline stats
+//! ```
+
+use std::io::Write;
+use std::process::ExitCode;
+
+use clap::Parser;
+use taskctl::cli::Cli;
+use taskctl::error::CliError;
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let mut out = std::io::stdout().lock();
+
+    match taskctl::run(cli, &mut out).await {
+        Ok(()) => {
+            let _ = out.flush();
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+
+            // List every invalid field of a validation error
+            if let CliError::Client(e) = &e {
+                for field in e.field_errors() {
+                    eprintln!("  {}: {}", field.field, field.message);
+                }
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
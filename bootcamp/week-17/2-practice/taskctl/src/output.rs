@@ -0,0 +1,235 @@
+//! Output Formats
+//!
+//! `table` is meant for people; `json` prints the API's own JSON and `csv`
+//! the columns of `GET /tasks/export?format=csv`, so both can be piped into
+//! other tools (or back into `POST /tasks/import`).
+
+use std::io::Write;
+
+use project_task_api::models::{ExportFormat, Priority, SearchResult, Task, TaskStats};
+use project_task_api::transfer::Encoder;
+use serde::Serialize;
+
+use crate::cli::OutputFormat;
+use crate::error::Result;
+
+/// Longest title shown in a table before it is cut
+const MAX_TITLE: usize = 48;
+
+/// Print tasks
+pub fn tasks(out: &mut dyn Write, format: OutputFormat, tasks: &[Task]) -> Result<()> {
+    match format {
+        OutputFormat::Table => {
+            let rows = tasks.iter().map(task_row).collect();
+            table(
+                out,
+                &["ID", "DONE", "PRIORITY", "DUE", "TITLE", "TAGS"],
+                rows,
+            )
+        }
+        OutputFormat::Json => json(out, &tasks),
+        OutputFormat::Csv => {
+            let mut encoder = Encoder::new(ExportFormat::Csv);
+            out.write_all(&encoder.start()?)?;
+            for task in tasks {
+                out.write_all(&encoder.task(task)?)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Print one task
+pub fn task(out: &mut dyn Write, format: OutputFormat, task: &Task) -> Result<()> {
+    match format {
+        OutputFormat::Json => json(out, task),
+        _ => tasks(out, format, std::slice::from_ref(task)),
+    }
+}
+
+/// Print search results, most relevant first
+pub fn search_results(
+    out: &mut dyn Write,
+    format: OutputFormat,
+    results: &[SearchResult],
+) -> Result<()> {
+    match format {
+        OutputFormat::Table => {
+            let rows = results
+                .iter()
+                .map(|result| {
+                    vec![
+                        result.task.id.to_string(),
+                        done(result.task.completed).to_string(),
+                        truncate(&strip_marks(&result.title_highlight)),
+                        result
+                            .snippet
+                            .as_deref()
+                            .map(strip_marks)
+                            .unwrap_or_default(),
+                    ]
+                })
+                .collect();
+            table(out, &["ID", "DONE", "TITLE", "MATCH"], rows)
+        }
+        OutputFormat::Json => json(out, &results),
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(out);
+            writer.write_record([
+                "id",
+                "title",
+                "completed",
+                "rank",
+                "title_highlight",
+                "snippet",
+            ])?;
+            for result in results {
+                writer.write_record([
+                    result.task.id.to_string(),
+                    result.task.title.clone(),
+                    result.task.completed.to_string(),
+                    result.rank.to_string(),
+                    result.title_highlight.clone(),
+                    result.snippet.clone().unwrap_or_default(),
+                ])?;
+            }
+            writer.flush()?;
+            Ok(())
+        }
+    }
+}
+
+/// Print task counts
+pub fn stats(out: &mut dyn Write, format: OutputFormat, stats: &TaskStats) -> Result<()> {
+    let counts = [
+        ("total", stats.total),
+        ("completed", stats.completed),
+        ("pending", stats.pending),
+        ("overdue", stats.overdue),
+        ("low", stats.by_priority.low),
+        ("medium", stats.by_priority.medium),
+        ("high", stats.by_priority.high),
+        ("urgent", stats.by_priority.urgent),
+    ];
+
+    match format {
+        OutputFormat::Table => {
+            let rows = counts
+                .iter()
+                .map(|(name, count)| vec![name.to_string(), count.to_string()])
+                .collect();
+            table(out, &["METRIC", "COUNT"], rows)?;
+
+            if !stats.roots.is_empty() {
+                writeln!(out)?;
+                let rows = stats
+                    .roots
+                    .iter()
+                    .map(|root| {
+                        vec![
+                            root.id.to_string(),
+                            truncate(&root.title),
+                            format!("{}/{}", root.completed, root.subtasks),
+                            format!("{:.0}%", root.percent),
+                        ]
+                    })
+                    .collect();
+                table(out, &["ID", "TITLE", "SUBTASKS", "PROGRESS"], rows)?;
+            }
+            Ok(())
+        }
+        OutputFormat::Json => json(out, stats),
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(out);
+            writer.write_record(["metric", "count"])?;
+            for (name, count) in counts {
+                writer.write_record([name, &count.to_string()])?;
+            }
+            writer.flush()?;
+            Ok(())
+        }
+    }
+}
+
+/// One table row per task
+fn task_row(task: &Task) -> Vec<String> {
+    vec![
+        task.id.to_string(),
+        done(task.completed).to_string(),
+        priority(task.priority).to_string(),
+        task.due_at
+            .map(|due| due.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default(),
+        match task.parent_id {
+            // Subtasks are indented under the title column
+            Some(_) => format!("└ {}", truncate(&task.title)),
+            None => truncate(&task.title),
+        },
+        task.tags.join(","),
+    ]
+}
+
+/// Columns padded to their widest cell; the last one is not padded
+fn table(out: &mut dyn Write, header: &[&str], rows: Vec<Vec<String>>) -> Result<()> {
+    let mut widths: Vec<usize> = header.iter().map(|name| name.chars().count()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let header = header.iter().map(|name| name.to_string()).collect();
+    for row in std::iter::once(header).chain(rows) {
+        let last = row.len() - 1;
+        let mut line = String::new();
+
+        for (i, (cell, width)) in row.iter().zip(&widths).enumerate() {
+            if i == last {
+                line.push_str(cell);
+            } else {
+                line.push_str(&format!("{:<width$}  ", cell, width = width));
+            }
+        }
+        writeln!(out, "{}", line.trim_end())?;
+    }
+
+    Ok(())
+}
+
+fn json<T: Serialize + ?Sized>(out: &mut dyn Write, value: &T) -> Result<()> {
+    serde_json::to_writer_pretty(&mut *out, value)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+fn done(completed: bool) -> &'static str {
+    if completed {
+        "x"
+    } else {
+        ""
+    }
+}
+
+fn priority(priority: Priority) -> &'static str {
+    match priority {
+        Priority::Low => "low",
+        Priority::Medium => "medium",
+        Priority::High => "high",
+        Priority::Urgent => "urgent",
+    }
+}
+
+fn truncate(text: &str) -> String {
+    if text.chars().count() <= MAX_TITLE {
+        return text.to_string();
+    }
+
+    let mut cut: String = text.chars().take(MAX_TITLE - 1).collect();
+    cut.push('…');
+    cut
+}
+
+/// Highlighted text without the `<mark>` tags
+fn strip_marks(text: &str) -> String {
+    text.replace("<mark>", "").replace("</mark>", "")
+}
@@ -0,0 +1,489 @@
+//! taskctl tests: embedded mode over SQLite and HTTP mode against the real router
+//!
+//! Run with: `cargo test -p taskctl`
+
+use std::path::Path;
+
+use clap::Parser;
+use project_task_api::{
+    auth::{AuthConfig, CurrentUser},
+    config::{AppConfig, DatabaseConfig},
+    db,
+    models::{Credentials, SearchResult, Task, TaskStats},
+    repository::SqliteTaskRepository,
+    routes,
+    state::AppState,
+};
+use task_api_client::TaskClient;
+use taskctl::backend::Backend;
+use taskctl::cli::{Cli, TargetArgs};
+use taskctl::config::{ConfigFile, Target, DEFAULT_URL};
+use taskctl::error::CliError;
+use tempfile::TempDir;
+use tokio::net::TcpListener;
+
+/// Embedded backend over an in-memory database, with one user
+async fn embedded() -> Backend {
+    let pool = db::create_test_pool().await.expect("Error creating pool");
+    let id = add_user(&pool, "ferris").await;
+
+    Backend::embedded(
+        SqliteTaskRepository::new(pool),
+        CurrentUser {
+            id,
+            username: "ferris".to_string(),
+        },
+    )
+}
+
+async fn add_user(pool: &db::DbPool, username: &str) -> i64 {
+    sqlx::query("INSERT INTO users (username, password_hash) VALUES (?, 'not-a-hash')")
+        .bind(username)
+        .execute(pool)
+        .await
+        .unwrap()
+        .last_insert_rowid()
+}
+
+/// Run `taskctl <args>` against `backend`, returning what it printed
+async fn exec(backend: &Backend, args: &[&str]) -> Result<String, CliError> {
+    let cli = Cli::try_parse_from(std::iter::once("taskctl").chain(args.iter().copied())).unwrap();
+    let mut out = Vec::new();
+
+    taskctl::execute(backend, cli, &mut out).await?;
+    Ok(String::from_utf8(out).unwrap())
+}
+
+/// Run a full command line, connecting like the binary does
+async fn run(args: &[&str]) -> Result<String, CliError> {
+    let cli = Cli::try_parse_from(std::iter::once("taskctl").chain(args.iter().copied())).unwrap();
+    let mut out = Vec::new();
+
+    taskctl::run(cli, &mut out).await?;
+    Ok(String::from_utf8(out).unwrap())
+}
+
+fn target_args(args: &[&str]) -> TargetArgs {
+    let mut line = vec!["taskctl"];
+    line.extend_from_slice(args);
+    line.push("stats");
+
+    Cli::try_parse_from(line).unwrap().target
+}
+
+fn write_config(dir: &Path, text: &str) -> String {
+    let path = dir.join("config.toml");
+    std::fs::write(&path, text).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+/// The task API with an in-memory database, on an ephemeral port
+async fn spawn_server() -> String {
+    let pool = db::create_test_pool().await.expect("Error creating pool");
+    let auth = AuthConfig::new("test-secret");
+
+    let app = routes::create_routes::<SqliteTaskRepository>();
+    let app = routes::with_middleware(app, &AppConfig::default())
+        .with_state(AppState::new(SqliteTaskRepository::new(pool), auth));
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    format!("http://{}", address)
+}
+
+/// Token of a new user of `base_url`
+async fn login(base_url: &str) -> String {
+    let client = TaskClient::new(base_url).unwrap();
+    let credentials = Credentials {
+        username: "ferris".to_string(),
+        password: "crab-secret".to_string(),
+    };
+
+    client.register(&credentials).await.unwrap();
+    client.login(&credentials).await.unwrap().access_token
+}
+
+// ============================================================
+// ===== Command Tests =====
+// ============================================================
+
+#[tokio::test]
+async fn test_add_and_list() {
+    let backend = embedded().await;
+
+    exec(
+        &backend,
+        &["add", "Learn Rust", "-P", "high", "-t", "bootcamp"],
+    )
+    .await
+    .unwrap();
+    exec(
+        &backend,
+        &["add", "Write tests", "--due", "2030-01-01T09:00:00Z"],
+    )
+    .await
+    .unwrap();
+
+    let output = exec(&backend, &["-o", "json", "list"]).await.unwrap();
+    let tasks: Vec<Task> = serde_json::from_str(&output).unwrap();
+
+    assert_eq!(tasks.len(), 2);
+    let rust = tasks
+        .iter()
+        .find(|task| task.title == "Learn Rust")
+        .unwrap();
+    assert_eq!(rust.tags, vec!["bootcamp"]);
+    assert_eq!(serde_json::to_value(rust.priority).unwrap(), "high");
+}
+
+#[tokio::test]
+async fn test_list_filters_and_limit() {
+    let backend = embedded().await;
+    for i in 1..=5 {
+        exec(&backend, &["add", &format!("Task {}", i), "-t", "work"])
+            .await
+            .unwrap();
+    }
+    exec(&backend, &["add", "Home task", "-t", "home"])
+        .await
+        .unwrap();
+    exec(&backend, &["done", "1", "2"]).await.unwrap();
+
+    let list = |args: &'static [&'static str]| {
+        let backend = backend.clone();
+        async move {
+            let mut line = vec!["-o", "json", "list"];
+            line.extend_from_slice(args);
+            let output = exec(&backend, &line).await.unwrap();
+            serde_json::from_str::<Vec<Task>>(&output).unwrap()
+        }
+    };
+
+    assert_eq!(list(&[]).await.len(), 6);
+    assert_eq!(list(&["--done"]).await.len(), 2);
+    assert_eq!(list(&["--pending"]).await.len(), 4);
+    assert_eq!(list(&["-t", "home"]).await.len(), 1);
+    assert_eq!(
+        list(&["-t", "work", "-t", "home", "--all-tags"])
+            .await
+            .len(),
+        0
+    );
+    assert_eq!(list(&["-n", "3"]).await.len(), 3);
+}
+
+#[tokio::test]
+async fn test_edit_sends_merge_patch() {
+    let backend = embedded().await;
+    exec(
+        &backend,
+        &["add", "Draft", "-d", "Old description", "-t", "a"],
+    )
+    .await
+    .unwrap();
+
+    let output = exec(
+        &backend,
+        &[
+            "-o",
+            "json",
+            "edit",
+            "1",
+            "--title",
+            "Final",
+            "--clear-description",
+            "-t",
+            "b",
+        ],
+    )
+    .await
+    .unwrap();
+    let task: Task = serde_json::from_str(&output).unwrap();
+
+    assert_eq!(task.title, "Final");
+    assert_eq!(task.description, None);
+    assert_eq!(task.tags, vec!["b"]);
+    assert!(!task.completed, "untouched fields keep their value");
+}
+
+#[tokio::test]
+async fn test_rm_deletes_tasks() {
+    let backend = embedded().await;
+    exec(&backend, &["add", "Keep"]).await.unwrap();
+    exec(&backend, &["add", "Drop"]).await.unwrap();
+
+    exec(&backend, &["rm", "2"]).await.unwrap();
+
+    let output = exec(&backend, &["-o", "json", "list"]).await.unwrap();
+    let tasks: Vec<Task> = serde_json::from_str(&output).unwrap();
+    assert_eq!(tasks.len(), 1);
+    assert_eq!(tasks[0].title, "Keep");
+
+    let result = exec(&backend, &["rm", "2"]).await;
+    assert!(matches!(result, Err(CliError::Api(_))));
+}
+
+#[tokio::test]
+async fn test_stats_and_search() {
+    let backend = embedded().await;
+    exec(
+        &backend,
+        &["add", "Learn Rust", "-d", "Ownership and borrowing"],
+    )
+    .await
+    .unwrap();
+    exec(&backend, &["add", "Buy milk"]).await.unwrap();
+    exec(&backend, &["done", "2"]).await.unwrap();
+
+    let output = exec(&backend, &["-o", "json", "stats"]).await.unwrap();
+    let stats: TaskStats = serde_json::from_str(&output).unwrap();
+    assert_eq!((stats.total, stats.completed, stats.pending), (2, 1, 1));
+
+    let output = exec(&backend, &["-o", "json", "search", "borrow"])
+        .await
+        .unwrap();
+    let results: Vec<SearchResult> = serde_json::from_str(&output).unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].task.title, "Learn Rust");
+
+    let output = exec(&backend, &["search", "borrow"]).await.unwrap();
+    assert!(output.contains("Ownership and borrowing"), "{}", output);
+    assert!(!output.contains("<mark>"), "tables show plain text");
+}
+
+// ============================================================
+// ===== Output Tests =====
+// ============================================================
+
+#[tokio::test]
+async fn test_table_output_is_aligned() {
+    let backend = embedded().await;
+    exec(&backend, &["add", "Short", "-t", "x"]).await.unwrap();
+    exec(&backend, &["add", "A much longer title", "-P", "urgent"])
+        .await
+        .unwrap();
+
+    let output = exec(&backend, &["list", "--sort", "priority"])
+        .await
+        .unwrap();
+    let lines: Vec<&str> = output.lines().collect();
+
+    assert_eq!(lines.len(), 3);
+    assert!(
+        lines[0].starts_with("ID  DONE  PRIORITY  DUE  TITLE"),
+        "{}",
+        output
+    );
+    assert!(lines[1].contains("urgent"));
+    let title = lines[0].find("TITLE").unwrap();
+    assert_eq!(&lines[1][title..title + 6], "A much");
+    assert_eq!(&lines[2][title..title + 5], "Short");
+}
+
+#[tokio::test]
+async fn test_csv_output_matches_export() {
+    let backend = embedded().await;
+    exec(&backend, &["add", "Learn, Rust", "-t", "a", "-t", "b"])
+        .await
+        .unwrap();
+
+    let output = exec(&backend, &["-o", "csv", "list"]).await.unwrap();
+    let mut reader = csv::Reader::from_reader(output.as_bytes());
+
+    assert_eq!(
+        reader.headers().unwrap().iter().collect::<Vec<_>>(),
+        vec![
+            "id",
+            "title",
+            "description",
+            "completed",
+            "due_at",
+            "priority",
+            "parent_id",
+            "tags",
+            "created_at",
+            "updated_at"
+        ]
+    );
+    let record = reader.records().next().unwrap().unwrap();
+    assert_eq!(&record[1], "Learn, Rust");
+    assert_eq!(&record[7], "a;b");
+}
+
+#[tokio::test]
+async fn test_completions() {
+    let output = run(&["completions", "bash"]).await.unwrap();
+
+    assert!(output.contains("taskctl"));
+    assert!(output.contains("search"));
+}
+
+// ============================================================
+// ===== Target Tests =====
+// ============================================================
+
+#[test]
+fn test_default_target_is_local_server() {
+    let target = Target::from_profile(&TargetArgs::default(), Default::default()).unwrap();
+
+    assert_eq!(
+        target,
+        Target::Http {
+            url: DEFAULT_URL.to_string(),
+            token: None,
+        }
+    );
+}
+
+#[test]
+fn test_profiles() {
+    let dir = TempDir::new().unwrap();
+    let config = write_config(
+        dir.path(),
+        r#"
+        default_profile = "local"
+
+        [profiles.local]
+        url = "http://localhost:3000"
+        token = "local-token"
+
+        [profiles.offline]
+        database = "tasks.db"
+        user = "ferris"
+        "#,
+    );
+
+    let target = Target::resolve(&target_args(&["--config", &config])).unwrap();
+    assert_eq!(
+        target,
+        Target::Http {
+            url: "http://localhost:3000".to_string(),
+            token: Some("local-token".to_string()),
+        }
+    );
+
+    let target = Target::resolve(&target_args(&["--config", &config, "-p", "offline"])).unwrap();
+    assert_eq!(
+        target,
+        Target::Embedded {
+            database: "tasks.db".to_string(),
+            user: "ferris".to_string(),
+        }
+    );
+
+    // Flags override the profile
+    let args = target_args(&["--config", &config, "--url", "http://other:8080"]);
+    assert_eq!(
+        Target::resolve(&args).unwrap(),
+        Target::Http {
+            url: "http://other:8080".to_string(),
+            token: Some("local-token".to_string()),
+        }
+    );
+    let args = target_args(&["--config", &config, "-p", "offline", "--user", "crab"]);
+    assert!(matches!(
+        Target::resolve(&args).unwrap(),
+        Target::Embedded { user, .. } if user == "crab"
+    ));
+
+    let result = Target::resolve(&target_args(&["--config", &config, "-p", "nope"]));
+    assert!(matches!(result, Err(CliError::UnknownProfile(name)) if name == "nope"));
+}
+
+#[test]
+fn test_invalid_config_files() {
+    let dir = TempDir::new().unwrap();
+
+    let missing = dir.path().join("missing.toml");
+    let result = ConfigFile::from_file(&missing, true);
+    assert!(matches!(result, Err(CliError::ReadConfig { .. })));
+    assert!(ConfigFile::from_file(&missing, false)
+        .unwrap()
+        .profiles
+        .is_empty());
+
+    let config = write_config(dir.path(), "[profiles.local]\nadress = \"typo\"\n");
+    let result = ConfigFile::from_file(Path::new(&config), true);
+    assert!(matches!(result, Err(CliError::ParseConfig { .. })));
+}
+
+#[test]
+fn test_embedded_target_needs_user() {
+    let args = target_args(&["--database", "tasks.db"]);
+    let result = Target::from_profile(&args, Default::default());
+
+    assert!(matches!(result, Err(CliError::MissingUser)));
+}
+
+// ============================================================
+// ===== Mode Tests =====
+// ============================================================
+
+#[tokio::test]
+async fn test_embedded_mode_opens_database_file() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("tasks.db");
+    let database = path.to_str().unwrap();
+
+    let pool = db::create_pool(&DatabaseConfig::with_url(format!(
+        "sqlite:{}?mode=rwc",
+        database
+    )))
+    .await
+    .unwrap();
+    add_user(&pool, "ferris").await;
+    pool.close().await;
+
+    let target = ["--database", database, "--user", "ferris"];
+    run(&[&target[..], &["add", "Offline task"]].concat())
+        .await
+        .unwrap();
+    let output = run(&[&target[..], &["-o", "json", "list"]].concat())
+        .await
+        .unwrap();
+    let tasks: Vec<Task> = serde_json::from_str(&output).unwrap();
+    assert_eq!(tasks[0].title, "Offline task");
+
+    let result = run(&["--database", database, "--user", "nobody", "list"]).await;
+    assert!(matches!(result, Err(CliError::UnknownUser(name)) if name == "nobody"));
+
+    let missing = dir.path().join("missing.db");
+    let result = run(&[
+        "--database",
+        missing.to_str().unwrap(),
+        "--user",
+        "ferris",
+        "list",
+    ])
+    .await;
+    assert!(
+        matches!(result, Err(CliError::Database(_))),
+        "no file is created"
+    );
+}
+
+#[tokio::test]
+async fn test_http_mode() {
+    let base_url = spawn_server().await;
+    let token = login(&base_url).await;
+    let target = ["--url", base_url.as_str(), "--token", token.as_str()];
+
+    run(&[&target[..], &["add", "Remote task", "-t", "api"]].concat())
+        .await
+        .unwrap();
+    run(&[&target[..], &["done", "1"]].concat()).await.unwrap();
+
+    let output = run(&[&target[..], &["-o", "json", "list", "--done"]].concat())
+        .await
+        .unwrap();
+    let tasks: Vec<Task> = serde_json::from_str(&output).unwrap();
+    assert_eq!(tasks.len(), 1);
+    assert_eq!(tasks[0].tags, vec!["api"]);
+
+    let result = run(&["--url", &base_url, "list"]).await;
+    assert!(
+        matches!(result, Err(CliError::Client(e)) if e.status().map(|s| s.as_u16()) == Some(401))
+    );
+}